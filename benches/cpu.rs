@@ -0,0 +1,121 @@
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+use fish_n_chip8::cpu::{CPU, DEFAULT_CYCLES_PER_FRAME};
+
+const PROGRAM_START: u16 = 0x200;
+const STEPS: u64 = 1000;
+const ROM_STEPS: u64 = 100_000;
+
+const TEST_ROMS: [(&str, &[u8]); 4] = [
+    ("corax+", include_bytes!("../roms/tests/3-corax+.ch8")),
+    ("flags", include_bytes!("../roms/tests/4-flags.ch8")),
+    ("quirks", include_bytes!("../roms/tests/5-quirks.ch8")),
+    ("scrolling", include_bytes!("../roms/tests/8-scrolling.ch8")),
+];
+
+fn assemble(program: &[u16]) -> Vec<u8> {
+    program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect()
+}
+
+fn cpu_with(rom: &[u8]) -> CPU {
+    let mut cpu = CPU::new();
+    cpu.load_rom(rom);
+    cpu
+}
+
+fn run(cpu: &mut CPU, steps: u64) {
+    for _ in 0..steps {
+        black_box(cpu.handle_opcode());
+    }
+}
+
+// loops a sprite being drawn one step further across and down the screen each time round
+fn draw_rom(high_res: bool, rows: u16) -> Vec<u8> {
+    let mut program = vec![];
+    if high_res {
+        program.push(0x00FF);
+    }
+
+    let set_sprite_addr = program.len();
+    program.extend([0xA000, 0x6000, 0x6100]);
+
+    let loop_start = PROGRAM_START + program.len() as u16 * 2;
+    program.extend([0xD010 | rows, 0x7003, 0x7101, 0x1000 | loop_start]);
+    program[set_sprite_addr] = 0xA000 | (PROGRAM_START + program.len() as u16 * 2);
+
+    let mut rom = assemble(&program);
+    rom.extend([0xA5; 32]); // enough for the 16x16 sprite
+    rom
+}
+
+fn dispatch(c: &mut Criterion) {
+    // a mix of arithmetic, skips and memory ops without any drawing
+    let rom = assemble(&[
+        0x6001, 0x6102, 0x8014, 0x8012, 0x8013, 0x8015, 0x8016, 0x801E,
+        0x7005, 0x3000, 0x4000, 0x5010, 0x9010, 0xA300, 0xF01E, 0xF007,
+        0x1200,
+    ]);
+    let mut cpu = cpu_with(&rom);
+
+    let mut group = c.benchmark_group("dispatch");
+    group.throughput(Throughput::Elements(STEPS));
+    group.bench_function("alu_and_skips", |b| b.iter(|| run(&mut cpu, STEPS)));
+    group.finish();
+}
+
+fn draw(c: &mut Criterion) {
+    let mut group = c.benchmark_group("draw");
+    group.throughput(Throughput::Elements(STEPS));
+
+    for (resolution, high_res) in [("low_res", false), ("high_res", true)] {
+        for rows in [1, 8, 15, 0] {
+            let mut cpu = cpu_with(&draw_rom(high_res, rows));
+            let name = if rows == 0 { String::from("16x16") } else { format!("8x{}", rows) };
+
+            group.bench_with_input(BenchmarkId::new(resolution, name), &rows, |b, _| b.iter(|| run(&mut cpu, STEPS)));
+        }
+    }
+    group.finish();
+}
+
+fn scroll(c: &mut Criterion) {
+    let mut group = c.benchmark_group("scroll");
+    group.throughput(Throughput::Elements(STEPS));
+
+    let programs: [(&str, &[u16]); 3] = [
+        ("down", &[0x00FF, 0x00C4, 0x1202]),
+        ("right", &[0x00FF, 0x00FB, 0x1202]),
+        ("left", &[0x00FF, 0x00FC, 0x1202]),
+    ];
+    for (direction, program) in programs {
+        let mut cpu = cpu_with(&assemble(program));
+        group.bench_function(direction, |b| b.iter(|| run(&mut cpu, STEPS)));
+    }
+    group.finish();
+}
+
+fn rom_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("rom");
+    group.throughput(Throughput::Elements(ROM_STEPS));
+
+    for (name, rom) in TEST_ROMS {
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || cpu_with(rom),
+                |mut cpu| {
+                    for step in 0..ROM_STEPS {
+                        if step % DEFAULT_CYCLES_PER_FRAME as u64 == 0 {
+                            cpu.timer_tick();
+                        }
+                        black_box(cpu.handle_opcode());
+                    }
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, dispatch, draw, scroll, rom_throughput);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+// plays Pong with the gym interface, once with a bot that follows the ball and once pressing keys at random,
+// as a starting point for training an agent
+//
+// cargo run --release --example pong -- path/to/PONG
+
+use std::env;
+use std::fs;
+use std::process;
+
+use fish_n_chip8::gym::{Environment, EnvironmentSettings, Observation};
+
+const EPISODES: u32 = 3;
+const EPISODE_FRAMES: u32 = 60 * 60;
+
+// the left player's keys
+const UP: u16 = 1 << 0x1;
+const DOWN: u16 = 1 << 0x4;
+
+const PADDLE_COLUMNS: usize = 4; // the paddles sit within this many columns of each edge
+const SCORE_ROWS: usize = 6;     // the score is drawn across the top
+const SCORE_REGISTER: usize = 0xE;
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("Usage: cargo run --example pong -- <pong rom>");
+        process::exit(2);
+    };
+    let rom = fs::read(&path).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", path, e);
+        process::exit(1);
+    });
+
+    // the reward is worked out from our own score below rather than with a RewardSource, which would count the
+    // other side scoring too
+    let settings = EnvironmentSettings {
+        frames_per_step: 2,
+        max_frames: Some(EPISODE_FRAMES),
+        ..Default::default()
+    };
+    let mut env = Environment::new(&rom, settings).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    println!("Following the ball: {:.1} average reward", average_reward(&mut env, follow_ball));
+    println!("Random keys: {:.1} average reward", average_reward(&mut env, |_| rand::random::<u16>() & (UP | DOWN)));
+}
+
+fn average_reward(env: &mut Environment, mut policy: impl FnMut(&Observation) -> u16) -> f32 {
+    let mut total = 0.0;
+
+    for _ in 0..EPISODES {
+        let mut observation = env.reset();
+        let mut score = our_score(env);
+        loop {
+            let step = env.step(policy(&observation));
+            total += our_score(env).saturating_sub(score) as f32;
+            score = our_score(env);
+            observation = step.observation;
+
+            if step.done {
+                break;
+            }
+        }
+    }
+
+    total / EPISODES as f32
+}
+
+// Pong keeps both scores in VE, the left player's in the tens and the right player's in the units
+fn our_score(env: &Environment) -> u8 {
+    env.cpu().registers()[SCORE_REGISTER] / 10
+}
+
+// moves the left paddle towards the ball
+fn follow_ball(observation: &Observation) -> u16 {
+    let lit_rows = |columns: std::ops::Range<usize>| -> Vec<usize> {
+        (SCORE_ROWS..observation.height)
+            .filter(|&y| columns.clone().any(|x| observation.framebuffer[y * observation.width + x] != 0))
+            .collect()
+    };
+    let middle = |rows: &[usize]| rows.iter().sum::<usize>() / rows.len();
+
+    let paddle = lit_rows(0..PADDLE_COLUMNS);
+    let ball = lit_rows(PADDLE_COLUMNS..observation.width - PADDLE_COLUMNS);
+    if paddle.is_empty() || ball.is_empty() {
+        return 0;
+    }
+
+    match middle(&ball).cmp(&middle(&paddle)) {
+        std::cmp::Ordering::Less => UP,
+        std::cmp::Ordering::Greater => DOWN,
+        std::cmp::Ordering::Equal => 0,
+    }
+}
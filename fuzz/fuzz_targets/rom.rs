@@ -0,0 +1,51 @@
+#![no_main]
+
+// runs whatever it's given as a ROM and checks the core copes: no panics, the stack stays bounded and PC stays in memory
+
+use libfuzzer_sys::fuzz_target;
+
+use fish_n_chip8::cpu::{Quirks, RegSaveLoadQuirk, ShiftingReg, JumpBehviour, CPU, DEFAULT_CYCLES_PER_FRAME, MAX_ROM_SIZE, MAX_STACK_DEPTH};
+use fish_n_chip8::random::RngAlgorithm;
+
+const FRAMES: usize = 100;
+
+fuzz_target!(|data: &[u8]| {
+    // the first byte picks the quirks and the next two which keys are held, the rest is the ROM
+    let [settings, keys_high, keys_low, rom @ ..] = data else {
+        return;
+    };
+    let rom = &rom[..rom.len().min(MAX_ROM_SIZE)];
+
+    let mut cpu = CPU::new();
+    cpu.seed_rng(0);
+    cpu.quirks = Quirks {
+        vf_reset: settings & 1 != 0,
+        shifting: if settings & 2 != 0 { ShiftingReg::VY } else { ShiftingReg::VX },
+        reg_save_load: match settings >> 2 & 3 {
+            0 => RegSaveLoadQuirk::Unchanged,
+            1 => RegSaveLoadQuirk::X,
+            _ => RegSaveLoadQuirk::XPlusOne,
+        },
+        jump: if settings & 16 != 0 { JumpBehviour::BXNN } else { JumpBehviour::BNNN },
+        screen_wrap: settings & 32 != 0,
+        rng: RngAlgorithm::ALL[(settings >> 6) as usize],
+        ..Quirks::default()
+    };
+    cpu.load_rom(rom);
+
+    let pressed_keys = u16::from_be_bytes([*keys_high, *keys_low]);
+    for _ in 0..FRAMES {
+        cpu.timer_tick();
+        for _ in 0..DEFAULT_CYCLES_PER_FRAME {
+            cpu.handle_opcode(pressed_keys, 0);
+
+            assert!(cpu.stack().len() <= MAX_STACK_DEPTH);
+            assert!(cpu.pc() <= 0xFF + 0xFFF, "PC {:X} is past anywhere BNNN can jump to", cpu.pc());
+        }
+        if cpu.halted() {
+            break;
+        }
+    }
+
+    cpu.display_hash();
+});
@@ -0,0 +1,39 @@
+// the built in LED matrix shader, DOT_RADIUS and GLOW are put in front of this from the settings
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@group(1) @binding(0)
+var t: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s: sampler;
+
+// how much light reaches here from the dot offset pixels away from this one
+fn glow_from(cell: vec2<f32>, size: vec2<f32>, offset: vec2<f32>) -> vec3<f32> {
+    let colour = textureSample(t, s, (floor(cell) + 0.5 + offset) / size).rgb;
+    let distance = length(fract(cell) - 0.5 - offset);
+    return colour * exp(-distance * distance * 6.0);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(t));
+    let cell = in.uv * size; // which pixel this is in, and where in it
+    let colour = in.color * textureSample(t, s, (floor(cell) + 0.5) / size);
+
+    let distance = length(fract(cell) - 0.5);
+    let edge = fwidth(distance);
+    let dot = 1.0 - smoothstep(DOT_RADIUS - edge, DOT_RADIUS + edge, distance);
+
+    var glow = glow_from(cell, size, vec2<f32>(0.0, 0.0));
+    glow += glow_from(cell, size, vec2<f32>(1.0, 0.0));
+    glow += glow_from(cell, size, vec2<f32>(-1.0, 0.0));
+    glow += glow_from(cell, size, vec2<f32>(0.0, 1.0));
+    glow += glow_from(cell, size, vec2<f32>(0.0, -1.0));
+
+    return vec4<f32>(min(colour.rgb * dot + glow * GLOW, vec3<f32>(1.0)), colour.a);
+}
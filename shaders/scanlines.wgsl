@@ -0,0 +1,24 @@
+// an example post-processing shader, darkening the bottom of each CHIP-8 pixel for a CRT look. Copy it into the
+// shaders folder of the user data directory and pick it under Appearance
+
+// has to match what ggez's vertex shader passes on
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+// the screen, one texel per CHIP-8 pixel
+@group(1) @binding(0)
+var t: texture_2d<f32>;
+
+@group(1) @binding(1)
+var s: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let colour = in.color * textureSample(t, s, in.uv);
+    let row = fract(in.uv.y * f32(textureDimensions(t).y)); // how far down its pixel this is
+    let shade = select(1.0, 0.6, row > 0.6);
+    return vec4<f32>(colour.rgb * shade, colour.a);
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+
+use std::io;
+use std::net::{SocketAddr, UdpSocket};
+
+use crate::bridge;
+use crate::cpu::CPU;
+
+// where a WLED controller with nothing set up yet puts itself, on its own access point, on the port it listens for
+// realtime colours on
+pub const DEFAULT_WLED_ADDRESS: &str = "192.168.4.1:21324";
+
+pub const MAX_LEDS: u16 = 490; // as many as fit in one packet
+
+const WLED_DRGB: u8 = 2;           // one RGB triple per LED from the first
+const WLED_TIMEOUT: u8 = 2;        // seconds before the lights go back to their own effect once the emulator stops sending
+const KEEP_ALIVE_FRAMES: u32 = 30; // an unchanged screen is still sent this often so the lights don't time out
+
+pub type Rgb = [u8; 3];
+
+// the colours to light a room with, from the screen as a whole and from the edges of it for lights placed round it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AmbientColours {
+    pub overall: Rgb,
+    pub top: Rgb,
+    pub right: Rgb,
+    pub bottom: Rgb,
+    pub left: Rgb,
+}
+
+impl AmbientColours {
+    pub fn from_screen(cpu: &CPU, on: Rgb, off: Rgb) -> AmbientColours {
+        from_pixels(cpu.width(), cpu.height(), |x, y| cpu.pixel(x, y), on, off)
+    }
+}
+
+// a pixel is either on or off so each colour is the two mixed by how much of that part of the screen is lit, which
+// is what it looks like from across the room anyway
+fn from_pixels(width: usize, height: usize, pixel: impl Fn(usize, usize) -> bool, on: Rgb, off: Rgb) -> AmbientColours {
+    let mix = |xs: std::ops::Range<usize>, ys: std::ops::Range<usize>| {
+        let total = xs.len() * ys.len();
+        let lit = ys.flat_map(|y| xs.clone().map(move |x| (x, y))).filter(|&(x, y)| pixel(x, y)).count();
+        let channel = |i: usize| ((on[i] as usize * lit + off[i] as usize * (total - lit)) / total.max(1)) as u8;
+        [channel(0), channel(1), channel(2)]
+    };
+
+    let edge_x = (width / 8).max(1); // the outer eighth of the screen counts as its edge
+    let edge_y = (height / 8).max(1);
+    AmbientColours {
+        overall: mix(0..width, 0..height),
+        top: mix(0..width, 0..edge_y),
+        right: mix(width - edge_x..width, 0..height),
+        bottom: mix(0..width, height - edge_y..height),
+        left: mix(0..edge_x, 0..height),
+    }
+}
+
+// something that can show the colours, implemented for more kinds of lights than WLED by anything building on the
+// emulator and passed to emulator_main_with_plugins. It's given the colours after every emulated frame
+pub trait AmbientLights {
+    fn send(&mut self, colours: &AmbientColours) -> io::Result<()>;
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug, Default)]
+pub enum AmbientMode {
+    #[default]
+    Overall, // every LED the same
+    Borders, // a strip round the screen, clockwise from the top left corner, with each side matching that edge
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct AmbientSettings {
+    pub enabled: bool,
+    pub address: String,
+    pub led_count: u16,
+    pub mode: AmbientMode,
+}
+
+impl Default for AmbientSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: String::from(DEFAULT_WLED_ADDRESS),
+            led_count: 30,
+            mode: AmbientMode::Overall,
+        }
+    }
+}
+
+// a WLED controller, sent colours with its UDP realtime protocol
+pub struct WledLights {
+    socket: UdpSocket,
+    target: SocketAddr,
+    led_count: u16,
+    mode: AmbientMode,
+    last_sent: Option<Vec<u8>>,
+    frames_since_sent: u32,
+}
+
+impl WledLights {
+    pub fn start(settings: &AmbientSettings) -> io::Result<WledLights> {
+        let (socket, target) = bridge::udp_socket(&settings.address)?;
+        Ok(WledLights { socket, target, led_count: settings.led_count, mode: settings.mode, last_sent: None, frames_since_sent: 0 })
+    }
+}
+
+impl AmbientLights for WledLights {
+    fn send(&mut self, colours: &AmbientColours) -> io::Result<()> {
+        let packet = wled_packet(colours, self.led_count, self.mode);
+        self.frames_since_sent += 1;
+        if self.last_sent.as_ref() == Some(&packet) && self.frames_since_sent < KEEP_ALIVE_FRAMES {
+            return Ok(());
+        }
+
+        bridge::send_to(&self.socket, &packet, self.target)?;
+        self.last_sent = Some(packet);
+        self.frames_since_sent = 0;
+        Ok(())
+    }
+}
+
+fn wled_packet(colours: &AmbientColours, led_count: u16, mode: AmbientMode) -> Vec<u8> {
+    let led_count = led_count as usize;
+    let mut packet = vec![WLED_DRGB, WLED_TIMEOUT];
+    for led in 0..led_count {
+        let colour = match mode {
+            AmbientMode::Overall => colours.overall,
+            AmbientMode::Borders => [colours.top, colours.right, colours.bottom, colours.left][led * 4 / led_count],
+        };
+        packet.extend(colour);
+    }
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mixes_the_screen_into_light() {
+        // the left half of an 8x8 screen lit, so the right edge is all off and the rest half and half
+        let colours = from_pixels(8, 8, |x, _| x < 4, [200, 100, 0], [0, 0, 50]);
+        assert_eq!(colours.overall, [100, 50, 25]);
+        assert_eq!(colours.top, [100, 50, 25]);
+        assert_eq!(colours.left, [200, 100, 0]);
+        assert_eq!(colours.right, [0, 0, 50]);
+
+        let overall = wled_packet(&colours, 2, AmbientMode::Overall);
+        assert_eq!(overall, [2, 2, 100, 50, 25, 100, 50, 25]);
+
+        let borders = wled_packet(&colours, 4, AmbientMode::Borders);
+        assert_eq!(borders[2..], [100, 50, 25, 0, 0, 50, 100, 50, 25, 200, 100, 0]);
+        assert_eq!(wled_packet(&colours, 0, AmbientMode::Borders), [2, 2]);
+    }
+}
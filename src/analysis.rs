@@ -0,0 +1,212 @@
+// Works out which bytes of a ROM are instructions by following every path the program can take from the entry
+// point, so sprites and other data aren't mistaken for code.
+//
+// It can't see through computed jumps (BNNN) or code the program writes itself, anything only reached those ways
+// is left as data
+
+use std::fmt;
+
+use crate::cpu::{PROGRAM_START, RAM_SIZE};
+use crate::instruction::Instruction;
+
+#[derive(PartialEq, PartialOrd, Clone, Copy, Debug)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+pub struct CodeMap {
+    instructions: Vec<bool>,         // set where an instruction starts, indexed from the start of the ROM
+    code: Vec<bool>,                 // set for both bytes of every instruction
+    opcodes: Vec<(usize, u16)>,      // every instruction found and its address
+    dead_ends: Vec<(usize, u16)>,    // opcodes that paths reached but aren't supported
+}
+
+impl CodeMap {
+    pub fn analyse(rom: &[u8]) -> CodeMap {
+        let mut instructions = vec![false; rom.len()];
+        let mut code = vec![false; rom.len()];
+        let mut opcodes = vec![];
+        let mut dead_ends = vec![];
+        let mut to_visit = vec![PROGRAM_START];
+
+        while let Some(addr) = to_visit.pop() {
+            let Some(offset) = addr.checked_sub(PROGRAM_START) else {
+                continue; // jumps below the ROM go into the interpreter's area which isn't ours to analyse
+            };
+            if offset + 1 >= rom.len() || instructions[offset] {
+                continue;
+            }
+
+            let opcode = (rom[offset] as u16) << 8 | rom[offset + 1] as u16;
+            let instruction = Instruction::decode(opcode);
+            if let Instruction::Unsupported(_) = instruction {
+                // a path that ends here was most likely never meant to be taken, or is for another platform
+                dead_ends.push((addr, opcode));
+                continue;
+            }
+
+            opcodes.push((addr, opcode));
+            instructions[offset] = true;
+            code[offset] = true;
+            code[offset + 1] = true;
+
+            let next = addr + 2;
+            match instruction {
+                Instruction::Jump(target) => to_visit.push(target as usize),
+                Instruction::Call(target) => to_visit.extend([target as usize, next]),
+                Instruction::Return | Instruction::Exit | Instruction::JumpOffset(..) => (),
+                Instruction::SkipIfEqual(..) | Instruction::SkipIfNotEqual(..)
+                | Instruction::SkipIfRegsEqual(..) | Instruction::SkipIfRegsNotEqual(..)
+                | Instruction::SkipIfKey(_) | Instruction::SkipIfNotKey(_)
+                | Instruction::SkipIfSecondKey(_) | Instruction::SkipIfNotSecondKey(_) => to_visit.extend([next, next + 2]),
+                _ => to_visit.push(next),
+            }
+        }
+
+        opcodes.sort_unstable();
+        dead_ends.sort_unstable();
+        dead_ends.dedup();
+
+        CodeMap { instructions, code, opcodes, dead_ends }
+    }
+
+    pub fn is_instruction(&self, addr: usize) -> bool {
+        addr.checked_sub(PROGRAM_START).is_some_and(|offset| self.instructions.get(offset) == Some(&true))
+    }
+
+    // false for data and for anything outside the ROM
+    pub fn is_code(&self, addr: usize) -> bool {
+        addr.checked_sub(PROGRAM_START).is_some_and(|offset| self.code.get(offset) == Some(&true))
+    }
+
+    pub fn code_bytes(&self) -> usize {
+        self.code.iter().filter(|&&code| code).count()
+    }
+
+    // in address order
+    pub fn opcodes(&self) -> &[(usize, u16)] {
+        &self.opcodes
+    }
+
+    pub fn dead_ends(&self) -> &[(usize, u16)] {
+        &self.dead_ends
+    }
+
+    // the most capable platform the reachable code needs, XO-CHIP only being recognised by the instructions
+    // this emulator doesn't support
+    pub fn platform(&self) -> Platform {
+        let xo_chip = self.dead_ends.iter().any(|&(_, opcode)| {
+            opcode & 0xF00F == 0x5002 || opcode & 0xF00F == 0x5003 || opcode & 0xFFF0 == 0x00D0
+                || opcode == 0xF000 || opcode == 0xF002 || opcode & 0xF0FF == 0xF03A || opcode & 0xF3FF == 0xF001
+        });
+        let super_chip = self.opcodes.iter().any(|&(_, opcode)| matches!(
+            Instruction::decode(opcode),
+            Instruction::ScrollDown(_) | Instruction::ScrollRight | Instruction::ScrollLeft | Instruction::Exit
+            | Instruction::LowRes | Instruction::HighRes | Instruction::HighResFont(_)
+            | Instruction::StoreFlags(_) | Instruction::LoadFlags(_) | Instruction::Draw(_, _, 0)
+        ));
+
+        if xo_chip {
+            Platform::XoChip
+        }
+        else if super_chip {
+            Platform::SuperChip
+        }
+        else {
+            Platform::Chip8
+        }
+    }
+}
+
+// the addresses instructions have actually run from while the program was played, which finds the code the analysis
+// can't see and shows what a session never got to
+pub struct Coverage {
+    ran: Vec<bool>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self { ran: vec![false; RAM_SIZE] }
+    }
+}
+
+impl Coverage {
+    pub fn record(&mut self, pc: u16) {
+        self.ran[pc as usize % RAM_SIZE] = true;
+    }
+
+    pub fn ran(&self, addr: usize) -> bool {
+        self.ran.get(addr) == Some(&true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.ran.contains(&true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom(program: &[u16]) -> Vec<u8> {
+        program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn data_after_a_jump() {
+        // draws the sprite after the loop, which would decode as 3C7E (SE VC, 0x7E) if it were code
+        let map = CodeMap::analyse(&rom(&[0xA206, 0xD001, 0x1204, 0x3C7E]));
+
+        assert!(map.is_instruction(0x200) && map.is_instruction(0x202) && map.is_instruction(0x204));
+        assert!(!map.is_code(0x206) && !map.is_code(0x207));
+        assert_eq!(map.code_bytes(), 6);
+    }
+
+    #[test]
+    fn follows_skips_and_calls() {
+        let map = CodeMap::analyse(&rom(&[
+            0x3000, // 200: SE V0, 0x00
+            0x120A, // 202: JP 0x20A
+            0x2208, // 204: CALL 0x208
+            0x1206, // 206: JP 0x206
+            0x00EE, // 208: RET
+            0x00FD, // 20A: EXIT
+            0xFFFF, // 20C: never reached
+        ]));
+
+        for addr in (0x200..0x20C).step_by(2) {
+            assert!(map.is_instruction(addr), "{:03X} should be code", addr);
+        }
+        assert!(!map.is_code(0x20C));
+    }
+
+    #[test]
+    fn platforms() {
+        assert_eq!(CodeMap::analyse(&rom(&[0x00E0, 0x1202])).platform(), Platform::Chip8);
+        assert_eq!(CodeMap::analyse(&rom(&[0x00FF, 0xD120, 0x1204])).platform(), Platform::SuperChip);
+        assert_eq!(CodeMap::analyse(&rom(&[0x00FF, 0xF002])).platform(), Platform::XoChip);
+        // only what can run counts, not data that happens to look like an instruction
+        assert_eq!(CodeMap::analyse(&rom(&[0x1202, 0x1202, 0x00FF])).platform(), Platform::Chip8);
+    }
+
+    #[test]
+    fn odd_addresses_and_out_of_range() {
+        let map = CodeMap::analyse(&rom(&[0x1203, 0x0060, 0x0112, 0x0300]));
+
+        assert!(map.is_instruction(0x203) && map.is_instruction(0x205));
+        assert!(!map.is_instruction(0x202) && !map.is_code(0x202));
+        assert!(!map.is_code(0x100) && !map.is_code(0x400));
+    }
+}
@@ -0,0 +1,129 @@
+use zip::ZipArchive;
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::MAX_ROM_SIZE;
+use crate::metadata::{self, RomMetadata};
+
+pub const ARCHIVE_EXTENSION: &str = "zip";
+const MAX_OTHER_SIZE: u64 = 0x20000; // for CHIP-8 binaries and options files. A CHIP-8 binary's 16 bit offsets and lengths can't reach past this
+
+// what counts as a ROM inside an archive, anything else in a ROM pack is readmes and screenshots
+pub const ROM_EXTENSIONS: [&str; 6] = ["ch8", "c8", "sc8", "xo8", "bin", metadata::C8B_EXTENSION];
+
+pub fn is_archive(path: &Path) -> bool {
+    has_extension(path, &[ARCHIVE_EXTENSION])
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|extension| extensions.iter().any(|wanted| extension.eq_ignore_ascii_case(wanted)))
+}
+
+fn open(archive: &Path) -> Result<ZipArchive<File>, String> {
+    let file = File::open(archive).map_err(|e| format!("Could not read {}: {}", archive.display(), e))?;
+    ZipArchive::new(file).map_err(|e| format!("{} isn't a zip archive that can be read: {}", archive.display(), e))
+}
+
+// the names of the ROMs in an archive, in the order they're stored
+pub fn roms(archive: &Path) -> Result<Vec<String>, String> {
+    let mut archive = open(archive)?;
+    let names = (0..archive.len()).filter_map(|i| archive.by_index_raw(i).ok().filter(|file| file.is_file()).map(|file| file.name().to_string()));
+    Ok(names.filter(|name| has_extension(Path::new(name), &ROM_EXTENSIONS)).collect())
+}
+
+// the ROM to load from an archive when nobody has said which one, only possible when there's just the one
+pub fn only_rom(archive: &Path) -> Result<String, String> {
+    let mut roms = roms(archive)?;
+    match roms.len() {
+        0 => Err(format!("There are no CHIP-8 ROMs in {}.", archive.display())),
+        1 => Ok(roms.remove(0)),
+        count => Err(format!("There are {} ROMs in {}, pick one with a path like {}.", count, archive.display(), archive.join(&roms[0]).display())),
+    }
+}
+
+// ROMs inside archives are given a path as if the archive was a folder (pack.zip/games/pong.ch8) so they can be
+// remembered and opened again like any other file. This splits one back into the archive and the name inside it
+pub fn split(path: &Path) -> Option<(PathBuf, String)> {
+    let archive = path.ancestors().skip(1).find(|ancestor| is_archive(ancestor) && ancestor.is_file())?;
+    let entry = path.strip_prefix(archive).ok()?.iter().map(|part| part.to_string_lossy()).collect::<Vec<_>>().join("/");
+    Some((archive.to_path_buf(), entry))
+}
+
+// a ROM straight out of the archive without unpacking it anywhere, with its hints the same as if it were a file
+pub fn read(archive_path: &Path, entry: &str) -> Result<(Vec<u8>, Option<RomMetadata>), String> {
+    let mut archive = open(archive_path)?;
+    let mut read_entry = |name: &str| -> Result<Vec<u8>, String> {
+        let mut file = archive.by_name(name).map_err(|e| format!("Could not find {} in {}: {}", name, archive_path.display(), e))?;
+
+        // the size in the archive is only what it says, so no more than the limit is ever read whatever it is
+        let limit = size_limit(name);
+        let too_big = || format!("{} in {} is bigger than the {} bytes it could be", name, archive_path.display(), limit);
+        if file.size() > limit {
+            return Err(too_big());
+        }
+        let mut contents = vec![];
+        file.by_ref().take(limit + 1).read_to_end(&mut contents).map_err(|e| format!("Could not read {} from {}: {}", name, archive_path.display(), e))?;
+        if contents.len() as u64 > limit {
+            return Err(too_big());
+        }
+        Ok(contents)
+    };
+
+    let contents = read_entry(entry)?;
+    let path = archive_path.join(entry);
+    metadata::parse(&path, contents, |options_path| {
+        let (_, options_entry) = split(options_path)?;
+        read_entry(&options_entry).ok().and_then(|options| String::from_utf8(options).ok())
+    })
+}
+
+fn size_limit(name: &str) -> u64 {
+    if has_extension(Path::new(name), &[metadata::C8B_EXTENSION, "json"]) {
+        MAX_OTHER_SIZE
+    }
+    else {
+        MAX_ROM_SIZE as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    #[test]
+    fn reads_roms_from_archives() {
+        let dir = env::temp_dir().join(format!("fish_n_chip8-archive-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("pack.zip");
+
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        let huge = vec![0; MAX_ROM_SIZE + 1];
+        for (name, contents) in [("readme.txt", &b"hello"[..]), ("games/pong.ch8", &[0x12, 0x00]), ("games/pong.json", br#"{"tickrate": 30}"#), ("brix.CH8", &[0x00, 0xE0]), ("huge.ch8", &huge)] {
+            writer.start_file(name, FileOptions::default()).unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        writer.finish().unwrap();
+
+        assert_eq!(roms(&path).unwrap(), ["games/pong.ch8", "brix.CH8", "huge.ch8"]);
+        assert!(only_rom(&path).unwrap_err().starts_with("There are 3 ROMs"));
+
+        let (archive, entry) = split(&path.join("games").join("pong.ch8")).unwrap();
+        assert_eq!((archive.as_path(), entry.as_str()), (path.as_path(), "games/pong.ch8"));
+        assert_eq!(split(&dir.join("pong.ch8")), None);
+
+        let (rom, metadata) = read(&archive, &entry).unwrap();
+        assert_eq!(rom, [0x12, 0x00]);
+        assert_eq!(metadata.unwrap().cycles_per_frame, Some(30));
+        assert!(read(&path, "brix.CH8").unwrap().1.is_none());
+        assert!(read(&path, "huge.ch8").unwrap_err().contains("bigger than"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,215 @@
+use ggegui::egui;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+
+use crate::library::Library;
+use crate::transition::TransitionKind;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+#[serde(default)]
+pub struct AttractSettings {
+    pub playlist: Vec<String>, // rom_ids from the library, played in this order
+    pub seconds_per_rom: f32,
+    pub idle_seconds: f32,     // how long after someone stops playing before it moves on again
+    pub transition: TransitionKind,
+    pub start_on_launch: bool, // for a display that should run it from the moment it's switched on
+}
+
+impl Default for AttractSettings {
+    fn default() -> Self {
+        Self {
+            playlist: vec![],
+            seconds_per_rom: 60.0,
+            idle_seconds: 30.0,
+            transition: TransitionKind::Fade,
+            start_on_launch: false,
+        }
+    }
+}
+
+// cycles through the playlist, holding on the ROM that's showing while somebody plays it
+#[derive(Default)]
+pub struct Attract {
+    position: usize,
+    on_screen: f32, // seconds the current ROM has been shown for without anyone playing
+    idle: f32,      // seconds since the last key was pressed, while somebody's playing
+    played: bool,
+    unplayable: HashMap<String, String>, // rom_ids that couldn't be loaded and why, left out until it's started again
+}
+
+impl Attract {
+    // called every frame with the seconds since the last one and whether any keys are held, giving back where in the
+    // playlist to switch to when it's time to
+    pub fn update(&mut self, settings: &AttractSettings, elapsed: f32, input: bool) -> Option<usize> {
+        if settings.playlist.is_empty() {
+            return None;
+        }
+
+        if input {
+            self.played = true;
+            self.idle = 0.0;
+            return None;
+        }
+        if self.played {
+            self.idle += elapsed;
+            if self.idle < settings.idle_seconds {
+                return None;
+            }
+        }
+        else {
+            self.on_screen += elapsed;
+            if self.on_screen < settings.seconds_per_rom {
+                return None;
+            }
+        }
+
+        self.on_screen = 0.0;
+        self.played = false;
+        self.advance(settings)
+    }
+
+    // leaves a ROM out of the rotation, giving back where to go instead or None if nothing's left to play
+    pub fn drop_unplayable(&mut self, settings: &AttractSettings, rom_id: String, reason: String) -> Option<usize> {
+        self.unplayable.insert(rom_id, reason);
+        self.advance(settings)
+    }
+
+    fn advance(&mut self, settings: &AttractSettings) -> Option<usize> {
+        for _ in 0..settings.playlist.len() {
+            self.position = (self.position + 1) % settings.playlist.len();
+            if !self.unplayable.contains_key(&settings.playlist[self.position]) {
+                return Some(self.position);
+            }
+        }
+        None
+    }
+
+    pub fn playing(&self) -> bool {
+        self.played
+    }
+
+    // why a ROM in the playlist is being passed over, if it is
+    pub fn skipped(&self, rom_id: &str) -> Option<&str> {
+        self.unplayable.get(rom_id).map(String::as_str)
+    }
+}
+
+pub enum AttractAction {
+    Start,
+    Stop,
+}
+
+// the playlist editor. Gives back whether to start or stop cycling through it
+pub fn show(ui: &mut egui::Ui, settings: &mut AttractSettings, library: &Library, running: Option<&Attract>) -> Option<AttractAction> {
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        if let Some(attract) = running {
+            if ui.button("Stop").clicked() {
+                action = Some(AttractAction::Stop);
+            }
+            if attract.playing() {
+                ui.label("Somebody's playing");
+            }
+        }
+        else if ui.add_enabled(!settings.playlist.is_empty(), egui::Button::new("Start")).clicked() {
+            action = Some(AttractAction::Start);
+        }
+        ui.checkbox(&mut settings.start_on_launch, "Start when the emulator opens");
+    });
+    ui.horizontal(|ui| {
+        ui.label("Show each ROM for: ");
+        ui.add(egui::DragValue::new(&mut settings.seconds_per_rom).clamp_range(5.0..=3600.0).suffix(" s"));
+        ui.label("Move on after no input for: ");
+        ui.add(egui::DragValue::new(&mut settings.idle_seconds).clamp_range(5.0..=3600.0).suffix(" s"));
+    });
+    ui.horizontal(|ui| {
+        ui.label("Transition: ");
+        ui.selectable_value(&mut settings.transition, TransitionKind::Fade, "Fade");
+        ui.selectable_value(&mut settings.transition, TransitionKind::Dissolve, "Dissolve");
+    });
+    ui.separator();
+
+    let name = |rom_id: &String| library.get(rom_id).map_or_else(|| format!("{} (not in the library)", rom_id), |entry| entry.name.clone());
+    let mut moved = None;
+    let mut removed = None;
+    egui::Grid::new("Playlist").striped(true).show(ui, |ui| {
+        for (i, rom_id) in settings.playlist.iter().enumerate() {
+            ui.label(name(rom_id));
+            match running.and_then(|attract| attract.skipped(rom_id)) {
+                Some(reason) => ui.colored_label(ui.visuals().warn_fg_color, "Skipped").on_hover_text(reason),
+                None => ui.label(""),
+            };
+            if ui.add_enabled(i > 0, egui::Button::new("⏶")).on_hover_text("Play earlier").clicked() {
+                moved = Some(i - 1);
+            }
+            if ui.add_enabled(i + 1 < settings.playlist.len(), egui::Button::new("⏷")).on_hover_text("Play later").clicked() {
+                moved = Some(i);
+            }
+            if ui.button("Remove").clicked() {
+                removed = Some(i);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(i) = moved {
+        settings.playlist.swap(i, i + 1);
+    }
+    if let Some(i) = removed {
+        settings.playlist.remove(i);
+    }
+
+    let mut added = None;
+    egui::ComboBox::from_id_source("Add to playlist").selected_text("Add a ROM from the library").show_ui(ui, |ui| {
+        let mut entries: Vec<_> = library.iter().filter(|(rom_id, _)| !settings.playlist.contains(rom_id)).collect();
+        entries.sort_by_key(|(_, entry)| entry.name.to_lowercase());
+        for (rom_id, entry) in entries {
+            if ui.selectable_label(false, &entry.name).clicked() {
+                added = Some(rom_id.clone());
+            }
+        }
+    });
+    settings.playlist.extend(added);
+
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycles_until_someone_plays() {
+        let settings = AttractSettings { playlist: vec![String::from("a"), String::from("b")], seconds_per_rom: 10.0, idle_seconds: 5.0, ..AttractSettings::default() };
+        let mut attract = Attract::default();
+
+        assert_eq!(attract.update(&settings, 9.0, false), None);
+        assert_eq!(attract.update(&settings, 1.0, false), Some(1));
+        assert_eq!(attract.update(&settings, 10.0, false), Some(0));
+
+        // a player holds it on this ROM until they've left it alone for idle_seconds
+        assert_eq!(attract.update(&settings, 8.0, true), None);
+        assert!(attract.playing());
+        assert_eq!(attract.update(&settings, 4.0, false), None);
+        assert_eq!(attract.update(&settings, 20.0, true), None);
+        assert_eq!(attract.update(&settings, 4.0, false), None);
+        assert_eq!(attract.update(&settings, 1.0, false), Some(1));
+        assert!(!attract.playing());
+
+        assert_eq!(attract.update(&AttractSettings::default(), 100.0, false), None);
+
+        // a ROM that won't load is passed over from then on
+        let settings = AttractSettings { playlist: vec![String::from("a"), String::from("b"), String::from("c")], ..settings };
+        let mut attract = Attract::default();
+        assert_eq!(attract.update(&settings, 10.0, false), Some(1));
+        assert_eq!(attract.drop_unplayable(&settings, String::from("b"), String::from("Could not read b.ch8")), Some(2));
+        assert_eq!(attract.skipped("b"), Some("Could not read b.ch8"));
+        assert_eq!(attract.skipped("a"), None);
+        assert_eq!(attract.update(&settings, 10.0, false), Some(0));
+        assert_eq!(attract.update(&settings, 10.0, false), Some(2));
+        assert_eq!(attract.drop_unplayable(&settings, String::from("c"), String::new()), Some(0));
+        assert_eq!(attract.drop_unplayable(&settings, String::from("a"), String::new()), None);
+        assert_eq!(attract.update(&settings, 10.0, false), None);
+    }
+}
@@ -0,0 +1,309 @@
+use ggez::{Context, GameError, GameResult};
+use rodio::cpal::{self, BufferSize, FromSample, SampleFormat, SizedSample, Stream, StreamConfig};
+use rodio::cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use rodio::source::UniformSourceIterator;
+use rodio::{Decoder, Source};
+use serde::{Deserialize, Serialize};
+
+use std::io::{Cursor, Read};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::time::Duration;
+
+pub const SAMPLE_RATE: u32 = 44100;
+pub const BEEP_FREQUENCY: f32 = 440.0;
+const BEEP_VOLUME: f32 = 0.25;
+
+// short enough that a one tick beep is still audible, long enough to avoid pops
+const ATTACK_TIME: Duration = Duration::from_millis(2);
+const RELEASE_TIME: Duration = Duration::from_millis(8);
+
+const BEEP_OVERRIDE_PATH: &str = "/beep.wav"; // used instead of the generated tone if it is in the resources folder
+
+pub const PATTERN_BYTES: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64; // plays the pattern at 4000 bits a second
+
+pub const BUFFER_SIZES: [u32; 6] = [64, 128, 256, 512, 1024, 2048];
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub device: Option<String>,   // None uses the system default output
+    pub buffer_size: Option<u32>, // in frames, None lets the backend decide
+}
+
+pub struct Buzzer {
+    _stream: Stream,
+    gate: Arc<AtomicBool>,
+    sample_rate: u32,
+}
+
+impl Buzzer {
+    pub fn new(ctx: &Context, settings: &AudioSettings) -> GameResult<Buzzer> {
+        let gate = Arc::new(AtomicBool::new(false));
+
+        // the stream plays constantly and the envelope fades the tone in and out, stopping it would cut the wave off mid cycle
+        let beep = load_override(ctx).unwrap_or_else(|| Box::new(SquareWave::new(BEEP_FREQUENCY)));
+        let (stream, config) = play(settings, Envelope::new(beep, gate.clone()))?;
+
+        Ok(Buzzer { _stream: stream, gate, sample_rate: config.sample_rate.0 })
+    }
+
+    pub fn set_beeping(&mut self, beeping: bool) {
+        self.gate.store(beeping, Ordering::Relaxed);
+    }
+
+    pub fn latency(&self, settings: &AudioSettings) -> Option<Duration> {
+        settings.buffer_size.map(|frames| Duration::from_secs_f32(frames as f32 / self.sample_rate as f32))
+    }
+}
+
+// plays the source on the device picked in the configuration until the stream is dropped. ggez's own audio context
+// can't pick the device or buffer size so everything opens its own stream
+fn play(settings: &AudioSettings, source: impl Source<Item = f32> + Send + 'static) -> GameResult<(Stream, StreamConfig)> {
+    let host = cpal::default_host();
+    let device = match &settings.device {
+        Some(name) => host.output_devices()
+            .map_err(audio_error)?
+            .find(|device| device.name().map_or(false, |device_name| &device_name == name))
+            .ok_or_else(|| GameError::AudioError(format!("Audio device \"{}\" is not available", name)))?,
+        None => host.default_output_device()
+            .ok_or_else(|| GameError::AudioError(String::from("There is no audio output device")))?,
+    };
+
+    let supported = device.default_output_config().map_err(audio_error)?;
+    let mut config: StreamConfig = supported.config();
+    if let Some(frames) = settings.buffer_size {
+        config.buffer_size = BufferSize::Fixed(frames);
+    }
+
+    let source = UniformSourceIterator::new(source, config.channels, config.sample_rate.0);
+
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build_stream::<f32>(&device, &config, source),
+        SampleFormat::I16 => build_stream::<i16>(&device, &config, source),
+        SampleFormat::U16 => build_stream::<u16>(&device, &config, source),
+        format => Err(GameError::AudioError(format!("Unsupported sample format {}", format))),
+    }?;
+    stream.play().map_err(audio_error)?;
+
+    Ok((stream, config))
+}
+
+// plays an XO-CHIP audio pattern on repeat, picking up changes to it and the pitch as it goes
+pub struct PatternPreview {
+    _stream: Stream,
+    pattern: Arc<[AtomicU8; PATTERN_BYTES]>,
+    pitch: Arc<AtomicU8>,
+}
+
+impl PatternPreview {
+    pub fn new(settings: &AudioSettings, pattern: &[u8; PATTERN_BYTES], pitch: u8) -> GameResult<PatternPreview> {
+        let shared_pattern = Arc::new(pattern.map(AtomicU8::new));
+        let shared_pitch = Arc::new(AtomicU8::new(pitch));
+        let wave = PatternWave { pattern: shared_pattern.clone(), pitch: shared_pitch.clone(), position: 0.0 };
+        let (stream, _) = play(settings, wave)?;
+
+        Ok(PatternPreview { _stream: stream, pattern: shared_pattern, pitch: shared_pitch })
+    }
+
+    pub fn update(&self, pattern: &[u8; PATTERN_BYTES], pitch: u8) {
+        for (shared, &byte) in self.pattern.iter().zip(pattern) {
+            shared.store(byte, Ordering::Relaxed);
+        }
+        self.pitch.store(pitch, Ordering::Relaxed);
+    }
+}
+
+// how many of the pattern's bits play each second
+pub fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2.0f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+pub fn output_device_names() -> Vec<String> {
+    match cpal::default_host().output_devices() {
+        Ok(devices) => devices.filter_map(|device| device.name().ok()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+fn build_stream<T>(device: &cpal::Device, config: &StreamConfig, mut source: impl Iterator<Item = f32> + Send + 'static) -> GameResult<Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for sample in data.iter_mut() {
+                *sample = T::from_sample(source.next().unwrap_or(0.0));
+            }
+        },
+        |e| println!("Audio stream error: {}", e),
+        None,
+    ).map_err(audio_error)
+}
+
+fn audio_error(e: impl std::fmt::Display) -> GameError {
+    GameError::AudioError(e.to_string())
+}
+
+fn load_override(ctx: &Context) -> Option<Box<dyn Source<Item = f32> + Send>> {
+    let mut bytes = vec![];
+    ctx.fs.open(BEEP_OVERRIDE_PATH).ok()?.read_to_end(&mut bytes).ok()?;
+
+    match Decoder::new(Cursor::new(bytes)) {
+        Ok(decoder) => Some(Box::new(decoder.repeat_infinite().convert_samples())),
+        Err(e) => {
+            println!("Ignoring {}, could not decode it: {}", BEEP_OVERRIDE_PATH, e);
+            None
+        }
+    }
+}
+
+struct Envelope<S> {
+    source: S,
+    gate: Arc<AtomicBool>,
+    gain: f32,
+    attack_step: f32,
+    release_step: f32,
+}
+
+impl<S: Source<Item = f32>> Envelope<S> {
+    fn new(source: S, gate: Arc<AtomicBool>) -> Envelope<S> {
+        let samples_per_second = source.sample_rate() as f32 * source.channels() as f32;
+
+        Envelope {
+            attack_step: 1.0 / (ATTACK_TIME.as_secs_f32() * samples_per_second),
+            release_step: 1.0 / (RELEASE_TIME.as_secs_f32() * samples_per_second),
+            source,
+            gate,
+            gain: 0.0,
+        }
+    }
+}
+
+impl<S: Source<Item = f32>> Iterator for Envelope<S> {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.source.next()?;
+
+        if self.gate.load(Ordering::Relaxed) {
+            self.gain = (self.gain + self.attack_step).min(1.0);
+        }
+        else {
+            self.gain = (self.gain - self.release_step).max(0.0);
+        }
+
+        Some(sample * self.gain)
+    }
+}
+
+impl<S: Source<Item = f32>> Source for Envelope<S> {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+pub struct SquareWave {
+    phase: f32,
+    step: f32,
+}
+
+impl SquareWave {
+    pub fn new(frequency: f32) -> SquareWave {
+        SquareWave {
+            phase: 0.0,
+            step: frequency / SAMPLE_RATE as f32,
+        }
+    }
+}
+
+impl Iterator for SquareWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.phase = (self.phase + self.step).fract();
+
+        if self.phase < 0.5 {
+            Some(BEEP_VOLUME)
+        }
+        else {
+            Some(-BEEP_VOLUME)
+        }
+    }
+}
+
+impl Source for SquareWave {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+// steps through the pattern's 128 bits one after the other, each 1 bit playing high and each 0 low
+struct PatternWave {
+    pattern: Arc<[AtomicU8; PATTERN_BYTES]>,
+    pitch: Arc<AtomicU8>,
+    position: f32, // in bits
+}
+
+impl Iterator for PatternWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let bits = (PATTERN_BYTES * 8) as f32;
+        self.position = (self.position + pattern_rate(self.pitch.load(Ordering::Relaxed)) / SAMPLE_RATE as f32) % bits;
+
+        let bit = self.position as usize;
+        let byte = self.pattern[bit / 8].load(Ordering::Relaxed);
+
+        if byte & (0x80 >> (bit % 8)) != 0 {
+            Some(BEEP_VOLUME)
+        }
+        else {
+            Some(-BEEP_VOLUME)
+        }
+    }
+}
+
+impl Source for PatternWave {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
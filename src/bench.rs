@@ -0,0 +1,60 @@
+use std::fs;
+use std::process;
+use std::time::Instant;
+
+use crate::cpu::{CPU, DEFAULT_CYCLES_PER_FRAME, MAX_ROM_SIZE};
+
+const DEFAULT_INSTRUCTIONS: u64 = 50_000_000;
+
+// runs a ROM flat out with no window or sound so changes to the core can be timed
+pub fn bench_main(args: &[String]) {
+    let dump_ascii = args.iter().any(|arg| arg == "--dump-ascii");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--dump-ascii").collect();
+
+    let Some(path) = args.first() else {
+        eprintln!("Usage: fish_n_chip8 --bench <rom> [instructions] [--dump-ascii]");
+        process::exit(2);
+    };
+
+    let instructions = match args.get(1).map(|count| count.parse::<u64>()) {
+        Some(Ok(count)) => count,
+        Some(Err(e)) => {
+            eprintln!("Invalid instruction count: {}", e);
+            process::exit(2);
+        }
+        None => DEFAULT_INSTRUCTIONS,
+    };
+
+    let rom = match fs::read(path) {
+        Ok(rom) if rom.len() <= MAX_ROM_SIZE => rom,
+        Ok(rom) => {
+            eprintln!("{} is {} bytes, the most that fits in memory is {} bytes", path, rom.len(), MAX_ROM_SIZE);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    let mut cpu = CPU::new();
+    cpu.load_rom(&rom);
+
+    let start = Instant::now();
+
+    for executed in 0..instructions {
+        // keep the timers ticking at the same rate as the default speed would
+        if executed % DEFAULT_CYCLES_PER_FRAME as u64 == 0 {
+            cpu.timer_tick();
+        }
+        cpu.handle_opcode();
+    }
+
+    let elapsed = start.elapsed();
+    println!("{} instructions in {:.3}s", instructions, elapsed.as_secs_f64());
+    println!("{:.2} million instructions per second", instructions as f64 / elapsed.as_secs_f64() / 1_000_000.0);
+    println!("Display hash at the end: {:016x}", cpu.display_hash());
+    if dump_ascii {
+        print!("{}", cpu.ascii_screen());
+    }
+}
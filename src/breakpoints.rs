@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use crate::analysis::CodeMap;
+use crate::cpu::{CPU, RAM_SIZE};
+use crate::instruction::Instruction;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum BreakpointKind {
+    Execute, // stops before the instruction at the address runs
+    Write,   // a watchpoint, stops after an instruction changes the byte at the address
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+}
+
+impl Comparison {
+    pub const ALL: [Comparison; 4] = [Comparison::Equal, Comparison::NotEqual, Comparison::Less, Comparison::Greater];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Equal => "==",
+            Comparison::NotEqual => "!=",
+            Comparison::Less => "<",
+            Comparison::Greater => ">",
+        }
+    }
+}
+
+// only stop if a register compares to a value
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Condition {
+    pub register: u8,
+    pub comparison: Comparison,
+    pub value: u8,
+}
+
+impl Default for Condition {
+    fn default() -> Self {
+        Self { register: 0, comparison: Comparison::Equal, value: 0 }
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, cpu: &CPU) -> bool {
+        let register = cpu.registers()[self.register as usize & 0xF];
+        match self.comparison {
+            Comparison::Equal => register == self.value,
+            Comparison::NotEqual => register != self.value,
+            Comparison::Less => register < self.value,
+            Comparison::Greater => register > self.value,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "V{:X} {} {:02X}", self.register, self.comparison.symbol(), self.value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Breakpoint {
+    pub kind: BreakpointKind,
+    pub address: u16,
+    pub enabled: bool,
+    pub condition: Option<Condition>,
+    pub hits: u32,
+}
+
+impl Default for Breakpoint {
+    fn default() -> Self {
+        Self {
+            kind: BreakpointKind::Execute,
+            address: 0x200,
+            enabled: true,
+            condition: None,
+            hits: 0,
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            BreakpointKind::Execute => write!(f, "Breakpoint at {:03X}", self.address)?,
+            BreakpointKind::Write => write!(f, "Watchpoint on {:03X}", self.address)?,
+        }
+        match self.condition {
+            Some(condition) => write!(f, " when {}", condition),
+            None => Ok(()),
+        }
+    }
+}
+
+// the bytes the watchpoints are watching, read before an instruction runs to compare with afterwards
+pub fn watched_values(breakpoints: &[Breakpoint], cpu: &CPU) -> Vec<u8> {
+    breakpoints.iter().map(|breakpoint| cpu.read_memory(breakpoint.address)).collect()
+}
+
+// checks after an instruction has run, counting a hit on every breakpoint that triggered and returning the first.
+// Execute breakpoints look at the instruction that's about to run so resuming doesn't stop straight away again
+pub fn check(breakpoints: &mut [Breakpoint], cpu: &CPU, before: &[u8]) -> Option<usize> {
+    let mut first = None;
+
+    for (i, breakpoint) in breakpoints.iter_mut().enumerate() {
+        let triggered = breakpoint.enabled && match breakpoint.kind {
+            BreakpointKind::Execute => cpu.pc() == breakpoint.address,
+            BreakpointKind::Write => before.get(i).is_some_and(|&value| value != cpu.read_memory(breakpoint.address)),
+        };
+
+        if triggered && breakpoint.condition.is_none_or(|condition| condition.holds(cpu)) {
+            breakpoint.hits += 1;
+            first = first.or(Some(i));
+        }
+    }
+
+    first
+}
+
+// the first address the instruction about to run will write over that the analyser found to be code, for stopping
+// on self-modifying code. Only FX55 and FX33 write to memory
+pub fn code_write(map: &CodeMap, cpu: &CPU) -> Option<u16> {
+    let length = match Instruction::decode(cpu.read_opcode(cpu.pc())) {
+        Instruction::StoreRegs(x) => x as usize + 1,
+        Instruction::Bcd(_) => 3,
+        _ => return None,
+    };
+
+    let start = cpu.addr_reg() as usize;
+    (start..start + length).map(|addr| addr % RAM_SIZE).find(|&addr| map.is_code(addr)).map(|addr| addr as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn breakpoints_and_watchpoints() {
+        // 200: V0 = 5, 202: I = 300, 204: [I] = V0, 206: loop forever
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06]);
+
+        let mut breakpoints = vec![
+            Breakpoint { address: 0x202, condition: Some(Condition { register: 0, comparison: Comparison::Equal, value: 5 }), ..Default::default() },
+            Breakpoint { kind: BreakpointKind::Write, address: 0x300, ..Default::default() },
+            Breakpoint { address: 0x206, enabled: false, ..Default::default() },
+        ];
+
+        let mut hits = vec![];
+        for _ in 0..5 {
+            let before = watched_values(&breakpoints, &cpu);
+            cpu.handle_opcode();
+            hits.push(check(&mut breakpoints, &cpu, &before));
+        }
+
+        assert_eq!(hits, [Some(0), None, Some(1), None, None]);
+        assert_eq!(breakpoints.iter().map(|breakpoint| breakpoint.hits).collect::<Vec<_>>(), [1, 1, 0]);
+        assert_eq!(breakpoints[0].to_string(), "Breakpoint at 202 when V0 == 05");
+
+        // before anything has run, with nothing watched yet, only the one on the first instruction stops it
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x60, 0x05]);
+        let mut breakpoints = vec![Breakpoint::default(), Breakpoint { kind: BreakpointKind::Write, address: 0x200, ..Default::default() }];
+        assert_eq!(check(&mut breakpoints, &cpu, &[]), Some(0));
+        assert_eq!(breakpoints[1].hits, 0);
+    }
+
+    #[test]
+    fn finds_writes_to_code() {
+        // 200: I = 20C, 202: [I] = V0, V1, 204: I = 20B, 206: [I] = V0, V1, 208: jump to 20A, 20A: loop forever, 20C: data
+        let rom = [0xA2, 0x0C, 0xF1, 0x55, 0xA2, 0x0B, 0xF1, 0x55, 0x12, 0x0A, 0x12, 0x0A, 0x00, 0x00];
+        let map = CodeMap::analyse(&rom);
+        let mut cpu = CPU::new();
+        cpu.load_rom(&rom);
+
+        let mut writes = vec![];
+        for _ in 0..5 {
+            writes.push(code_write(&map, &cpu));
+            cpu.handle_opcode();
+        }
+
+        // writing over the data is fine, but the second write starts on the last byte of the loop
+        assert_eq!(writes, [None, None, None, Some(0x20B), None]);
+    }
+}
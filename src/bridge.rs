@@ -0,0 +1,106 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, ToSocketAddrs, UdpSocket};
+
+use crate::cpu::CPU;
+
+pub const DEFAULT_BRIDGE_ADDRESS: &str = "127.0.0.1:9000";
+
+const FRAME_ADDRESS: &str = "/chip8/frame";
+
+// sends the screen as an OSC message over UDP whenever it changes, for mirroring it onto LED matrices and other
+// hardware. The message is /chip8/frame with the width and height as ints and a blob of the pixels, one bit each, a
+// row at a time with the leftmost pixel in the top bit and each row padded to a whole byte
+pub struct DisplayBridge {
+    socket: UdpSocket,
+    target: SocketAddr,
+    last_sent: Option<u64>, // the display_hash of the last frame sent
+}
+
+impl DisplayBridge {
+    pub fn start(address: &str) -> io::Result<DisplayBridge> {
+        let (socket, target) = udp_socket(address)?;
+        Ok(DisplayBridge { socket, target, last_sent: None })
+    }
+
+    pub fn send(&mut self, cpu: &CPU) -> io::Result<()> {
+        let hash = cpu.display_hash();
+        if self.last_sent == Some(hash) {
+            return Ok(());
+        }
+        self.last_sent = Some(hash);
+
+        let message = frame_message(cpu.width(), cpu.height(), |x, y| cpu.pixel(x, y));
+        send_to(&self.socket, &message, self.target)
+    }
+}
+
+// a socket for sending to address, for anything that mirrors the screen onto other hardware
+pub(crate) fn udp_socket(address: &str) -> io::Result<(UdpSocket, SocketAddr)> {
+    let target = address.to_socket_addrs()?.next().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the address didn't resolve to anything"))?;
+    let local: SocketAddr = if target.is_ipv4() { (Ipv4Addr::UNSPECIFIED, 0).into() } else { "[::]:0".parse().expect("a valid address") };
+    let socket = UdpSocket::bind(local)?;
+    socket.set_nonblocking(true)?; // a frame that can't go straight away isn't worth holding the emulator up for
+
+    Ok((socket, target))
+}
+
+pub(crate) fn send_to(socket: &UdpSocket, message: &[u8], target: SocketAddr) -> io::Result<()> {
+    match socket.send_to(message, target) {
+        // nothing listening on the other end yet, or a full buffer, neither of which the player needs telling about
+        Err(e) if matches!(e.kind(), io::ErrorKind::ConnectionRefused | io::ErrorKind::WouldBlock) => Ok(()),
+        result => result.map(|_| ()),
+    }
+}
+
+fn frame_message(width: usize, height: usize, pixel: impl Fn(usize, usize) -> bool) -> Vec<u8> {
+    let row_bytes = width.div_ceil(8);
+    let mut pixels = vec![0; row_bytes * height];
+    for y in 0..height {
+        for x in 0..width {
+            if pixel(x, y) {
+                pixels[y * row_bytes + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+
+    let mut message = osc_string(FRAME_ADDRESS);
+    message.extend(osc_string(",iib"));
+    message.extend((width as i32).to_be_bytes());
+    message.extend((height as i32).to_be_bytes());
+    message.extend((pixels.len() as i32).to_be_bytes());
+    message.extend(&pixels);
+    pad(&mut message);
+
+    message
+}
+
+// null terminated and padded to a multiple of 4 bytes, like everything in OSC
+fn osc_string(string: &str) -> Vec<u8> {
+    let mut bytes = string.as_bytes().to_vec();
+    bytes.push(0);
+    pad(&mut bytes);
+    bytes
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packs_frames_into_osc() {
+        // a diagonal line on a 12x2 screen, so the rows need padding out to 2 bytes
+        let message = frame_message(12, 2, |x, y| x == y || x == 11);
+
+        let mut expected = b"/chip8/frame\0\0\0\0,iib\0\0\0\0".to_vec();
+        expected.extend([0, 0, 0, 12, 0, 0, 0, 2, 0, 0, 0, 4]);
+        expected.extend([0b1000_0000, 0b0001_0000, 0b0100_0000, 0b0001_0000]);
+        assert_eq!(message, expected);
+
+        assert_eq!(message.len() % 4, 0);
+        assert_eq!(osc_string("/abc"), b"/abc\0\0\0\0");
+    }
+}
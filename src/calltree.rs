@@ -0,0 +1,232 @@
+use ggegui::egui::{self, RichText};
+
+use std::collections::HashMap;
+
+use crate::cpu::CPU;
+
+const MAX_NODES: usize = 10_000; // calls past this in one frame are still counted, just not kept in the tree
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CallNode {
+    pub addr: u16,
+    pub from: u16,         // the 2NNN that called it
+    pub instructions: u32, // including the ones in the subroutines it called
+    pub children: Vec<CallNode>,
+    pub returned: bool,    // false if it was still running when the frame ended
+    pub continued: bool,   // true if it was called in an earlier frame
+}
+
+impl CallNode {
+    // the instructions it ran itself, not counting its subroutines
+    pub fn own_instructions(&self) -> u32 {
+        self.instructions - self.children.iter().map(|child| child.instructions).sum::<u32>()
+    }
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct FrameCalls {
+    pub frame: u32,
+    pub instructions: u32,
+    pub outside: u32, // run outside of any subroutine
+    pub calls: Vec<CallNode>,
+    pub truncated: bool, // there were more than MAX_NODES calls
+}
+
+impl FrameCalls {
+    // the subroutines that ran the most instructions themselves, most first
+    pub fn heaviest(&self) -> Vec<(u16, u32)> {
+        let mut totals = HashMap::new();
+        let mut nodes: Vec<&CallNode> = self.calls.iter().collect();
+        while let Some(node) = nodes.pop() {
+            *totals.entry(node.addr).or_insert(0) += node.own_instructions();
+            nodes.extend(&node.children);
+        }
+
+        let mut totals: Vec<(u16, u32)> = totals.into_iter().collect();
+        totals.sort_by_key(|&(addr, instructions)| (std::cmp::Reverse(instructions), addr));
+        totals
+    }
+}
+
+// every 2NNN and 00EE as a tree for each frame, for seeing which subroutine is taking up the frame when the flat stack
+// only shows where the program is right now
+#[derive(Default)]
+pub struct CallTree {
+    pub enabled: bool,
+    frame: u32,
+    current: FrameCalls,
+    open: Vec<CallNode>, // the calls that haven't returned yet, outermost first
+    nodes: usize,
+    last: Option<FrameCalls>, // the last whole frame, which is what's shown
+}
+
+impl CallTree {
+    pub fn next_frame(&mut self, cpu: &CPU) {
+        self.frame = self.frame.wrapping_add(1);
+        if !self.enabled {
+            self.open.clear();
+            self.current = FrameCalls { frame: self.frame, ..Default::default() };
+            return;
+        }
+
+        // the calls still running carry on into the next frame. Loading a state or ROM can take away calls that never
+        // returned, so anything deeper than the stack is now is forgotten
+        let continued: Vec<CallNode> = self.open.iter().take(cpu.stack().len()).map(|call| CallNode {
+            instructions: 0,
+            children: vec![],
+            continued: true,
+            ..call.clone()
+        }).collect();
+        while let Some(call) = self.open.pop() {
+            self.attach(call);
+        }
+        self.open = continued;
+
+        let finished = std::mem::replace(&mut self.current, FrameCalls { frame: self.frame, ..Default::default() });
+        self.nodes = 0;
+        if finished.instructions > 0 {
+            self.last = Some(finished);
+        }
+    }
+
+    // called before an instruction runs, the stack depth it started with
+    pub fn pending(&self, cpu: &CPU) -> Option<(u16, usize)> {
+        self.enabled.then(|| (cpu.pc(), cpu.stack().len()))
+    }
+
+    // called after it ran. A call or return is told apart from the stack growing or shrinking, so one that overflowed
+    // or underflowed isn't counted as either
+    pub fn finish(&mut self, pending: Option<(u16, usize)>, cpu: &CPU) {
+        let Some((pc, depth)) = pending else {
+            return;
+        };
+
+        // a 2NNN counts towards the caller and a 00EE towards the subroutine returning
+        self.current.instructions += 1;
+        match self.open.last_mut() {
+            Some(call) => call.instructions += 1,
+            None => self.current.outside += 1,
+        }
+
+        if cpu.stack().len() > depth {
+            self.open.push(CallNode { addr: cpu.pc(), from: pc, instructions: 0, children: vec![], returned: false, continued: false });
+        }
+        else if cpu.stack().len() < depth {
+            if let Some(call) = self.open.pop() {
+                self.attach(CallNode { returned: true, ..call });
+            }
+        }
+    }
+
+    fn attach(&mut self, call: CallNode) {
+        let kept = self.nodes < MAX_NODES;
+        self.nodes += 1;
+        self.current.truncated |= !kept;
+        match self.open.last_mut() {
+            Some(parent) => {
+                parent.instructions += call.instructions;
+                if kept {
+                    parent.children.push(call);
+                }
+            },
+            None => {
+                if kept {
+                    self.current.calls.push(call);
+                }
+            },
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, cpu: &CPU) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "Record calls");
+            if ui.button("Clear").clicked() {
+                self.last = None;
+            }
+        });
+        let stack: Vec<String> = cpu.stack().iter().map(|addr| format!("{:03X}", addr)).collect();
+        ui.label(format!("Returns to: {}", if stack.is_empty() { String::from("nothing") } else { stack.join(" < ") }));
+        ui.separator();
+
+        let Some(frame) = &self.last else {
+            ui.label("The calls from the last frame show up here while recording.");
+            return;
+        };
+
+        let total = frame.instructions.max(1);
+        let percent = |instructions: u32| instructions as f32 * 100.0 / total as f32;
+        ui.label(format!("Frame {}: {} instructions, {} outside any subroutine", frame.frame, frame.instructions, frame.outside));
+        for (addr, instructions) in frame.heaviest().into_iter().take(3) {
+            ui.monospace(format!("{:03X}  {:>6} instructions itself  {:>3.0}%", addr, instructions, percent(instructions)));
+        }
+        if frame.truncated {
+            ui.label(format!("Only the first {} calls are shown.", MAX_NODES));
+        }
+        ui.separator();
+
+        egui::ScrollArea::vertical().auto_shrink(false).show(ui, |ui| {
+            for (i, call) in frame.calls.iter().enumerate() {
+                show_call(ui, i, call, &percent);
+            }
+        });
+    }
+}
+
+fn show_call(ui: &mut egui::Ui, i: usize, call: &CallNode, percent: &dyn Fn(u32) -> f32) {
+    let mut text = format!("{:03X} from {:03X}  {} instructions  {:.0}%", call.addr, call.from, call.instructions, percent(call.instructions));
+    if call.continued {
+        text += "  called in an earlier frame";
+    }
+    if !call.returned {
+        text += "  still running";
+    }
+
+    if call.children.is_empty() {
+        ui.monospace(text);
+        return;
+    }
+    egui::CollapsingHeader::new(RichText::new(text).monospace()).id_source(i).show(ui, |ui| {
+        for (i, child) in call.children.iter().enumerate() {
+            show_call(ui, i, child, percent);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_a_tree_of_calls() {
+        // 200: call 206, 202: call 20A, 204: loop, 206: call 20A, 208: return, 20A: V0 += 1, 20C: return
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x22, 0x06, 0x22, 0x0A, 0x12, 0x04, 0x22, 0x0A, 0x00, 0xEE, 0x70, 0x01, 0x00, 0xEE]);
+
+        let mut tree = CallTree { enabled: true, ..Default::default() };
+        let mut run = |tree: &mut CallTree, steps: usize| {
+            for _ in 0..steps {
+                let pending = tree.pending(&cpu);
+                cpu.handle_opcode();
+                tree.finish(pending, &cpu);
+            }
+            tree.next_frame(&cpu);
+        };
+
+        // the frame ends partway through the second call to 20A
+        run(&mut tree, 7);
+        let leaf = CallNode { addr: 0x20A, from: 0x206, instructions: 2, children: vec![], returned: true, continued: false };
+        let frame = tree.last.clone().unwrap();
+        assert_eq!((frame.instructions, frame.outside), (7, 2));
+        assert_eq!(frame.calls, [
+            CallNode { addr: 0x206, from: 0x200, instructions: 4, children: vec![leaf.clone()], returned: true, continued: false },
+            CallNode { from: 0x202, instructions: 1, returned: false, ..leaf.clone() },
+        ]);
+        assert_eq!(frame.heaviest(), [(0x20A, 3), (0x206, 2)]);
+
+        // so it carries on into the next one
+        run(&mut tree, 3);
+        let frame = tree.last.clone().unwrap();
+        assert_eq!((frame.instructions, frame.outside), (3, 2));
+        assert_eq!(frame.calls, [CallNode { from: 0x202, instructions: 1, continued: true, ..leaf }]);
+    }
+}
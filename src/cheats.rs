@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::CPU;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum CheatKind {
+    Poke,   // written once when asked to
+    Freeze, // written every frame while enabled
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Cheat {
+    pub name: String,
+    pub address: u16,
+    pub value: u8,
+    pub kind: CheatKind,
+    pub enabled: bool,
+}
+
+impl Default for Cheat {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            address: 0x200,
+            value: 0,
+            kind: CheatKind::Freeze,
+            enabled: true,
+        }
+    }
+}
+
+impl Cheat {
+    pub fn write(&self, cpu: &mut CPU) {
+        // skipping unchanged bytes keeps the CPU's decoded instructions cached
+        if cpu.read_memory(self.address) != self.value {
+            cpu.write_memory(self.address, self.value);
+        }
+    }
+}
+
+pub fn apply_frozen(cheats: &[Cheat], cpu: &mut CPU) {
+    for cheat in cheats.iter().filter(|cheat| cheat.enabled && cheat.kind == CheatKind::Freeze) {
+        cheat.write(cpu);
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum SearchFilter {
+    Equal(u8),
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+impl SearchFilter {
+    fn matches(self, old: u8, new: u8) -> bool {
+        match self {
+            SearchFilter::Equal(value) => new == value,
+            SearchFilter::Changed => new != old,
+            SearchFilter::Unchanged => new == old,
+            SearchFilter::Increased => new > old,
+            SearchFilter::Decreased => new < old,
+        }
+    }
+}
+
+// narrows down where a game keeps something by comparing snapshots of RAM as it changes
+pub struct MemorySearch {
+    candidates: Vec<u16>,
+    snapshot: Vec<u8>,
+    searches: u32,
+}
+
+impl MemorySearch {
+    pub fn new(memory: &[u8]) -> MemorySearch {
+        MemorySearch {
+            candidates: (0..memory.len() as u16).collect(),
+            snapshot: memory.to_vec(),
+            searches: 0,
+        }
+    }
+
+    pub fn refine(&mut self, memory: &[u8], filter: SearchFilter) {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&addr| filter.matches(snapshot[addr as usize], memory[addr as usize]));
+        self.snapshot.copy_from_slice(memory);
+        self.searches += 1;
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    pub fn previous(&self, addr: u16) -> u8 {
+        self.snapshot[addr as usize]
+    }
+
+    pub fn searches(&self) -> u32 {
+        self.searches
+    }
+}
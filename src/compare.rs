@@ -0,0 +1,167 @@
+use std::path::Path;
+use std::process;
+
+use crate::config::Config;
+use crate::cpu::{CPU, MAX_ROM_SIZE};
+use crate::movie::InputMovie;
+
+const DEFAULT_FRAMES: u32 = 60 * 60;
+const RUN_SEED: u64 = 0; // runs need the same random numbers every time or they'd diverge straight away
+
+// a headless run of a ROM at a config's speed and quirks
+pub(crate) struct Run {
+    pub(crate) cpu: CPU,
+    pub(crate) config: Config,
+    cycle_budget: f64,
+}
+
+impl Run {
+    pub(crate) fn new(rom: &[u8], config: Config) -> Run {
+        let mut cpu = CPU::new();
+        cpu.quirks = config.quirks;
+        cpu.seed_rng(RUN_SEED);
+        cpu.load_rom(rom);
+
+        Run { cpu, config, cycle_budget: 0.0 }
+    }
+
+    pub(crate) fn cycles_this_frame(&mut self) -> u32 {
+        self.config.cycles_per_tick(&mut self.cycle_budget)
+    }
+}
+
+// plays the same input through a ROM under two configurations and stops at the first point they disagree,
+// for checking a change to the core or a quirk doesn't change how a game behaves
+pub fn compare_main(args: &[String]) {
+    let dump_ascii = args.iter().any(|arg| arg == "--dump-ascii");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--dump-ascii").collect();
+
+    let [rom_path, config_a, config_b, rest @ ..] = args.as_slice() else {
+        eprintln!("Usage: fish_n_chip8 --compare <rom> <config a> <config b> [input movie] [frames] [--dump-ascii]");
+        eprintln!("Use - for a config to run with the defaults");
+        eprintln!("--dump-ascii draws both screens where the runs diverge or at the end");
+        process::exit(2);
+    };
+
+    let rom = read_rom(rom_path);
+    let movie = match rest.first() {
+        Some(path) => InputMovie::load(Path::new(path)).unwrap_or_else(|e| exit_with(e)),
+        None => InputMovie::default(),
+    };
+    let frames = match rest.get(1).map(|frames| frames.parse::<u32>()) {
+        Some(Ok(frames)) => frames,
+        Some(Err(e)) => exit_with(format!("Invalid frame count: {}", e)),
+        None => DEFAULT_FRAMES.max(movie.length()),
+    };
+
+    let mut a = Run::new(&rom, load_config(config_a));
+    let mut b = Run::new(&rom, load_config(config_b));
+    // with the same speed they can be checked after every instruction, otherwise only where frames end
+    let same_speed = a.config.speed_mode == b.config.speed_mode
+        && a.config.cycles_per_frame == b.config.cycles_per_frame
+        && a.config.instructions_per_second == b.config.instructions_per_second;
+
+    for frame in 0..frames {
+        let keys = movie.keys_at(frame);
+        for run in [&mut a, &mut b] {
+            run.cpu.set_keys(keys);
+            run.cpu.timer_tick();
+        }
+
+        if same_speed {
+            for instruction in 0..a.cycles_this_frame() {
+                let pc = a.cpu.pc();
+                let display_changed = a.cpu.handle_opcode().display_changed | b.cpu.handle_opcode().display_changed;
+
+                if let Some(differences) = differences(&a.cpu, &b.cpu, display_changed) {
+                    report(frame, Some((instruction, pc)), &differences, dump_ascii.then_some([&a.cpu, &b.cpu]));
+                }
+            }
+            b.cycles_this_frame();
+        }
+        else {
+            for run in [&mut a, &mut b] {
+                for _ in 0..run.cycles_this_frame() {
+                    run.cpu.handle_opcode();
+                }
+            }
+
+            if let Some(differences) = differences(&a.cpu, &b.cpu, true) {
+                report(frame, None, &differences, dump_ascii.then_some([&a.cpu, &b.cpu]));
+            }
+        }
+    }
+
+    println!("No differences in {} frames", frames);
+    if dump_ascii {
+        print!("{}", a.cpu.ascii_screen());
+    }
+}
+
+pub(crate) fn read_rom(path: &str) -> Vec<u8> {
+    match std::fs::read(path) {
+        Ok(rom) if rom.len() <= MAX_ROM_SIZE => rom,
+        Ok(rom) => exit_with(format!("{} is {} bytes, the most that fits in memory is {} bytes", path, rom.len(), MAX_ROM_SIZE)),
+        Err(e) => exit_with(format!("Could not read {}: {}", path, e)),
+    }
+}
+
+pub(crate) fn load_config(path: &str) -> Config {
+    if path == "-" {
+        return Config::default();
+    }
+
+    Config::from_file(Path::new(path)).unwrap_or_else(|e| exit_with(e))
+}
+
+pub(crate) fn differences(a: &CPU, b: &CPU, check_display: bool) -> Option<Vec<String>> {
+    let mut differences = vec![];
+    let mut check = |name: &str, a: String, b: String| {
+        if a != b {
+            differences.push(format!("{}: {} vs {}", name, a, b));
+        }
+    };
+
+    check("PC", format!("{:03X}", a.pc()), format!("{:03X}", b.pc()));
+    check("I", format!("{:03X}", a.addr_reg()), format!("{:03X}", b.addr_reg()));
+    for reg in 0..16 {
+        check(&format!("V{:X}", reg), format!("{:02X}", a.registers()[reg]), format!("{:02X}", b.registers()[reg]));
+    }
+    check("Stack", format!("{:03X?}", a.stack()), format!("{:03X?}", b.stack()));
+    check("Delay timer", a.delay_timer().to_string(), b.delay_timer().to_string());
+    check("Sound timer", a.sound_timer().to_string(), b.sound_timer().to_string());
+    check("Resolution", format!("{}x{}", a.width(), a.height()), format!("{}x{}", b.width(), b.height()));
+
+    if check_display && a.display_hash() != b.display_hash() && (a.width(), a.height()) == (b.width(), b.height()) {
+        let differing: Vec<(usize, usize)> = (0..a.height())
+            .flat_map(|y| (0..a.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| a.pixel(x, y) != b.pixel(x, y))
+            .collect();
+
+        if let Some((x, y)) = differing.first() {
+            differences.push(format!("Screen: {} pixels differ, the first at ({}, {})", differing.len(), x, y));
+        }
+    }
+
+    if differences.is_empty() { None } else { Some(differences) }
+}
+
+fn report(frame: u32, instruction: Option<(u32, u16)>, differences: &[String], screens: Option<[&CPU; 2]>) -> ! {
+    match instruction {
+        Some((instruction, pc)) => println!("Runs diverged on frame {} at instruction {} of the frame (PC was {:03X} before it)", frame, instruction, pc),
+        None => println!("Runs diverged by the end of frame {}", frame),
+    }
+    for difference in differences {
+        println!("  {}", difference);
+    }
+    for (name, cpu) in ["A", "B"].into_iter().zip(screens.into_iter().flatten()) {
+        print!("Screen with config {}:\n{}", name, cpu.ascii_screen());
+    }
+
+    process::exit(1);
+}
+
+pub(crate) fn exit_with(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(2);
+}
@@ -0,0 +1,496 @@
+use ggegui::egui::{self, Color32};
+use ggez::graphics::Color;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+
+use crate::ambient::AmbientSettings;
+use crate::attract::AttractSettings;
+use crate::audio::AudioSettings;
+use crate::breakpoints::Breakpoint;
+use crate::bridge::DEFAULT_BRIDGE_ADDRESS;
+use crate::cheats::Cheat;
+use crate::cpu::{Quirks, DEFAULT_CYCLES_PER_FRAME};
+use crate::crowd::{DEFAULT_CROWD_HOLD_TICKS, DEFAULT_CROWD_PORT, DEFAULT_CROWD_QUEUE_LENGTH};
+use crate::hotkeys::Hotkeys;
+use crate::netplay::DEFAULT_NETPLAY_PORT;
+use crate::patch::PatchFile;
+use crate::input::{ControlScheme, InputMacro, KeyboardSettings, TouchSettings};
+use crate::library::{Library, LibrarySort};
+use crate::midi::MidiSettings;
+use crate::shaders::ScreenShader;
+use crate::remote::DEFAULT_REMOTE_PORT;
+use crate::speedrun::SpeedrunSettings;
+use crate::transition::TransitionKind;
+
+const CONFIG_FILE: &str = "config.toml";
+const CONFIG_BACKUP_FILE: &str = "config.toml.bak";
+
+pub const TIMER_HZ: f64 = 60.0; // how often the timers count down, and so how often a frame's worth of instructions runs
+pub const DEFAULT_INSTRUCTIONS_PER_SECOND: u32 = 700;
+
+pub const DEFAULT_OFF_COLOUR: Color = Color {r: 0.057805423, g: 0.057805423, b: 0.057805423, a: 1.0};
+pub const DEFAULT_ON_COLOUR: Color = Color::WHITE;
+pub const DEFAULT_OFF_PIXEL_ALPHA: f32 = 0.5;
+pub const DEFAULT_GRADIENT_COLOUR: Color = Color {r: 0.5, g: 0.5, b: 0.5, a: 1.0};
+
+pub const DEFAULT_UI_SCALE: f32 = 1.0;
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 3.0;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum SpeedMode {
+    CyclesPerFrame,
+    InstructionsPerSecond,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum Theme {
+    Dark,
+    Light,
+    Custom,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct CustomTheme {
+    pub dark_base: bool,
+    pub panel_fill: Color,
+    pub window_fill: Color,
+    pub text: Color,
+    pub accent: Color,
+}
+
+impl Default for CustomTheme {
+    fn default() -> Self {
+        Self {
+            dark_base: true,
+            panel_fill: Color::from_rgb(27, 27, 27),
+            window_fill: Color::from_rgb(27, 27, 27),
+            text: Color::from_rgb(200, 200, 200),
+            accent: Color::from_rgb(0, 92, 128),
+        }
+    }
+}
+
+// how pixels that are off are drawn
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum OffPixels {
+    Coloured,    // in the off colour
+    Translucent, // in the off colour, see-through by off_pixel_alpha
+    Hidden,      // not at all, whatever is behind the screen shows through
+}
+
+// lit pixels shading from the on colour at the top or left of the screen to gradient_colour at the other side, like
+// LED panels that are brighter at one end
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum Gradient {
+    Off,
+    Rows,
+    Columns,
+}
+
+// for the built in LED matrix shader
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct LedMatrixSettings {
+    pub dot_radius: f32, // as a fraction of a pixel, 0.5 just touches the next one
+    pub glow: f32,       // 0 to 1
+}
+
+impl Default for LedMatrixSettings {
+    fn default() -> Self {
+        Self {
+            dot_radius: 0.4,
+            glow: 0.3,
+        }
+    }
+}
+
+// the old screen disappearing over the new one
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(default)]
+pub struct TransitionSettings {
+    pub kind: Option<TransitionKind>, // None for the screen just changing
+    pub length: f32,                  // seconds
+    pub on_load: bool,
+    pub on_reset: bool,
+    pub on_clear: bool, // whenever the program clears the screen, which some do every frame
+}
+
+impl Default for TransitionSettings {
+    fn default() -> Self {
+        Self {
+            kind: None,
+            length: 0.5,
+            on_load: true,
+            on_reset: true,
+            on_clear: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+#[serde(default)]
+pub struct AccessibilitySettings {
+    pub high_contrast: bool,  // white on black whatever the colours are set to
+    pub reduce_flicker: bool, // pixels fade out over a few frames instead of blinking off
+    pub visual_beep: bool,    // flash a border round the screen while the buzzer sounds
+    pub reduce_motion: bool,  // no shader, gradient, transitions, fading or UI animation, just the pixels as they are
+}
+
+// where the window was when the emulator was last closed
+#[derive(Serialize, Deserialize, PartialEq, Clone, Default)]
+#[serde(default)]
+pub struct WindowGeometry {
+    pub width: f32, // the inside of the window, and what it goes back to after being fullscreen
+    pub height: f32,
+    pub position: Option<(i32, i32)>, // some platforms such as Wayland don't say where windows are
+    pub monitor: Option<String>,
+    pub fullscreen: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub speed_mode: SpeedMode,
+    pub cycles_per_frame: u16,
+    pub instructions_per_second: u32,
+    pub pixel_on_colour: Color,
+    pub pixel_off_colour: Color,
+    pub off_pixels: OffPixels,
+    pub off_pixel_alpha: f32,
+    pub gradient: Gradient,
+    pub gradient_colour: Color,
+    pub shader: Option<ScreenShader>,
+    pub led_matrix: LedMatrixSettings,
+    pub transitions: TransitionSettings,
+    pub quirks: Quirks,
+    pub theme: Theme,
+    pub custom_theme: CustomTheme,
+    pub ui_scale: f32,
+    pub audio: AudioSettings,
+    pub buzzer_indicator: bool,
+    pub accessibility: AccessibilitySettings,
+    pub keyboard: KeyboardSettings,
+    pub control_schemes: HashMap<String, ControlScheme>, // keyed by rom_id
+    pub macros: Vec<InputMacro>,
+    pub cheats: HashMap<String, Vec<Cheat>>,             // keyed by rom_id
+    pub breakpoints: HashMap<String, Vec<Breakpoint>>,   // keyed by rom_id
+    pub patches: HashMap<String, Vec<PatchFile>>,        // keyed by the unpatched ROM's rom_id, applied in order
+    pub library: Library,
+    pub library_sort: LibrarySort,
+    pub touch: TouchSettings,
+    pub midi: MidiSettings,
+    pub speedrun: SpeedrunSettings,
+    pub hotkeys: Hotkeys,
+    pub background_tabs: bool, // keep running the tabs that aren't focused instead of pausing them
+    pub remote_api: bool,
+    pub remote_api_port: u16,
+    pub crowd_play: bool,
+    pub crowd_play_port: u16,
+    pub crowd_play_public: bool, // let other computers connect, not just a chat bot running on this one
+    pub crowd_queue_length: usize,
+    pub crowd_hold_ticks: u32,
+    pub display_bridge: bool,
+    pub display_bridge_address: String,
+    pub ambient: AmbientSettings,
+    pub attract: AttractSettings,
+    pub netplay_port: u16,
+    pub netplay_address: String, // the last host joined
+    pub tour_finished: bool,     // the tour only starts by itself the first time
+    pub confirm_discard: bool,   // ask before loading another ROM or quitting while recording or with rewind history
+    pub window: Option<WindowGeometry>, // None until the emulator has been closed once
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            speed_mode: SpeedMode::CyclesPerFrame,
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            instructions_per_second: DEFAULT_INSTRUCTIONS_PER_SECOND,
+            pixel_on_colour: DEFAULT_ON_COLOUR,
+            pixel_off_colour: DEFAULT_OFF_COLOUR,
+            off_pixels: OffPixels::Coloured,
+            off_pixel_alpha: DEFAULT_OFF_PIXEL_ALPHA,
+            gradient: Gradient::Off,
+            gradient_colour: DEFAULT_GRADIENT_COLOUR,
+            shader: None,
+            led_matrix: LedMatrixSettings::default(),
+            transitions: TransitionSettings::default(),
+            quirks: Quirks::default(),
+            theme: Theme::Dark,
+            custom_theme: CustomTheme::default(),
+            ui_scale: DEFAULT_UI_SCALE,
+            audio: AudioSettings::default(),
+            buzzer_indicator: true,
+            accessibility: AccessibilitySettings::default(),
+            keyboard: KeyboardSettings::default(),
+            control_schemes: HashMap::new(),
+            macros: vec![],
+            cheats: HashMap::new(),
+            breakpoints: HashMap::new(),
+            patches: HashMap::new(),
+            library: Library::new(),
+            library_sort: LibrarySort::default(),
+            touch: TouchSettings::default(),
+            midi: MidiSettings::default(),
+            speedrun: SpeedrunSettings::default(),
+            hotkeys: Hotkeys::default(),
+            background_tabs: false,
+            remote_api: false,
+            remote_api_port: DEFAULT_REMOTE_PORT,
+            crowd_play: false,
+            crowd_play_port: DEFAULT_CROWD_PORT,
+            crowd_play_public: false,
+            crowd_queue_length: DEFAULT_CROWD_QUEUE_LENGTH,
+            crowd_hold_ticks: DEFAULT_CROWD_HOLD_TICKS,
+            display_bridge: false,
+            display_bridge_address: String::from(DEFAULT_BRIDGE_ADDRESS),
+            ambient: AmbientSettings::default(),
+            attract: AttractSettings::default(),
+            netplay_port: DEFAULT_NETPLAY_PORT,
+            netplay_address: String::new(),
+            tour_finished: false,
+            confirm_discard: true,
+            window: None,
+        }
+    }
+}
+
+// the settings that decide how a ROM plays, in a file of their own for sharing known good setups
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SharedSettings {
+    pub rom: Option<String>, // the rom_id they were made for, if they were made for one
+    pub speed_mode: SpeedMode,
+    pub cycles_per_frame: u16,
+    pub instructions_per_second: u32,
+    pub quirks: Quirks,
+    pub pixel_on_colour: Color,
+    pub pixel_off_colour: Color,
+    pub keyboard: KeyboardSettings,
+    pub control_scheme: Option<ControlScheme>,
+}
+
+impl Default for SharedSettings {
+    fn default() -> Self {
+        SharedSettings::from_config(&Config::default(), Quirks::default(), None)
+    }
+}
+
+impl SharedSettings {
+    pub fn from_config(config: &Config, quirks: Quirks, rom_id: Option<&String>) -> SharedSettings {
+        SharedSettings {
+            rom: rom_id.cloned(),
+            speed_mode: config.speed_mode,
+            cycles_per_frame: config.cycles_per_frame,
+            instructions_per_second: config.instructions_per_second,
+            quirks,
+            pixel_on_colour: config.pixel_on_colour,
+            pixel_off_colour: config.pixel_off_colour,
+            keyboard: config.keyboard,
+            control_scheme: rom_id.and_then(|rom_id| config.control_schemes.get(rom_id)).copied(),
+        }
+    }
+
+    // everything apart from the quirks, which belong to the CPU while it's running
+    pub fn apply(&self, config: &mut Config, rom_id: Option<&String>) {
+        config.speed_mode = self.speed_mode;
+        config.cycles_per_frame = self.cycles_per_frame;
+        config.instructions_per_second = self.instructions_per_second;
+        config.pixel_on_colour = self.pixel_on_colour;
+        config.pixel_off_colour = self.pixel_off_colour;
+        config.keyboard = self.keyboard;
+
+        if let (Some(scheme), Some(rom_id)) = (self.control_scheme, rom_id) {
+            config.control_schemes.insert(rom_id.clone(), scheme);
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let contents = toml::to_string_pretty(self).map_err(|e| format!("Could not save the settings: {}", e))?;
+        fs::write(path, contents).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<SharedSettings, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        toml::from_str(&contents).map_err(|e| format!("{} isn't a settings file: {}", path.display(), e))
+    }
+}
+
+impl Config {
+    pub fn load(dir: &Path) -> Result<Config, String> {
+        // a missing config just means it's the first run, but one that can't be read shouldn't be quietly replaced
+        let path = dir.join(CONFIG_FILE);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(format!("Could not read {}: {}", path.display(), e)),
+        };
+        toml::from_str(&contents).map_err(|e| format!("{} isn't a valid configuration: {}", path.display(), e))
+    }
+
+    // how many instructions to run before the next timer tick, shared by the GUI and the headless runs
+    pub fn cycles_per_tick(&self, cycle_budget: &mut f64) -> u32 {
+        match self.speed_mode {
+            SpeedMode::CyclesPerFrame => self.cycles_per_frame as u32,
+            SpeedMode::InstructionsPerSecond => {
+                // the leftover fraction is carried over so speeds that aren't a multiple of 60 still average out
+                *cycle_budget += self.instructions_per_second as f64 / TIMER_HZ;
+                let cycles = *cycle_budget as u32;
+                *cycle_budget -= cycles as f64;
+
+                cycles
+            }
+        }
+    }
+
+    // moves a config that couldn't be loaded out of the way so saving the defaults doesn't overwrite it
+    pub fn back_up(dir: &Path) -> io::Result<PathBuf> {
+        let backup = dir.join(CONFIG_BACKUP_FILE);
+        fs::rename(dir.join(CONFIG_FILE), &backup)?;
+        Ok(backup)
+    }
+
+    // the settings back to their defaults, keeping what's been built up along the way: the library, everything kept
+    // for each ROM, the attract playlist and the macros
+    pub fn reset(&self) -> Config {
+        Config {
+            control_schemes: self.control_schemes.clone(),
+            macros: self.macros.clone(),
+            cheats: self.cheats.clone(),
+            breakpoints: self.breakpoints.clone(),
+            patches: self.patches.clone(),
+            library: self.library.clone(),
+            library_sort: self.library_sort,
+            attract: AttractSettings { playlist: self.attract.playlist.clone(), ..AttractSettings::default() },
+            netplay_address: self.netplay_address.clone(),
+            tour_finished: self.tour_finished,
+            ..Config::default()
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        toml::from_str(&contents).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let contents = toml::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join(CONFIG_FILE), contents)
+    }
+
+    pub fn style(&self) -> egui::Style {
+        let mut style = egui::Style::default();
+
+        style.visuals = match self.theme {
+            Theme::Dark => egui::Visuals::dark(),
+            Theme::Light => egui::Visuals::light(),
+            Theme::Custom => {
+                let custom = &self.custom_theme;
+                let mut visuals = if custom.dark_base { egui::Visuals::dark() } else { egui::Visuals::light() };
+
+                visuals.panel_fill = to_color32(custom.panel_fill);
+                visuals.window_fill = to_color32(custom.window_fill);
+                visuals.override_text_color = Some(to_color32(custom.text));
+                visuals.selection.bg_fill = to_color32(custom.accent);
+                visuals.hyperlink_color = to_color32(custom.accent);
+
+                visuals
+            }
+        };
+
+        let scale = self.ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE);
+        for font in style.text_styles.values_mut() {
+            font.size *= scale;
+        }
+
+        let spacing = &mut style.spacing;
+        spacing.item_spacing *= scale;
+        spacing.button_padding *= scale;
+        spacing.interact_size *= scale;
+        spacing.indent *= scale;
+        spacing.slider_width *= scale;
+        spacing.combo_width *= scale;
+        spacing.text_edit_width *= scale;
+        spacing.icon_width *= scale;
+        spacing.icon_width_inner *= scale;
+        spacing.icon_spacing *= scale;
+        spacing.tooltip_width *= scale;
+        spacing.menu_width *= scale;
+
+        if self.accessibility.reduce_motion {
+            style.animation_time = 0.0; // panels and windows snap open and shut
+        }
+
+        style
+    }
+}
+
+// identifies a ROM by its contents so settings follow it even if the file is renamed or moved
+pub fn rom_id(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+fn to_color32(colour: Color) -> Color32 {
+    let (r, g, b) = colour.to_rgb();
+    Color32::from_rgb(r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheats::Cheat;
+    use crate::library::LibraryEntry;
+
+    #[test]
+    fn reset_keeps_the_library_and_rom_data() {
+        let mut config = Config { cycles_per_frame: 1000, ui_scale: 2.0, ..Config::default() };
+        config.library.insert(String::from("0123"), LibraryEntry { name: String::from("Pong"), favourite: true, play_time: 90.0, ..Default::default() });
+        config.cheats.insert(String::from("0123"), vec![Cheat::default()]);
+        config.attract.playlist.push(String::from("0123"));
+        config.attract.seconds_per_rom = 5.0;
+
+        let reset = config.reset();
+        assert_eq!((reset.cycles_per_frame, reset.ui_scale), (DEFAULT_CYCLES_PER_FRAME, DEFAULT_UI_SCALE));
+        assert_eq!(reset.library, config.library);
+        assert_eq!(reset.cheats["0123"].len(), 1);
+        assert_eq!(reset.attract.playlist, ["0123"]);
+        assert_eq!(reset.attract.seconds_per_rom, AttractSettings::default().seconds_per_rom);
+    }
+
+    #[test]
+    fn instructions_per_second_average_out() {
+        let config = Config { speed_mode: SpeedMode::InstructionsPerSecond, instructions_per_second: 1000, ..Config::default() };
+        let mut cycle_budget = 0.0;
+        let cycles: u32 = (0..TIMER_HZ as u32).map(|_| config.cycles_per_tick(&mut cycle_budget)).sum();
+        assert_eq!(cycles, 1000);
+
+        let config = Config { speed_mode: SpeedMode::CyclesPerFrame, cycles_per_frame: 15, ..config };
+        assert_eq!(config.cycles_per_tick(&mut cycle_budget), 15);
+    }
+
+    #[test]
+    fn only_a_missing_config_loads_the_defaults() {
+        let dir = std::env::temp_dir().join(format!("fish_n_chip8-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(Config::load(&dir).unwrap().cycles_per_frame, DEFAULT_CYCLES_PER_FRAME);
+
+        Config { cycles_per_frame: 1000, ..Config::default() }.save(&dir).unwrap();
+        assert_eq!(Config::load(&dir).unwrap().cycles_per_frame, 1000);
+
+        // a broken config is kept to fix by hand rather than replaced with the defaults
+        fs::write(dir.join(CONFIG_FILE), "cycles_per_frame = \"lots\"").unwrap();
+        assert!(Config::load(&dir).is_err());
+        let backup = Config::back_up(&dir).unwrap();
+        assert_eq!(fs::read_to_string(backup).unwrap(), "cycles_per_frame = \"lots\"");
+        assert!(Config::load(&dir).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,12 +1,22 @@
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
 
-use rand::rngs::ThreadRng;
-use rand::{thread_rng, Rng};
+use std::fmt;
+
+use crate::explain::Quirk;
+use crate::instruction::{AluOp, Instruction};
+use crate::random::{Random, RngAlgorithm};
 
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 
-const RAM_SIZE: usize = 4096;
+pub const RAM_SIZE: usize = 4096;
+pub const PROGRAM_START: usize = 0x200;
+
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - PROGRAM_START;
+pub const MAX_STACK_DEPTH: usize = 16; // as deep as SUPER-CHIP's stack goes
+pub const RECENT_INSTRUCTIONS: usize = 16;
+
+pub const DEFAULT_CYCLES_PER_FRAME: u16 = 12; // at 60 frames a second
 
 const LOW_RES_FONT: [u8; 5 * 16] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -52,32 +62,33 @@ const HIGH_RES_FONT_END: usize = HIGH_RES_FONT_START + HIGH_RES_FONT.len();
 const LOW_RES_FONT_START: usize = 0x50;
 const LOW_RES_FONT_END: usize = LOW_RES_FONT_START + LOW_RES_FONT.len();
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub enum RegSaveLoadQuirk {
     Unchanged,
     X,
     XPlusOne,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub enum ShiftingReg {
     VX,
     VY,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub enum JumpBehviour {
     BNNN,
     BXNN,
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
 pub enum ScrollingBehviour {
     Modern,
     Legacy,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(default)]
 pub struct Quirks {
     pub vf_reset: bool,
     pub shifting: ShiftingReg,
@@ -85,10 +96,13 @@ pub struct Quirks {
     pub jump: JumpBehviour,
     pub screen_wrap: bool,
     pub scrolling: ScrollingBehviour,
+    pub rng: RngAlgorithm,
+    pub key_wait_grace: u8, // how many frames a key can have been held for when FX0A starts and still count once let go of
+    pub display_wait: bool, // DXYN waits for the next frame if a sprite has already been drawn this one, as the original interpreter waited for the display
 }
 
-impl Quirks {
-    pub fn default() -> Self {
+impl Default for Quirks {
+    fn default() -> Self {
         Self {
             shifting: ShiftingReg::VX,
             vf_reset: false,
@@ -96,50 +110,143 @@ impl Quirks {
             jump: JumpBehviour::BNNN,
             screen_wrap: false,
             scrolling: ScrollingBehviour::Modern,
+            rng: RngAlgorithm::Standard,
+            key_wait_grace: 0,
+            display_wait: false,
+        }
+    }
+}
+
+// something the program did that can't be carried on from, the CPU halts on the instruction that caused it
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum CpuError {
+    UnsupportedOpcode { opcode: u16, addr: u16 },
+    StackOverflow { addr: u16 },
+    StackUnderflow { addr: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::UnsupportedOpcode { opcode, addr } => write!(f, "Unsupported opcode {:04X} at {:03X}", opcode, addr),
+            CpuError::StackOverflow { addr } => write!(f, "Stack overflow at {:03X}, more than {} nested subroutine calls", addr, MAX_STACK_DEPTH),
+            CpuError::StackUnderflow { addr } => write!(f, "Return at {:03X} without a subroutine to return from", addr),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct StepEffects {
+    pub display_changed: bool,
+    pub resolution_changed: bool,
+    pub display_watch_hit: bool, // the instruction did what the display watch is waiting for
+    pub screen_cleared: bool,
+}
+
+// a visual breakpoint, for catching the instruction that draws something
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DisplayWatch {
+    AnyUpdate,           // anything that draws, clears or scrolls the screen
+    Pixel(usize, usize), // the pixel at (x, y) turning on or off, in high resolution coordinates
+}
+
+impl fmt::Display for DisplayWatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisplayWatch::AnyUpdate => write!(f, "Display update"),
+            DisplayWatch::Pixel(x, y) => write!(f, "Pixel ({}, {}) changing", x, y),
         }
     }
 }
 
-#[derive(PartialEq, Clone, Copy)]
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
 pub enum Resolution {
     HighRes,
     LowRes,
 }
 
+// everything needed to carry on from a point in a program, apart from the random number generator's state
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SaveState {
+    pub resolution: Resolution,
+    pub memory: Vec<u8>,
+    pub display: Vec<u8>, // 16 bytes a row, the leftmost pixel in the top bit of the first
+    pub regs: [u8; 16],
+    pub addr_reg: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub pressed_key: Option<u8>,
+    pub ignore_keys: u16,
+    pub waiting_for_key_press: bool,
+    #[serde(default)]
+    pub keys: u16,
+    #[serde(default)]
+    pub second_keypad: u16,
+    #[serde(default)]
+    pub key_ages: [u8; 16],
+    pub halted: bool,
+    pub error: Option<CpuError>,
+}
+
+#[derive(Clone)]
 pub struct CPU {
-    pub pixels: Vec<Vec<bool>>,
     pub resolution: Resolution,
     pub quirks: Quirks,
+    pub display_watch: Option<DisplayWatch>,
     memory: [u8; RAM_SIZE],
     delay_timer: u8,
     sound_timer: u8,
     pressed_key: Option<u8>,
-    ignore_keys: HashSet<u8>,
+    ignore_keys: u16,
     waiting_for_key_press: bool,
+    keys: u16,          // bit N set while key N is held down
+    second_keypad: u16, // the same for CHIP-8X's second keypad
+    key_ages: [u8; 16], // how many frames each held key has been down during, counting the one it was pressed in
+    halted: bool,
+    error: Option<CpuError>,
     stack: Vec<u16>,
     regs: [u8; 16],
     addr_reg: u16,
     pc: u16,
-    rng: ThreadRng,
+    rng: Random,
+    quirks_used: u8, // a bit for each explain::Quirk an instruction has run under where the setting made a difference
+    drawn_this_frame: bool,
+    recent: [u16; RECENT_INSTRUCTIONS], // where the last instructions were, for working out how it got somewhere
+    executed: u64,
+    display: [u128; HEIGHT * 2], // one row per u128, the leftmost pixel is the most significant bit
+    decoded: Vec<Option<Instruction>>, // indexed by address, filled in the first time each instruction runs
 }
 
 impl CPU {
     pub fn new() -> CPU {
         let mut created = Self {
-            pixels: vec![vec![false; WIDTH]; HEIGHT],
             resolution: Resolution::LowRes,
             quirks: Quirks::default(),
+            display_watch: None,
             memory: [0; RAM_SIZE],
             delay_timer: 0,
             sound_timer: 0,
             pressed_key: None,
-            ignore_keys: HashSet::new(),
+            ignore_keys: 0,
             waiting_for_key_press: false,
-            stack: vec![],
+            keys: 0,
+            second_keypad: 0,
+            key_ages: [0; 16],
+            halted: false,
+            error: None,
+            stack: Vec::with_capacity(MAX_STACK_DEPTH),
             regs: [0; 16],
             addr_reg: 0,
             pc: 0x200,
-            rng: thread_rng(),
+            rng: Random::from_entropy(),
+            quirks_used: 0,
+            drawn_this_frame: false,
+            recent: [0; RECENT_INSTRUCTIONS],
+            executed: 0,
+            display: [0; HEIGHT * 2],
+            decoded: vec![None; RAM_SIZE],
         };
 
         created.memory[LOW_RES_FONT_START..LOW_RES_FONT_END].copy_from_slice(&LOW_RES_FONT);
@@ -148,17 +255,131 @@ impl CPU {
         created
     }
 
-    pub fn load_rom(&mut self, rom: &Vec<u8>) {
-        self.memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+    pub fn load_rom(&mut self, rom: &[u8]) {
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        self.invalidate_decoded(PROGRAM_START, PROGRAM_START + rom.len());
     }
 
-    pub fn key_released(&mut self, key: u8) {
-        if self.waiting_for_key_press && !self.ignore_keys.remove(&key) {
-            self.pressed_key = Some(key);
+    pub fn save_state(&self) -> SaveState {
+        SaveState {
+            resolution: self.resolution,
+            memory: self.memory.to_vec(),
+            display: self.display.iter().flat_map(|row| row.to_be_bytes()).collect(),
+            regs: self.regs,
+            addr_reg: self.addr_reg,
+            pc: self.pc,
+            stack: self.stack.clone(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            pressed_key: self.pressed_key,
+            ignore_keys: self.ignore_keys,
+            waiting_for_key_press: self.waiting_for_key_press,
+            keys: self.keys,
+            second_keypad: self.second_keypad,
+            key_ages: self.key_ages,
+            halted: self.halted,
+            error: self.error,
+        }
+    }
+
+    // the quirks are left as they are, they're a setting rather than part of the program's state
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), String> {
+        if state.memory.len() != RAM_SIZE || state.display.len() != self.display.len() * 16 {
+            return Err(String::from("The savestate's memory or screen is the wrong size"));
+        }
+        if state.stack.len() > MAX_STACK_DEPTH {
+            return Err(format!("The savestate's stack is {} deep, more than the {} allowed", state.stack.len(), MAX_STACK_DEPTH));
+        }
+
+        self.resolution = state.resolution;
+        self.memory.copy_from_slice(&state.memory);
+        for (row, bytes) in self.display.iter_mut().zip(state.display.chunks_exact(16)) {
+            *row = u128::from_be_bytes(bytes.try_into().expect("chunks are 16 bytes"));
+        }
+        self.regs = state.regs;
+        self.addr_reg = state.addr_reg;
+        self.pc = state.pc;
+        self.stack = state.stack.clone();
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.pressed_key = state.pressed_key;
+        self.ignore_keys = state.ignore_keys;
+        self.waiting_for_key_press = state.waiting_for_key_press;
+        self.keys = state.keys;
+        self.second_keypad = state.second_keypad;
+        self.key_ages = state.key_ages;
+        self.halted = state.halted;
+        self.error = state.error;
+        self.decoded.fill(None);
+
+        Ok(())
+    }
+
+    pub fn keys(&self) -> u16 {
+        self.keys
+    }
+
+    // keys has bit N set for each key N held down, any that were held before and aren't now count as released
+    pub fn set_keys(&mut self, keys: u16) {
+        for key in 0..16 {
+            if self.keys & !keys & (1 << key) != 0 {
+                self.key_released(key);
+            }
+            if !self.keys & keys & (1 << key) != 0 {
+                self.key_ages[key as usize] = 0;
+            }
+        }
+        self.keys = keys;
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        if let Some(bit) = key_bit(key) {
+            if self.keys & bit == 0 {
+                self.key_ages[key as usize] = 0;
+            }
+            self.keys |= bit;
+        }
+    }
+
+    pub fn waiting_for_key(&self) -> bool {
+        self.waiting_for_key_press
+    }
+
+    // the keys FX0A won't take until they've been let go of and pressed again
+    pub fn ignored_keys(&self) -> u16 {
+        self.ignore_keys
+    }
+
+    // counts as a release even if the key wasn't held, so a press and release between two frames isn't missed
+    pub fn key_up(&mut self, key: u8) {
+        if let Some(bit) = key_bit(key) {
+            self.keys &= !bit;
+            self.key_released(key);
+        }
+    }
+
+    pub fn set_second_keypad(&mut self, keys: u16) {
+        self.second_keypad = keys;
+    }
+
+    fn key_released(&mut self, key: u8) {
+        if self.waiting_for_key_press {
+            // keys already held when FX0A started have to be let go of once before they count
+            if self.ignore_keys & (1 << key) == 0 {
+                self.pressed_key = Some(key);
+            }
+            self.ignore_keys &= !(1 << key);
         }
     }
 
     pub fn timer_tick(&mut self) -> bool{
+        self.drawn_this_frame = false;
+        for key in 0..16 {
+            if self.keys & (1 << key) != 0 {
+                self.key_ages[key] = self.key_ages[key].saturating_add(1);
+            }
+        }
+
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
@@ -171,145 +392,332 @@ impl CPU {
         }
     }
 
+    // for when the random numbers have to come out the same every run, such as netplay
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.rng = Random::seeded(seed);
+    }
+
+    // set once the program exits or jumps to itself, after that stepping does nothing
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn error(&self) -> Option<CpuError> {
+        self.error
+    }
+
+    // the quirks that have mattered so far: an instruction they change has run in a way where the setting decides
+    // what happens, such as 8XY6 with X and Y being different registers. Changing any of the others can't have made
+    // a difference yet
+    pub fn quirks_used(&self) -> Vec<Quirk> {
+        Quirk::ALL.into_iter().filter(|&quirk| self.quirks_used & (1 << quirk as u8) != 0).collect()
+    }
+
+    // the addresses of the last instructions that ran, oldest first
+    pub fn recent_instructions(&self) -> impl Iterator<Item = u16> + '_ {
+        let count = self.executed.min(RECENT_INSTRUCTIONS as u64);
+        (self.executed - count..self.executed).map(|i| self.recent[(i % RECENT_INSTRUCTIONS as u64) as usize])
+    }
+
+    fn use_quirk(&mut self, quirk: Quirk) {
+        self.quirks_used |= 1 << quirk as u8;
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn addr_reg(&self) -> u16 {
+        self.addr_reg
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.regs
+    }
+
+    pub fn stack(&self) -> &[u16] {
+        &self.stack
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn read_memory(&self, addr: u16) -> u8 {
+        self.memory[addr as usize % RAM_SIZE]
+    }
+
+    // the two bytes at addr as an opcode, wrapping round past the end of memory like the program counter does
+    pub fn read_opcode(&self, addr: u16) -> u16 {
+        (self.read_memory(addr) as u16) << 8 | self.read_memory(addr.wrapping_add(1)) as u16
+    }
+
+    pub fn write_memory(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize % RAM_SIZE;
+
+        self.memory[addr] = value;
+        self.invalidate_decoded(addr, addr + 1);
+    }
+
     pub fn height(&self) -> usize {
-        self.pixels.len()
+        match self.resolution {
+            Resolution::LowRes => HEIGHT,
+            Resolution::HighRes => HEIGHT * 2,
+        }
     }
 
     pub fn width(&self) -> usize {
-        self.pixels[0].len()
+        match self.resolution {
+            Resolution::LowRes => WIDTH,
+            Resolution::HighRes => WIDTH * 2,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.display[y] & (1 << (127 - x)) != 0
+    }
+
+    // FNV-1a over the resolution and the visible pixels, the same on every platform and build so it can be written down and compared against later
+    pub fn display_hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        feed(&(self.width() as u16).to_be_bytes());
+        feed(&(self.height() as u16).to_be_bytes());
+        for row in &self.display[..self.height()] {
+            feed(&row.to_be_bytes()[..self.width() / 8]);
+        }
+
+        hash
+    }
+
+    // the screen drawn with block characters two pixel rows to a line, in a box so blank edges still show, for
+    // looking at in a terminal
+    pub fn ascii_screen(&self) -> String {
+        let border = format!("+{}+\n", "-".repeat(self.width()));
+        let mut text = border.clone();
+
+        for y in (0..self.height()).step_by(2) {
+            text.push('|');
+            text.extend((0..self.width()).map(|x| match (self.pixel(x, y), self.pixel(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            }));
+            text.push_str("|\n");
+        }
+
+        text + &border
+    }
+
+    // carries on the display hash over everything else that decides what the program does next, including where
+    // the random number generator is up to, so two runs that hash the same at a frame will go on to do the same thing.
+    // Unlike display_hash it isn't meant to be written down across versions
+    pub fn state_hash(&self) -> u64 {
+        let mut hash = self.display_hash();
+        let mut feed = |bytes: &[u8]| {
+            for byte in bytes {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        feed(&self.memory);
+        feed(&self.regs);
+        feed(&self.addr_reg.to_be_bytes());
+        feed(&self.pc.to_be_bytes());
+        for addr in &self.stack {
+            feed(&addr.to_be_bytes());
+        }
+        feed(&[self.delay_timer, self.sound_timer, self.pressed_key.unwrap_or(0xFF)]);
+        feed(&self.ignore_keys.to_be_bytes());
+        feed(&[self.waiting_for_key_press as u8, self.halted as u8]);
+        feed(format!("{:?}", self.error).as_bytes());
+        feed(&self.rng.fingerprint().to_be_bytes());
+
+        hash
     }
 
-    pub fn handle_opcode(&mut self, pressed_keys: &HashSet<u8>) {
-        let opcode = (self.memory[self.pc as usize] as u16) << 8 | (self.memory[self.pc as usize + 1] as u16);
-        let opcode_type = (opcode & 0xF000) >> 12;      // TAAA
-        let reg_x = (opcode as usize & 0x0F00) >> 8;    // AXAA
-        let reg_y = (opcode as usize & 0x00F0) >> 4;    // AAYA
-        let nnn = opcode & 0x0FFF;                      // ANNN
-        let nn = (opcode & 0x00FF) as u8;               // AANN
-        let n = (opcode & 0x000F) as u8;                // AAAN
+    fn row_mask(&self) -> u128 {
+        !0 << (128 - self.width())
+    }
+
+    // the keys are whatever set_keys and friends last left them as
+    pub fn handle_opcode(&mut self) -> StepEffects {
+        if self.halted {
+            return StepEffects::default();
+        }
+
+        // addresses past the end of memory wrap back round to the start
+        let pc = self.pc as usize % RAM_SIZE;
+        self.pc = pc as u16;
+        self.recent[(self.executed % RECENT_INSTRUCTIONS as u64) as usize] = self.pc;
+        self.executed += 1;
+        let instruction = match self.decoded[pc] {
+            Some(instruction) => instruction,
+            None => {
+                let opcode = self.read_opcode(pc as u16);
+                let instruction = Instruction::decode(opcode);
+                self.decoded[pc] = Some(instruction);
+                instruction
+            }
+        };
+
+        let mut effects = StepEffects::default();
+        let watched_pixel = match self.display_watch {
+            Some(DisplayWatch::Pixel(x, y)) if x < WIDTH * 2 && y < HEIGHT * 2 => Some(self.pixel(x, y)),
+            _ => None,
+        };
 
         self.pc += 2;
 
-        match opcode_type {
-            0x0 => {
-                if opcode & 0xFFF0 == 0x00C0 {
-                    // 00CN: Scroll display N pixels down; in low resolution mode, N/2 pixels
-                    self.pixels.remove(self.height() - 1);
-                    self.pixels.remove(self.height() - 1);
+        match instruction {
+            Instruction::ScrollDown(n) => {
+                // 00CN: Scroll display N pixels down; in low resolution mode, N/2 pixels
+                let height = self.height();
+                let n = (n as usize).min(height);
 
-                    for _ in 0..n {
-                        self.pixels.insert(0, vec![false; self.width()]);
-                    }
+                self.display.copy_within(0..height - n, n);
+                self.display[..n].fill(0);
+                effects.display_changed = true;
+            }
+            Instruction::ClearScreen => {
+                // 00E0 - clear screen
+                self.display.fill(0);
+                effects.display_changed = true;
+                effects.screen_cleared = true;
+            }
+            Instruction::Return => {
+                // 00EE - return from a subroutine
+                match self.stack.pop() {
+                    Some(addr) => self.pc = addr,
+                    None => self.fail(CpuError::StackUnderflow { addr: pc as u16 }),
                 }
-                else {
-                    match opcode {
-                        0x00E0 => {
-                            // 00E0 - clear screen
-                            self.pixels = vec![vec![false; self.width()]; self.height()];
-                        }
-                        0x00EE => self.pc = {
-                            // 00EE - return from a subroutine
-                            self.stack.pop().expect("Stack should not be empty")
-                        },
-                        0x00FB => {
-                            // 00FB - scroll right by 4 pixels in highres or 2 in lowres SUPERCHIP
-                            for row in self.pixels.iter_mut() {
-                                let mut new_row = vec![false, false, false, false];
-                                new_row.append(&mut row[..row.len() - 4].to_vec());
-                                *row = new_row;
-                            }
-                        },
-                        0x00FC => {
-                            // 00FC - scroll left by 4 pixels in highres or 2 in lowres SUPERCHIP
-                            for row in self.pixels.iter_mut() {
-                                let mut new_row = vec![false, false, false, false];
-                                *row = row[4..].to_vec();
-                                row.append(&mut new_row);
-                            }
-                        },
-                        0x00FD => {
-                            // 00FD - exit interperter SUPERCHIP
-                            self.load_rom(&vec![0x12, 0x00]); // just go to infinte loop
-                        },
-                        0x00FE => {
-                            // 00FE - enable lowres SUPERCHIP
-                            self.pixels = vec![vec![false; WIDTH]; HEIGHT];
-                            self.resolution = Resolution::LowRes;
-                        },
-                        0x00FF => {
-                            // 00FF - enable highres SUPERCHIP
-                            self.pixels = vec![vec![false; WIDTH * 2]; HEIGHT * 2];
-                            self.resolution = Resolution::HighRes;
-                        },
-                        unsopported => panic!("Unsopported opcode {:#06x} at {:#06x}", unsopported, self.pc),
-                    }
+            },
+            Instruction::ScrollRight => {
+                // 00FB - scroll right by 4 pixels in highres or 2 in lowres SUPERCHIP
+                let mask = self.row_mask();
+                for row in self.display.iter_mut() {
+                    *row = (*row >> 4) & mask;
                 }
-            }
-            0x1 => {
+                effects.display_changed = true;
+            },
+            Instruction::ScrollLeft => {
+                // 00FC - scroll left by 4 pixels in highres or 2 in lowres SUPERCHIP
+                for row in self.display.iter_mut() {
+                    *row <<= 4;
+                }
+                effects.display_changed = true;
+            },
+            Instruction::Exit => {
+                // 00FD - exit interperter SUPERCHIP
+                self.pc -= 2;
+                self.halted = true;
+            },
+            Instruction::LowRes => {
+                // 00FE - enable lowres SUPERCHIP
+                self.display.fill(0);
+                self.resolution = Resolution::LowRes;
+                effects.display_changed = true;
+                effects.resolution_changed = true;
+            },
+            Instruction::HighRes => {
+                // 00FF - enable highres SUPERCHIP
+                self.display.fill(0);
+                self.resolution = Resolution::HighRes;
+                effects.display_changed = true;
+                effects.resolution_changed = true;
+            },
+            Instruction::Jump(nnn) => {
                 // 1NNN - Jumps to address NNN
+                if nnn == self.pc - 2 {
+                    self.halted = true; // jumping to itself is how most programs end
+                }
                 self.pc = nnn;
             }
-            0x2 => {
+            Instruction::Call(nnn) => {
                 // 2NNN - call subroutine
-                self.stack.push(self.pc);
-                self.pc = nnn;
+                if self.stack.len() == MAX_STACK_DEPTH {
+                    self.fail(CpuError::StackOverflow { addr: pc as u16 });
+                }
+                else {
+                    self.stack.push(self.pc);
+                    self.pc = nnn;
+                }
             }
-            0x3 => {
+            Instruction::SkipIfEqual(reg_x, nn) => {
                 // 3XNN - skip next instruction if VX == NN
-                if self.regs[reg_x] == nn {
+                if self.regs[reg_x as usize] == nn {
                     self.pc += 2;
                 }
             }
-            0x4 => {
+            Instruction::SkipIfNotEqual(reg_x, nn) => {
                 // 4XNN - skip next instruction if VX != NN
-                if self.regs[reg_x] != nn {
+                if self.regs[reg_x as usize] != nn {
                     self.pc += 2;
                 }
             }
-            0x5 => {
+            Instruction::SkipIfRegsEqual(reg_x, reg_y) => {
                 // 5XY0 - skip next instruction if VX == VY
-                if self.regs[reg_x] == self.regs[reg_y] {
+                if self.regs[reg_x as usize] == self.regs[reg_y as usize] {
                     self.pc += 2;
                 }
             }
-            0x6 => {
+            Instruction::Load(reg_x, nn) => {
                 // 6XNN - sets VX to NN
-                self.regs[reg_x] = nn;
+                self.regs[reg_x as usize] = nn;
             }
-            0x7 => {
+            Instruction::Add(reg_x, nn) => {
                 // 7XNN - VX += NN
-                self.regs[reg_x] = (self.regs[reg_x]).wrapping_add(nn);
+                self.regs[reg_x as usize] = (self.regs[reg_x as usize]).wrapping_add(nn);
             }
-            0x8 => {
+            Instruction::Alu(op, reg_x, reg_y) => {
                 // 8XYO - perform operation - on VX and VY
-                if self.quirks.vf_reset {
-                    self.regs[15] = 0;
+                match op {
+                    // the rest set VF themselves afterwards
+                    AluOp::Or | AluOp::And | AluOp::Xor => self.use_quirk(Quirk::VfReset),
+                    AluOp::ShiftRight | AluOp::ShiftLeft if reg_x != reg_y => self.use_quirk(Quirk::Shifting),
+                    _ => {},
                 }
 
-                let reg_y = self.regs[reg_y];
-                let reg_x = &mut self.regs[reg_x];
+                let reg_y = self.regs[reg_y as usize];
+                let reg_x = &mut self.regs[reg_x as usize];
 
-                match n {
+                match op {
                     // 8XY0 - 8XY3 are fairly self-explanatory
-                    0x0 => *reg_x = reg_y,
-                    0x1 => *reg_x |= reg_y,
-                    0x2 => *reg_x &= reg_y,
-                    0x3 => *reg_x ^= reg_y,
-                    0x4 => {
+                    AluOp::Move => *reg_x = reg_y,
+                    AluOp::Or => *reg_x |= reg_y,
+                    AluOp::And => *reg_x &= reg_y,
+                    AluOp::Xor => *reg_x ^= reg_y,
+                    AluOp::Add => {
                         // 8XY4 - VX += VY. VF is set to 1 if overflow happened. only lower 8 bits are kept
                         let result = reg_x.wrapping_add(reg_y);
                         let overflow = reg_x.overflowing_add(reg_y).1;
                         *reg_x = result;
                         self.regs[15] = if overflow { 1 } else { 0 };
                     }
-                    0x5 => {
+                    AluOp::Sub => {
                         // 8XY5 - VX -= VY. VF is set to 0 if underflow happened. only lower 8 bits are kept
                         let before_sub = *reg_x;
                         let result = reg_x.wrapping_sub(reg_y);
                         *reg_x = result;
                         self.regs[15] = if before_sub >= reg_y { 1 } else { 0 };
                     }
-                    0x6 => {
+                    AluOp::ShiftRight => {
                         // 8XY6 - VX >>= 1. VF is set to LSB of VX before shift
                         let mut reg = match self.quirks.shifting {
                             ShiftingReg::VX => *reg_x,
@@ -321,14 +729,14 @@ impl CPU {
                         *reg_x = reg;
                         self.regs[15] = before_shift & 1;
                     }
-                    0x7 => {
+                    AluOp::SubReversed => {
                         // 8XY7 - VX = VY - VX. VF is set to 0 if underflow happened. only lower 8 bits are kept
                         let before_add = *reg_x;
                         let result = reg_y.wrapping_sub(*reg_x);
                         *reg_x = result;
                         self.regs[15] = if reg_y >= before_add { 1 } else { 0 };
                     }
-                    0xE => {
+                    AluOp::ShiftLeft => {
                         // 8XYE - VX <<= 1. VF is set to MSB of VX before shift
                         let mut reg = match self.quirks.shifting {
                             ShiftingReg::VX => *reg_x,
@@ -340,223 +748,844 @@ impl CPU {
                         *reg_x = reg;
                         self.regs[15] = (before_shift & 0b1000_0000) >> 7;
                     }
-                    _ => panic!("Unsopported opcode {:#06x} at {:#06x}", opcode, self.pc),
                 };
+
+                // the original interpreter left VF as 0 after the logic ones, once VF had been read if it was an operand
+                if self.quirks.vf_reset && matches!(op, AluOp::Or | AluOp::And | AluOp::Xor) {
+                    self.regs[15] = 0;
+                }
             }
-            0x9 => {
+            Instruction::SkipIfRegsNotEqual(reg_x, reg_y) => {
                 // 9XY0 - skip next instruction if VX != VY
-                if self.regs[reg_x] != self.regs[reg_y] {
+                if self.regs[reg_x as usize] != self.regs[reg_y as usize] {
                     self.pc += 2;
                 }
             }
-            0xA => self.addr_reg = nnn, // ANNN - sets I to NNN
-            0xB => {
+            Instruction::LoadAddr(nnn) => self.addr_reg = nnn, // ANNN - sets I to NNN
+            Instruction::JumpOffset(reg_x, nnn) => {
                 // BNNN jump to NNN + V0
                 // BXNN jump to XNN + VX
+                if reg_x != 0 {
+                    self.use_quirk(Quirk::Jump);
+                }
                 match self.quirks.jump {
                     JumpBehviour::BNNN => self.pc = self.regs[0] as u16 + nnn,
-                    JumpBehviour::BXNN => self.pc = self.regs[reg_x] as u16 + nnn as u16,
+                    JumpBehviour::BXNN => self.pc = self.regs[reg_x as usize] as u16 + nnn,
                 }
             }
-            0xC => {
+            Instruction::Random(reg_x, nn) => {
                 // CXNN - VX = rand & NN; rand 0-255
-                self.regs[reg_x] = self.rng.gen::<u8>() & nn;
+                if nn != 0 {
+                    self.use_quirk(Quirk::Random);
+                }
+                self.regs[reg_x as usize] = self.rng.next(self.quirks.rng) & nn;
             }
-            0xD => {
+            Instruction::Draw(reg_x, reg_y, rows) => {
                 // DXYN - Draw sprit to coord (VX, VY) - width 8 pixels, height N pixels.
                 //        Read from memory location I. VF set to 1 if any pixels erased
-                let start_col = self.regs[reg_x] as usize % self.width();
-                let start_row = self.regs[reg_y] as usize % self.height();
-                let rows = n;
+                if self.drawn_this_frame && self.quirks.display_wait {
+                    // try again until the next frame starts
+                    self.use_quirk(Quirk::DisplayWait);
+                    self.pc -= 2;
+                    return effects;
+                }
+                self.drawn_this_frame = true;
+
+                let start_col = self.regs[reg_x as usize] as usize % self.width();
+                let start_row = self.regs[reg_y as usize] as usize % self.height();
 
                 if rows == 0 {
-                    let rows = 16;
-                    let sprite: Vec<u16> = self.memory[self.addr_reg as usize..(self.addr_reg + rows * 2 as u16) as usize].to_vec()
-                        .chunks_exact(2)
-                        .into_iter()
-                        .map(|a| u16::from_ne_bytes([a[0], a[1]]))
-                        .collect();
-                    self.regs[15] = 0;
-    
-                    for (row, sprite_row) in sprite.iter().enumerate() {
-                        let mut row = start_row + row;
-                        if row > self.height() {
-                            if self.quirks.screen_wrap {
-                                row = row % self.height();
-                            }
-                            else {
-                                break;
-                            }
-                        }
-                        self.draw_sprite(start_col, row, (*sprite_row & 0xFF) as u8);
-                        self.draw_sprite(start_col + 8, row, (*sprite_row >> 8) as u8);
-                    }
+                    // DXY0 - 16x16 sprite SUPERCHIP, each row is two bytes
+                    self.draw_sprite(start_col, start_row, 16, 16);
                 }
                 else {
-                    let sprite = &self.memory[self.addr_reg as usize..(self.addr_reg + rows as u16) as usize].to_vec();
-                    self.regs[15] = 0;
-    
-                    for (row, sprite_row) in sprite.iter().enumerate() {
-                        let mut row = start_row + row;
-                        if row > self.height() {
-                            if self.quirks.screen_wrap {
-                                row = row % self.height();
-                            }
-                            else {
-                                break;
-                            }
-                        }
-                        self.draw_sprite(start_col, row, *sprite_row);
-                    }
-                } 
+                    self.draw_sprite(start_col, start_row, rows as usize, 8);
+                }
+                effects.display_changed = true;
             }
-            0xE => {
-                match opcode & 0x00FF {
-                    0x9E => {
-                        // EX9E - skip next instruction if key in VX pressed
-                        if pressed_keys.contains(&self.regs[reg_x]) {
-                            self.pc += 2;
-                        }
-                    }
-                    0xA1 => {
-                        // EXA1 - skip next instruction if key in VX not pressed
-                        if !pressed_keys.contains(&self.regs[reg_x]) {
-                            self.pc += 2;
-                        }
-                    }
-                    _ => panic!("Unsopported opcode {:#06x} at {:#06x}", opcode, self.pc),
+            Instruction::SkipIfKey(reg_x) => {
+                // EX9E - skip next instruction if key in VX pressed
+                if key_down(self.keys, self.regs[reg_x as usize]) {
+                    self.pc += 2;
                 }
             }
-            0xF => {
-                match nn {
-                    0x07 => {
-                        // FX07 - Sets VX to delay time
-                        self.regs[reg_x] = self.delay_timer;
-                    },
-                    0x0A => {
-                        // FX0A - Get key. Blocking instruction. Waits for key input and then puts it in VX. However, timers should still decrement
-                        if self.pressed_key == None {
-                            if !self.waiting_for_key_press {
-                                self.ignore_keys = pressed_keys.clone();
-                                self.waiting_for_key_press = true;
-                            }
-
-                            self.pc -= 2;
-                        }
-                        else if let Some(key) = self.pressed_key {
-                            self.regs[reg_x] = key;
-                            self.ignore_keys = HashSet::new();
-                            self.pressed_key = None;
-                            self.waiting_for_key_press = false;
-                        }
-                    },
-                    0x15 => {
-                        // FX15 - Delay timer = VX
-                        self.delay_timer = self.regs[reg_x];
-                    },
-                    0x18 => {
-                        // FX18 - Sound timer = VX
-                        self.sound_timer = self.regs[reg_x];
-                    },
-                    0x1E => {
-                        // FX1E - I += VX. VF not affected
-                        self.addr_reg += self.regs[reg_x] as u16;
-                    },
-                    0x29 => {
-                        // FX29 - I = addr of hex character in VX
-                        let reg = self.regs[reg_x] as u16;
-                        self.addr_reg = LOW_RES_FONT_START as u16 + reg * 5;
-                    },
-                    0x30 => {
-                        // FX29 - I = addr of hex character in VX
-                        let reg = self.regs[reg_x] as u16;
-                        self.addr_reg = HIGH_RES_FONT_START as u16 + reg * 10;
-                    },
-                    0x33 => {
-                        // FX33 - Store BCD of VX in I. I is hundreds. I + 1 tens. I + 2 units.
-                        let mut bcd: u32 = self.regs[reg_x] as u32;
-
-                        for _ in 0..8 {
-                            if bcd & 0x00F00 >= 0x00500 {
-                                bcd += 0x00300;
-                            }
-                            if bcd & 0x0F000 >= 0x05000 {
-                                bcd += 0x03000;
-                            }
-                            if bcd & 0xF0000 >= 0x50000 {
-                                bcd += 0x30000;
-                            }
-                            bcd <<= 1;
+            Instruction::SkipIfNotKey(reg_x) => {
+                // EXA1 - skip next instruction if key in VX not pressed
+                if !key_down(self.keys, self.regs[reg_x as usize]) {
+                    self.pc += 2;
+                }
+            }
+            Instruction::SkipIfSecondKey(reg_x) => {
+                // EXF2 - skip next instruction if key in VX pressed on the second keypad CHIP-8X
+                if key_down(self.second_keypad, self.regs[reg_x as usize]) {
+                    self.pc += 2;
+                }
+            }
+            Instruction::SkipIfNotSecondKey(reg_x) => {
+                // EXF5 - skip next instruction if key in VX not pressed on the second keypad CHIP-8X
+                if !key_down(self.second_keypad, self.regs[reg_x as usize]) {
+                    self.pc += 2;
+                }
+            }
+            Instruction::GetDelay(reg_x) => {
+                // FX07 - Sets VX to delay time
+                self.regs[reg_x as usize] = self.delay_timer;
+            },
+            Instruction::WaitForKey(reg_x) => {
+                // FX0A - Get key. Blocking instruction. Waits for key input and then puts it in VX. However, timers should still decrement
+                if self.pressed_key == None {
+                    if !self.waiting_for_key_press {
+                        if self.keys != 0 {
+                            self.use_quirk(Quirk::KeyWaitGrace);
                         }
+                        // a key pressed just before is more likely a quick tap that was meant for this than one held
+                        // down from earlier
+                        let grace = self.quirks.key_wait_grace;
+                        let recent = (0..16).filter(|&key| grace > 0 && self.key_ages[key] <= grace).fold(0, |recent, key| recent | 1 << key);
+                        self.ignore_keys = self.keys & !recent;
+                        self.waiting_for_key_press = true;
+                    }
 
-                        self.memory[self.addr_reg as usize] = ((bcd & 0xF0000) >> 16) as u8;
-                        self.memory[self.addr_reg as usize + 1] = ((bcd & 0x0F000) >> 12) as u8;
-                        self.memory[self.addr_reg as usize + 2] = ((bcd & 0x00F00) >> 8) as u8;
-                    },
-                    0x55 => {
-                        // FX55 - Dump regs V0 - VX(inclusive) to I - I + X. I is unmodified
-                        let total_regs = reg_x as u16 + 1;
+                    self.pc -= 2;
+                }
+                else if let Some(key) = self.pressed_key {
+                    self.regs[reg_x as usize] = key;
+                    self.ignore_keys = 0;
+                    self.pressed_key = None;
+                    self.waiting_for_key_press = false;
+                }
+            },
+            Instruction::SetDelay(reg_x) => {
+                // FX15 - Delay timer = VX
+                self.delay_timer = self.regs[reg_x as usize];
+            },
+            Instruction::SetSound(reg_x) => {
+                // FX18 - Sound timer = VX
+                self.sound_timer = self.regs[reg_x as usize];
+            },
+            Instruction::AddAddr(reg_x) => {
+                // FX1E - I += VX. VF not affected
+                self.addr_reg = self.addr_reg.wrapping_add(self.regs[reg_x as usize] as u16);
+            },
+            Instruction::LowResFont(reg_x) => {
+                // FX29 - I = addr of hex character in VX
+                let reg = self.regs[reg_x as usize] as u16;
+                self.addr_reg = LOW_RES_FONT_START as u16 + reg * 5;
+            },
+            Instruction::HighResFont(reg_x) => {
+                // FX30 - I = addr of big hex character in VX SUPERCHIP
+                let reg = self.regs[reg_x as usize] as u16;
+                self.addr_reg = HIGH_RES_FONT_START as u16 + reg * 10;
+            },
+            Instruction::Bcd(reg_x) => {
+                // FX33 - Store BCD of VX in I. I is hundreds. I + 1 tens. I + 2 units.
+                let mut bcd: u32 = self.regs[reg_x as usize] as u32;
 
-                        for i in 0..total_regs {
-                            self.memory[(self.addr_reg + i) as usize] = self.regs[(i) as usize];
-                        }
+                for _ in 0..8 {
+                    if bcd & 0x00F00 >= 0x00500 {
+                        bcd += 0x00300;
+                    }
+                    if bcd & 0x0F000 >= 0x05000 {
+                        bcd += 0x03000;
+                    }
+                    if bcd & 0xF0000 >= 0x50000 {
+                        bcd += 0x30000;
+                    }
+                    bcd <<= 1;
+                }
 
-                        match self.quirks.reg_save_load {
-                            RegSaveLoadQuirk::Unchanged => {},
-                            RegSaveLoadQuirk::X => self.addr_reg += total_regs,
-                            RegSaveLoadQuirk::XPlusOne => self.addr_reg += total_regs + 1,
-                        };
-                    },
-                    0x65 => {
-                        // FX65 - Load regs V0 - VX(inclusive) from I - I + X. I is unmodified
-                        let total_regs = reg_x as u16 + 1;
+                let addr = self.addr_reg as usize;
+                self.memory[addr % RAM_SIZE] = ((bcd & 0xF0000) >> 16) as u8;
+                self.memory[(addr + 1) % RAM_SIZE] = ((bcd & 0x0F000) >> 12) as u8;
+                self.memory[(addr + 2) % RAM_SIZE] = ((bcd & 0x00F00) >> 8) as u8;
+                self.invalidate_decoded(addr, addr + 3);
+            },
+            Instruction::StoreRegs(reg_x) => {
+                // FX55 - Dump regs V0 - VX(inclusive) to I - I + X. I is unmodified
+                let total_regs = reg_x as u16 + 1;
 
-                        for i in 0..total_regs {
-                            self.regs[i as usize] = self.memory[(self.addr_reg + i) as usize];
-                        }
+                let addr = self.addr_reg as usize;
+                for i in 0..total_regs as usize {
+                    self.memory[(addr + i) % RAM_SIZE] = self.regs[i];
+                }
+                self.invalidate_decoded(addr, addr + total_regs as usize);
 
-                        match self.quirks.reg_save_load {
-                            RegSaveLoadQuirk::Unchanged => {},
-                            RegSaveLoadQuirk::X => self.addr_reg += total_regs,
-                            RegSaveLoadQuirk::XPlusOne => self.addr_reg += total_regs + 1,
-                        };
-                    },
-                    0x75 => {},
-                    0x85 => {},
-                    _ => panic!("Unsopported opcode {:#06x} at {:#06x}", opcode, self.pc),
+                self.use_quirk(Quirk::RegSaveLoad);
+                match self.quirks.reg_save_load {
+                    RegSaveLoadQuirk::Unchanged => {},
+                    RegSaveLoadQuirk::X => self.addr_reg = self.addr_reg.wrapping_add(total_regs),
+                    RegSaveLoadQuirk::XPlusOne => self.addr_reg = self.addr_reg.wrapping_add(total_regs + 1),
+                };
+            },
+            Instruction::LoadRegs(reg_x) => {
+                // FX65 - Load regs V0 - VX(inclusive) from I - I + X. I is unmodified
+                let total_regs = reg_x as u16 + 1;
+
+                let addr = self.addr_reg as usize;
+                for i in 0..total_regs as usize {
+                    self.regs[i] = self.memory[(addr + i) % RAM_SIZE];
                 }
-            }
-            _ => panic!("should only be a nibble"),
+
+                self.use_quirk(Quirk::RegSaveLoad);
+                match self.quirks.reg_save_load {
+                    RegSaveLoadQuirk::Unchanged => {},
+                    RegSaveLoadQuirk::X => self.addr_reg = self.addr_reg.wrapping_add(total_regs),
+                    RegSaveLoadQuirk::XPlusOne => self.addr_reg = self.addr_reg.wrapping_add(total_regs + 1),
+                };
+            },
+            Instruction::StoreFlags(_) => {},
+            Instruction::LoadFlags(_) => {},
+            Instruction::Unsupported(opcode) => self.fail(CpuError::UnsupportedOpcode { opcode, addr: pc as u16 }),
         };
+
+        // only instructions that touch the display can set it off, so the rest don't pay for checking
+        if effects.display_changed {
+            effects.display_watch_hit = match self.display_watch {
+                Some(DisplayWatch::AnyUpdate) => true,
+                Some(DisplayWatch::Pixel(x, y)) => watched_pixel.is_some_and(|before| before != self.pixel(x, y)),
+                None => false,
+            };
+        }
+
+        effects
+    }
+
+    fn fail(&mut self, error: CpuError) {
+        self.pc -= 2;
+        self.halted = true;
+        self.error = Some(error);
     }
 
-    fn draw_sprite(&mut self, start_col: usize, row: usize, sprite_row: u8) {
-        for col_i in 0..8 {
-            let mut col = col_i + start_col;
+    // an instruction starting one byte before a write overlaps it too, so that one has to go as well
+    fn invalidate_decoded(&mut self, start: usize, end: usize) {
+        let len = (end - start).min(RAM_SIZE);
+        let start = start % RAM_SIZE;
+        let end = start + len;
+
+        self.decoded[start.saturating_sub(1)..end.min(RAM_SIZE)].fill(None);
+        if end > RAM_SIZE {
+            // the write wrapped round past the end of memory
+            self.decoded[..end - RAM_SIZE].fill(None);
+        }
+        if start == 0 {
+            self.decoded[RAM_SIZE - 1] = None;
+        }
+    }
+
+    // the sprite is read straight out of memory at I, sprite_width / 8 bytes per row
+    fn draw_sprite(&mut self, start_col: usize, start_row: usize, rows: usize, sprite_width: usize) {
+        let width = self.width();
+        let height = self.height();
+        let mask = self.row_mask();
+        let bytes_per_row = sprite_width / 8;
+        let sprite_start = self.addr_reg as usize;
+        let mut collided = 0;
+
+        if start_col + sprite_width > width || start_row + rows > height {
+            self.use_quirk(Quirk::ScreenWrap);
+        }
 
-            if col >= self.width() {
+        for row_i in 0..rows {
+            let sprite_row = (0..bytes_per_row)
+                .map(|byte| self.memory[(sprite_start + row_i * bytes_per_row + byte) % RAM_SIZE])
+                .fold(0u128, |row, byte| row << 8 | byte as u128);
+
+            let mut row = start_row + row_i;
+            if row >= height {
                 if self.quirks.screen_wrap {
-                    col = col % self.width();
+                    row %= height;
                 }
                 else {
                     break;
                 }
             }
 
-            let sprite_pixel = (sprite_row & (1 << (7 - col_i))) == 1 << (7 - col_i); // the 7 - col_i is to make the sprite_row be read in the correct direction
-            let screen_pixel = self.pixels[row][col];
-            
-            if sprite_pixel != screen_pixel {
-                self.pixels[row][col] = true;
-            } else {
-                self.pixels[row][col] = false;
+            let aligned = sprite_row << (128 - sprite_width); // line the sprite up with the leftmost pixel
+            let mut bits = (aligned >> start_col) & mask;
+            if self.quirks.screen_wrap && start_col + sprite_width > width {
+                bits |= (aligned << (width - start_col)) & mask;
             }
 
-            // if gone from set to unset then set VF to 1
-            if screen_pixel == true && self.pixels[row][col] == false {
-                self.regs[15] = 1;
-            }
+            // any bit set in both is a pixel being turned off
+            collided += (self.display[row] & bits).count_ones();
+            self.display[row] ^= bits;
         }
 
+        self.regs[15] = if collided > 0 { 1 } else { 0 };
+    }
+}
+
+fn key_down(pressed_keys: u16, key: u8) -> bool {
+    key_bit(key).map_or(false, |bit| pressed_keys & bit != 0)
+}
+
+// anything past F isn't a key on the keypad so is never pressed
+fn key_bit(key: u8) -> Option<u16> {
+    1u16.checked_shl(key as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpu_with(program: &[u16]) -> CPU {
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+        let mut cpu = CPU::new();
+        cpu.load_rom(&rom);
+        cpu
+    }
+
+    fn run(cpu: &mut CPU, instructions: usize) {
+        for _ in 0..instructions {
+            cpu.handle_opcode();
+        }
+    }
+
+    fn run_program(program: &[u16]) -> CPU {
+        let mut cpu = cpu_with(program);
+        run(&mut cpu, program.len());
+        cpu
+    }
+
+    fn lit_pixels(cpu: &CPU) -> Vec<(usize, usize)> {
+        (0..cpu.height())
+            .flat_map(|y| (0..cpu.width()).map(move |x| (x, y)))
+            .filter(|&(x, y)| cpu.pixel(x, y))
+            .collect()
+    }
+
+    #[test]
+    fn load_and_add() {
+        let cpu = run_program(&[0x6A12, 0x7A34, 0x6BFF, 0x7B02]);
+        assert_eq!(cpu.regs[0xA], 0x46);
+        assert_eq!(cpu.regs[0xB], 0x01); // wraps around
+        assert_eq!(cpu.regs[0xF], 0);    // 7XNN never touches the flag
+    }
+
+    #[test]
+    fn logic_ops() {
+        let cpu = run_program(&[0x60F0, 0x613C, 0x8200, 0x8211, 0x8300, 0x8312, 0x8400, 0x8413]);
+        assert_eq!(cpu.regs[2], 0xFC);
+        assert_eq!(cpu.regs[3], 0x30);
+        assert_eq!(cpu.regs[4], 0xCC);
+    }
+
+    #[test]
+    fn vf_reset_quirk() {
+        for (vf_reset, expected) in [(false, 0x55), (true, 0)] {
+            let mut cpu = cpu_with(&[0x6F55, 0x8011]);
+            cpu.quirks.vf_reset = vf_reset;
+            run(&mut cpu, 2);
+            assert_eq!(cpu.regs[0xF], expected);
+        }
+
+        // only the logic instructions reset it, and after reading it
+        let mut cpu = cpu_with(&[0x6F55, 0x80F0, 0x81F1]);
+        cpu.quirks.vf_reset = true;
+        run(&mut cpu, 3);
+        assert_eq!((cpu.regs[0], cpu.regs[1], cpu.regs[0xF]), (0x55, 0x55, 0));
+    }
+
+    #[test]
+    fn add_sets_carry() {
+        let cpu = run_program(&[0x60FF, 0x6102, 0x8014]);
+        assert_eq!((cpu.regs[0], cpu.regs[0xF]), (0x01, 1));
+
+        let cpu = run_program(&[0x6010, 0x6120, 0x8014]);
+        assert_eq!((cpu.regs[0], cpu.regs[0xF]), (0x30, 0));
+    }
+
+    #[test]
+    fn flag_is_written_after_the_result() {
+        // with VF as the destination the flag wins
+        let cpu = run_program(&[0x6FFF, 0x6002, 0x8F04]);
+        assert_eq!(cpu.regs[0xF], 1);
+    }
+
+    #[test]
+    fn sub_sets_not_borrow() {
+        let cpu = run_program(&[0x6005, 0x6103, 0x8015]);
+        assert_eq!((cpu.regs[0], cpu.regs[0xF]), (0x02, 1));
+
+        let cpu = run_program(&[0x6003, 0x6105, 0x8015]);
+        assert_eq!((cpu.regs[0], cpu.regs[0xF]), (0xFE, 0));
+
+        let cpu = run_program(&[0x6003, 0x6105, 0x8017]);
+        assert_eq!((cpu.regs[0], cpu.regs[0xF]), (0x02, 1));
+
+        let cpu = run_program(&[0x6005, 0x6103, 0x8017]);
+        assert_eq!((cpu.regs[0], cpu.regs[0xF]), (0xFE, 0));
+    }
+
+    #[test]
+    fn shifts_follow_the_quirk() {
+        for (shifting, right, left) in [(ShiftingReg::VX, (0x40, 1), (0x02, 1)), (ShiftingReg::VY, (0x01, 0), (0x04, 0))] {
+            let mut cpu = cpu_with(&[0x6081, 0x6102, 0x8016]);
+            cpu.quirks.shifting = shifting;
+            run(&mut cpu, 3);
+            assert_eq!((cpu.regs[0], cpu.regs[0xF]), right);
+
+            let mut cpu = cpu_with(&[0x6081, 0x6102, 0x801E]);
+            cpu.quirks.shifting = shifting;
+            run(&mut cpu, 3);
+            assert_eq!((cpu.regs[0], cpu.regs[0xF]), left);
+        }
+    }
+
+    #[test]
+    fn skips() {
+        let cases = [
+            (0x3012, true),  // 3XNN with V0 == 12
+            (0x3013, false),
+            (0x4012, false), // 4XNN
+            (0x4013, true),
+            (0x5010, true),  // 5XY0 with V0 == V1
+            (0x5020, false),
+            (0x9010, false), // 9XY0
+            (0x9020, true),
+        ];
+
+        for (opcode, skipped) in cases {
+            let cpu = run_program(&[0x6012, 0x6112, 0x6234, opcode]);
+            assert_eq!(cpu.pc(), if skipped { 0x20A } else { 0x208 }, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn call_and_return() {
+        let mut cpu = cpu_with(&[0x2206, 0x6001, 0x1204, 0x00EE]);
+        run(&mut cpu, 1);
+        assert_eq!((cpu.pc(), cpu.stack()), (0x206, &[0x202][..]));
+        run(&mut cpu, 1);
+        assert_eq!((cpu.pc(), cpu.stack()), (0x202, &[][..]));
+        run(&mut cpu, 1);
+        assert_eq!(cpu.regs[0], 1);
+    }
+
+    #[test]
+    fn jump_to_itself_halts() {
+        let mut cpu = cpu_with(&[0x1202]);
+        run(&mut cpu, 1);
+        assert!(!cpu.halted());
+
+        let mut cpu = cpu_with(&[0x1200]);
+        run(&mut cpu, 1);
+        assert!(cpu.halted());
+        run(&mut cpu, 10);
+        assert_eq!(cpu.pc(), 0x200);
+    }
+
+    #[test]
+    fn exit_halts() {
+        let mut cpu = cpu_with(&[0x00FD, 0x6001]);
+        run(&mut cpu, 5);
+        assert!(cpu.halted());
+        assert_eq!((cpu.pc(), cpu.regs[0]), (0x200, 0));
+    }
+
+    #[test]
+    fn jump_with_offset() {
+        for (jump, expected) in [(JumpBehviour::BNNN, 0x310), (JumpBehviour::BXNN, 0x320)] {
+            let mut cpu = cpu_with(&[0x6010, 0x6320, 0xB300]);
+            cpu.quirks.jump = jump;
+            run(&mut cpu, 3);
+            assert_eq!(cpu.pc(), expected);
+        }
+    }
+
+    #[test]
+    fn index_register() {
+        let cpu = run_program(&[0xA123, 0x6005, 0xF01E]);
+        assert_eq!(cpu.addr_reg(), 0x128);
+
+        let cpu = run_program(&[0x600A, 0xF029]);
+        assert_eq!(cpu.addr_reg() as usize, LOW_RES_FONT_START + 0xA * 5);
+
+        let cpu = run_program(&[0x600A, 0xF030]);
+        assert_eq!(cpu.addr_reg() as usize, HIGH_RES_FONT_START + 0xA * 10);
+    }
+
+    #[test]
+    fn random_is_masked_and_seedable() {
+        let mut a = cpu_with(&[0xC00F, 0xC1FF]);
+        let mut b = cpu_with(&[0xC00F, 0xC1FF]);
+        a.seed_rng(1);
+        b.seed_rng(1);
+        run(&mut a, 2);
+        run(&mut b, 2);
+
+        assert!(a.regs[0] <= 0x0F);
+        assert_eq!(a.regs, b.regs);
+    }
+
+    #[test]
+    fn bcd() {
+        for (value, digits) in [(0x00, [0, 0, 0]), (0x09, [0, 0, 9]), (0x7B, [1, 2, 3]), (0xFF, [2, 5, 5])] {
+            let cpu = run_program(&[0x6000 | value, 0xA300, 0xF033]);
+            assert_eq!(cpu.memory[0x300..0x303], digits);
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn store_and_load_registers() {
+        let quirks = [(RegSaveLoadQuirk::Unchanged, 0x300), (RegSaveLoadQuirk::X, 0x303), (RegSaveLoadQuirk::XPlusOne, 0x304)];
+
+        for (reg_save_load, addr) in quirks {
+            let mut cpu = cpu_with(&[0x6011, 0x6122, 0x6233, 0x6344, 0xA300, 0xF255]);
+            cpu.quirks.reg_save_load = reg_save_load;
+            run(&mut cpu, 6);
+            assert_eq!(cpu.memory[0x300..0x304], [0x11, 0x22, 0x33, 0]);
+            assert_eq!(cpu.addr_reg(), addr);
+
+            let mut cpu = cpu_with(&[0xA208, 0xF265, 0x1206, 0x0000, 0xAABB, 0xCC00]);
+            cpu.quirks.reg_save_load = reg_save_load;
+            run(&mut cpu, 2);
+            assert_eq!(cpu.regs[..4], [0xAA, 0xBB, 0xCC, 0]);
+            assert_eq!(cpu.addr_reg(), addr - 0x300 + 0x208);
+        }
+    }
+
+    #[test]
+    fn self_modifying_code_is_decoded_again() {
+        // runs 6001, then overwrites it with 6002 using FX55 and runs it again
+        let mut cpu = cpu_with(&[0x6001, 0x6060, 0x6102, 0xA200, 0xF155, 0x1200]);
+        run(&mut cpu, 6);
+        assert_eq!(cpu.regs[0], 0x60);
+        run(&mut cpu, 1);
+        assert_eq!(cpu.regs[0], 0x02);
+        assert_eq!(cpu.memory[0x200..0x202], [0x60, 0x02]);
+    }
+
+    #[test]
+    fn timers() {
+        let mut cpu = run_program(&[0x6003, 0xF015, 0xF018]);
+        assert!(cpu.timer_tick());
+        assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (2, 2));
+
+        cpu.timer_tick();
+        cpu.timer_tick();
+        assert!(!cpu.timer_tick());
+        assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (0, 0));
+
+        let cpu = run_program(&[0x6005, 0xF015, 0xF107]);
+        assert_eq!(cpu.regs[1], 5);
+    }
+
+    #[test]
+    fn key_skips() {
+        let mut cpu = cpu_with(&[0x6005, 0xE09E, 0x0000, 0xE0A1]);
+        cpu.handle_opcode();
+        cpu.key_down(5);
+        cpu.handle_opcode();
+        assert_eq!(cpu.pc(), 0x206);
+        cpu.key_up(5);
+        cpu.handle_opcode();
+        assert_eq!(cpu.pc(), 0x20A);
+
+        let mut cpu = cpu_with(&[0x6005, 0xE0F2, 0x0000, 0xE0F5]);
+        cpu.set_keys(1 << 5);
+        cpu.handle_opcode();
+        cpu.set_second_keypad(1 << 5);
+        cpu.handle_opcode();
+        assert_eq!(cpu.pc(), 0x206);
+        cpu.set_second_keypad(0);
+        cpu.handle_opcode();
+        assert_eq!(cpu.pc(), 0x20A);
+    }
+
+    #[test]
+    fn wait_for_key_needs_a_release() {
+        let mut cpu = cpu_with(&[0xF30A, 0x6001]);
+
+        cpu.handle_opcode();
+        cpu.set_keys(1 << 7);
+        cpu.handle_opcode();
+        assert_eq!(cpu.pc(), 0x200);
+
+        cpu.set_keys(0);
+        cpu.handle_opcode();
+        assert_eq!((cpu.pc(), cpu.regs[3]), (0x202, 7));
+    }
+
+    #[test]
+    fn wait_for_key_ignores_keys_already_held() {
+        let mut cpu = cpu_with(&[0xF30A]);
+
+        cpu.key_down(2);
+        cpu.handle_opcode();
+        cpu.key_up(2);
+        cpu.handle_opcode();
+        assert_eq!(cpu.pc(), 0x200);
+
+        cpu.key_down(2);
+        cpu.handle_opcode();
+        cpu.key_up(2);
+        cpu.handle_opcode();
+        assert_eq!((cpu.pc(), cpu.regs[3]), (0x202, 2));
+    }
+
+    #[test]
+    fn wait_for_key_grace_period() {
+        let mut cpu = cpu_with(&[0xF30A]);
+        cpu.quirks.key_wait_grace = 2;
+
+        // held for three frames, too long ago to count
+        cpu.key_down(4);
+        for _ in 0..3 {
+            cpu.timer_tick();
+        }
+        // pressed in the last two frames so it's probably meant for FX0A
+        cpu.key_down(9);
+        cpu.timer_tick();
+        cpu.handle_opcode();
+        assert!(cpu.waiting_for_key());
+        assert_eq!(cpu.ignored_keys(), 1 << 4);
+
+        cpu.key_up(9);
+        cpu.handle_opcode();
+        assert_eq!((cpu.pc(), cpu.regs[3]), (0x202, 9));
+    }
+
+    #[test]
+    fn draw_and_collide() {
+        // draws the 0 glyph at (1, 2) then again to erase it
+        let mut cpu = cpu_with(&[0x6001, 0x6102, 0xF229, 0xD015, 0xD015]);
+        run(&mut cpu, 4);
+
+        assert_eq!(cpu.regs[0xF], 0);
+        assert_eq!(lit_pixels(&cpu).len(), 14);
+        assert!(cpu.pixel(1, 2) && cpu.pixel(4, 2) && !cpu.pixel(5, 2));
+        assert!(cpu.pixel(1, 3) && !cpu.pixel(2, 3));
+
+        run(&mut cpu, 1);
+        assert_eq!(cpu.regs[0xF], 1);
+        assert!(lit_pixels(&cpu).is_empty());
+    }
+
+    #[test]
+    fn sprites_clip_or_wrap() {
+        // an 8x1 line drawn 4 pixels from the right and bottom edges, with coordinates that are themselves past the edge
+        let program = [0x6000 | (64 + 60), 0x6100 | 31, 0xA20C, 0xD012, 0x1208, 0x0000, 0xFFFF];
+
+        let mut cpu = cpu_with(&program);
+        run(&mut cpu, 4);
+        assert_eq!(lit_pixels(&cpu), (60..64).map(|x| (x, 31)).collect::<Vec<_>>());
+
+        let mut cpu = cpu_with(&program);
+        cpu.quirks.screen_wrap = true;
+        run(&mut cpu, 4);
+        let mut expected: Vec<_> = [31, 0].iter().flat_map(|&y| (0..4).chain(60..64).map(move |x| (x, y))).collect();
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(lit_pixels(&cpu), expected);
+    }
+
+    #[test]
+    fn clear_screen() {
+        let mut cpu = cpu_with(&[0xF029, 0xD005, 0x00E0]);
+        run(&mut cpu, 2);
+        assert!(!lit_pixels(&cpu).is_empty());
+        run(&mut cpu, 1);
+        assert!(lit_pixels(&cpu).is_empty());
+    }
+
+    #[test]
+    fn resolution_switching() {
+        let mut cpu = cpu_with(&[0xF029, 0xD005, 0x00FF, 0x00FE]);
+        run(&mut cpu, 2);
+
+        let effects = cpu.handle_opcode();
+        assert!(effects.resolution_changed);
+        assert_eq!((cpu.width(), cpu.height()), (128, 64));
+        assert!(lit_pixels(&cpu).is_empty());
+
+        cpu.handle_opcode();
+        assert_eq!((cpu.width(), cpu.height()), (64, 32));
+    }
+
+    #[test]
+    fn ascii_screen() {
+        // the 0 then the 1 from the font, 5 rows tall so the last line only has its top half
+        let screen = run_program(&[0xF029, 0xD005, 0x6101, 0xF129, 0x6005, 0xD025]).ascii_screen();
+        let lines: Vec<&str> = screen.lines().collect();
+
+        assert_eq!(lines.len(), 16 + 2);
+        assert_eq!(lines[0], format!("+{}+", "-".repeat(64)));
+        assert_eq!(lines[1].trim_end_matches(['|', ' ']), "|█▀▀█  ▄█");
+        assert_eq!(lines[2].trim_end_matches(['|', ' ']), "|█  █   █");
+        assert_eq!(lines[3].trim_end_matches(['|', ' ']), "|▀▀▀▀  ▀▀▀");
+        assert_eq!(lines[4], lines[16]);
+    }
+
+    #[test]
+    fn state_hash_follows_the_random_numbers() {
+        // V0 = random, so the same seed has to get the same state
+        let mut a = cpu_with(&[0xC0FF]);
+        let mut b = a.clone();
+        a.seed_rng(3);
+        b.seed_rng(3);
+        assert_eq!(a.state_hash(), b.state_hash());
+
+        b.seed_rng(4);
+        assert_ne!(a.state_hash(), b.state_hash());
+        b.seed_rng(3);
+        run(&mut a, 1);
+        assert_ne!(a.state_hash(), b.state_hash());
+        run(&mut b, 1);
+        assert_eq!(a.state_hash(), b.state_hash());
+    }
+
+    #[test]
+    fn display_hash_only_depends_on_the_screen() {
+        // written down so a change to how it's worked out gets noticed, old hashes would stop matching
+        let blank = CPU::new().display_hash();
+        assert_eq!(blank, 0xc413b1e2498686d5);
+
+        let mut cpu = cpu_with(&[0xF029, 0xD005, 0xD005]);
+        run(&mut cpu, 2);
+        assert_ne!(cpu.display_hash(), blank);
+        run(&mut cpu, 1);
+        assert_eq!(cpu.display_hash(), blank);
+
+        // a blank high res screen isn't the same as a blank low res one
+        let cpu = run_program(&[0x00FF]);
+        assert_ne!(cpu.display_hash(), blank);
+    }
+
+    #[test]
+    fn big_sprites() {
+        // DXY0 in high res draws a 16x16 sprite from 32 bytes
+        let mut cpu = cpu_with(&[0x00FF, 0xA300, 0xD010]);
+        cpu.memory[0x300..0x320].fill(0xFF);
+        run(&mut cpu, 3);
+        assert_eq!(lit_pixels(&cpu).len(), 256);
+        assert!(cpu.pixel(15, 15) && !cpu.pixel(16, 0) && !cpu.pixel(0, 16));
+    }
+
+    #[test]
+    fn only_quirks_that_made_a_difference_are_used() {
+        // shifting a register into itself and B0NN do the same whatever the quirks
+        let cpu = run_program(&[0x8116, 0xB004]);
+        assert!(cpu.quirks_used().is_empty());
+
+        // an 8x5 sprite at x = 60 goes past the right edge
+        let cpu = run_program(&[0x8126, 0x603C, 0xD015, 0xF155]);
+        assert_eq!(cpu.quirks_used(), [Quirk::Shifting, Quirk::RegSaveLoad, Quirk::ScreenWrap]);
+    }
+
+    #[test]
+    fn scrolling() {
+        let dot = |x: u8, y: u8| [0x6000 | x as u16, 0x6100 | y as u16, 0xA300, 0xD011];
+
+        let mut cpu = cpu_with(&[dot(10, 3).as_slice(), &[0x00C2, 0x00FB, 0x00FC, 0x00FC]].concat());
+        cpu.memory[0x300] = 0x80;
+        run(&mut cpu, 4);
+
+        run(&mut cpu, 1);
+        assert_eq!(lit_pixels(&cpu), [(10, 5)]);
+        run(&mut cpu, 1);
+        assert_eq!(lit_pixels(&cpu), [(14, 5)]);
+        run(&mut cpu, 2);
+        assert_eq!(lit_pixels(&cpu), [(6, 5)]);
+    }
+
+    #[test]
+    fn read_opcode_wraps() {
+        let mut cpu = cpu_with(&[0x1234]);
+        cpu.write_memory(0xFFF, 0xAB);
+        cpu.write_memory(0x000, 0xCD);
+        assert_eq!(cpu.read_opcode(0x200), 0x1234);
+        assert_eq!(cpu.read_opcode(0xFFF), 0xABCD);
+    }
+
+    #[test]
+    fn display_watch() {
+        // a dot at (0, 0), another at (5, 0), then clear the screen
+        let mut cpu = cpu_with(&[0x6000, 0xA300, 0xD011, 0x6005, 0xD011, 0x00E0]);
+        cpu.memory[0x300] = 0x80;
+        cpu.display_watch = Some(DisplayWatch::Pixel(5, 0));
+
+        let hits: Vec<bool> = (0..6).map(|_| cpu.handle_opcode().display_watch_hit).collect();
+        assert_eq!(hits, [false, false, false, false, true, true]);
+
+        let mut cpu = cpu_with(&[0x6000, 0xA300, 0xD011]);
+        cpu.display_watch = Some(DisplayWatch::AnyUpdate);
+        let hits: Vec<bool> = (0..3).map(|_| cpu.handle_opcode().display_watch_hit).collect();
+        assert_eq!(hits, [false, false, true]);
+    }
+
+    #[test]
+    fn scrolling_drops_pixels_off_the_edges() {
+        let mut cpu = cpu_with(&[0x6000, 0x6100 | 31, 0xA300, 0xD011, 0x00C1, 0x6000 | 62, 0x6100, 0xD011, 0x00FB]);
+        cpu.memory[0x300] = 0x80;
+
+        run(&mut cpu, 5);
+        assert!(lit_pixels(&cpu).is_empty());
+        run(&mut cpu, 4);
+        assert!(lit_pixels(&cpu).is_empty());
+    }
+
+    #[test]
+    fn unsupported_opcodes_halt() {
+        let cpu = run_program(&[0x6001, 0x8008, 0x6002]);
+        assert!(cpu.halted());
+        assert_eq!(cpu.error(), Some(CpuError::UnsupportedOpcode { opcode: 0x8008, addr: 0x202 }));
+        assert_eq!((cpu.pc(), cpu.regs[0]), (0x202, 1));
+    }
+
+    #[test]
+    fn stack_limits() {
+        let cpu = run_program(&[0x00EE]);
+        assert_eq!(cpu.error(), Some(CpuError::StackUnderflow { addr: 0x200 }));
+
+        let mut cpu = cpu_with(&[0x2200]);
+        run(&mut cpu, MAX_STACK_DEPTH + 5);
+        assert_eq!(cpu.stack().len(), MAX_STACK_DEPTH);
+        assert_eq!(cpu.error(), Some(CpuError::StackOverflow { addr: 0x200 }));
+    }
+
+    #[test]
+    fn save_and_load_state() {
+        let mut cpu = cpu_with(&[
+            0x6A05, // 200: VA = 5
+            0x2206, // 202: call 206
+            0x7A01, // 204: VA += 1
+            0xA20C, // 206: I = 20C
+            0xD001, // 208: draw a row at 0, 0
+            0x00EE, // 20A: return
+            0xF000, // 20C: sprite
+        ]);
+        run(&mut cpu, 3);
+        let state = cpu.save_state();
+
+        // change the code after saving so loading has to throw away what was decoded since
+        cpu.write_memory(0x205, 0x02);
+        run(&mut cpu, 3);
+        assert_eq!(cpu.registers()[0xA], 7);
+
+        cpu.load_state(&state).unwrap();
+        assert_eq!((cpu.pc(), cpu.registers()[0xA], cpu.stack()), (0x208, 5, &[0x204][..]));
+        assert!(lit_pixels(&cpu).is_empty());
+
+        run(&mut cpu, 3);
+        assert_eq!(cpu.registers()[0xA], 6);
+        assert_eq!(lit_pixels(&cpu), [(0, 0), (1, 0), (2, 0), (3, 0)]);
+
+        let mut too_deep = state.clone();
+        too_deep.stack = vec![0x200; MAX_STACK_DEPTH + 1];
+        assert!(cpu.load_state(&too_deep).is_err());
+    }
+
+    #[test]
+    fn memory_accesses_wrap() {
+        // FX55 and a sprite straddling the end of memory, then running the instruction split across it
+        let mut cpu = cpu_with(&[0x60AA, 0x61BB, 0xAFFF, 0xF155, 0xD232, 0x6000, 0xBFFF]);
+        run(&mut cpu, 7);
+        assert_eq!((cpu.memory[0xFFF], cpu.memory[0]), (0xAA, 0xBB));
+        assert!(cpu.pixel(0, 0) && !cpu.pixel(1, 0) && cpu.pixel(0, 1));
+        assert_eq!(cpu.pc(), 0xFFF);
+
+        cpu.handle_opcode();
+        assert_eq!(cpu.addr_reg(), 0xABB);
+        assert!(cpu.error().is_none());
+    }
+}
@@ -0,0 +1,88 @@
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::Config;
+use crate::cpu::CPU;
+use crate::diagnostics;
+use crate::savestate::{self, SAVESTATE_EXTENSION};
+
+thread_local! {
+    // the hook runs where the panic happened, before unwinding, so it's the only place the backtrace can come from
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// what was going on when the emulator crashed
+pub struct CrashContext<'a> {
+    pub cpu: &'a CPU,
+    pub config: &'a Config,
+    pub rom_name: &'a str,
+    pub rom_id: Option<&'a str>,
+}
+
+// keeps hold of each panic's message and backtrace for the crash report, still printing them as usual
+pub fn install_panic_hook() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let panic = format!("{}\n\n{}", info, Backtrace::force_capture());
+        LAST_PANIC.with(|last| *last.borrow_mut() = Some(panic));
+        default_hook(info);
+    }));
+}
+
+// writes a text report and a savestate of the CPU into dir, returning where the report went
+pub fn write_report(dir: &Path, context: &CrashContext) -> Result<PathBuf, String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+    let state_path = dir.join(format!("crash-{}", time)).with_extension(SAVESTATE_EXTENSION);
+    let report_path = dir.join(format!("crash-{}.txt", time));
+
+    savestate::save(&state_path, context.cpu)?;
+    fs::write(&report_path, report(context, &state_path)).map_err(|e| format!("Could not write {}: {}", report_path.display(), e))?;
+
+    Ok(report_path)
+}
+
+fn report(context: &CrashContext, state_path: &Path) -> String {
+    let panic = LAST_PANIC.with(|last| last.borrow().clone()).unwrap_or_else(|| String::from("The panic message wasn't caught"));
+    let config = toml::to_string_pretty(context.config).unwrap_or_else(|e| format!("Could not be written out: {}", e));
+
+    format!(
+        "fish_n_chip8 {} crashed\n\n{}\n\nROM: {} ({})\nSavestate: {}\n\n{}\nConfig:\n{}",
+        env!("CARGO_PKG_VERSION"),
+        panic,
+        context.rom_name,
+        context.rom_id.unwrap_or("no ROM loaded"),
+        state_path.display(),
+        diagnostics::report(context.cpu),
+        config,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_report_and_savestate() {
+        let dir = std::env::temp_dir().join(format!("fish_n_chip8-crash-{}", std::process::id()));
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x63, 0x07]);
+        cpu.handle_opcode();
+
+        let context = CrashContext { cpu: &cpu, config: &Config::default(), rom_name: "Test", rom_id: None };
+        let report_path = write_report(&dir, &context).unwrap();
+        let report = fs::read_to_string(&report_path).unwrap();
+        let state_path = report_path.with_extension(SAVESTATE_EXTENSION);
+        let state = savestate::load(&state_path);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(report.contains("ROM: Test (no ROM loaded)"));
+        assert!(report.contains("V0 00  V1 00  V2 00  V3 07"));
+        assert_eq!(state.map(|saved| saved.state.regs[3]), Ok(7));
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_CROWD_PORT: u16 = 8266;
+pub const DEFAULT_CROWD_QUEUE_LENGTH: usize = 20;
+pub const DEFAULT_CROWD_HOLD_TICKS: u32 = 6;
+
+const MAX_KEYS_PER_MESSAGE: usize = 8; // so one person can't fill the whole queue at once
+const MAX_LINE_LENGTH: usize = 256;    // including the newline, anyone sending more is disconnected
+const MAX_CONNECTIONS: usize = 100;
+const MAX_CONNECTIONS_PER_ADDRESS: usize = 2;
+const MIN_MESSAGE_INTERVAL: Duration = Duration::from_millis(250); // messages from one connection closer together are turned away
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);     // so connections that never send anything don't keep their place
+const ACCEPT_POLL_TIME: Duration = Duration::from_millis(100);
+
+type Connections = Arc<Mutex<HashMap<IpAddr, usize>>>; // how many connections there are from each address
+
+// takes key presses from anyone who connects, one line of hex digits per message, and plays them in the order they came in
+pub struct CrowdServer {
+    queue: Arc<Mutex<VecDeque<u8>>>,
+    running: Arc<AtomicBool>,
+    current: Option<(u8, u32)>, // key and ticks since it was pressed
+}
+
+impl CrowdServer {
+    // only a chat bot on this computer can connect unless public is set, then anyone on the network can
+    pub fn start(port: u16, max_queue_length: usize, public: bool) -> io::Result<CrowdServer> {
+        let address = if public { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+        let listener = TcpListener::bind((address, port))?;
+        listener.set_nonblocking(true)?;
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let running = Arc::new(AtomicBool::new(true));
+        let connections = Connections::default();
+
+        let thread_queue = queue.clone();
+        let thread_running = running.clone();
+        thread::spawn(move || {
+            while thread_running.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((mut stream, address)) => {
+                        let Some(slot) = ConnectionSlot::take(&connections, address.ip()) else {
+                            let _ = stream.write_all(b"busy, too many connections\n");
+                            continue;
+                        };
+                        let queue = thread_queue.clone();
+                        thread::spawn(move || {
+                            handle_connection(stream, queue, max_queue_length);
+                            drop(slot);
+                        });
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_TIME),
+                    Err(e) => println!("Crowd play could not accept a connection: {}", e),
+                }
+            }
+        });
+
+        Ok(CrowdServer { queue, running, current: None })
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.lock().map_or(0, |queue| queue.len())
+    }
+
+    // each key is held and then released for as long again so repeats of the same key still count
+    pub fn tick(&mut self, hold_ticks: u32) -> u16 {
+        let hold_ticks = hold_ticks.max(1);
+
+        if let Some((_, ticks)) = &mut self.current {
+            *ticks += 1;
+            if *ticks >= hold_ticks * 2 {
+                self.current = None;
+            }
+        }
+        if self.current.is_none() {
+            self.current = self.queue.lock().ok().and_then(|mut queue| queue.pop_front()).map(|key| (key, 0));
+        }
+
+        match self.current {
+            Some((key, ticks)) if ticks < hold_ticks => 1 << key,
+            _ => 0,
+        }
+    }
+}
+
+impl Drop for CrowdServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+// one connection's place in the count, given back when it's dropped
+struct ConnectionSlot {
+    connections: Connections,
+    address: IpAddr,
+}
+
+impl ConnectionSlot {
+    fn take(connections: &Connections, address: IpAddr) -> Option<ConnectionSlot> {
+        let mut counts = connections.lock().ok()?;
+        let total: usize = counts.values().sum();
+        let count = counts.entry(address).or_insert(0);
+        if total >= MAX_CONNECTIONS || *count >= MAX_CONNECTIONS_PER_ADDRESS {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionSlot { connections: connections.clone(), address })
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        if let Ok(mut counts) = self.connections.lock() {
+            if let Some(count) = counts.get_mut(&self.address) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(&self.address);
+                }
+            }
+        }
+    }
+}
+
+// the next line, or None once the connection closes or sends a line longer than anyone would type
+fn read_message(reader: &mut impl BufRead) -> Option<String> {
+    let mut line = String::new();
+    reader.take(MAX_LINE_LENGTH as u64 + 1).read_line(&mut line).ok()?;
+    if line.is_empty() || line.len() > MAX_LINE_LENGTH {
+        return None;
+    }
+    Some(line)
+}
+
+fn handle_connection(stream: TcpStream, queue: Arc<Mutex<VecDeque<u8>>>, max_queue_length: usize) {
+    if stream.set_nonblocking(false).is_err() || stream.set_read_timeout(Some(IDLE_TIMEOUT)).is_err() {
+        return;
+    }
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+
+    let mut reader = BufReader::new(stream);
+    let mut last_message: Option<Instant> = None;
+    while let Some(line) = read_message(&mut reader) {
+        if last_message.is_some_and(|last| last.elapsed() < MIN_MESSAGE_INTERVAL) {
+            if writer.write_all(b"too fast, slow down\n").is_err() {
+                break;
+            }
+            continue;
+        }
+        last_message = Some(Instant::now());
+
+        let keys: Vec<u8> = line.chars().filter_map(|c| c.to_digit(16)).map(|key| key as u8).take(MAX_KEYS_PER_MESSAGE).collect();
+        let reply = match queue.lock() {
+            Ok(mut queue) if queue.len() + keys.len() <= max_queue_length => {
+                queue.extend(&keys);
+                format!("queued {}\n", keys.len())
+            },
+            Ok(_) => String::from("busy, try again later\n"),
+            Err(_) => break,
+        };
+
+        if writer.write_all(reply.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn limits_lines_and_connections() {
+        let long = "5".repeat(MAX_LINE_LENGTH);
+        let mut reader = Cursor::new(format!("4 4 6\n{}\n", long));
+        assert_eq!(read_message(&mut reader).as_deref(), Some("4 4 6\n"));
+        assert_eq!(read_message(&mut reader), None);
+
+        let connections = Connections::default();
+        let address = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let first = ConnectionSlot::take(&connections, address).unwrap();
+        let _second = ConnectionSlot::take(&connections, address).unwrap();
+        assert!(ConnectionSlot::take(&connections, address).is_none());
+        assert!(ConnectionSlot::take(&connections, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))).is_some());
+
+        drop(first);
+        assert!(ConnectionSlot::take(&connections, address).is_some());
+    }
+}
@@ -0,0 +1,308 @@
+use ggegui::Gui;
+use ggegui::egui::{self, RichText};
+
+use ggez::{Context, ContextBuilder, GameResult};
+use ggez::conf::WindowSetup;
+use ggez::event::{self, EventHandler};
+use ggez::glam::Vec2;
+use ggez::graphics::{Canvas, Color, DrawParam};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tungstenite::{Message, WebSocket};
+
+use std::net::{Ipv4Addr, TcpStream};
+use std::process;
+use std::time::Duration;
+
+use crate::config::Config;
+use crate::instruction::Instruction;
+use crate::remote::DEFAULT_REMOTE_PORT;
+
+const WINDOW_TITLE: &str = "Fish n CHIP-8 debugger";
+const WINDOW_SIZE: (f32, f32) = (760.0, 560.0);
+
+// the emulator answers once a frame so this only runs out if it has frozen
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+const DISASSEMBLY_BEFORE: u16 = 8; // instructions shown before the one about to run
+const DISASSEMBLY_AFTER: u16 = 24;
+const MEMORY_BYTES_PER_ROW: usize = 16;
+const HIGHLIGHT_STEPS: u32 = 20; // how many steps or frames a changed register stays highlighted for, fading out
+
+// what get_state replies with
+#[derive(Deserialize)]
+struct MachineState {
+    pc: u16,
+    i: u16,
+    registers: [u8; 16],
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    paused: bool,
+    halted: bool,
+    error: Option<String>,
+}
+
+// how long ago each of I, V0 to VF and the timers changed, counted in the times the machine has moved on since, so
+// a paused program keeps its highlights until the next step
+#[derive(Default)]
+struct Changes {
+    previous: Option<(u16, Vec<u16>)>,
+    ages: Vec<u32>,
+}
+
+impl Changes {
+    fn update(&mut self, state: &MachineState) {
+        let values: Vec<u16> = [state.i].into_iter()
+            .chain(state.registers.iter().map(|&value| value as u16))
+            .chain([state.delay_timer as u16, state.sound_timer as u16])
+            .collect();
+
+        match &self.previous {
+            Some((pc, previous)) if *pc != state.pc || *previous != values => {
+                for (age, (value, before)) in self.ages.iter_mut().zip(values.iter().zip(previous)) {
+                    *age = if value != before { 0 } else { age.saturating_add(1) };
+                }
+            },
+            Some(_) => (),
+            None => self.ages = vec![HIGHLIGHT_STEPS; values.len()],
+        }
+        self.previous = Some((state.pc, values));
+    }
+
+    // the background to show the value with, fading from the selection colour to nothing
+    fn highlight(&self, ui: &egui::Ui, index: usize) -> egui::Color32 {
+        let age = self.ages.get(index).copied().unwrap_or(HIGHLIGHT_STEPS).min(HIGHLIGHT_STEPS);
+        ui.visuals().selection.bg_fill.linear_multiply(1.0 - age as f32 / HIGHLIGHT_STEPS as f32)
+    }
+}
+
+// the debugger panels in a window of their own so they don't take space from the game on small screens. It runs
+// as a second process and follows the emulator through the remote control API
+struct Debugger {
+    socket: WebSocket<TcpStream>,
+    gui: Gui,
+    state: Option<MachineState>,
+    memory: Vec<u8>,
+    changes: Changes,
+    disconnected: Option<String>, // why it stopped following the emulator
+}
+
+impl Debugger {
+    fn new(ctx: &mut Context, socket: WebSocket<TcpStream>) -> Debugger {
+        let mut gui = Gui::new(ctx);
+        gui.ctx().set_style(Config::load(ctx.fs.user_config_dir()).unwrap_or_default().style());
+
+        Debugger { socket, gui, state: None, memory: vec![], changes: Changes::default(), disconnected: None }
+    }
+
+    fn send(&mut self, command: Value) -> Result<Value, String> {
+        self.socket.send(Message::Text(command.to_string())).map_err(|e| format!("Lost the connection to the emulator: {}", e))?;
+
+        loop {
+            match self.socket.read() {
+                Ok(Message::Text(text)) => {
+                    let reply: Value = serde_json::from_str(&text).map_err(|e| format!("The emulator sent an invalid reply: {}", e))?;
+                    return match reply["ok"].as_bool() {
+                        Some(true) => Ok(reply),
+                        _ => Err(reply["error"].as_str().unwrap_or("The emulator could not run a command").to_string()),
+                    };
+                },
+                Ok(Message::Close(_)) => return Err(String::from("The emulator was closed")),
+                Ok(_) => continue,
+                Err(e) => return Err(format!("Lost the connection to the emulator: {}", e)),
+            }
+        }
+    }
+
+    fn refresh(&mut self) -> Result<(), String> {
+        let state = self.send(json!({ "command": "get_state" }))?;
+        let state = serde_json::from_value(state).map_err(|e| format!("The emulator sent an invalid state: {}", e))?;
+        self.changes.update(&state);
+        self.state = Some(state);
+
+        let memory = self.send(json!({ "command": "get_memory" }))?;
+        self.memory = serde_json::from_value(memory["memory"].clone()).map_err(|e| format!("The emulator sent invalid memory: {}", e))?;
+
+        Ok(())
+    }
+
+    fn opcode(&self, addr: u16) -> Option<u16> {
+        let addr = addr as usize;
+        Some((*self.memory.get(addr)? as u16) << 8 | *self.memory.get(addr + 1)? as u16)
+    }
+}
+
+impl EventHandler for Debugger {
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        if self.disconnected.is_none() {
+            if let Err(e) = self.refresh() {
+                self.disconnected = Some(e);
+            }
+        }
+
+        let gui_ctx = &self.gui.ctx();
+        let mut command = None;
+
+        egui::TopBottomPanel::top("Controls").show(gui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                match (&self.disconnected, &self.state) {
+                    (Some(e), _) => {
+                        ui.colored_label(ui.visuals().error_fg_color, e);
+                    },
+                    (None, Some(state)) => {
+                        if ui.button(if state.paused { "Run" } else { "Pause" }).clicked() {
+                            command = Some(json!({ "command": if state.paused { "resume" } else { "pause" } }));
+                        }
+                        if ui.add_enabled(state.paused && !state.halted, egui::Button::new("Step")).clicked() {
+                            command = Some(json!({ "command": "step" }));
+                        }
+                        if let Some(error) = &state.error {
+                            ui.colored_label(ui.visuals().warn_fg_color, error);
+                        }
+                        else if state.halted {
+                            ui.label("The program has stopped");
+                        }
+                    },
+                    (None, None) => {
+                        ui.label("Connecting...");
+                    },
+                }
+            });
+        });
+
+        let Some(state) = &self.state else {
+            self.gui.update(ctx);
+            return Ok(());
+        };
+
+        egui::SidePanel::left("Registers").resizable(false).show(gui_ctx, |ui| {
+            ui.heading("Registers");
+            egui::Grid::new("Register values").striped(true).show(ui, |ui| {
+                let changes = &self.changes;
+                let row = |ui: &mut egui::Ui, name: &str, value: String, index: Option<usize>| {
+                    ui.label(name);
+                    let text = RichText::new(value).monospace();
+                    ui.label(match index {
+                        Some(index) => text.background_color(changes.highlight(ui, index)),
+                        None => text,
+                    });
+                    ui.end_row();
+                };
+
+                // the program counter changes every step so it isn't highlighted
+                row(ui, "PC", format!("{:03X}", state.pc), None);
+                row(ui, "I", format!("{:03X}", state.i), Some(0));
+                for (x, value) in state.registers.iter().enumerate() {
+                    row(ui, &format!("V{:X}", x), format!("{:02X}", value), Some(1 + x));
+                }
+                for (i, (name, value)) in [("Delay", state.delay_timer), ("Sound", state.sound_timer)].into_iter().enumerate() {
+                    row(ui, name, format!("{:02X}", value), Some(17 + i));
+                }
+            });
+            ui.separator();
+
+            ui.heading("Stack");
+            if state.stack.is_empty() {
+                ui.label("Empty");
+            }
+            // the most recent call first
+            for addr in state.stack.iter().rev() {
+                ui.monospace(format!("{:03X}", addr));
+            }
+        });
+
+        egui::CentralPanel::default().show(gui_ctx, |ui| {
+            ui.columns(2, |columns| {
+                columns[0].heading("Disassembly");
+                let start = state.pc.saturating_sub(DISASSEMBLY_BEFORE * 2);
+                for addr in (start..state.pc + DISASSEMBLY_AFTER * 2).step_by(2) {
+                    let Some(opcode) = self.opcode(addr) else {
+                        break;
+                    };
+                    let text = RichText::new(format!("{:03X}: {:04X}  {}", addr, opcode, Instruction::decode(opcode))).monospace();
+                    columns[0].label(if addr == state.pc { text.strong().background_color(columns[0].visuals().selection.bg_fill) } else { text });
+                }
+
+                columns[1].heading("Memory");
+                let row_height = columns[1].text_style_height(&egui::TextStyle::Monospace);
+                let rows = self.memory.len().div_ceil(MEMORY_BYTES_PER_ROW);
+                egui::ScrollArea::vertical().auto_shrink(false).show_rows(&mut columns[1], row_height, rows, |ui, visible| {
+                    for row in visible {
+                        let start = row * MEMORY_BYTES_PER_ROW;
+                        let bytes: Vec<String> = self.memory[start..(start + MEMORY_BYTES_PER_ROW).min(self.memory.len())]
+                            .iter()
+                            .map(|byte| format!("{:02X}", byte))
+                            .collect();
+                        ui.monospace(format!("{:03X}: {}", start, bytes.join(" ")));
+                    }
+                });
+            });
+        });
+
+        self.gui.update(ctx);
+
+        if let Some(command) = command {
+            if let Err(e) = self.send(command) {
+                self.disconnected = Some(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = Canvas::from_frame(ctx, Color::BLACK);
+        canvas.draw(&self.gui, DrawParam::default().dest(Vec2::ZERO));
+
+        canvas.finish(ctx)
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        self.gui.input.text_input_event(character);
+        Ok(())
+    }
+
+    fn resize_event(&mut self, _ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        self.gui.input.resize_event(width, height);
+        Ok(())
+    }
+}
+
+// opened by the emulator's Detach debugger button, with the remote control port to connect to
+pub fn debugger_main(args: &[String]) {
+    let port = match args.first().map(|port| port.parse()) {
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            eprintln!("Usage: fish_n_chip8 --debugger [remote control port]");
+            process::exit(2);
+        },
+        None => DEFAULT_REMOTE_PORT,
+    };
+
+    let socket = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+        .and_then(|stream| stream.set_read_timeout(Some(REPLY_TIMEOUT)).map(|()| stream))
+        .map_err(|e| e.to_string())
+        .and_then(|stream| tungstenite::client(format!("ws://127.0.0.1:{}", port), stream).map_err(|e| e.to_string()));
+    let socket = match socket {
+        Ok((socket, _)) => socket,
+        Err(e) => {
+            eprintln!("Could not connect to the emulator on port {}: {}\nTurn on the remote control API in its configuration.", port, e);
+            process::exit(1);
+        },
+    };
+
+    let (mut ctx, event_loop) = ContextBuilder::new("fish_n_chip8", "jenningsfan")
+        .window_setup(WindowSetup::default().title(WINDOW_TITLE))
+        .window_mode(ggez::conf::WindowMode::default()
+            .dimensions(WINDOW_SIZE.0, WINDOW_SIZE.1)
+            .resizable(true)
+        )
+        .build()
+        .expect("Failed to create game context");
+
+    let debugger = Debugger::new(&mut ctx, socket);
+
+    event::run(ctx, event_loop, debugger);
+}
@@ -0,0 +1,58 @@
+use crate::cpu::CPU;
+use crate::instruction::Instruction;
+
+// where the program is and how it got there as plain text, shown when it stops on an error and for pasting into bug
+// reports
+pub fn report(cpu: &CPU) -> String {
+    let mut report = match cpu.error() {
+        Some(error) => format!("The program stopped: {}\n\n", error),
+        None => String::new(),
+    };
+
+    report += &format!("PC {}\n", instruction_at(cpu, cpu.pc()));
+    for row in cpu.registers().chunks(8).enumerate() {
+        let (start, registers) = row;
+        let registers: Vec<String> = registers.iter().enumerate().map(|(i, value)| format!("V{:X} {:02X}", start * 8 + i, value)).collect();
+        report += &format!("{}\n", registers.join("  "));
+    }
+    report += &format!("I {:03X}  DT {:02X}  ST {:02X}\n", cpu.addr_reg(), cpu.delay_timer(), cpu.sound_timer());
+
+    let stack: Vec<String> = cpu.stack().iter().map(|addr| format!("{:03X}", addr)).collect();
+    report += &format!("Stack: {}\n", if stack.is_empty() { String::from("empty") } else { stack.join(" ") });
+
+    report += "\nLast instructions, oldest first:\n";
+    for addr in cpu.recent_instructions() {
+        report += &format!("  {}\n", instruction_at(cpu, addr));
+    }
+
+    report
+}
+
+fn instruction_at(cpu: &CPU, addr: u16) -> String {
+    let opcode = cpu.read_opcode(addr);
+    format!("{:03X}: {:04X}  {}", addr, opcode, Instruction::decode(opcode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_how_it_stopped() {
+        // 200: V3 = 7, 202: call 206, 204: return with nothing to return to, 206: return to 204
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x63, 0x07, 0x22, 0x06, 0x00, 0xEE, 0x00, 0xEE]);
+        for _ in 0..4 {
+            cpu.handle_opcode();
+        }
+
+        let report = report(&cpu);
+        let lines: Vec<&str> = report.lines().collect();
+        assert_eq!(lines[0], "The program stopped: Return at 204 without a subroutine to return from");
+        assert_eq!(lines[2], "PC 204: 00EE  RET");
+        assert!(lines[3].starts_with("V0 00  V1 00  V2 00  V3 07"));
+        assert_eq!(lines[6], "Stack: empty");
+        assert_eq!(lines[8], "Last instructions, oldest first:");
+        assert_eq!(&lines[9..], ["  200: 6307  LD V3, 0x07", "  202: 2206  CALL 0x206", "  206: 00EE  RET", "  204: 00EE  RET"]);
+    }
+}
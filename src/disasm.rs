@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::process;
+
+use crate::analysis::{CodeMap, Coverage};
+use crate::cpu::{MAX_ROM_SIZE, PROGRAM_START};
+use crate::instruction::Instruction;
+
+const DATA_BYTES_PER_LINE: usize = 8;
+
+// prints a ROM as instructions where the program can reach and as bytes everywhere else
+pub fn disasm_main(args: &[String]) {
+    let annotate = args.iter().any(|arg| arg == "--annotated");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--annotated").collect();
+    let (Some(path), 1) = (args.first(), args.len()) else {
+        eprintln!("Usage: fish_n_chip8 --disasm <rom> [--annotated]");
+        process::exit(2);
+    };
+
+    let rom = match fs::read(path) {
+        Ok(rom) if rom.len() <= MAX_ROM_SIZE => rom,
+        Ok(rom) => {
+            eprintln!("{} is {} bytes, the most that fits in memory is {} bytes", path, rom.len(), MAX_ROM_SIZE);
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Could not read {}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    if annotate {
+        print!("{}", annotated(&rom, path, None));
+        return;
+    }
+
+    let map = CodeMap::analyse(&rom);
+    let end = PROGRAM_START + rom.len();
+    let mut addr = PROGRAM_START;
+
+    while addr < end {
+        let offset = addr - PROGRAM_START;
+
+        if map.is_instruction(addr) {
+            let opcode = (rom[offset] as u16) << 8 | rom[offset + 1] as u16;
+            println!("{:03X}: {:04X}      {}", addr, opcode, Instruction::decode(opcode));
+            addr += 2;
+        }
+        else {
+            // data runs until the next instruction, split into lines of a few bytes
+            let length = (addr..end)
+                .take(DATA_BYTES_PER_LINE)
+                .take_while(|&data| !map.is_instruction(data))
+                .count();
+            let bytes: Vec<String> = rom[offset..offset + length].iter().map(|byte| format!("0x{:02X}", byte)).collect();
+
+            println!("{:03X}: DB {}", addr, bytes.join(", "));
+            addr += length;
+        }
+    }
+
+    println!();
+    println!("{} of {} bytes are reachable code", map.code_bytes(), rom.len());
+}
+
+// a listing to read a ROM from, with labels where the code calls, jumps and points I, each byte of data drawn as the
+// sprite row it would be, and the instructions that ran marked with a * if there's coverage from playing it
+pub fn annotated(rom: &[u8], name: &str, coverage: Option<&Coverage>) -> String {
+    let map = CodeMap::analyse(rom);
+    let labels = labels(&map, rom.len());
+    let ran = |addr: usize| coverage.is_some_and(|coverage| coverage.ran(addr));
+    let marker = |addr: usize| match coverage {
+        Some(_) if ran(addr) => "* ",
+        Some(_) => "  ",
+        None => "",
+    };
+
+    let mut lines = vec![];
+    let mut instructions = 0;
+    let mut instructions_ran = 0;
+    let mut addr = PROGRAM_START;
+    while addr < PROGRAM_START + rom.len() {
+        if let Some(label) = labels.get(&addr) {
+            lines.push(String::new());
+            lines.push(format!("{}:", label));
+        }
+
+        let offset = addr - PROGRAM_START;
+        let found_by_running = !map.is_instruction(addr) && ran(addr); // such as through a BNNN jump table
+        if (map.is_instruction(addr) || found_by_running) && offset + 1 < rom.len() {
+            let opcode = (rom[offset] as u16) << 8 | rom[offset + 1] as u16;
+            let instruction = Instruction::decode(opcode);
+            let mut comments: Vec<&str> = instruction.target().and_then(|target| labels.get(&(target as usize))).map(String::as_str).into_iter().collect();
+            if found_by_running {
+                comments.push("only found by running it");
+            }
+
+            let comment = if comments.is_empty() { String::new() } else { format!("; {}", comments.join(", ")) };
+            lines.push(format!("{}{:03X}: {:04X}  {:<20}{}", marker(addr), addr, opcode, instruction.to_string(), comment).trim_end().to_string());
+            instructions += 1;
+            instructions_ran += ran(addr) as usize;
+            addr += 2;
+        }
+        else {
+            let byte = rom[offset];
+            let row: String = (0..8).map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' }).collect();
+            lines.push(format!("{}{:03X}: {:02X}    {:<20}; {}", marker(addr), addr, byte, format!("DB 0x{:02X}", byte), row));
+            addr += 1;
+        }
+    }
+
+    let mut header = vec![
+        format!("; {}, {} bytes", name, rom.len()),
+        format!("; {} of them are reachable code", map.code_bytes()),
+    ];
+    if coverage.is_some() {
+        header.push(format!("; * marks the {} of {} instructions that ran while it was played", instructions_ran, instructions));
+    }
+    if lines.first().is_some_and(|line| !line.is_empty()) {
+        header.push(String::new());
+    }
+    header.extend(lines);
+    header.join("\n") + "\n"
+}
+
+// calls are named first so a subroutine that's also jumped to is still called a subroutine
+fn labels(map: &CodeMap, rom_length: usize) -> BTreeMap<usize, String> {
+    let mut labels = BTreeMap::new();
+    for prefix in ["sub", "label", "data"] {
+        for &(_, opcode) in map.opcodes() {
+            let target = match (prefix, Instruction::decode(opcode)) {
+                ("sub", Instruction::Call(target)) => target,
+                ("label", Instruction::Jump(target) | Instruction::JumpOffset(_, target)) => target,
+                ("data", Instruction::LoadAddr(target)) => target,
+                _ => continue,
+            } as usize;
+
+            if (PROGRAM_START..PROGRAM_START + rom_length).contains(&target) {
+                labels.entry(target).or_insert_with(|| format!("{}_{:03X}", prefix, target));
+            }
+        }
+    }
+    labels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotates_a_listing() {
+        // 200: I = 20A, 202: call 208, 204: loop forever, 206: an instruction only a BNNN could get to, 208: return,
+        // 20A: a sprite row
+        let rom = [0xA2, 0x0A, 0x22, 0x08, 0x12, 0x04, 0x60, 0x01, 0x00, 0xEE, 0x3C];
+        let mut coverage = Coverage::default();
+        for pc in [0x200, 0x202, 0x208, 0x206] {
+            coverage.record(pc);
+        }
+
+        let listing = annotated(&rom, "test.ch8", Some(&coverage));
+        assert_eq!(listing.lines().collect::<Vec<_>>(), [
+            "; test.ch8, 11 bytes",
+            "; 8 of them are reachable code",
+            "; * marks the 4 of 5 instructions that ran while it was played",
+            "",
+            "* 200: A20A  LD I, 0x20A         ; data_20A",
+            "* 202: 2208  CALL 0x208          ; sub_208",
+            "",
+            "label_204:",
+            "  204: 1204  JP 0x204            ; label_204",
+            "* 206: 6001  LD V0, 0x01         ; only found by running it",
+            "",
+            "sub_208:",
+            "* 208: 00EE  RET",
+            "",
+            "data_20A:",
+            "  20A: 3C    DB 0x3C             ; ..####..",
+        ]);
+    }
+}
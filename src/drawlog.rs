@@ -0,0 +1,138 @@
+use ggegui::egui::{self, RichText};
+
+use std::collections::VecDeque;
+use std::fs;
+
+use crate::cpu::CPU;
+use crate::instruction::Instruction;
+use crate::io::show_error;
+
+const MAX_CALLS: usize = 10_000; // the oldest are dropped past this so leaving it on doesn't eat memory
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DrawCall {
+    pub frame: u32,
+    pub pc: u16,
+    pub addr_reg: u16,
+    pub x: u8, // the values of VX and VY, before wrapping onto the screen
+    pub y: u8,
+    pub height: u8, // 0 for a 16x16 sprite
+    pub collided: bool,
+}
+
+// every DXYN that ran with where it drew and whether it turned pixels off, for working out why a sprite ends up in
+// the wrong place or rubs out the wrong thing
+#[derive(Default)]
+pub struct DrawLog {
+    pub enabled: bool,
+    frame: u32,
+    calls: VecDeque<DrawCall>,
+    only_collisions: bool,
+}
+
+impl DrawLog {
+    pub fn next_frame(&mut self) {
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    // called before an instruction runs, the draw it's about to do if it's a DXYN
+    pub fn pending(&self, cpu: &CPU) -> Option<DrawCall> {
+        if !self.enabled {
+            return None;
+        }
+
+        let pc = cpu.pc();
+        let Instruction::Draw(x, y, height) = Instruction::decode(cpu.read_opcode(pc)) else {
+            return None;
+        };
+
+        Some(DrawCall {
+            frame: self.frame,
+            pc,
+            addr_reg: cpu.addr_reg(),
+            x: cpu.registers()[x as usize],
+            y: cpu.registers()[y as usize],
+            height,
+            collided: false,
+        })
+    }
+
+    // called after it ran, VF having the collision
+    pub fn finish(&mut self, pending: Option<DrawCall>, cpu: &CPU) {
+        let Some(call) = pending else {
+            return;
+        };
+
+        if self.calls.len() == MAX_CALLS {
+            self.calls.pop_front();
+        }
+        self.calls.push_back(DrawCall { collided: cpu.registers()[0xF] != 0, ..call });
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frame,pc,i,x,y,height,collided\n");
+        for call in &self.calls {
+            csv += &format!("{},{:03X},{:03X},{},{},{},{}\n", call.frame, call.pc, call.addr_reg, call.x, call.y, call.height, call.collided);
+        }
+        csv
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.enabled, "Log draws");
+            ui.checkbox(&mut self.only_collisions, "Only collisions");
+            if ui.button("Clear").clicked() {
+                self.calls.clear();
+            }
+            if ui.add_enabled(!self.calls.is_empty(), egui::Button::new("Export CSV")).clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("CSV", &["csv"]).set_file_name("draws.csv").save_file() {
+                    if let Err(e) = fs::write(&path, self.to_csv()) {
+                        show_error("Could not export the draw log", format!("Could not write {}: {}", path.display(), e));
+                    }
+                }
+            }
+        });
+        ui.label(format!("{} draws logged, the last {} are kept", self.calls.len(), MAX_CALLS));
+        ui.separator();
+
+        let calls: Vec<&DrawCall> = self.calls.iter().rev().filter(|call| call.collided || !self.only_collisions).collect();
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        ui.monospace(" Frame  PC    I    X    Y  Height");
+        egui::ScrollArea::vertical().auto_shrink(false).show_rows(ui, row_height, calls.len(), |ui, visible| {
+            for call in &calls[visible] {
+                let height = if call.height == 0 { String::from("16x16") } else { call.height.to_string() };
+                let text = format!("{:>6}  {:03X}  {:03X}  {:>3}  {:>3}  {:>5}", call.frame, call.pc, call.addr_reg, call.x, call.y, height);
+                if call.collided {
+                    ui.label(RichText::new(text + "  collided").monospace().color(ui.visuals().warn_fg_color));
+                }
+                else {
+                    ui.monospace(text);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_draws_and_collisions() {
+        // 200: I = the font's 0, 202: V1 = 3, 204: draw it at V0, V1, 206: the same again so it collides
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0xA0, 0x50, 0x61, 0x03, 0xD0, 0x15, 0xD0, 0x15]);
+
+        let mut log = DrawLog { enabled: true, ..Default::default() };
+        for _ in 0..4 {
+            let pending = log.pending(&cpu);
+            cpu.handle_opcode();
+            log.finish(pending, &cpu);
+            log.next_frame();
+        }
+
+        let first = DrawCall { frame: 2, pc: 0x204, addr_reg: 0x50, x: 0, y: 3, height: 5, collided: false };
+        assert_eq!(log.calls, [first, DrawCall { frame: 3, pc: 0x206, collided: true, ..first }]);
+        assert_eq!(log.to_csv().lines().nth(2), Some("3,206,050,0,3,5,true"));
+    }
+}
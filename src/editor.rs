@@ -0,0 +1,161 @@
+use ggegui::egui::{self, text::LayoutJob, Color32, FontId, TextFormat};
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::io::show_error;
+use crate::octo::{self, AssembleError, KEYWORDS};
+
+const EXAMPLE_PROGRAM: &str = "\
+# Press Run to assemble this and load it into the emulator
+: main
+\tclear
+\ti := smile
+\tv0 := 28
+\tv1 := 12
+\tsprite v0 v1 8
+\tloop again
+
+: smile
+\t0x3C 0x42 0xA5 0x81 0xA5 0x99 0x42 0x3C
+";
+
+const DEFAULT_NAME: &str = "Untitled.8o";
+
+// a text editor for Octo source which assembles it and hands the ROM back to be run
+pub struct OctoEditor {
+    source: String,
+    path: Option<PathBuf>,
+    errors: Vec<AssembleError>,
+}
+
+impl Default for OctoEditor {
+    fn default() -> Self {
+        Self {
+            source: String::from(EXAMPLE_PROGRAM),
+            path: None,
+            errors: vec![],
+        }
+    }
+}
+
+impl OctoEditor {
+    pub fn name(&self) -> String {
+        self.path.as_ref()
+            .and_then(|path| path.file_name())
+            .map_or_else(|| String::from(DEFAULT_NAME), |name| name.to_string_lossy().into_owned())
+    }
+
+    // returns the assembled ROM when Run is pressed and the source has no errors
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<Vec<u8>> {
+        let mut assembled = None;
+
+        ui.horizontal(|ui| {
+            if ui.button("Open").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("Octo source", &["8o"]).add_filter("All files", &["*"]).pick_file() {
+                    match fs::read_to_string(&path) {
+                        Ok(source) => {
+                            self.source = source;
+                            self.path = Some(path);
+                            self.errors.clear();
+                        },
+                        Err(e) => show_error("Could not open source", format!("Could not read {}: {}", path.display(), e)),
+                    }
+                }
+            }
+            if ui.button("Save").clicked() {
+                let path = self.path.clone().or_else(|| rfd::FileDialog::new().add_filter("Octo source", &["8o"]).set_file_name(DEFAULT_NAME).save_file());
+                if let Some(path) = path {
+                    match fs::write(&path, &self.source) {
+                        Ok(()) => self.path = Some(path),
+                        Err(e) => show_error("Could not save source", format!("Could not write {}: {}", path.display(), e)),
+                    }
+                }
+            }
+            if ui.button("Run").clicked() {
+                match octo::assemble(&self.source) {
+                    Ok(rom) => {
+                        self.errors.clear();
+                        assembled = Some(rom);
+                    },
+                    Err(errors) => self.errors = errors,
+                }
+            }
+            ui.label(self.name());
+        });
+
+        // errors are marked until the next Run, the lines they're on may have moved by then but it's usually near enough
+        let error_lines: HashSet<usize> = self.errors.iter().map(|error| error.line).collect();
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            let mut job = highlight(ui, text, &error_lines);
+            job.wrap.max_width = wrap_width;
+            ui.fonts(|fonts| fonts.layout_job(job))
+        };
+
+        egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+            ui.add(egui::TextEdit::multiline(&mut self.source)
+                .code_editor()
+                .desired_rows(20)
+                .desired_width(f32::INFINITY)
+                .layouter(&mut layouter));
+        });
+
+        for error in &self.errors {
+            ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+        }
+
+        assembled
+    }
+}
+
+fn highlight(ui: &egui::Ui, text: &str, error_lines: &HashSet<usize>) -> LayoutJob {
+    let font_id = FontId::monospace(egui::TextStyle::Monospace.resolve(ui.style()).size);
+    let visuals = ui.visuals();
+    let (keyword_colour, register_colour, number_colour, directive_colour) = if visuals.dark_mode {
+        (Color32::from_rgb(86, 156, 214), Color32::from_rgb(78, 201, 176), Color32::from_rgb(181, 206, 168), Color32::from_rgb(197, 134, 192))
+    }
+    else {
+        (Color32::from_rgb(0, 0, 255), Color32::from_rgb(38, 127, 153), Color32::from_rgb(9, 134, 88), Color32::from_rgb(175, 0, 219))
+    };
+
+    let mut job = LayoutJob::default();
+
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        let background = if error_lines.contains(&(i + 1)) {
+            visuals.error_fg_color.gamma_multiply(0.25)
+        }
+        else {
+            Color32::TRANSPARENT
+        };
+        let format = |colour| TextFormat { font_id: font_id.clone(), color: colour, background, ..Default::default() };
+
+        let mut written = 0;
+        for (start, word) in octo::words(line) {
+            job.append(&line[written..start], 0.0, format(visuals.text_color()));
+
+            let colour = if word.starts_with(':') {
+                directive_colour
+            }
+            else if octo::is_register(word) {
+                register_colour
+            }
+            else if octo::parse_number(word).is_some() {
+                number_colour
+            }
+            else if KEYWORDS.contains(&word) {
+                keyword_colour
+            }
+            else {
+                visuals.text_color()
+            };
+            job.append(word, 0.0, format(colour));
+            written = start + word.len();
+        }
+
+        // whatever's left is a comment or the end of the line
+        job.append(&line[written..], 0.0, format(visuals.weak_text_color()));
+    }
+
+    job
+}
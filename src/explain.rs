@@ -0,0 +1,183 @@
+// Describes instructions in plain English for people learning how CHIP-8 works, taking the quirks into account
+// so the description matches what this emulator will actually do
+
+use crate::cpu::{JumpBehviour, Quirks, RegSaveLoadQuirk, ShiftingReg};
+use crate::instruction::{AluOp, Instruction};
+
+// a quirk that changes what an instruction does
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Quirk {
+    VfReset,
+    Shifting,
+    RegSaveLoad,
+    Jump,
+    ScreenWrap,
+    Random,
+    KeyWaitGrace,
+    DisplayWait,
+}
+
+impl Quirk {
+    pub const ALL: [Quirk; 8] = [
+        Quirk::VfReset, Quirk::Shifting, Quirk::RegSaveLoad, Quirk::Jump, Quirk::ScreenWrap, Quirk::Random, Quirk::KeyWaitGrace, Quirk::DisplayWait,
+    ];
+}
+
+pub struct Explanation {
+    pub pattern: &'static str, // the opcode pattern the instruction comes from, e.g. "7XNN"
+    pub text: String,
+    pub quirks: Vec<Quirk>,
+}
+
+pub fn explain(instruction: Instruction, quirks: &Quirks) -> Explanation {
+    let mut related = vec![];
+    let (pattern, text) = match instruction {
+        Instruction::ScrollDown(n) => ("00CN", format!("scroll the screen down {} pixels, the rows that come in at the top are blank", n)),
+        Instruction::ClearScreen => ("00E0", String::from("turn off every pixel on the screen")),
+        Instruction::Return => ("00EE", String::from("return from a subroutine, carrying on from the address on top of the stack")),
+        Instruction::ScrollRight => ("00FB", String::from("scroll the screen right 4 pixels")),
+        Instruction::ScrollLeft => ("00FC", String::from("scroll the screen left 4 pixels")),
+        Instruction::Exit => ("00FD", String::from("stop the program")),
+        Instruction::LowRes => ("00FE", String::from("switch to the 64x32 low resolution screen and clear it")),
+        Instruction::HighRes => ("00FF", String::from("switch to the 128x64 high resolution screen and clear it")),
+        Instruction::Jump(nnn) => ("1NNN", format!("jump to 0x{:03X}, if that is this instruction the program has ended and loops here forever", nnn)),
+        Instruction::Call(nnn) => ("2NNN", format!("call the subroutine at 0x{:03X}, pushing the address after this onto the stack to return to", nnn)),
+        Instruction::SkipIfEqual(x, nn) => ("3XNN", format!("skip the next instruction if V{:X} is 0x{:02X}", x, nn)),
+        Instruction::SkipIfNotEqual(x, nn) => ("4XNN", format!("skip the next instruction unless V{:X} is 0x{:02X}", x, nn)),
+        Instruction::SkipIfRegsEqual(x, y) => ("5XY0", format!("skip the next instruction if V{:X} and V{:X} are equal", x, y)),
+        Instruction::Load(x, nn) => ("6XNN", format!("set V{:X} to 0x{:02X}", x, nn)),
+        Instruction::Add(x, nn) => ("7XNN", format!("add 0x{:02X} to V{:X} without touching VF, going past 0xFF wraps round to 0", nn, x)),
+        Instruction::Alu(op, x, y) => {
+            let (pattern, text) = match op {
+                AluOp::Move => ("8XY0", format!("copy V{:X} into V{:X}", y, x)),
+                AluOp::Or => ("8XY1", format!("set V{:X} to V{:X} OR V{:X}, keeping every bit set in either", x, x, y)),
+                AluOp::And => ("8XY2", format!("set V{:X} to V{:X} AND V{:X}, keeping only the bits set in both", x, x, y)),
+                AluOp::Xor => ("8XY3", format!("set V{:X} to V{:X} XOR V{:X}, keeping the bits set in one but not the other", x, x, y)),
+                AluOp::Add => ("8XY4", format!("add V{:X} to V{:X}, VF becomes 1 if it went past 0xFF and 0 if not", y, x)),
+                AluOp::Sub => ("8XY5", format!("subtract V{:X} from V{:X}, VF becomes 0 if it went below 0 and 1 if not", y, x)),
+                AluOp::SubReversed => ("8XY7", format!("set V{:X} to V{:X} minus V{:X}, VF becomes 0 if it went below 0 and 1 if not", x, y, x)),
+                AluOp::ShiftRight | AluOp::ShiftLeft => {
+                    related.push(Quirk::Shifting);
+                    let source = match quirks.shifting {
+                        ShiftingReg::VX => x,
+                        ShiftingReg::VY => y,
+                    };
+                    if op == AluOp::ShiftRight {
+                        ("8XY6", format!("set V{:X} to V{:X} shifted right one bit, halving it, and put the bit that falls off in VF", x, source))
+                    }
+                    else {
+                        ("8XYE", format!("set V{:X} to V{:X} shifted left one bit, doubling it, and put the bit that falls off in VF", x, source))
+                    }
+                },
+            };
+
+            let text = if quirks.vf_reset && matches!(op, AluOp::Or | AluOp::And | AluOp::Xor) {
+                format!("{}, then set VF to 0", text)
+            }
+            else {
+                text
+            };
+            if matches!(op, AluOp::Or | AluOp::And | AluOp::Xor) {
+                related.push(Quirk::VfReset);
+            }
+
+            (pattern, text)
+        },
+        Instruction::SkipIfRegsNotEqual(x, y) => ("9XY0", format!("skip the next instruction if V{:X} and V{:X} are different", x, y)),
+        Instruction::LoadAddr(nnn) => ("ANNN", format!("point I at 0x{:03X}", nnn)),
+        Instruction::JumpOffset(x, nnn) => {
+            related.push(Quirk::Jump);
+            match quirks.jump {
+                JumpBehviour::BNNN => ("BNNN", format!("jump to 0x{:03X} plus V0", nnn)),
+                JumpBehviour::BXNN => ("BXNN", format!("jump to 0x{:03X} plus V{:X}", nnn, x)),
+            }
+        },
+        Instruction::Random(x, nn) => {
+            related.push(Quirk::Random);
+            ("CXNN", format!("set V{:X} to a random number from the {} generator, keeping only the bits set in 0x{:02X}", x, quirks.rng.name(), nn))
+        },
+        Instruction::Draw(x, y, n) => {
+            related.push(Quirk::ScreenWrap);
+            related.push(Quirk::DisplayWait);
+            let size = if n == 0 { String::from("16x16 sprite") } else { format!("8x{} sprite", n) };
+            let edges = if quirks.screen_wrap { "wrapping round" } else { "cut off" };
+            let wait = if quirks.display_wait { ". If a sprite has already been drawn this frame it waits for the next one first" } else { "" };
+            ("DXYN", format!(
+                "draw the {} at I with its top left corner at (V{:X}, V{:X}), flipping the pixels it covers. \
+                VF becomes 1 if it turned any pixels off and 0 if not. Anything past the edge of the screen is {}{}",
+                size, x, y, edges, wait,
+            ))
+        },
+        Instruction::SkipIfKey(x) => ("EX9E", format!("skip the next instruction if the key in V{:X} is held down", x)),
+        Instruction::SkipIfNotKey(x) => ("EXA1", format!("skip the next instruction unless the key in V{:X} is held down", x)),
+        Instruction::SkipIfSecondKey(x) => ("EXF2", format!("skip the next instruction if the key in V{:X} is held down on the second keypad", x)),
+        Instruction::SkipIfNotSecondKey(x) => ("EXF5", format!("skip the next instruction unless the key in V{:X} is held down on the second keypad", x)),
+        Instruction::GetDelay(x) => ("FX07", format!("copy the delay timer into V{:X}", x)),
+        Instruction::WaitForKey(x) => {
+            related.push(Quirk::KeyWaitGrace);
+            let held = match quirks.key_wait_grace {
+                0 => String::from("Keys already held down when it starts have to be let go of and pressed again"),
+                1 => String::from("Keys already held down when it starts have to be let go of and pressed again, unless they were pressed this frame"),
+                frames => format!("Keys already held down when it starts have to be let go of and pressed again, unless they were pressed in the last {} frames", frames),
+            };
+            ("FX0A", format!("wait until a key is pressed and let go, then put it in V{:X}. {}. The timers keep counting down while it waits", x, held))
+        },
+        Instruction::SetDelay(x) => ("FX15", format!("set the delay timer to V{:X}, it counts down 60 times a second", x)),
+        Instruction::SetSound(x) => ("FX18", format!("set the sound timer to V{:X}, the buzzer sounds until it counts down to 0", x)),
+        Instruction::AddAddr(x) => ("FX1E", format!("add V{:X} to I", x)),
+        Instruction::LowResFont(x) => ("FX29", format!("point I at the small font's sprite for the digit in V{:X}", x)),
+        Instruction::HighResFont(x) => ("FX30", format!("point I at the big font's sprite for the digit in V{:X}", x)),
+        Instruction::Bcd(x) => ("FX33", format!("write V{:X} out in decimal, the hundreds at I, the tens at I + 1 and the ones at I + 2", x)),
+        Instruction::StoreRegs(x) | Instruction::LoadRegs(x) => {
+            related.push(Quirk::RegSaveLoad);
+            let (pattern, action) = match instruction {
+                Instruction::StoreRegs(_) => ("FX55", format!("copy V0 to V{:X} into memory", x)),
+                _ => ("FX65", format!("fill V0 to V{:X} from memory", x)),
+            };
+            let i = match quirks.reg_save_load {
+                RegSaveLoadQuirk::Unchanged => String::from("I stays where it is"),
+                RegSaveLoadQuirk::X => format!("I moves on by {}", x as u16 + 1),
+                RegSaveLoadQuirk::XPlusOne => format!("I moves on by {}", x as u16 + 2),
+            };
+
+            (pattern, format!("{}, starting at I. Afterwards {}", action, i))
+        },
+        Instruction::StoreFlags(x) => ("FX75", format!("save V0 to V{:X} to the flag registers, which this emulator ignores", x)),
+        Instruction::LoadFlags(x) => ("FX85", format!("load V0 to V{:X} from the flag registers, which this emulator ignores", x)),
+        Instruction::Unsupported(_) => ("????", String::from("nothing, this isn't an instruction this emulator knows and the program will stop here")),
+    };
+
+    Explanation { pattern, text: capitalise(&text), quirks: related }
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_quirks() {
+        let mut quirks = Quirks::default();
+        let add = explain(Instruction::decode(0x7305), &quirks);
+        assert_eq!(add.pattern, "7XNN");
+        assert_eq!(add.text, "Add 0x05 to V3 without touching VF, going past 0xFF wraps round to 0");
+        assert!(add.quirks.is_empty());
+
+        let shift = Instruction::decode(0x8126);
+        assert!(explain(shift, &quirks).text.starts_with("Set V1 to V1 shifted right"));
+        quirks.shifting = ShiftingReg::VY;
+        let shift = explain(shift, &quirks);
+        assert!(shift.text.starts_with("Set V1 to V2 shifted right"));
+        assert_eq!(shift.quirks, [Quirk::Shifting]);
+
+        quirks.vf_reset = true;
+        assert!(explain(Instruction::decode(0x8121), &quirks).text.ends_with("then set VF to 0"));
+    }
+}
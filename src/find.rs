@@ -0,0 +1,127 @@
+use crate::analysis::CodeMap;
+use crate::instruction::Instruction;
+
+pub const MAX_RESULTS: usize = 1000;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum FindKind {
+    Bytes,
+    Opcode,
+    Text,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Query {
+    Bytes(Vec<Option<u8>>),              // None matches any byte
+    Opcode { value: u16, mask: u16 },    // a pattern like DXY0, the letters matching any nibble
+    Mnemonic(String),                    // part of the disassembly, such as "DRW" or "LD I"
+    Text(Vec<u8>),
+}
+
+impl Query {
+    pub fn parse(kind: FindKind, text: &str) -> Result<Query, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err(String::from("There's nothing to find"));
+        }
+
+        match kind {
+            FindKind::Bytes => {
+                let digits: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+                if digits.len() % 2 == 1 {
+                    return Err(format!("{} isn't a whole number of bytes", text));
+                }
+                digits.as_bytes().chunks(2).map(|pair| match pair {
+                    b"??" => Ok(None),
+                    pair => u8::from_str_radix(std::str::from_utf8(pair).unwrap_or_default(), 16).map(Some)
+                        .map_err(|_| format!("{} isn't a hex byte or ??", String::from_utf8_lossy(pair))),
+                }).collect::<Result<_, _>>().map(Query::Bytes)
+            },
+            FindKind::Opcode => Ok(opcode_pattern(text).unwrap_or_else(|| Query::Mnemonic(text.to_uppercase()))),
+            FindKind::Text => Ok(Query::Text(text.as_bytes().to_vec())),
+        }
+    }
+
+    // how many bytes a match covers
+    pub fn length(&self) -> usize {
+        match self {
+            Query::Bytes(pattern) => pattern.len(),
+            Query::Opcode { .. } | Query::Mnemonic(_) => 2,
+            Query::Text(text) => text.len(),
+        }
+    }
+
+    fn matches(&self, memory: &[u8], addr: usize) -> bool {
+        let Some(bytes) = memory.get(addr..addr + self.length()) else {
+            return false;
+        };
+        let opcode = || (bytes[0] as u16) << 8 | bytes[1] as u16;
+
+        match self {
+            Query::Bytes(pattern) => pattern.iter().zip(bytes).all(|(wanted, byte)| wanted.is_none_or(|wanted| wanted == *byte)),
+            Query::Opcode { value, mask } => opcode() & mask == *value,
+            Query::Mnemonic(text) => Instruction::decode(opcode()).to_string().to_uppercase().contains(text),
+            Query::Text(text) => bytes == text.as_slice(),
+        }
+    }
+}
+
+// four characters each a hex digit or X, Y, N or K for any nibble, as opcodes are usually written
+fn opcode_pattern(text: &str) -> Option<Query> {
+    if text.chars().count() != 4 {
+        return None;
+    }
+
+    let mut value = 0;
+    let mut mask = 0;
+    for c in text.chars() {
+        value <<= 4;
+        mask <<= 4;
+        match c.to_ascii_uppercase() {
+            'X' | 'Y' | 'N' | 'K' | '?' => (),
+            c => {
+                value |= c.to_digit(16)? as u16;
+                mask |= 0xF;
+            },
+        }
+    }
+    Some(Query::Opcode { value, mask })
+}
+
+// every address a match starts at, up to MAX_RESULTS. Given the code map, opcodes are only looked for where the
+// analysis found instructions
+pub fn find(memory: &[u8], query: &Query, code: Option<&CodeMap>) -> Vec<usize> {
+    let opcode = matches!(query, Query::Opcode { .. } | Query::Mnemonic(_));
+    (0..memory.len())
+        .filter(|&addr| !opcode || code.is_none_or(|map| map.is_instruction(addr)))
+        .filter(|&addr| query.matches(memory, addr))
+        .take(MAX_RESULTS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_bytes_opcodes_and_text() {
+        // 200: draw a 16x16 sprite, 202: draw 5 rows, 204: loop forever, 206: "HI"
+        let mut memory = vec![0; 0x210];
+        memory[0x200..0x208].copy_from_slice(&[0xD0, 0x10, 0xD0, 0x15, 0x12, 0x04, b'H', b'I']);
+        let map = CodeMap::analyse(&memory[0x200..]);
+
+        let query = Query::parse(FindKind::Opcode, "dxy0").unwrap();
+        assert_eq!(query, Query::Opcode { value: 0xD000, mask: 0xF00F });
+        assert_eq!(find(&memory, &query, None), [0x200]);
+        let query = Query::parse(FindKind::Opcode, "drw").unwrap();
+        assert_eq!(find(&memory, &query, Some(&map)), [0x200, 0x202]);
+
+        let query = Query::parse(FindKind::Bytes, "d0 ??").unwrap();
+        assert_eq!(find(&memory, &query, None), [0x200, 0x202]);
+        assert!(Query::parse(FindKind::Bytes, "D0 1").is_err());
+        assert!(Query::parse(FindKind::Bytes, "G0").is_err());
+
+        let query = Query::parse(FindKind::Text, "HI").unwrap();
+        assert_eq!((find(&memory, &query, None), query.length()), (vec![0x206], 2));
+    }
+}
@@ -0,0 +1,72 @@
+use ggegui::egui::{self, ColorImage, TextureHandle, TextureOptions};
+
+use std::time::Duration;
+
+use crate::cpu::{SaveState, CPU};
+
+const THUMBNAIL_WIDTH: f32 = 128.0;
+
+pub struct Screenshot {
+    pub file_name: String,
+    pub taken: Duration,        // how far into the session
+    pub rom_id: Option<String>, // None for the splash screen
+    pub state: SaveState,
+    thumbnail: TextureHandle,
+}
+
+// the screenshots taken since the emulator was started, each with the state it was taken in
+#[derive(Default)]
+pub struct Gallery {
+    screenshots: Vec<Screenshot>,
+}
+
+impl Gallery {
+    // pixels is the screen as RGBA, one per CHIP-8 pixel
+    pub fn add(&mut self, ctx: &egui::Context, pixels: &[u8], cpu: &CPU, file_name: String, taken: Duration, rom_id: Option<String>) {
+        let image = ColorImage::from_rgba_unmultiplied([cpu.width(), cpu.height()], pixels);
+        let thumbnail = ctx.load_texture(format!("Screenshot {}", file_name), image, TextureOptions::NEAREST);
+
+        self.screenshots.push(Screenshot { file_name, taken, rom_id, state: cpu.save_state(), thumbnail });
+    }
+
+    // the state to restore if one of the screenshots was clicked
+    pub fn show(&self, ui: &mut egui::Ui, rom_id: Option<&String>) -> Option<SaveState> {
+        if self.screenshots.is_empty() {
+            ui.label("Screenshots taken with the screenshot hotkey will show up here.");
+            return None;
+        }
+
+        let mut restore = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            // the newest first
+            for screenshot in self.screenshots.iter().rev() {
+                ui.horizontal(|ui| {
+                    let [width, height] = screenshot.thumbnail.size();
+                    let size = egui::vec2(THUMBNAIL_WIDTH, THUMBNAIL_WIDTH * height as f32 / width as f32);
+                    ui.image((screenshot.thumbnail.id(), size));
+
+                    ui.vertical(|ui| {
+                        ui.label(&screenshot.file_name);
+                        ui.label(format!("{} into the session", format_duration(screenshot.taken)));
+                        ui.monospace(format!("PC {:03X}  I {:03X}", screenshot.state.pc, screenshot.state.addr_reg));
+
+                        let same_rom = screenshot.rom_id.is_some() && screenshot.rom_id.as_ref() == rom_id;
+                        let button = ui.add_enabled(same_rom, egui::Button::new("Restore state"))
+                            .on_disabled_hover_text("This was taken in a different ROM");
+                        if button.clicked() {
+                            restore = Some(screenshot.state.clone());
+                        }
+                    });
+                });
+                ui.separator();
+            }
+        });
+
+        restore
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
@@ -0,0 +1,193 @@
+// a reset/step interface over the headless core in the style of OpenAI Gym, for training agents to play games
+//
+// each step holds down a set of keys for a number of frames then hands back what the screen and memory look
+// like afterwards, and a reward read from wherever the game keeps its score
+
+use crate::cpu::{Quirks, CPU, DEFAULT_CYCLES_PER_FRAME, MAX_ROM_SIZE};
+
+// where the game keeps the number the reward is worked out from
+#[derive(Clone, Copy, Debug)]
+pub enum RewardSource {
+    Memory(u16),
+    Register(u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct EnvironmentSettings {
+    pub quirks: Quirks,
+    pub cycles_per_frame: u16,
+    pub frames_per_step: u32,
+    pub reward: Option<RewardSource>, // None always gives a reward of 0
+    pub max_frames: Option<u32>,      // ends the episode after this many frames even if the game doesn't
+    pub seed: u64,                    // each reset starts the random numbers from here so episodes can be replayed
+}
+
+impl Default for EnvironmentSettings {
+    fn default() -> Self {
+        Self {
+            quirks: Quirks::default(),
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            frames_per_step: 1,
+            reward: None,
+            max_frames: None,
+            seed: 0,
+        }
+    }
+}
+
+pub struct Observation {
+    pub width: usize,
+    pub height: usize,
+    pub framebuffer: Vec<u8>, // a byte a pixel, 1 when lit, left to right then top to bottom
+    pub ram: Vec<u8>,
+}
+
+pub struct Step {
+    pub observation: Observation,
+    pub reward: f32,
+    pub done: bool, // the program halted or the frame limit was reached, call reset to go again
+}
+
+pub struct Environment {
+    rom: Vec<u8>,
+    settings: EnvironmentSettings,
+    cpu: CPU,
+    frame: u32,
+    last_score: u8,
+}
+
+impl Environment {
+    pub fn new(rom: &[u8], settings: EnvironmentSettings) -> Result<Environment, String> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(format!("The ROM is {} bytes, the most that fits in memory is {} bytes", rom.len(), MAX_ROM_SIZE));
+        }
+        if let Some(RewardSource::Register(reg)) = settings.reward {
+            if reg > 0xF {
+                return Err(format!("There is no register V{:X}", reg));
+            }
+        }
+
+        let mut environment = Environment { rom: rom.to_vec(), settings, cpu: CPU::new(), frame: 0, last_score: 0 };
+        environment.reset();
+
+        Ok(environment)
+    }
+
+    // starts the game again from power on
+    pub fn reset(&mut self) -> Observation {
+        self.cpu = CPU::new();
+        self.cpu.quirks = self.settings.quirks;
+        self.cpu.seed_rng(self.settings.seed);
+        self.cpu.load_rom(&self.rom);
+
+        self.frame = 0;
+        self.last_score = self.score();
+
+        self.observe()
+    }
+
+    // keys has bit N set for each key N to hold down through the step
+    pub fn step(&mut self, keys: u16) -> Step {
+        // keys let go since the last step count as released for FX0A
+        self.cpu.set_keys(keys);
+
+        for _ in 0..self.settings.frames_per_step {
+            if self.done() {
+                break;
+            }
+
+            self.cpu.timer_tick();
+            for _ in 0..self.settings.cycles_per_frame {
+                self.cpu.handle_opcode();
+            }
+            self.frame += 1;
+        }
+
+        let score = self.score();
+        let reward = score as f32 - self.last_score as f32;
+        self.last_score = score;
+
+        Step { observation: self.observe(), reward, done: self.done() }
+    }
+
+    pub fn observe(&self) -> Observation {
+        let (width, height) = (self.cpu.width(), self.cpu.height());
+        let mut framebuffer = Vec::with_capacity(width * height);
+        for y in 0..height {
+            framebuffer.extend((0..width).map(|x| self.cpu.pixel(x, y) as u8));
+        }
+
+        Observation { width, height, framebuffer, ram: self.cpu.memory().to_vec() }
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    // for reading anything the observation leaves out, such as registers and timers
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    fn done(&self) -> bool {
+        self.cpu.halted() || self.settings.max_frames.is_some_and(|max_frames| self.frame >= max_frames)
+    }
+
+    fn score(&self) -> u8 {
+        match self.settings.reward {
+            Some(RewardSource::Memory(addr)) => self.cpu.read_memory(addr),
+            Some(RewardSource::Register(reg)) => self.cpu.registers()[reg as usize],
+            None => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewards_and_episodes() {
+        // counts up in V3 while key 5 is held and keeps the count at 0x303
+        let rom = [
+            0x65, 0x05, // 200: LD V5, 0x05
+            0xE5, 0xA1, // 202: SKNP V5
+            0x73, 0x01, // 204: ADD V3, 0x01
+            0xA3, 0x00, // 206: LD I, 0x300
+            0xF3, 0x55, // 208: LD [I], V3
+            0x12, 0x02, // 20A: JP 0x202
+        ];
+        let settings = EnvironmentSettings {
+            cycles_per_frame: 5,
+            frames_per_step: 2,
+            reward: Some(RewardSource::Memory(0x303)),
+            max_frames: Some(6),
+            ..Default::default()
+        };
+        let mut env = Environment::new(&rom, settings).unwrap();
+
+        let nothing = env.step(0);
+        assert_eq!(nothing.reward, 0.0);
+        assert!(!nothing.done);
+
+        let pressed = env.step(1 << 5);
+        assert!(pressed.reward > 0.0);
+        assert_eq!(pressed.observation.ram[0x303] as f32, pressed.reward);
+
+        assert!(env.step(0).done);
+        assert_eq!(env.frame(), 6);
+
+        let observation = env.reset();
+        assert_eq!(env.frame(), 0);
+        assert_eq!(observation.ram[0x303], 0);
+        assert_eq!(observation.framebuffer.len(), observation.width * observation.height);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        assert!(Environment::new(&vec![0; MAX_ROM_SIZE + 1], EnvironmentSettings::default()).is_err());
+
+        let settings = EnvironmentSettings { reward: Some(RewardSource::Register(0x10)), ..Default::default() };
+        assert!(Environment::new(&[0x12, 0x00], settings).is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use ggegui::egui::{self, RichText};
+
+use crate::analysis::CodeMap;
+use crate::cpu::{CPU, PROGRAM_START};
+use crate::find::{self, FindKind, Query};
+use crate::instruction::Instruction;
+use crate::io::show_error;
+use crate::savestate::{self, SAVESTATE_EXTENSION};
+
+const BYTES_PER_ROW: usize = 16;
+const DISASSEMBLY_BEFORE: usize = 4; // instructions shown before the selected one
+const DISASSEMBLY_AFTER: usize = 24;
+const MAX_HISTORY: usize = 100;
+
+// where the view was before each jump, to go back and forward through like a browser. Each is the start and length of
+// the selection
+#[derive(Default)]
+struct History {
+    back: Vec<(usize, usize)>,
+    forward: Vec<(usize, usize)>,
+}
+
+impl History {
+    fn visit(&mut self, from: (usize, usize)) {
+        if self.back.len() == MAX_HISTORY {
+            self.back.remove(0);
+        }
+        self.back.push(from);
+        self.forward.clear();
+    }
+
+    fn back(&mut self, current: (usize, usize)) -> Option<(usize, usize)> {
+        let previous = self.back.pop()?;
+        self.forward.push(current);
+        Some(previous)
+    }
+
+    fn forward(&mut self, current: (usize, usize)) -> Option<(usize, usize)> {
+        let next = self.forward.pop()?;
+        self.back.push(current);
+        Some(next)
+    }
+}
+
+// memory as hex, marking the bytes that differ from a reference as the program runs so it's easy to see what a
+// routine touches, with the instructions at the selected address beside it
+pub struct HexView {
+    reference: Option<(String, Vec<u8>)>, // what it's compared against and that memory
+    only_changed: bool,                   // hide the rows where nothing differs
+    selection: Option<(usize, usize)>,    // the start and length of what's picked out
+    scroll_to: Option<usize>,             // an address to bring into view on the next frame
+    find_kind: FindKind,
+    find_text: String,
+    find_only_code: bool,                 // only look for opcodes where the analysis found instructions
+    found: Option<Result<Vec<usize>, String>>,
+    history: History,
+    goto_text: String,
+    goto_error: bool,
+}
+
+impl Default for HexView {
+    fn default() -> Self {
+        Self {
+            reference: None,
+            only_changed: false,
+            selection: None,
+            scroll_to: None,
+            find_kind: FindKind::Bytes,
+            find_text: String::new(),
+            find_only_code: true,
+            found: None,
+            history: History::default(),
+            goto_text: String::new(),
+            goto_error: false,
+        }
+    }
+}
+
+impl HexView {
+    // picks out some memory and scrolls to it
+    pub fn select(&mut self, addr: usize, length: usize) {
+        self.selection = Some((addr, length.max(1)));
+        self.scroll_to = Some(addr);
+    }
+
+    // jumps somewhere, remembering where it was to come back to
+    fn go_to(&mut self, addr: usize, length: usize, pc: usize) {
+        self.history.visit(self.selection.unwrap_or((pc, 2)));
+        self.select(addr, length);
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, cpu: &CPU) {
+        let memory = cpu.memory();
+        let pc = cpu.pc() as usize;
+        self.show_navigation(ui, memory, pc, cpu.addr_reg() as usize);
+        self.show_find(ui, memory, pc);
+        ui.separator();
+
+        egui::SidePanel::right("Memory disassembly").resizable(true).show_inside(ui, |ui| {
+            self.show_disassembly(ui, memory, pc);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("Compare against: ");
+            if ui.button("Memory now").clicked() {
+                self.reference = Some((String::from("the snapshot"), memory.to_vec()));
+            }
+            if ui.button("Savestate").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("Savestates", &[SAVESTATE_EXTENSION]).pick_file() {
+                    match savestate::load(&path) {
+                        Ok(saved) if saved.state.memory.len() == memory.len() => {
+                            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                            self.reference = Some((name, saved.state.memory));
+                        },
+                        Ok(_) => show_error("Could not compare", String::from("The savestate's memory is the wrong size")),
+                        Err(e) => show_error("Could not load the state", e),
+                    }
+                }
+            }
+            if self.reference.is_some() && ui.button("Stop comparing").clicked() {
+                self.reference = None;
+            }
+        });
+
+        let reference = self.reference.as_ref().map(|(name, reference)| {
+            let changed = memory.iter().zip(reference).filter(|(byte, before)| byte != before).count();
+            ui.horizontal(|ui| {
+                ui.label(format!("{} bytes differ from {}", changed, name));
+                ui.checkbox(&mut self.only_changed, "Only rows with changes");
+            });
+
+            reference
+        });
+        ui.separator();
+
+        let rows: Vec<usize> = (0..memory.len() / BYTES_PER_ROW)
+            .filter(|row| {
+                let range = row * BYTES_PER_ROW..(row + 1) * BYTES_PER_ROW;
+                match reference {
+                    Some(reference) if self.only_changed => memory[range.clone()] != reference[range],
+                    _ => true,
+                }
+            })
+            .collect();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        let mut scroll = egui::ScrollArea::vertical().auto_shrink(false);
+        if let Some(row) = self.scroll_to.take().and_then(|addr| rows.iter().position(|&row| row == addr / BYTES_PER_ROW)) {
+            scroll = scroll.vertical_scroll_offset(row as f32 * (row_height + ui.spacing().item_spacing.y));
+        }
+        let selected = |addr: usize| self.selection.is_some_and(|(start, length)| (start..start + length).contains(&addr));
+        scroll.show_rows(ui, row_height, rows.len(), |ui, visible| {
+            for &row in &rows[visible] {
+                ui.horizontal(|ui| {
+                    ui.spacing_mut().item_spacing.x = ui.spacing().item_spacing.x / 2.0;
+
+                    let start = row * BYTES_PER_ROW;
+                    ui.monospace(format!("{:03X}:", start));
+
+                    for addr in start..start + BYTES_PER_ROW {
+                        let mut text = RichText::new(format!("{:02X}", memory[addr])).monospace();
+                        if selected(addr) {
+                            text = text.background_color(ui.visuals().selection.bg_fill);
+                        }
+                        let before = reference.map(|reference| reference[addr]).filter(|&before| before != memory[addr]);
+                        match before {
+                            Some(before) => ui.label(text.strong().color(ui.visuals().warn_fg_color))
+                                .on_hover_text(format!("{:03X}, was {:02X}", addr, before)),
+                            None => ui.label(text).on_hover_text(format!("{:03X}", addr)),
+                        };
+                    }
+                });
+            }
+        });
+    }
+
+    fn show_navigation(&mut self, ui: &mut egui::Ui, memory: &[u8], pc: usize, addr_reg: usize) {
+        let current = self.selection.unwrap_or((pc, 2));
+        ui.horizontal(|ui| {
+            if ui.add_enabled(!self.history.back.is_empty(), egui::Button::new("⏴")).on_hover_text("Back").clicked() {
+                if let Some((addr, length)) = self.history.back(current) {
+                    self.select(addr, length);
+                }
+            }
+            if ui.add_enabled(!self.history.forward.is_empty(), egui::Button::new("⏵")).on_hover_text("Forward").clicked() {
+                if let Some((addr, length)) = self.history.forward(current) {
+                    self.select(addr, length);
+                }
+            }
+
+            ui.label("Go to: ");
+            let edit = ui.add(egui::TextEdit::singleline(&mut self.goto_text).hint_text("200").desired_width(40.0));
+            let entered = edit.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if ui.button("Go").clicked() || entered {
+                let text = self.goto_text.trim();
+                match usize::from_str_radix(text.trim_start_matches("0x"), 16) {
+                    Ok(addr) if addr < memory.len() => {
+                        self.goto_error = false;
+                        self.go_to(addr, 1, pc);
+                    },
+                    _ => self.goto_error = true,
+                }
+            }
+            if self.goto_error {
+                ui.colored_label(ui.visuals().error_fg_color, format!("Not an address below {:03X}", memory.len()));
+            }
+
+            if ui.button("Follow I").on_hover_text(format!("Go to {:03X}, where I points", addr_reg)).clicked() {
+                self.go_to(addr_reg % memory.len(), 1, pc);
+            }
+            let target = self.selection.and_then(|(addr, _)| target(memory, addr));
+            let follow = ui.add_enabled(target.is_some(), egui::Button::new("Follow target"))
+                .on_hover_text("Go to where the selected instruction jumps, calls or points I. Double clicking an instruction does too");
+            if let (true, Some(target)) = (follow.clicked(), target) {
+                self.go_to(target, 2, pc);
+            }
+        });
+    }
+
+    fn show_find(&mut self, ui: &mut egui::Ui, memory: &[u8], pc: usize) {
+        ui.horizontal(|ui| {
+            ui.label("Find: ");
+            ui.selectable_value(&mut self.find_kind, FindKind::Bytes, "Bytes").on_hover_text("Hex bytes, ?? matching any byte, such as A2 ?? D0");
+            ui.selectable_value(&mut self.find_kind, FindKind::Opcode, "Opcode").on_hover_text("A pattern such as DXY0, X, Y, N and K matching any nibble, or part of an instruction such as LD I");
+            ui.selectable_value(&mut self.find_kind, FindKind::Text, "Text");
+
+            let edit = ui.add(egui::TextEdit::singleline(&mut self.find_text).desired_width(120.0));
+            let entered = edit.lost_focus() && ui.input(|input| input.key_pressed(egui::Key::Enter));
+            if ui.button("Find").clicked() || entered {
+                let code = (self.find_kind == FindKind::Opcode && self.find_only_code).then(|| CodeMap::analyse(&memory[PROGRAM_START..]));
+                self.found = Some(Query::parse(self.find_kind, &self.find_text).map(|query| {
+                    let found = find::find(memory, &query, code.as_ref());
+                    if let Some(&first) = found.first() {
+                        self.go_to(first, query.length(), pc);
+                    }
+                    found
+                }));
+            }
+            if self.find_kind == FindKind::Opcode {
+                ui.checkbox(&mut self.find_only_code, "Only reachable code");
+            }
+        });
+
+        let length = Query::parse(self.find_kind, &self.find_text).map_or(1, |query| query.length());
+        let mut picked = None;
+        match &self.found {
+            Some(Ok(found)) if found.is_empty() => {
+                ui.label("Not found");
+            },
+            Some(Ok(found)) => {
+                let more = if found.len() == find::MAX_RESULTS { " (only the first are shown)" } else { "" };
+                ui.label(format!("{} found{}", found.len(), more));
+                egui::ScrollArea::vertical().id_source("Found").max_height(100.0).show(ui, |ui| {
+                    for &addr in found {
+                        let opcode = (memory[addr] as u16) << 8 | memory.get(addr + 1).copied().unwrap_or(0) as u16;
+                        let text = format!("{:03X}: {:04X}  {}", addr, opcode, Instruction::decode(opcode));
+                        let selected = self.selection.is_some_and(|(start, _)| start == addr);
+                        if ui.selectable_label(selected, RichText::new(text).monospace()).clicked() {
+                            picked = Some(addr);
+                        }
+                    }
+                });
+            },
+            Some(Err(e)) => {
+                ui.colored_label(ui.visuals().error_fg_color, e);
+            },
+            None => (),
+        }
+        if let Some(addr) = picked {
+            self.go_to(addr, length, pc);
+        }
+    }
+
+    // the instructions around the selection, or the program counter if nothing's selected
+    fn show_disassembly(&mut self, ui: &mut egui::Ui, memory: &[u8], pc: usize) {
+        ui.heading("Disassembly");
+        let centre = self.selection.map_or(pc, |(start, _)| start);
+        let start = centre.saturating_sub(DISASSEMBLY_BEFORE * 2);
+
+        let mut picked = None;
+        let mut followed = None;
+        for addr in (start..centre + DISASSEMBLY_AFTER * 2).step_by(2).take_while(|addr| addr + 1 < memory.len()) {
+            let opcode = (memory[addr] as u16) << 8 | memory[addr + 1] as u16;
+            let mut text = RichText::new(format!("{:03X}: {:04X}  {}", addr, opcode, Instruction::decode(opcode))).monospace();
+            if addr == pc {
+                text = text.strong();
+            }
+            let response = ui.selectable_label(addr == centre && self.selection.is_some(), text);
+            if response.double_clicked() {
+                followed = target(memory, addr);
+            }
+            else if response.clicked() {
+                picked = Some(addr);
+            }
+        }
+        if let Some(target) = followed {
+            self.go_to(target, 2, pc);
+        }
+        else if let Some(addr) = picked {
+            self.select(addr, 2);
+        }
+    }
+}
+
+// the address the instruction at addr refers to, if it's in memory
+fn target(memory: &[u8], addr: usize) -> Option<usize> {
+    let opcode = (*memory.get(addr)? as u16) << 8 | *memory.get(addr + 1)? as u16;
+    Instruction::decode(opcode).target().map(|target| target as usize).filter(|&target| target < memory.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn goes_back_and_forward() {
+        let mut history = History::default();
+        history.visit((0x200, 2));
+        history.visit((0x300, 1));
+
+        assert_eq!(history.back((0x400, 2)), Some((0x300, 1)));
+        assert_eq!(history.back((0x300, 1)), Some((0x200, 2)));
+        assert_eq!(history.back((0x200, 2)), None);
+        assert_eq!(history.forward((0x200, 2)), Some((0x300, 1)));
+
+        // going somewhere new forgets the way forward
+        history.visit((0x300, 1));
+        assert_eq!(history.forward((0x500, 2)), None);
+        assert_eq!(history.back.len(), 2);
+    }
+}
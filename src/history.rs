@@ -0,0 +1,168 @@
+use std::collections::VecDeque;
+use std::ops::RangeInclusive;
+
+use crate::cpu::CPU;
+
+pub const SNAPSHOT_INTERVAL: u32 = 60; // a second apart, so seeking never replays more than a second
+pub const MAX_FRAMES: usize = 60 * 60 * 10; // ten minutes, older frames are forgotten
+
+// the keys going into one 60Hz frame
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub struct FrameInput {
+    pub keys: u16,
+    pub second_keypad: u16,
+    pub released: u16, // let go of after the frame, for FX0A
+    pub cycles: u32,   // the instructions it ran, fewer than usual if something paused it part way through
+}
+
+// every frame's input since recording started and a copy of the CPU every so often, so any frame can be got back
+// to by restoring the copy before it and replaying the input from there
+pub struct History {
+    start: u32, // the frame number of the oldest frame still kept
+    inputs: VecDeque<FrameInput>,
+    snapshots: VecDeque<(u32, CPU)>, // taken before their frame ran, oldest first
+    snapshot_next: bool,
+}
+
+impl History {
+    pub fn new() -> History {
+        History { start: 0, inputs: VecDeque::new(), snapshots: VecDeque::new(), snapshot_next: false }
+    }
+
+    // the frames that can be sought to, the last being the one that would run next
+    pub fn frames(&self) -> RangeInclusive<u32> {
+        self.start..=self.start + self.inputs.len() as u32
+    }
+
+    // called before each frame runs. Recording from earlier than the end forgets the frames after, like typing
+    // over a selection
+    pub fn record(&mut self, frame: u32, cpu: &CPU, input: FrameInput) {
+        let frame = frame.max(self.start);
+        self.inputs.truncate((frame - self.start) as usize);
+        while self.snapshots.back().is_some_and(|&(snapshot, _)| snapshot >= frame) {
+            self.snapshots.pop_back();
+        }
+
+        if self.snapshots.is_empty() || self.snapshot_next || frame.is_multiple_of(SNAPSHOT_INTERVAL) {
+            self.snapshots.push_back((frame, cpu.clone()));
+        }
+        self.snapshot_next = false;
+        self.inputs.push_back(input);
+
+        if self.inputs.len() > MAX_FRAMES {
+            // everything before the second snapshot goes so there's always one to start replaying from
+            let (forget, _) = self.snapshots[1];
+            self.inputs.drain(..(forget - self.start) as usize);
+            self.snapshots.pop_front();
+            self.start = forget;
+        }
+    }
+
+    // the CPU as it was before a frame ran, run_frame being how the emulator runs one frame with its input
+    pub fn seek(&self, frame: u32, mut run_frame: impl FnMut(&mut CPU, FrameInput)) -> Option<CPU> {
+        if !self.frames().contains(&frame) {
+            return None;
+        }
+
+        let (from, snapshot) = self.snapshots.iter().rev().find(|&&(snapshot, _)| snapshot <= frame)?;
+        let mut cpu = snapshot.clone();
+        for input in self.inputs.range((from - self.start) as usize..(frame - self.start) as usize) {
+            replay(&mut cpu, *input, &mut run_frame);
+        }
+
+        Some(cpu)
+    }
+
+    // keys let go of after the last frame recorded
+    pub fn release(&mut self, keys: u16) {
+        if let Some(input) = self.inputs.back_mut() {
+            input.released |= keys;
+        }
+    }
+
+    // how many instructions the last frame recorded ran, once it's finished
+    pub fn ran(&mut self, cycles: u32) {
+        if let Some(input) = self.inputs.back_mut() {
+            input.cycles = cycles;
+        }
+    }
+
+    // something other than the program changed the CPU after the last frame recorded, which replaying the input
+    // wouldn't do again, so the next frame keeps a copy of the CPU to start from
+    pub fn changed(&mut self) {
+        self.snapshot_next = true;
+    }
+
+    pub fn input(&self, frame: u32) -> Option<FrameInput> {
+        self.inputs.get(frame.checked_sub(self.start)? as usize).copied()
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History::new()
+    }
+}
+
+pub fn replay(cpu: &mut CPU, input: FrameInput, run_frame: &mut impl FnMut(&mut CPU, FrameInput)) {
+    cpu.set_keys(input.keys);
+    cpu.set_second_keypad(input.second_keypad);
+    run_frame(cpu, input);
+    for key in 0..16 {
+        if input.released & (1 << key) != 0 {
+            cpu.key_up(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_frame(cpu: &mut CPU, input: FrameInput) {
+        cpu.timer_tick();
+        for _ in 0..input.cycles {
+            cpu.handle_opcode();
+        }
+    }
+
+    #[test]
+    fn seeking_matches_running() {
+        // 200: V0 = random, 202: V1 += 1, 204: V2 = key pressed and let go, 206: back to 200
+        let mut cpu = CPU::new();
+        cpu.seed_rng(7);
+        cpu.load_rom(&[0xC0, 0xFF, 0x71, 0x01, 0xF2, 0x0A, 0x12, 0x00]);
+
+        let mut history = History::new();
+        let mut states = vec![];
+        for frame in 0..300 {
+            let held = (frame / 7) % 3 == 0;
+            let keys = if held { 1 << (frame % 16) } else { 0 };
+            let released = if !held && frame % 7 == 0 { 0xFFFF } else { 0 };
+            let cycles = if frame % 11 == 0 { 3 } else { 10 }; // as if a breakpoint stopped some frames early
+            history.record(frame, &cpu, FrameInput { keys, ..Default::default() });
+            states.push(cpu.save_state());
+            replay(&mut cpu, FrameInput { keys, released, cycles, ..Default::default() }, &mut run_frame);
+            history.release(released);
+            history.ran(cycles);
+
+            // and something outside the program, like a plugin, changing how much 202 adds to V1 now and then
+            if frame % 50 == 25 {
+                cpu.write_memory(0x203, (frame / 50) as u8 + 2);
+                history.changed();
+            }
+        }
+        states.push(cpu.save_state());
+
+        for frame in [0, 25, 26, 59, 60, 61, 137, 299, 300] {
+            let sought = history.seek(frame, run_frame).unwrap().save_state();
+            assert_eq!((sought.regs, sought.pc), (states[frame as usize].regs, states[frame as usize].pc), "frame {}", frame);
+        }
+        assert!(history.seek(301, run_frame).is_none());
+
+        // recording from the middle again drops what came after
+        history.record(100, &cpu, FrameInput::default());
+        assert_eq!(history.frames(), 0..=101);
+        assert_eq!(history.input(100), Some(FrameInput::default()));
+    }
+}
@@ -0,0 +1,201 @@
+use ggez::input::keyboard::{KeyCode, KeyMods, KeyboardContext};
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+
+use crate::input::{ControlScheme, InputMacro, KeyboardSettings};
+
+pub const FAST_FORWARD_SPEED: u32 = 4;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum HotkeyAction {
+    Pause,
+    Reset,
+    SaveState,
+    LoadState,
+    FastForward, // runs while held rather than once per press
+    Screenshot,
+    Fullscreen,
+    Step,
+    Split,
+    HideMenuBar,
+}
+
+impl HotkeyAction {
+    pub const ALL: [HotkeyAction; 10] = [
+        HotkeyAction::Pause,
+        HotkeyAction::Reset,
+        HotkeyAction::SaveState,
+        HotkeyAction::LoadState,
+        HotkeyAction::FastForward,
+        HotkeyAction::Screenshot,
+        HotkeyAction::Fullscreen,
+        HotkeyAction::Step,
+        HotkeyAction::Split,
+        HotkeyAction::HideMenuBar,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HotkeyAction::Pause => "Pause",
+            HotkeyAction::Reset => "Reset",
+            HotkeyAction::SaveState => "Quick save state",
+            HotkeyAction::LoadState => "Quick load state",
+            HotkeyAction::FastForward => "Fast forward (hold)",
+            HotkeyAction::Screenshot => "Screenshot",
+            HotkeyAction::Fullscreen => "Fullscreen",
+            HotkeyAction::Step => "Step one instruction",
+            HotkeyAction::Split => "Speedrun split",
+            HotkeyAction::HideMenuBar => "Hide menu bar",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub struct KeyCombo {
+    pub key: KeyCode,
+    #[serde(default)]
+    pub ctrl: bool,
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub alt: bool,
+}
+
+impl KeyCombo {
+    pub fn new(key: KeyCode) -> KeyCombo {
+        KeyCombo { key, ctrl: false, shift: false, alt: false }
+    }
+
+    // the first key to go down this frame along with whichever modifiers are held, for binding a hotkey
+    pub fn just_pressed(key_ctx: &KeyboardContext) -> Option<KeyCombo> {
+        let key = key_ctx.pressed_keys().iter().copied().find(|&key| !is_modifier(key) && key_ctx.is_key_just_pressed(key))?;
+        let mods = key_ctx.active_mods();
+
+        Some(KeyCombo { key, ctrl: mods.contains(KeyMods::CTRL), shift: mods.contains(KeyMods::SHIFT), alt: mods.contains(KeyMods::ALT) })
+    }
+
+    pub fn is_pressed(&self, key_ctx: &KeyboardContext) -> bool {
+        key_ctx.is_key_pressed(self.key) && self.modifiers_match(key_ctx)
+    }
+
+    pub fn is_just_pressed(&self, key_ctx: &KeyboardContext) -> bool {
+        key_ctx.is_key_just_pressed(self.key) && self.modifiers_match(key_ctx)
+    }
+
+    // exactly these modifiers, so Ctrl+F5 and F5 can do different things
+    fn modifiers_match(&self, key_ctx: &KeyboardContext) -> bool {
+        let mods = key_ctx.active_mods();
+        mods.contains(KeyMods::CTRL) == self.ctrl && mods.contains(KeyMods::SHIFT) == self.shift && mods.contains(KeyMods::ALT) == self.alt
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (held, name) in [(self.ctrl, "Ctrl+"), (self.shift, "Shift+"), (self.alt, "Alt+")] {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        write!(f, "{:?}", self.key)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct Hotkeys {
+    pub pause: KeyCombo,
+    pub reset: KeyCombo,
+    pub save_state: KeyCombo,
+    pub load_state: KeyCombo,
+    pub fast_forward: KeyCombo,
+    pub screenshot: KeyCombo,
+    pub fullscreen: KeyCombo,
+    pub step: KeyCombo,
+    pub split: KeyCombo,
+    pub hide_menu_bar: KeyCombo,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        // none of these are used by the keypad on any of the layouts
+        Self {
+            pause: KeyCombo::new(KeyCode::Pause),
+            reset: KeyCombo::new(KeyCode::Back),
+            save_state: KeyCombo::new(KeyCode::F5),
+            load_state: KeyCombo::new(KeyCode::F9),
+            fast_forward: KeyCombo::new(KeyCode::Tab),
+            screenshot: KeyCombo::new(KeyCode::F12),
+            fullscreen: KeyCombo::new(KeyCode::F11),
+            step: KeyCombo::new(KeyCode::F10),
+            split: KeyCombo::new(KeyCode::Return),
+            hide_menu_bar: KeyCombo::new(KeyCode::F1),
+        }
+    }
+}
+
+impl Hotkeys {
+    pub fn binding(&self, action: HotkeyAction) -> KeyCombo {
+        match action {
+            HotkeyAction::Pause => self.pause,
+            HotkeyAction::Reset => self.reset,
+            HotkeyAction::SaveState => self.save_state,
+            HotkeyAction::LoadState => self.load_state,
+            HotkeyAction::FastForward => self.fast_forward,
+            HotkeyAction::Screenshot => self.screenshot,
+            HotkeyAction::Fullscreen => self.fullscreen,
+            HotkeyAction::Step => self.step,
+            HotkeyAction::Split => self.split,
+            HotkeyAction::HideMenuBar => self.hide_menu_bar,
+        }
+    }
+
+    pub fn binding_mut(&mut self, action: HotkeyAction) -> &mut KeyCombo {
+        match action {
+            HotkeyAction::Pause => &mut self.pause,
+            HotkeyAction::Reset => &mut self.reset,
+            HotkeyAction::SaveState => &mut self.save_state,
+            HotkeyAction::LoadState => &mut self.load_state,
+            HotkeyAction::FastForward => &mut self.fast_forward,
+            HotkeyAction::Screenshot => &mut self.screenshot,
+            HotkeyAction::Fullscreen => &mut self.fullscreen,
+            HotkeyAction::Step => &mut self.step,
+            HotkeyAction::Split => &mut self.split,
+            HotkeyAction::HideMenuBar => &mut self.hide_menu_bar,
+        }
+    }
+
+    // the actions whose hotkey was pressed this frame, not counting fast forward which is held
+    pub fn just_pressed(&self, key_ctx: &KeyboardContext) -> Vec<HotkeyAction> {
+        HotkeyAction::ALL.into_iter()
+            .filter(|&action| action != HotkeyAction::FastForward && self.binding(action).is_just_pressed(key_ctx))
+            .collect()
+    }
+
+    // everything else the action's key does, modifiers or not, since the keypad doesn't look at them
+    pub fn conflicts(&self, action: HotkeyAction, keyboard: &KeyboardSettings, scheme: Option<&ControlScheme>, macros: &[InputMacro]) -> Vec<String> {
+        let combo = self.binding(action);
+        let mut conflicts = vec![];
+
+        if let Some(key) = keyboard.key_for_input(scheme, Some(combo.key), 0) {
+            conflicts.push(format!("keypad key {:X}", key));
+        }
+        if let Some(key) = keyboard.second_keypad_key(Some(combo.key)) {
+            conflicts.push(format!("second keypad key {:X}", key));
+        }
+        if macros.iter().any(|input_macro| input_macro.trigger == combo.key) {
+            conflicts.push(String::from("a macro"));
+        }
+        for other in HotkeyAction::ALL {
+            if other != action && self.binding(other) == combo {
+                conflicts.push(other.name().to_lowercase());
+            }
+        }
+
+        conflicts
+    }
+}
+
+fn is_modifier(key: KeyCode) -> bool {
+    matches!(key, KeyCode::LShift | KeyCode::RShift | KeyCode::LControl | KeyCode::RControl | KeyCode::LAlt | KeyCode::RAlt | KeyCode::LWin | KeyCode::RWin)
+}
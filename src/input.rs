@@ -0,0 +1,381 @@
+use ggez::input::keyboard::{KeyCode, KeyboardContext, ScanCode};
+use serde::{Deserialize, Serialize};
+
+// the keypad in the order its keys are laid out, left to right and top to bottom
+pub const KEYPAD_GRID: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC,
+    0x4, 0x5, 0x6, 0xD,
+    0x7, 0x8, 0x9, 0xE,
+    0xA, 0x0, 0xB, 0xF,
+];
+
+// physical key positions of 1234 / QWER / ASDF / ZXCV on a US keyboard
+#[cfg(not(target_os = "macos"))]
+const SCANCODE_GRID: [ScanCode; 16] = [
+    0x02, 0x03, 0x04, 0x05,
+    0x10, 0x11, 0x12, 0x13,
+    0x1E, 0x1F, 0x20, 0x21,
+    0x2C, 0x2D, 0x2E, 0x2F,
+];
+
+#[cfg(target_os = "macos")]
+const SCANCODE_GRID: [ScanCode; 16] = [
+    0x12, 0x13, 0x14, 0x15,
+    0x0C, 0x0D, 0x0E, 0x0F,
+    0x00, 0x01, 0x02, 0x03,
+    0x06, 0x07, 0x08, 0x09,
+];
+
+// where on the keyboard a key is, the grid positions count across the 4x4 block the same way as KEYPAD_GRID
+#[derive(PartialEq, Clone, Copy)]
+enum Position {
+    Grid(usize),
+    Up,
+    Down,
+    Left,
+    Right,
+    Space,
+}
+
+const WASD_UP: usize = 5;
+const WASD_LEFT: usize = 8;
+const WASD_DOWN: usize = 9;
+const WASD_RIGHT: usize = 10;
+
+// the numpad laid out the same way as the keypad, for the second keypad
+const NUMPAD_GRID: [KeyCode; 16] = [
+    KeyCode::Numpad7, KeyCode::Numpad8, KeyCode::Numpad9, KeyCode::NumpadDivide,
+    KeyCode::Numpad4, KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::NumpadMultiply,
+    KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3, KeyCode::NumpadSubtract,
+    KeyCode::Numpad0, KeyCode::NumpadDecimal, KeyCode::NumpadEnter, KeyCode::NumpadAdd,
+];
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum DirectionKeys {
+    Arrows,
+    Wasd, // whichever keys are in the WASD positions on the chosen layout
+}
+
+// maps keys players expect to use onto the keypad keys a particular game reads
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct ControlScheme {
+    pub direction_keys: DirectionKeys,
+    pub up: u8,
+    pub down: u8,
+    pub left: u8,
+    pub right: u8,
+    pub action: u8, // space
+}
+
+impl Default for ControlScheme {
+    fn default() -> Self {
+        // most games move with the 2/4/6/8 cross and use 5 for everything else
+        Self {
+            direction_keys: DirectionKeys::Arrows,
+            up: 0x2,
+            down: 0x8,
+            left: 0x4,
+            right: 0x6,
+            action: 0x5,
+        }
+    }
+}
+
+impl ControlScheme {
+    fn key_for_position(&self, position: Position) -> Option<u8> {
+        match (self.direction_keys, position) {
+            (DirectionKeys::Arrows, Position::Up) | (DirectionKeys::Wasd, Position::Grid(WASD_UP)) => Some(self.up),
+            (DirectionKeys::Arrows, Position::Down) | (DirectionKeys::Wasd, Position::Grid(WASD_DOWN)) => Some(self.down),
+            (DirectionKeys::Arrows, Position::Left) | (DirectionKeys::Wasd, Position::Grid(WASD_LEFT)) => Some(self.left),
+            (DirectionKeys::Arrows, Position::Right) | (DirectionKeys::Wasd, Position::Grid(WASD_RIGHT)) => Some(self.right),
+            (_, Position::Space) => Some(self.action),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum KeyboardLayout {
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Dvorak,
+    Colemak,
+}
+
+impl KeyboardLayout {
+    pub const ALL: [KeyboardLayout; 5] = [
+        KeyboardLayout::Qwerty,
+        KeyboardLayout::Azerty,
+        KeyboardLayout::Qwertz,
+        KeyboardLayout::Dvorak,
+        KeyboardLayout::Colemak,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyboardLayout::Qwerty => "QWERTY",
+            KeyboardLayout::Azerty => "AZERTY",
+            KeyboardLayout::Qwertz => "QWERTZ",
+            KeyboardLayout::Dvorak => "Dvorak",
+            KeyboardLayout::Colemak => "Colemak",
+        }
+    }
+
+    // the keys that sit where 1234 / QWER / ASDF / ZXCV are on a QWERTY keyboard
+    pub fn grid(self) -> [KeyCode; 16] {
+        use KeyCode::*;
+
+        match self {
+            KeyboardLayout::Qwerty => [Key1, Key2, Key3, Key4, Q, W, E, R, A, S, D, F, Z, X, C, V],
+            KeyboardLayout::Azerty => [Key1, Key2, Key3, Key4, A, Z, E, R, Q, S, D, F, W, X, C, V],
+            KeyboardLayout::Qwertz => [Key1, Key2, Key3, Key4, Q, W, E, R, A, S, D, F, Y, X, C, V],
+            KeyboardLayout::Dvorak => [Key1, Key2, Key3, Key4, Apostrophe, Comma, Period, P, A, O, E, U, Semicolon, Q, J, K],
+            KeyboardLayout::Colemak => [Key1, Key2, Key3, Key4, Q, W, F, P, A, R, S, T, Z, X, C, D],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct KeyboardSettings {
+    pub layout: KeyboardLayout,
+    pub use_scancodes: bool, // go by where the key physically is and ignore the layout entirely
+    pub second_keypad: bool, // the numpad is a second keypad for CHIP-8X
+    pub share_second_keypad: bool, // the numpad presses keys on the first keypad too, for two player games
+}
+
+impl Default for KeyboardSettings {
+    fn default() -> Self {
+        Self {
+            layout: KeyboardLayout::Qwerty,
+            use_scancodes: false,
+            second_keypad: true,
+            share_second_keypad: false,
+        }
+    }
+}
+
+impl KeyboardSettings {
+    pub fn key_for_input(&self, scheme: Option<&ControlScheme>, keycode: Option<KeyCode>, scancode: ScanCode) -> Option<u8> {
+        let position = match keycode.and_then(named_position) {
+            Some(position) => position,
+            None => self.grid_position(keycode, scancode)?,
+        };
+
+        key_for_position(scheme, position)
+    }
+
+    pub fn pressed_keys(&self, scheme: Option<&ControlScheme>, key_ctx: &KeyboardContext) -> u16 {
+        let mut pressed_keys = 0;
+        let mut press = |position: Option<Position>| {
+            if let Some(key) = position.and_then(|position| key_for_position(scheme, position)) {
+                pressed_keys |= 1 << key;
+            }
+        };
+
+        for &keycode in key_ctx.pressed_keys() {
+            match named_position(keycode) {
+                Some(position) => press(Some(position)),
+                None if !self.use_scancodes => press(self.grid_position(Some(keycode), 0)),
+                None => {},
+            }
+        }
+        if self.use_scancodes {
+            for &scancode in key_ctx.pressed_scancodes() {
+                press(self.grid_position(None, scancode));
+            }
+        }
+
+        pressed_keys
+    }
+
+    pub fn second_keypad_key(&self, keycode: Option<KeyCode>) -> Option<u8> {
+        if !self.second_keypad {
+            return None;
+        }
+
+        let keycode = keycode?;
+        NUMPAD_GRID.iter().position(|&grid_keycode| grid_keycode == keycode).map(|position| KEYPAD_GRID[position])
+    }
+
+    pub fn second_keypad_keys(&self, key_ctx: &KeyboardContext) -> u16 {
+        key_ctx.pressed_keys().iter()
+            .filter_map(|&keycode| self.second_keypad_key(Some(keycode)))
+            .fold(0, |pressed_keys, key| pressed_keys | 1 << key)
+    }
+
+    fn grid_position(&self, keycode: Option<KeyCode>, scancode: ScanCode) -> Option<Position> {
+        let index = if self.use_scancodes {
+            SCANCODE_GRID.iter().position(|&grid_scancode| grid_scancode == scancode)
+        }
+        else {
+            let keycode = keycode?;
+            self.layout.grid().iter().position(|&grid_keycode| grid_keycode == keycode)
+        };
+
+        index.map(Position::Grid)
+    }
+}
+
+fn named_position(keycode: KeyCode) -> Option<Position> {
+    match keycode {
+        KeyCode::Up => Some(Position::Up),
+        KeyCode::Down => Some(Position::Down),
+        KeyCode::Left => Some(Position::Left),
+        KeyCode::Right => Some(Position::Right),
+        KeyCode::Space => Some(Position::Space),
+        _ => None,
+    }
+}
+
+fn key_for_position(scheme: Option<&ControlScheme>, position: Position) -> Option<u8> {
+    // the control scheme goes first so WASD can take over from the keypad keys underneath it
+    scheme.and_then(|scheme| scheme.key_for_position(position)).or(match position {
+        Position::Grid(index) => Some(KEYPAD_GRID[index]),
+        _ => None,
+    })
+}
+
+pub const SWIPE_PRESS_TICKS: u32 = 6; // how long a swipe holds its key down for
+const MIN_SWIPE_DISTANCE: f32 = 48.0; // in pixels, anything shorter is a tap
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct TouchSettings {
+    pub show_keypad: bool,
+    pub swipes: bool,
+    pub swipe_up: u8,
+    pub swipe_down: u8,
+    pub swipe_left: u8,
+    pub swipe_right: u8,
+}
+
+impl Default for TouchSettings {
+    fn default() -> Self {
+        Self {
+            show_keypad: false,
+            swipes: false,
+            swipe_up: 0x2,
+            swipe_down: 0x8,
+            swipe_left: 0x4,
+            swipe_right: 0x6,
+        }
+    }
+}
+
+impl TouchSettings {
+    pub fn key_for_swipe(&self, start: (f32, f32), end: (f32, f32)) -> Option<u8> {
+        if !self.swipes {
+            return None;
+        }
+
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        if dx.hypot(dy) < MIN_SWIPE_DISTANCE {
+            return None;
+        }
+
+        if dx.abs() > dy.abs() {
+            Some(if dx > 0.0 { self.swipe_right } else { self.swipe_left })
+        }
+        else {
+            Some(if dy > 0.0 { self.swipe_down } else { self.swipe_up })
+        }
+    }
+}
+
+// keys that aren't used by any of the layouts or control schemes so they are free to trigger macros
+pub const MACRO_TRIGGER_KEYS: [KeyCode; 20] = [
+    KeyCode::G, KeyCode::H, KeyCode::J, KeyCode::K, KeyCode::L,
+    KeyCode::B, KeyCode::N, KeyCode::M, KeyCode::LShift, KeyCode::LControl,
+    KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5,
+    KeyCode::F6, KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10,
+];
+
+pub const DEFAULT_TURBO_RATE: f32 = 15.0;
+pub const DEFAULT_SEQUENCE_HOLD_TICKS: u32 = 4;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub enum MacroAction {
+    Turbo { key: u8, rate: f32 },                    // presses and releases key rate times a second while held
+    Sequence { keys: Vec<u8>, hold_ticks: u32 },     // presses each key in turn once per trigger press
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct InputMacro {
+    pub trigger: KeyCode,
+    pub action: MacroAction,
+}
+
+impl Default for InputMacro {
+    fn default() -> Self {
+        Self {
+            trigger: MACRO_TRIGGER_KEYS[0],
+            action: MacroAction::Turbo { key: 0x5, rate: DEFAULT_TURBO_RATE },
+        }
+    }
+}
+
+// turns held trigger keys into keypad presses, one tick at a time so the timing doesn't depend on the frame rate
+#[derive(Default)]
+pub struct MacroPlayer {
+    progress: Vec<Option<u32>>, // ticks since each macro started, None while it isn't running
+    triggers_held: Vec<bool>,
+}
+
+impl MacroPlayer {
+    pub fn tick(&mut self, macros: &[InputMacro], key_ctx: &KeyboardContext, ticks_per_second: f64) -> u16 {
+        self.progress.resize(macros.len(), None);
+        self.triggers_held.resize(macros.len(), false);
+
+        let mut pressed_keys = 0;
+
+        for (i, input_macro) in macros.iter().enumerate() {
+            let held = key_ctx.is_key_pressed(input_macro.trigger);
+            let just_pressed = held && !self.triggers_held[i];
+            self.triggers_held[i] = held;
+
+            match &input_macro.action {
+                MacroAction::Turbo { key, rate } => {
+                    if !held {
+                        self.progress[i] = None;
+                        continue;
+                    }
+
+                    let ticks = self.progress[i].map_or(0, |ticks| ticks + 1);
+                    self.progress[i] = Some(ticks);
+
+                    // held down for the first half of each cycle and let go for the second
+                    let cycles = ticks as f64 * *rate as f64 / ticks_per_second;
+                    if cycles.fract() < 0.5 {
+                        pressed_keys |= 1 << (key & 0xF);
+                    }
+                },
+                MacroAction::Sequence { keys, hold_ticks } => {
+                    if just_pressed && self.progress[i].is_none() {
+                        self.progress[i] = Some(0);
+                    }
+                    let Some(ticks) = self.progress[i] else {
+                        continue;
+                    };
+
+                    // each key is held and then released for as long again so repeats of the same key still count
+                    let hold_ticks = (*hold_ticks).max(1);
+                    let step = (ticks / hold_ticks) as usize;
+                    match keys.get(step / 2) {
+                        Some(key) => {
+                            if step % 2 == 0 {
+                                pressed_keys |= 1 << (key & 0xF);
+                            }
+                            self.progress[i] = Some(ticks + 1);
+                        },
+                        None => self.progress[i] = None,
+                    }
+                },
+            }
+        }
+
+        pressed_keys
+    }
+}
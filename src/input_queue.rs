@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+struct KeyEvent {
+    key: u8,
+    down: bool,
+    time: Instant,
+}
+
+// the keys for one emulated frame
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct FrameKeys {
+    pub held: u16,     // held at any point in the frame, so a tap shorter than a frame is still seen
+    pub released: u16, // let go of during the frame
+}
+
+// keypad presses and releases in the order they happened, handed out to the frames they happened in. When the host
+// stalls and several frames run at once each one gets its own share of the input instead of all of it landing on the
+// first. The times are when the events reached the emulator, winit doesn't say when the key was actually pressed
+#[derive(Default)]
+pub struct InputQueue {
+    events: VecDeque<KeyEvent>,
+    held: u16, // after every event taken so far
+}
+
+impl InputQueue {
+    pub fn new() -> InputQueue {
+        InputQueue::default()
+    }
+
+    pub fn push(&mut self, key: u8, down: bool, time: Instant) {
+        if key < 16 {
+            self.events.push_back(KeyEvent { key, down, time });
+        }
+    }
+
+    // the keys for a frame ending at time, anything after it is left for later frames
+    pub fn take_until(&mut self, time: Instant) -> FrameKeys {
+        let mut frame = FrameKeys { held: self.held, released: 0 };
+
+        while let Some(event) = self.events.front().filter(|event| event.time <= time).copied() {
+            self.events.pop_front();
+            let bit = 1 << event.key;
+            if event.down {
+                self.held |= bit;
+                frame.held |= bit;
+            }
+            else {
+                self.held &= !bit;
+                frame.released |= bit;
+            }
+        }
+
+        frame
+    }
+
+    // lets go of everything, for when the keys stop belonging to the game
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.held = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn events_land_in_their_frames() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut queue = InputQueue::new();
+
+        queue.push(5, true, at(1));
+        queue.push(5, false, at(2));
+        queue.push(7, true, at(10));
+        queue.push(7, false, at(40));
+        queue.push(16, true, at(3));
+
+        // the tap on 5 is shorter than a frame but still counts as held for it
+        assert_eq!(queue.take_until(at(16)), FrameKeys { held: 1 << 5 | 1 << 7, released: 1 << 5 });
+        assert_eq!(queue.take_until(at(33)), FrameKeys { held: 1 << 7, released: 0 });
+        assert_eq!(queue.take_until(at(50)), FrameKeys { held: 1 << 7, released: 1 << 7 });
+        assert_eq!(queue.take_until(at(66)), FrameKeys::default());
+    }
+}
@@ -0,0 +1,200 @@
+// Opcodes are decoded once into an Instruction so the CPU doesn't have to pull the nibbles apart every cycle
+
+use std::fmt;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum AluOp {
+    Move,
+    Or,
+    And,
+    Xor,
+    Add,
+    Sub,
+    ShiftRight,
+    SubReversed,
+    ShiftLeft,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Instruction {
+    ScrollDown(u8),                 // 00CN
+    ClearScreen,                    // 00E0
+    Return,                         // 00EE
+    ScrollRight,                    // 00FB
+    ScrollLeft,                     // 00FC
+    Exit,                           // 00FD
+    LowRes,                         // 00FE
+    HighRes,                        // 00FF
+    Jump(u16),                      // 1NNN
+    Call(u16),                      // 2NNN
+    SkipIfEqual(u8, u8),            // 3XNN
+    SkipIfNotEqual(u8, u8),         // 4XNN
+    SkipIfRegsEqual(u8, u8),        // 5XY0
+    Load(u8, u8),                   // 6XNN
+    Add(u8, u8),                    // 7XNN
+    Alu(AluOp, u8, u8),             // 8XYN
+    SkipIfRegsNotEqual(u8, u8),     // 9XY0
+    LoadAddr(u16),                  // ANNN
+    JumpOffset(u8, u16),            // BNNN / BXNN
+    Random(u8, u8),                 // CXNN
+    Draw(u8, u8, u8),               // DXYN
+    SkipIfKey(u8),                  // EX9E
+    SkipIfNotKey(u8),               // EXA1
+    SkipIfSecondKey(u8),            // EXF2
+    SkipIfNotSecondKey(u8),         // EXF5
+    GetDelay(u8),                   // FX07
+    WaitForKey(u8),                 // FX0A
+    SetDelay(u8),                   // FX15
+    SetSound(u8),                   // FX18
+    AddAddr(u8),                    // FX1E
+    LowResFont(u8),                 // FX29
+    HighResFont(u8),                // FX30
+    Bcd(u8),                        // FX33
+    StoreRegs(u8),                  // FX55
+    LoadRegs(u8),                   // FX65
+    StoreFlags(u8),                 // FX75
+    LoadFlags(u8),                  // FX85
+    Unsupported(u16),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Instruction {
+        let opcode_type = (opcode & 0xF000) >> 12;      // TAAA
+        let reg_x = ((opcode & 0x0F00) >> 8) as u8;     // AXAA
+        let reg_y = ((opcode & 0x00F0) >> 4) as u8;     // AAYA
+        let nnn = opcode & 0x0FFF;                      // ANNN
+        let nn = (opcode & 0x00FF) as u8;               // AANN
+        let n = (opcode & 0x000F) as u8;                // AAAN
+
+        match opcode_type {
+            0x0 if opcode & 0xFFF0 == 0x00C0 => Instruction::ScrollDown(n),
+            0x0 => match opcode {
+                0x00E0 => Instruction::ClearScreen,
+                0x00EE => Instruction::Return,
+                0x00FB => Instruction::ScrollRight,
+                0x00FC => Instruction::ScrollLeft,
+                0x00FD => Instruction::Exit,
+                0x00FE => Instruction::LowRes,
+                0x00FF => Instruction::HighRes,
+                _ => Instruction::Unsupported(opcode),
+            },
+            0x1 => Instruction::Jump(nnn),
+            0x2 => Instruction::Call(nnn),
+            0x3 => Instruction::SkipIfEqual(reg_x, nn),
+            0x4 => Instruction::SkipIfNotEqual(reg_x, nn),
+            0x5 => Instruction::SkipIfRegsEqual(reg_x, reg_y),
+            0x6 => Instruction::Load(reg_x, nn),
+            0x7 => Instruction::Add(reg_x, nn),
+            0x8 => {
+                let op = match n {
+                    0x0 => AluOp::Move,
+                    0x1 => AluOp::Or,
+                    0x2 => AluOp::And,
+                    0x3 => AluOp::Xor,
+                    0x4 => AluOp::Add,
+                    0x5 => AluOp::Sub,
+                    0x6 => AluOp::ShiftRight,
+                    0x7 => AluOp::SubReversed,
+                    0xE => AluOp::ShiftLeft,
+                    _ => return Instruction::Unsupported(opcode),
+                };
+                Instruction::Alu(op, reg_x, reg_y)
+            }
+            0x9 => Instruction::SkipIfRegsNotEqual(reg_x, reg_y),
+            0xA => Instruction::LoadAddr(nnn),
+            0xB => Instruction::JumpOffset(reg_x, nnn),
+            0xC => Instruction::Random(reg_x, nn),
+            0xD => Instruction::Draw(reg_x, reg_y, n),
+            0xE => match nn {
+                0x9E => Instruction::SkipIfKey(reg_x),
+                0xA1 => Instruction::SkipIfNotKey(reg_x),
+                0xF2 => Instruction::SkipIfSecondKey(reg_x),
+                0xF5 => Instruction::SkipIfNotSecondKey(reg_x),
+                _ => Instruction::Unsupported(opcode),
+            },
+            0xF => match nn {
+                0x07 => Instruction::GetDelay(reg_x),
+                0x0A => Instruction::WaitForKey(reg_x),
+                0x15 => Instruction::SetDelay(reg_x),
+                0x18 => Instruction::SetSound(reg_x),
+                0x1E => Instruction::AddAddr(reg_x),
+                0x29 => Instruction::LowResFont(reg_x),
+                0x30 => Instruction::HighResFont(reg_x),
+                0x33 => Instruction::Bcd(reg_x),
+                0x55 => Instruction::StoreRegs(reg_x),
+                0x65 => Instruction::LoadRegs(reg_x),
+                0x75 => Instruction::StoreFlags(reg_x),
+                0x85 => Instruction::LoadFlags(reg_x),
+                _ => Instruction::Unsupported(opcode),
+            },
+            _ => panic!("should only be a nibble"),
+        }
+    }
+
+    // the address it jumps to, calls or points I at. For BNNN that's only the start of where it could go
+    pub fn target(self) -> Option<u16> {
+        match self {
+            Instruction::Jump(target) | Instruction::Call(target) | Instruction::LoadAddr(target) | Instruction::JumpOffset(_, target) => Some(target),
+            _ => None,
+        }
+    }
+}
+
+// written out in the usual mnemonics, e.g. "LD V3, 0x1F"
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ClearScreen => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::Jump(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            Instruction::SkipIfEqual(x, nn) => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfNotEqual(x, nn) => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            Instruction::SkipIfRegsEqual(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::Load(x, nn) => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Add(x, nn) => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Instruction::Alu(op, x, y) => {
+                let mnemonic = match op {
+                    AluOp::Move => "LD",
+                    AluOp::Or => "OR",
+                    AluOp::And => "AND",
+                    AluOp::Xor => "XOR",
+                    AluOp::Add => "ADD",
+                    AluOp::Sub => "SUB",
+                    AluOp::ShiftRight => "SHR",
+                    AluOp::SubReversed => "SUBN",
+                    AluOp::ShiftLeft => "SHL",
+                };
+                write!(f, "{} V{:X}, V{:X}", mnemonic, x, y)
+            }
+            Instruction::SkipIfRegsNotEqual(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadAddr(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            Instruction::JumpOffset(_, nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Instruction::Random(x, nn) => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipIfKey(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipIfNotKey(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::SkipIfSecondKey(x) => write!(f, "SKP2 V{:X}", x),
+            Instruction::SkipIfNotSecondKey(x) => write!(f, "SKNP2 V{:X}", x),
+            Instruction::GetDelay(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitForKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddAddr(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LowResFont(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::HighResFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::StoreRegs(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegs(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            Instruction::Unsupported(opcode) => write!(f, "DW 0x{:04X}", opcode),
+        }
+    }
+}
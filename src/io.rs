@@ -3,391 +3,3973 @@ use ggegui::egui::{self, menu, Window, widgets};
 use rfd;
 
 use ggez::{Context, ContextBuilder, GameResult};
-use ggez::audio::{self, SoundSource, Source};
-use ggez::conf::WindowSetup;
-use ggez::event::{self, EventHandler};
+use ggez::conf::{FullscreenType, WindowSetup};
+use ggez::event::{self, EventHandler, MouseButton};
 use ggez::glam::Vec2;
-use ggez::graphics::{Canvas, Color, DrawParam, Image, InstanceArray};
-use ggez::input::keyboard::{KeyCode, KeyboardContext, KeyInput};
+use ggez::graphics::{Canvas, Color, DrawMode, DrawParam, Image, ImageEncodingFormat, ImageFormat, Mesh, Rect, Sampler};
+use ggez::input::keyboard::{KeyCode, KeyInput};
+use ggez::winit::dpi::PhysicalPosition;
 
-use std::collections::HashSet;
-use std::{env, path, fs};
+use serde_json::json;
 
-use crate::cpu::{self, CPU, ShiftingReg, RegSaveLoadQuirk, JumpBehviour, Quirks};
+use std::{mem, thread};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{env, path, fs, process};
 
-const DEFAULT_CYCLES_PER_FRAME: u16 = 12;
-
-const DEFAULT_OFF_COLOUR: Color = Color {r: 0.057805423, g: 0.057805423, b: 0.057805423, a: 1.0};
-const DEFAULT_ON_COLOUR: Color = Color::WHITE;
+use crate::ambient::{self, AmbientColours, AmbientLights, AmbientMode, WledLights};
+use crate::analysis::{CodeMap, Coverage};
+use crate::archive;
+use crate::attract::{self, Attract, AttractAction};
+use crate::audio::{self, Buzzer, BUFFER_SIZES};
+use crate::crash::{self, CrashContext};
+use crate::crowd::{self, CrowdServer};
+use crate::diagnostics;
+use crate::disasm;
+use crate::drawlog::DrawLog;
+use crate::editor::OctoEditor;
+use crate::explain::{self, Quirk};
+use crate::gallery::Gallery;
+use crate::hexview::HexView;
+use crate::history::{FrameInput, History};
+use crate::library;
+use crate::lite::LiteOptions;
+use crate::manifest;
+use crate::metadata::{self, RomMetadata};
+use crate::shaders::{self, PostShader, ScreenShader, SHADER_DIR};
+use crate::hotkeys::{HotkeyAction, Hotkeys, KeyCombo, FAST_FORWARD_SPEED};
+use crate::breakpoints::{self, Breakpoint, BreakpointKind, Comparison, Condition};
+use crate::bridge::{DisplayBridge, DEFAULT_BRIDGE_ADDRESS};
+use crate::calltree::CallTree;
+use crate::cheats::{self, Cheat, CheatKind, MemorySearch, SearchFilter};
+use crate::config::{self, Config, Gradient, LedMatrixSettings, OffPixels, SharedSettings, SpeedMode, Theme, TransitionSettings, WindowGeometry, DEFAULT_INSTRUCTIONS_PER_SECOND, DEFAULT_OFF_COLOUR, DEFAULT_ON_COLOUR, DEFAULT_UI_SCALE, TIMER_HZ};
+use crate::cpu::{self, CPU, DisplayWatch, ShiftingReg, RegSaveLoadQuirk, JumpBehviour, Quirks, DEFAULT_CYCLES_PER_FRAME};
+use crate::instruction::Instruction;
+use crate::netplay::{NetplayHost, NetplayRules, NetplaySession, DEFAULT_NETPLAY_PORT};
+use crate::patch::{Patch, PatchFile};
+use crate::midi::{self, MidiKeypad, MidiSettings, NoteEvent};
+use crate::patterns::PatternEditor;
+use crate::plugins::{Plugin, Plugins, PLUGIN_DIR};
+use crate::random::RngAlgorithm;
+use crate::roms::BUILT_IN_ROMS;
+use crate::saves::{self, SaveAction, SaveSlot, QUICK_SAVE};
+use crate::savestate::{self, DiffSide, SavedState, StateDiff, SAVESTATE_EXTENSION};
+use crate::speedrun::{self, RunTimer};
+use crate::sprites::{SpriteAction, SpriteEditor};
+use crate::tabs::{RomSnapshot, Tab, UndoSnapshot, NEW_TAB_NAME};
+use crate::tour::{Tour, TourAnchors, TourEvent, TOUR_ROM};
+use crate::transition::{Transition, TransitionKind};
+use crate::undo::UndoStack;
+use crate::web_export;
+use crate::recording::{Recording, VIDEO_EXTENSIONS};
+use crate::remote::{Command, RemoteServer, DEFAULT_REMOTE_PORT};
+use crate::input_queue::InputQueue;
+use crate::input::{self, ControlScheme, DirectionKeys, InputMacro, KeyboardLayout, MacroAction, MacroPlayer, KEYPAD_GRID, MACRO_TRIGGER_KEYS, SWIPE_PRESS_TICKS};
 
 const DEFAULT_PIXEL_SIZE: f32 = 16.0;
 const MENU_BAR_HEIGHT: f32 = 24.0;
+const MIN_WINDOW_SIZE: (f32, f32) = (cpu::WIDTH as f32, cpu::HEIGHT as f32 + MENU_BAR_HEIGHT); // a pixel for each CHIP-8 pixel
 const SCREEN_SIZE: (f32, f32) = (cpu::WIDTH as f32 * DEFAULT_PIXEL_SIZE, cpu::HEIGHT as f32 * DEFAULT_PIXEL_SIZE + MENU_BAR_HEIGHT);
 
+const MAX_TICKS_PER_UPDATE: u32 = 6; // after a long stall give up catching up instead of running everything at once
+const IDLE_FRAME_TIME: Duration = Duration::from_millis(50); // how long to sleep each frame while there is nothing to emulate
+
+// how long a pixel takes to fade out with reduced flicker, long enough to cover a sprite being redrawn
+const PHOSPHOR_FADE_TIME: Duration = Duration::from_millis(100);
+const VISUAL_BEEP_COLOUR: Color = Color { r: 1.0, g: 0.75, b: 0.0, a: 1.0 };
+const VISUAL_BEEP_WIDTH: f32 = 8.0;
+
+const SCREENSHOT_SCALE: usize = 8; // CHIP-8 pixels are too small to see in a 64x32 image
+const SCREENSHOT_DIR: &str = "/screenshots"; // in the user data directory
+const CRASH_DIR: &str = "crashes";           // so is this
+
+const WINDOW_TITLE: &str = "Fish n CHIP-8";
+const DONT_ASK_AGAIN: &str = "Don't ask again";
+const SPLASH_ROM: &[u8] = include_bytes!("../roms/tests/1-chip8-logo.ch8"); // shown while no ROM is loaded
+
+type StepPreviewKey = (u64, Quirks, u16); // the state hash, quirks and keys a Step preview ran with
+
 pub struct EmulatorIO {
-    pixels_batch: InstanceArray,
-    beep_sound: Source,
+    screen_image: Image,
+    screen_pixels: Vec<u8>,
+    post_shader: Option<PostShader>,
+    transition: Option<Transition>,
+    phosphor: Vec<f32>, // how brightly each pixel is lit while it fades out
+    display_dirty: bool,
+    tick_accumulator: Duration,
+    cycle_budget: f64,
+    buzzer: Option<Buzzer>,
+    audio_devices: Vec<String>,
+    beeping: bool,
+    paused: bool,
+    fast_forward: bool,
+    fullscreen: bool,
+    menu_bar_hidden: bool, // so the screen can have the whole window
+    gui_input_stale: bool, // the window changed while egui was left out, so it has to be told the new size
+    lite: bool,            // the low resource profile, where the GUI isn't run at all while the menu bar is hidden
+    save_config: bool,     // false when a config that couldn't be loaded couldn't be moved aside either
+    binding_hotkey: Option<HotkeyAction>, // waiting for the next key press to bind to this
+    macro_player: MacroPlayer,
+    macro_keys: u16,
+    run_timer: RunTimer,
+    recording: Option<Recording>,
+    history: Option<History>, // while the timeline is recording
+    timeline_frame: u32,      // the frame that runs next
+    input_queue: InputQueue,  // the keypad keys pressed on the keyboard
+    touch_keys: u16,
+    swipe_start: Option<(f32, f32)>,
+    swipe_press: Option<(u8, u32)>, // key and ticks left
+    remote: Option<RemoteServer>,
+    plugins: Plugins,
+    remote_keys: u16,
+    midi: Option<MidiKeypad>,
+    display_bridge: Option<DisplayBridge>,
+    ambient_lights: Option<Box<dyn AmbientLights>>, // the ones set up in the configuration
+    built_in_lights: Vec<Box<dyn AmbientLights>>,   // passed in by a build of the emulator, always sent to
+    midi_keys: u16,
+    midi_learn: Option<u8>, // the keypad key the next note will be mapped to
+    midi_ports: Vec<String>,
+    crowd: Option<CrowdServer>,
+    crowd_keys: u16,
+    netplay: Option<NetplaySession>,
+    netplay_host: Option<NetplayHost>,
+    netplay_keys: u32,
+    debugger: Option<process::Child>, // the detached debugger window
     cpu: CPU,
-    cycles_per_frame: u16,
+    tabs: Vec<Tab>,   // the focused tab's entry is a placeholder, its state is in the fields here
+    active_tab: usize,
+    background_accumulator: Duration,
+    config: Config,
     gui: Gui,
+    gui_style_dirty: bool,
     config_window_open: bool,
+    cheats_window_open: bool,
+    breakpoints_window_open: bool,
+    breakpoint_hit: Option<String>, // why it last paused, until it's resumed
+    at_entry: bool,                 // nothing has run since a reset, so a breakpoint on the first instruction can stop it
+    watch_pixel: (usize, usize),    // the pixel picked for a display watch
+    code_trap: Option<CodeMap>,     // the code to stop on writes to, while breaking on self-modifying code
+    coverage: Coverage,             // for the current ROM since it was loaded or restarted
+    error_reported: bool,
+    halt_window_open: bool,
+    search_window_open: bool,
+    patches_window_open: bool,
+    netplay_window_open: bool,
+    editor_window_open: bool,
+    step_window_open: bool,
+    step_preview: Option<(StepPreviewKey, Result<StateDiff, String>)>, // what Step will change
+    gallery_window_open: bool,
+    saves_window_open: bool,
+    attract_window_open: bool,
+    save_slots: Vec<SaveSlot>, // the current ROM's, as they were when the save manager was last opened or changed
+    save_name: String,
+    library_window_open: bool,
+    memory_window_open: bool,
+    timeline_window_open: bool,
+    draw_log_window_open: bool,
+    call_tree_window_open: bool,
+    hex_view: HexView,
+    draw_log: DrawLog,
+    call_tree: CallTree,
+    gallery: Gallery,
+    octo_editor: OctoEditor,
+    sprites_window_open: bool,
+    sprite_editor: SpriteEditor,
+    patterns_window_open: bool,
+    rom_properties: Option<Vec<(&'static str, String)>>, // shown while Some
+    archive_choice: Option<(path::PathBuf, Vec<String>)>, // an archive with more than one ROM in it, shown while Some
+    state_diff: Option<[DiffSide; 2]>,                    // shown while Some
+    pattern_editor: PatternEditor,
+    tour: Option<Tour>,
+    memory_search: Option<MemorySearch>,
+    search_value: u8,
+    watches: Vec<u16>,
     last_loaded_rom: Option<Vec<u8>>,
-    menu_bar_height: f32,
+    rom_path: Option<path::PathBuf>, // if the ROM came from a file
+    undo: UndoStack<UndoSnapshot>,
+    attract: Option<Attract>, // while cycling through the attract mode playlist
+    rom_id: Option<String>,
+    menu_bar_height: f32, // in physical pixels like everything ggez draws, egui works in points
+    scale_factor: f32,    // physical pixels per point, 2 on a display scaled to 200%
     height_offset: f32,
     width_offset: f32,
     pixel_size: f32,
-    pixel_on_colour: Color,
-    pixel_off_colour: Color,
 }
 
 impl EmulatorIO {
-    pub fn new(ctx: &mut Context) -> EmulatorIO {
-        let pixel_rect = Image::from_color(
-            &ctx.gfx,
-            DEFAULT_PIXEL_SIZE as u32,
-            DEFAULT_PIXEL_SIZE as u32,
-            None,
-        );
-        let pixels_batch = InstanceArray::new(&ctx.gfx, pixel_rect);
+    pub fn new(ctx: &mut Context, plugins: Vec<Box<dyn Plugin>>, lights: Vec<Box<dyn AmbientLights>>) -> EmulatorIO {
+        let dir = ctx.fs.user_config_dir().to_path_buf();
+        let (config, save_config) = match Config::load(&dir) {
+            Ok(config) => (config, true),
+            Err(e) => {
+                let (kept, save_config) = match Config::back_up(&dir) {
+                    Ok(backup) => (format!("It has been moved to {} and the defaults are being used instead.", backup.display()), true),
+                    Err(backup) => (format!("The defaults are being used and won't be saved over it, as it couldn't be moved aside: {}", backup), false),
+                };
+                show_error("Could not load the configuration", format!("{}\n\n{}", e, kept));
+                (Config::default(), save_config)
+            },
+        };
 
+        let mut created = EmulatorIO::with_config(ctx, plugins, lights, config);
+        created.save_config = save_config;
+        created
+    }
+
+    fn with_config(ctx: &mut Context, plugins: Vec<Box<dyn Plugin>>, lights: Vec<Box<dyn AmbientLights>>, config: Config) -> EmulatorIO {
         let mut created = EmulatorIO {
-            pixels_batch,
-            beep_sound: audio::Source::new(ctx, "/beep.wav").unwrap(),
+            screen_image: Image::from_color(&ctx.gfx, cpu::WIDTH as u32, cpu::HEIGHT as u32, None),
+            screen_pixels: Vec::with_capacity(cpu::WIDTH * 2 * cpu::HEIGHT * 2 * 4),
+            post_shader: None,
+            transition: None,
+            phosphor: vec![],
+            display_dirty: true,
+            tick_accumulator: Duration::ZERO,
+            cycle_budget: 0.0,
+            buzzer: None,
+            audio_devices: vec![],
+            beeping: false,
+            paused: false,
+            fast_forward: false,
+            fullscreen: false,
+            menu_bar_hidden: false,
+            gui_input_stale: false,
+            lite: false,
+            save_config: true,
+            binding_hotkey: None,
+            macro_player: MacroPlayer::default(),
+            macro_keys: 0,
+            run_timer: RunTimer::default(),
+            recording: None,
+            history: None,
+            timeline_frame: 0,
+            input_queue: InputQueue::new(),
+            touch_keys: 0,
+            swipe_start: None,
+            swipe_press: None,
+            remote: None,
+            plugins: Plugins::new(plugins),
+            remote_keys: 0,
+            midi: None,
+            display_bridge: None,
+            ambient_lights: None,
+            built_in_lights: lights,
+            midi_keys: 0,
+            midi_learn: None,
+            midi_ports: vec![],
+            crowd: None,
+            crowd_keys: 0,
+            netplay: None,
+            netplay_host: None,
+            netplay_keys: 0,
+            debugger: None,
             cpu: CPU::new(),
-            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            tabs: vec![Tab::new(CPU::new())],
+            active_tab: 0,
+            background_accumulator: Duration::ZERO,
+            config,
             gui: Gui::new(ctx),
+            gui_style_dirty: true,
             menu_bar_height: MENU_BAR_HEIGHT,
+            scale_factor: 1.0,
             height_offset: 0.0,
             width_offset: 0.0,
             last_loaded_rom: None,
+            rom_path: None,
+            undo: UndoStack::default(),
+            attract: None,
+            rom_id: None,
             config_window_open: false,
+            cheats_window_open: false,
+            breakpoints_window_open: false,
+            breakpoint_hit: None,
+            at_entry: true,
+            watch_pixel: (0, 0),
+            code_trap: None,
+            coverage: Coverage::default(),
+            error_reported: false,
+            halt_window_open: false,
+            search_window_open: false,
+            patches_window_open: false,
+            netplay_window_open: false,
+            editor_window_open: false,
+            step_window_open: false,
+            step_preview: None,
+            gallery_window_open: false,
+            saves_window_open: false,
+            attract_window_open: false,
+            save_slots: vec![],
+            save_name: String::new(),
+            library_window_open: false,
+            memory_window_open: false,
+            timeline_window_open: false,
+            draw_log_window_open: false,
+            call_tree_window_open: false,
+            hex_view: HexView::default(),
+            draw_log: DrawLog::default(),
+            call_tree: CallTree::default(),
+            gallery: Gallery::default(),
+            octo_editor: OctoEditor::default(),
+            sprites_window_open: false,
+            sprite_editor: SpriteEditor::default(),
+            patterns_window_open: false,
+            rom_properties: None,
+            archive_choice: None,
+            state_diff: None,
+            pattern_editor: PatternEditor::default(),
+            tour: None,
+            memory_search: None,
+            search_value: 0,
+            watches: vec![],
             pixel_size: DEFAULT_PIXEL_SIZE,
-            pixel_off_colour: DEFAULT_OFF_COLOUR,
-            pixel_on_colour: DEFAULT_ON_COLOUR,
         };
-        
-        created.beep_sound.set_repeat(true);
+        created.cpu.quirks = created.config.quirks;
+        if !created.config.tour_finished {
+            created.tour = Some(Tour::default());
+        }
+
+        created.restart_buzzer(ctx);
+        created.set_shader(ctx, created.config.shader.clone());
+        created.restart_remote();
+        created.restart_crowd();
+        created.restart_midi();
+        created.restart_display_bridge();
+        created.restart_ambient_lights();
+        created.load_plugin_scripts(ctx);
 
-        let rom = vec![0x12, 0x00]; // infinte loop
-        created.cpu.load_rom(&rom);
+        created.cpu.load_rom(SPLASH_ROM);
 
-        created.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().0).unwrap();
+        created.update_scale_factor(ctx);
+        created.restore_window(ctx);
+        report_error("Could not resize the screen", created.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1));
+
+        if created.config.attract.start_on_launch && !created.config.attract.playlist.is_empty() {
+            created.start_attract(ctx);
+        }
 
         created
     }
 
-    fn key_for_keycode(&self, keycode: Option<&KeyCode>) -> Option<u8> {
-        if let Some(keycode) = keycode {
-            match *keycode {
-                KeyCode::Key1 => return Some(0x1),
-                KeyCode::Key2 => return Some(0x2),
-                KeyCode::Key3 => return Some(0x3),
-                KeyCode::Key4 => return Some(0xC),
-                KeyCode::Q => return Some(0x4),
-                KeyCode::W => return Some(0x5),
-                KeyCode::E => return Some(0x6),
-                KeyCode::R => return Some(0xD),
-                KeyCode::A => return Some(0x7),
-                KeyCode::S => return Some(0x8),
-                KeyCode::D => return Some(0x9),
-                KeyCode::F => return Some(0xE),
-                KeyCode::Z => return Some(0xA),
-                KeyCode::X => return Some(0x0),
-                KeyCode::C => return Some(0xB),
-                KeyCode::V => return Some(0xF),
-                _ => return None,
-            };
+    fn start_lite(&mut self, ctx: &mut Context, options: &LiteOptions, quirks: Quirks) {
+        self.lite = true;
+        self.menu_bar_hidden = true;
+        self.gui_input_stale = true; // the fullscreen window it's about to get hasn't been passed on
+        self.cpu.quirks = quirks;
+        if options.mute {
+            self.buzzer = None;
         }
-        else {
-            return None;
+        if !options.windowed {
+            self.fullscreen = true;
+            report_error("Could not go fullscreen", ctx.gfx.set_fullscreen(FullscreenType::Desktop));
+        }
+        if let Some(rom) = &options.rom {
+            self.load_rom_file(ctx, rom);
         }
     }
 
-    fn get_pressed_keys(&self, key_ctx: &KeyboardContext) -> HashSet<u8> {
-        let pressed = key_ctx.pressed_keys();
-        let mut pressed_nums: HashSet<u8> = HashSet::new();
-        
-        for key in pressed {
-            if let Some(key) = self.key_for_keycode(Some(key)) {
-                pressed_nums.insert(key);
-            }
+    // whether egui is left out of this frame altogether
+    fn gui_skipped(&self) -> bool {
+        self.lite && self.menu_bar_hidden
+    }
+
+    // puts the window back where it was last time, only moving it if the monitor it was on is still plugged in
+    fn restore_window(&mut self, ctx: &mut Context) {
+        let Some(geometry) = self.config.window.clone() else {
+            // the first time, make the window the same size on screen whatever the display is scaled to
+            report_error("Could not resize the window", ctx.gfx.set_drawable_size(SCREEN_SIZE.0 * self.scale_factor, SCREEN_SIZE.1 * self.scale_factor));
+            return;
+        };
+
+        let window = ctx.gfx.window();
+        let monitor_connected = window.available_monitors().any(|monitor| monitor.name() == geometry.monitor);
+        if let (Some((x, y)), true) = (geometry.position, monitor_connected) {
+            window.set_outer_position(PhysicalPosition::new(x, y));
         }
 
-        pressed_nums
+        if geometry.width >= MIN_WINDOW_SIZE.0 && geometry.height >= MIN_WINDOW_SIZE.1 {
+            report_error("Could not restore the window size", ctx.gfx.set_drawable_size(geometry.width, geometry.height));
+        }
+        if geometry.fullscreen {
+            self.fullscreen = true;
+            report_error("Could not go fullscreen", ctx.gfx.set_fullscreen(FullscreenType::Desktop));
+        }
     }
 
-    fn update_cpu(&mut self, ctx: &mut Context) -> GameResult {
-        let pressed_keys = self.get_pressed_keys(&ctx.keyboard);
+    // ggez doesn't pass on scale factor changes, such as from dragging the window onto another monitor, so this is
+    // checked every frame
+    fn update_scale_factor(&mut self, ctx: &mut Context) {
+        let scale_factor = ctx.gfx.window().scale_factor() as f32;
+        if scale_factor == self.scale_factor {
+            return;
+        }
 
-        if self.cpu.timer_tick() {
-            self.beep_sound.play_later()?;
+        self.menu_bar_height *= scale_factor / self.scale_factor;
+        self.scale_factor = scale_factor;
+        match self.gui_skipped() {
+            true => self.gui_input_stale = true,
+            false => self.gui.input.set_scale_factor(scale_factor, ctx.gfx.drawable_size()),
         }
-        else {
-            self.beep_sound.stop(&ctx.audio)?;
+        report_error("Could not resize the screen", self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1));
+    }
+
+    fn save_window(&mut self, ctx: &Context) {
+        let window = ctx.gfx.window();
+        let mut geometry = self.config.window.clone().unwrap_or(WindowGeometry { width: SCREEN_SIZE.0, height: SCREEN_SIZE.1, ..Default::default() });
+        geometry.fullscreen = self.fullscreen;
+        geometry.monitor = window.current_monitor().and_then(|monitor| monitor.name());
+
+        // fullscreen and minimised windows are the wrong size to come back to
+        let (width, height) = ctx.gfx.drawable_size();
+        if !self.fullscreen && window.is_minimized() != Some(true) && width > 0.0 && height > 0.0 {
+            geometry.width = width;
+            geometry.height = height;
+            geometry.position = window.outer_position().ok().map(|position| (position.x, position.y));
         }
 
-        for _ in 0..self.cycles_per_frame {
-            let old_res = self.cpu.resolution;
-            self.cpu.handle_opcode(&pressed_keys);
-            if self.cpu.resolution != old_res {
-                self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)?;
+        self.config.window = Some(geometry);
+    }
+
+    fn update_cpu(&mut self, ctx: &mut Context) -> GameResult {
+        // the timers and CPU run at a fixed 60Hz however often ggez calls update
+        let tick_length = Duration::from_secs_f64(1.0 / TIMER_HZ);
+        // typing into a text box such as the editor shouldn't press keys in the game too
+        let typing = self.gui.ctx().wants_keyboard_input();
+        let second_keypad = if typing { 0 } else { self.config.keyboard.second_keypad_keys(&ctx.keyboard) };
+
+        let speed = if self.fast_forward { FAST_FORWARD_SPEED } else { 1 };
+        self.tick_accumulator += ctx.time.delta() * speed;
+        let now = Instant::now();
+
+        let mut ticks = 0;
+        while self.tick_accumulator >= tick_length && ticks < MAX_TICKS_PER_UPDATE * speed {
+            // the input is only taken once the other player's keys are here to run it with, so none is lost waiting
+            if let Some(netplay) = &mut self.netplay {
+                match netplay.ready() {
+                    Ok(true) => {},
+                    Ok(false) => {
+                        // waiting on the other player, picking up from here once they catch up
+                        self.tick_accumulator = Duration::ZERO;
+                        break;
+                    },
+                    Err(e) => {
+                        show_error("Netplay disconnected", format!("Lost the connection to the other player: {}", e));
+                        self.netplay = None;
+                        break;
+                    },
+                }
+            }
+            self.tick_accumulator -= tick_length;
+            ticks += 1;
+
+            // each tick gets the key presses from its own slice of time, the ones still to run being the most behind
+            let typed = self.input_queue.take_until(now - self.tick_accumulator / speed);
+            let held_keys = typed.held | self.touch_keys | self.remote_keys | self.midi_keys;
+            let macro_keys = self.macro_player.tick(&self.config.macros, &ctx.keyboard, TIMER_HZ);
+            let swipe_keys = self.tick_swipe();
+            let crowd_keys = self.crowd.as_mut().map_or(0, |crowd| crowd.tick(self.config.crowd_hold_ticks));
+            // filtered before netplay sends them, so both players run with the keys the plugins decided on
+            let keys = self.plugins.filter_input(held_keys | macro_keys | swipe_keys | crowd_keys);
+
+            if let Some(netplay) = &mut self.netplay {
+                match netplay.exchange(keys as u32 | (second_keypad as u32) << 16) {
+                    Ok(keys) => {
+                        self.tick_cpu(ctx, keys as u16, (keys >> 16) as u16)?;
+
+                        // releases have to come from the synced keys too or FX0A would finish on different frames for each player
+                        self.release_keys((self.netplay_keys & !keys) as u16);
+                        self.netplay_keys = keys;
+                    },
+                    Err(e) => {
+                        show_error("Netplay disconnected", format!("Lost the connection to the other player: {}", e));
+                        self.netplay = None;
+                        break;
+                    },
+                }
+            }
+            else {
+                self.tick_cpu(ctx, keys, second_keypad)?;
+
+                // FX0A waits for a key to be let go of so the macros have to report their releases too
+                self.release_keys(typed.released | (self.macro_keys | self.crowd_keys) & !(macro_keys | crowd_keys) & !held_keys);
+            }
+            self.macro_keys = macro_keys;
+            self.crowd_keys = crowd_keys;
+
+            if self.paused {
+                break; // a breakpoint was hit
             }
         }
+        if ticks == MAX_TICKS_PER_UPDATE * speed {
+            self.tick_accumulator = Duration::ZERO;
+        }
+
+        if let Some(buzzer) = &mut self.buzzer {
+            buzzer.set_beeping(self.beeping);
+        }
 
         Ok(())
     }
 
-    fn update_gui(&mut self, ctx: &mut Context) -> GameResult {
-        if ctx.gfx.drawable_size() == (0.0, 0.0) {
-            return Ok(());
+    fn release_keys(&mut self, released: u16) {
+        if let Some(history) = &mut self.history {
+            history.release(released);
+        }
+        for key in 0..16 {
+            if released & (1 << key) != 0 {
+                self.cpu.key_up(key);
+            }
         }
+    }
 
-        let gui_ctx = &self.gui.ctx();
+    fn start_netplay(&mut self, session: NetplaySession) {
+        // both players start the ROM from scratch with the same seed so they stay identical from here on
+        self.restart_rom();
+        self.cpu.seed_rng(session.seed());
+        self.tick_accumulator = Duration::ZERO;
+        self.cycle_budget = 0.0;
+        self.netplay_keys = 0;
+        self.netplay_host = None;
+        self.netplay = Some(session);
+    }
 
-        let height = egui::TopBottomPanel::top("MenuBar").show(gui_ctx, |ui| {
-            menu::bar(ui, |ui| {
-                if ui.button("Load ROM").clicked() {
-                    if let Some(path) = rfd::FileDialog::new().pick_file() {
-                        ctx.gfx.set_window_title(format!("{} - Fish n CHIP-8", path.file_name().unwrap().to_str().unwrap()).as_str());
+    fn poll_netplay_host(&mut self) {
+        let Some(host) = &self.netplay_host else {
+            return;
+        };
 
-                        let quirks = self.cpu.quirks;
+        match host.accept() {
+            Ok(Some(session)) => self.start_netplay(session),
+            Ok(None) => {},
+            Err(e) => {
+                show_error("Netplay failed", format!("Could not start a game with the other player: {}", e));
+                self.netplay_host = None;
+            },
+        }
+    }
 
-                        let rom = fs::read(path).unwrap();
-                        self.last_loaded_rom = Some(rom.clone());
+    fn restart_remote(&mut self) {
+        self.remote = None; // stops the old server
 
-                        self.cpu = CPU::new();
-                        self.cpu.load_rom(&rom);
-                        self.cpu.quirks = quirks;
-                        self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1).unwrap();
-                    }
-                }
-                if ui.button("Restart current ROM").clicked() {
-                    if let Some(rom) = &self.last_loaded_rom {
-                        let quirks = self.cpu.quirks;
+        if self.config.remote_api {
+            match RemoteServer::start(self.config.remote_api_port) {
+                Ok(remote) => self.remote = Some(remote),
+                Err(e) => show_error(
+                    "Remote control unavailable",
+                    format!("Could not listen on port {}: {}\n\nPick another port in the configuration.", self.config.remote_api_port, e),
+                ),
+            }
+        }
+    }
 
-                        self.cpu = CPU::new();
-                        self.cpu.load_rom(rom);
-                        self.cpu.quirks = quirks;
-                    }
-                }
-                if ui.button("Configuration").clicked() {
-                    self.config_window_open = true;
-                }
-                if self.config_window_open {
-                    Window::new("Configuration").open(&mut self.config_window_open).resizable(true).show(gui_ctx, |ui| {
-                        ui.horizontal(|ui| {
-                            ui.label("Cyles per frame: ");
-                            ui.add(egui::DragValue::new(&mut self.cycles_per_frame));
-                            
-                            if ui.button("Reset to default").clicked() {
-                                self.cycles_per_frame = DEFAULT_CYCLES_PER_FRAME;
-                            }
-                        });
-                        ui.separator();
+    fn restart_crowd(&mut self) {
+        self.crowd = None; // stops the old server
 
-                        ui.heading("Apperance: ");
-                        ui.horizontal(|ui| {
-                            ui.label("Pixel size: ");
-                            ui.add(egui::DragValue::new(&mut self.pixel_size)).changed().then(|| {
-                                    let width = self.pixel_size * self.cpu.width() as f32;
-                                    ctx.gfx.set_drawable_size(width, width / 2.0 + self.menu_bar_height).unwrap();
-                            });
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Background: ");
+        if self.config.crowd_play {
+            match CrowdServer::start(self.config.crowd_play_port, self.config.crowd_queue_length, self.config.crowd_play_public) {
+                Ok(crowd) => self.crowd = Some(crowd),
+                Err(e) => show_error(
+                    "Crowd play unavailable",
+                    format!("Could not listen on port {}: {}\n\nPick another port in the configuration.", self.config.crowd_play_port, e),
+                ),
+            }
+        }
+    }
 
-                            let colour = self.pixel_off_colour;
-                            let mut colour = [colour.r, colour.g, colour.b];
-                            widgets::color_picker::color_edit_button_rgb(ui, &mut colour);
-                            self.pixel_off_colour = Color::new(colour[0], colour[1], colour[2], 100.0);
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Foreground: ");
+    fn restart_display_bridge(&mut self) {
+        self.display_bridge = None;
 
-                            let colour = self.pixel_on_colour;
-                            let mut colour = [colour.r, colour.g, colour.b];
-                            widgets::color_picker::color_edit_button_rgb(ui, &mut colour);
-                            self.pixel_on_colour = Color::new(colour[0], colour[1], colour[2], 100.0);
-                        });
-                        if ui.button("Reset apperance to default").clicked() {
-                            self.pixel_off_colour = DEFAULT_OFF_COLOUR;
-                            self.pixel_on_colour = DEFAULT_ON_COLOUR;
+        if self.config.display_bridge {
+            match DisplayBridge::start(&self.config.display_bridge_address) {
+                Ok(bridge) => self.display_bridge = Some(bridge),
+                Err(e) => show_error(
+                    "Display bridge unavailable",
+                    format!("Could not send to {}: {}\n\nCheck the address in the configuration.", self.config.display_bridge_address, e),
+                ),
+            }
+        }
+    }
 
-                            let width = DEFAULT_PIXEL_SIZE * self.cpu.width() as f32;
-                            ctx.gfx.set_drawable_size(width, width / 2.0 + self.menu_bar_height).unwrap();
-                        }
-                        ui.separator();
+    fn restart_ambient_lights(&mut self) {
+        self.ambient_lights = None;
 
-                        ui.heading("Quirks: ");
-                        ui.horizontal(|ui| {
-                            ui.label("VF reset on all 8XYO opcodes: ");
-                            ui.checkbox(&mut self.cpu.quirks.vf_reset, "");
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Shifting opcodes operate on: ");
-                            ui.selectable_value(&mut self.cpu.quirks.shifting, ShiftingReg::VX, "vX");
-                            ui.selectable_value(&mut self.cpu.quirks.shifting, ShiftingReg::VY, "vY");
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Register save/load opcode behaviour: ");
-                            ui.selectable_value(&mut self.cpu.quirks.reg_save_load, RegSaveLoadQuirk::Unchanged, "Do not modify I");
-                            ui.selectable_value(&mut self.cpu.quirks.reg_save_load, RegSaveLoadQuirk::X, "I = I + X");
-                            ui.selectable_value(&mut self.cpu.quirks.reg_save_load, RegSaveLoadQuirk::XPlusOne, "I = I + X + 1");
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Jump opcode behaviour: ");
-                            ui.selectable_value(&mut self.cpu.quirks.jump, JumpBehviour::BNNN, "BNNN");
-                            ui.selectable_value(&mut self.cpu.quirks.jump, JumpBehviour::BXNN, "BXNN");
-                        });
-                        ui.horizontal(|ui| {
-                            ui.label("Sprites wrap at edges of screen: ");
-                            ui.checkbox(&mut self.cpu.quirks.screen_wrap, "");
-                        });
-                        if ui.button("Reset quirks to default").clicked() {
-                            self.cpu.quirks = Quirks::default();
-                        }
-                        ui.separator();
+        if self.config.ambient.enabled {
+            match WledLights::start(&self.config.ambient) {
+                Ok(lights) => self.ambient_lights = Some(Box::new(lights)),
+                Err(e) => show_error(
+                    "Ambient lights unavailable",
+                    format!("Could not send to {}: {}\n\nCheck the address in the configuration.", self.config.ambient.address, e),
+                ),
+            }
+        }
+    }
 
-                        if ui.button("Reset all to default").clicked() {
-                            self.cpu.quirks = Quirks::default();
-                            self.cycles_per_frame = DEFAULT_CYCLES_PER_FRAME;
+    fn restart_midi(&mut self) {
+        self.midi = None; // disconnects from the old device
+        self.release_keys(self.midi_keys);
+        self.midi_keys = 0;
 
-                            let width = DEFAULT_PIXEL_SIZE * self.cpu.width() as f32;
-                            ctx.gfx.set_drawable_size(width, width / 2.0 + self.menu_bar_height).unwrap();
+        if self.config.midi.enabled {
+            match MidiKeypad::connect(self.config.midi.port.as_deref()) {
+                Ok(midi) => self.midi = Some(midi),
+                Err(e) => show_error("MIDI input unavailable", format!("Could not connect to the MIDI device: {}", e)),
+            }
+        }
+    }
 
-                            self.pixel_off_colour = DEFAULT_OFF_COLOUR;
-                            self.pixel_on_colour = DEFAULT_ON_COLOUR;
-                        }
-                    });
-                }
-            });
-        }).response.rect.height();
+    fn handle_midi(&mut self) {
+        let Some(midi) = &self.midi else {
+            return;
+        };
+        let events: Vec<NoteEvent> = midi.events().collect();
 
-        self.gui.update(ctx);
-        self.menu_bar_height = height;
-        //ctx.gfx.set_drawable_size(SCREEN_SIZE.0, SCREEN_SIZE.1 as f32 + height)?; // make room for whole game
+        for event in events {
+            if let (true, Some(key)) = (event.down, self.midi_learn) {
+                self.config.midi.learn(event.note, key);
+                self.midi_learn = None;
+                continue;
+            }
 
-        Ok(())
+            let Some(key) = self.config.midi.key_for(event.note) else {
+                continue;
+            };
+            if event.down {
+                self.midi_keys |= 1 << key;
+            }
+            else {
+                self.midi_keys &= !(1 << key);
+                self.release_keys(1 << key);
+            }
+        }
     }
 
-    fn draw_gui(&mut self, canvas: &mut Canvas) {
-        canvas.draw(
-            &self.gui,
-            DrawParam::default().dest(Vec2::ZERO),
-        );
-    }
+    fn detach_debugger(&mut self) {
+        if let Some(Ok(None)) = self.debugger.as_mut().map(process::Child::try_wait) {
+            return; // it's still open
+        }
 
-    fn draw_pixel_grid(&mut self, _ctx: &mut Context, canvas: &mut Canvas) {
-        self.pixels_batch.clear();
+        // the debugger follows the emulator through the remote control API
+        if !self.config.remote_api {
+            self.config.remote_api = true;
+            self.restart_remote();
+        }
+        if self.remote.is_none() {
+            return; // restart_remote has already said why
+        }
 
-        for (col_i, row) in self.cpu.pixels.iter().enumerate() {
-            for (row_i, pixel) in row.iter().enumerate() {
-                if *pixel {
-                    self.pixels_batch.push(
-                        DrawParam::new().dest(Vec2::new(
-                            row_i as f32 * self.pixel_size + self.width_offset,
-                            col_i as f32 * self.pixel_size + self.height_offset + self.menu_bar_height,
-                        ))
-                        .color(self.pixel_on_colour),
-                    );
-                }
-                else {
-                    self.pixels_batch.push(
-                        DrawParam::new()
-                        .dest(Vec2::new(
-                            row_i as f32 * self.pixel_size + self.width_offset,
-                            col_i as f32 * self.pixel_size + self.height_offset + self.menu_bar_height,
-                        ))
-                        .color(self.pixel_off_colour),
-                    );
-                }
-            }
+        let spawned = env::current_exe().and_then(|exe| {
+            process::Command::new(exe).arg("--debugger").arg(self.config.remote_api_port.to_string()).spawn()
+        });
+        match spawned {
+            Ok(debugger) => self.debugger = Some(debugger),
+            Err(e) => show_error("Could not open the debugger", e.to_string()),
         }
+    }
 
-        canvas.draw(&self.pixels_batch, DrawParam::new());
+    fn load_plugin_scripts(&mut self, ctx: &Context) {
+        self.plugins.unload_scripts();
+        let errors = self.plugins.load_scripts(&ctx.fs.user_data_dir().join(PLUGIN_DIR));
+        if !errors.is_empty() {
+            show_error("Could not load plugins", errors.join("\n"));
+        }
     }
-}
 
-impl EventHandler for EmulatorIO {
-    fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> GameResult {
-        let key = self.key_for_keycode(input.keycode.as_ref());
+    fn handle_remote_requests(&mut self, ctx: &mut Context) -> GameResult {
+        let Some(remote) = &self.remote else {
+            return Ok(());
+        };
+        let requests: Vec<_> = remote.requests().collect();
+
+        for request in requests {
+            let result = match &request.command {
+                Command::LoadRom { path: Some(path), .. } => read_rom(path::Path::new(path)).map(|(rom, metadata)| {
+                    let file_name = path::Path::new(path).file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                    self.start_rom(ctx, rom, &file_name);
+                    if let Some(metadata) = metadata {
+                        self.apply_metadata(&metadata);
+                    }
+                    json!({})
+                }),
+                Command::LoadRom { rom: Some(rom), .. } if rom.len() > cpu::MAX_ROM_SIZE => {
+                    Err(format!("ROMs can be at most {} bytes but this one is {} bytes", cpu::MAX_ROM_SIZE, rom.len()))
+                },
+                Command::LoadRom { rom: Some(rom), .. } => {
+                    self.start_rom(ctx, rom.clone(), "Remote ROM");
+                    Ok(json!({}))
+                },
+                Command::LoadRom { .. } => Err(String::from("load_rom needs either a path or a rom")),
+                Command::Pause => {
+                    self.paused = true;
+                    Ok(json!({}))
+                },
+                Command::Resume => {
+                    self.paused = false;
+                    Ok(json!({}))
+                },
+                Command::Step { count } => {
+                    for _ in 0..*count {
+                        self.step_instruction(ctx, self.remote_keys)?;
+                    }
+                    Ok(json!({ "pc": self.cpu.pc(), "steps": count }))
+                },
+                Command::SetKeys { keys } => {
+                    self.release_keys(self.remote_keys & !keys);
+                    self.remote_keys = *keys;
+                    Ok(json!({}))
+                },
+                Command::GetFramebuffer => {
+                    let rows: Vec<String> = (0..self.cpu.height())
+                        .map(|y| (0..self.cpu.width()).map(|x| if self.cpu.pixel(x, y) { '1' } else { '0' }).collect())
+                        .collect();
+                    Ok(json!({ "width": self.cpu.width(), "height": self.cpu.height(), "rows": rows, "hash": format!("{:016x}", self.cpu.display_hash()) }))
+                },
+                Command::GetState => Ok(json!({
+                    "pc": self.cpu.pc(),
+                    "i": self.cpu.addr_reg(),
+                    "registers": self.cpu.registers(),
+                    "stack": self.cpu.stack(),
+                    "delay_timer": self.cpu.delay_timer(),
+                    "sound_timer": self.cpu.sound_timer(),
+                    "paused": self.paused,
+                    "halted": self.cpu.halted(),
+                    "error": self.cpu.error().map(|error| error.to_string()),
+                    "display_hash": format!("{:016x}", self.cpu.display_hash()),
+                })),
+                Command::GetMemory => Ok(json!({ "memory": self.cpu.memory() })),
+            };
 
-        if let Some(key) = key  {
-            self.cpu.key_released(key);
+            request.reply(result);
         }
 
         Ok(())
     }
 
-    fn update(&mut self, ctx: &mut Context) -> GameResult {
-        self.update_cpu(ctx)?;
-        self.update_gui(ctx)?;
+    fn tick_swipe(&mut self) -> u16 {
+        match self.swipe_press {
+            Some((key, 0)) => {
+                self.swipe_press = None;
+                self.release_keys(1 << key);
+                0
+            },
+            Some((key, ticks_left)) => {
+                self.swipe_press = Some((key, ticks_left - 1));
+                1 << key
+            },
+            None => 0,
+        }
+    }
 
-        if ctx.time.ticks() % 100 == 0 {
-            println!("Delta frame time: {:?} ", ctx.time.delta());
-            println!("Average FPS: {}", ctx.time.fps());
+    fn handle_hotkeys(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some(action) = self.binding_hotkey {
+            if let Some(combo) = KeyCombo::just_pressed(&ctx.keyboard) {
+                *self.config.hotkeys.binding_mut(action) = combo;
+                self.binding_hotkey = None;
+            }
+            return Ok(());
+        }
+
+        let typing = self.gui.ctx().wants_keyboard_input();
+        // the other player couldn't keep up
+        self.fast_forward = !typing && self.netplay.is_none() && self.config.hotkeys.fast_forward.is_pressed(&ctx.keyboard);
+        if typing {
+            return Ok(());
+        }
+
+        for action in self.config.hotkeys.just_pressed(&ctx.keyboard) {
+            match action {
+                HotkeyAction::Pause => self.paused = !self.paused,
+                HotkeyAction::Reset => {
+                    self.remember_for_undo("Restart");
+                    self.start_transition(self.config.transitions.on_reset);
+                    self.restart_rom();
+                    self.run_timer.reset(); // restarting does this too but there may be no ROM to restart
+                },
+                HotkeyAction::SaveState => {
+                    if let Some(rom_id) = &self.rom_id {
+                        let data_dir = ctx.fs.user_data_dir();
+                        let result = saves::import_loose_quick_save(data_dir, rom_id)
+                            .and_then(|()| saves::save(&saves::archive_path(data_dir, rom_id), QUICK_SAVE, &self.cpu));
+                        if let Err(e) = result {
+                            show_error("Could not save the state", e);
+                        }
+                    }
+                },
+                HotkeyAction::LoadState => {
+                    if let Some(rom_id) = &self.rom_id {
+                        let data_dir = ctx.fs.user_data_dir();
+                        if let Err(e) = saves::import_loose_quick_save(data_dir, rom_id) {
+                            show_error("Could not load the state", e);
+                        }
+                        let path = saves::archive_path(data_dir, rom_id);
+                        if saves::has(&path, QUICK_SAVE) {
+                            match saves::load(&path, QUICK_SAVE) {
+                                Ok(saved) => self.restore_saved(ctx, saved),
+                                Err(e) => show_error("Could not load the state", e),
+                            }
+                        }
+                    }
+                },
+                HotkeyAction::Screenshot => report_error("Could not save a screenshot", self.save_screenshot(ctx)),
+                HotkeyAction::Fullscreen => {
+                    self.fullscreen = !self.fullscreen;
+                    ctx.gfx.set_fullscreen(if self.fullscreen { FullscreenType::Desktop } else { FullscreenType::Windowed })?;
+                },
+                HotkeyAction::HideMenuBar => self.menu_bar_hidden = !self.menu_bar_hidden,
+                HotkeyAction::Step => {
+                    self.paused = true;
+                    let pressed_keys = self.config.keyboard.pressed_keys(self.control_scheme(), &ctx.keyboard);
+                    self.step_instruction(ctx, pressed_keys)?;
+                },
+                HotkeyAction::Split => {
+                    if self.config.speedrun.overlay {
+                        self.run_timer.split();
+                    }
+                },
+                HotkeyAction::FastForward => {},
+            }
         }
 
         Ok(())
     }
-    
-    fn draw(&mut self, ctx: &mut Context) -> GameResult {
-        let mut canvas = Canvas::from_frame(ctx, Color::BLACK);
-        
-        self.draw_pixel_grid(ctx, &mut canvas);
-        self.draw_gui(&mut canvas);
 
-        canvas.finish(ctx)
+    fn load_state_file(&mut self, ctx: &mut Context, path: &path::Path) {
+        match savestate::load(path) {
+            Ok(saved) => self.restore_saved(ctx, saved),
+            Err(e) => show_error("Could not load the state", e),
+        }
     }
 
-    fn text_input_event( &mut self, _ctx: &mut ggez::Context, character: char) -> GameResult {
-		self.gui.input.text_input_event(character);
-		Ok(())
-	}
+    fn restore_saved(&mut self, ctx: &mut Context, saved: SavedState) {
+        // the program might not carry on the same way under other quirks, older states don't say what they were
+        if let Some(header) = saved.header {
+            self.cpu.quirks = header.quirks;
+        }
+        self.restore_state(ctx, &saved.state);
+    }
 
-    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) -> Result<(), ggez::GameError> {
-        if width == 0.0 || height == 0.0 {
-            return Ok(());
+    // the slots are the current ROM's, so they're read again when it changes if they're on show
+    fn rom_changed(&mut self, ctx: &Context) {
+        match self.saves_window_open {
+            true => self.refresh_save_slots(ctx),
+            false => self.save_slots.clear(),
         }
+    }
 
-        self.pixel_size = (width / self.cpu.width() as f32).min(height / self.cpu.height() as f32).floor(); // allow resizing from both directions without part of the screen being cut off
+    fn refresh_save_slots(&mut self, ctx: &Context) {
+        self.save_slots.clear();
+        let Some(rom_id) = &self.rom_id else {
+            return;
+        };
 
-        let pixel_rect = Image::from_color(
-            &ctx.gfx,
-            self.pixel_size as u32,
-            self.pixel_size as u32,
-            None,
-        );
-        self.pixels_batch = InstanceArray::new(&ctx.gfx, pixel_rect);
-        self.gui.input.resize_event(width, height);
+        let data_dir = ctx.fs.user_data_dir();
+        match saves::import_loose_quick_save(data_dir, rom_id).and_then(|()| saves::slots(&saves::archive_path(data_dir, rom_id))) {
+            Ok(slots) => self.save_slots = slots,
+            Err(e) => show_error("Could not read the saves", e),
+        }
+    }
 
-        self.height_offset = ((height - self.menu_bar_height) - self.pixel_size * self.cpu.height() as f32) / 2.0;
-        self.width_offset = (width - self.pixel_size * self.cpu.width() as f32) / 2.0;
+    fn restore_state(&mut self, ctx: &mut Context, state: &cpu::SaveState) {
+        match self.cpu.load_state(state) {
+            Ok(()) => {
+                self.display_dirty = true;
+                self.error_reported = self.cpu.error().is_some();
+                self.restart_history();
+                report_error("Could not resize the screen", self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1));
+            },
+            Err(e) => show_error("Could not load the state", e),
+        }
+    }
+
+    // saves the screen as it was last drawn, scaled up
+    fn save_screenshot(&mut self, ctx: &mut Context) -> GameResult {
+        let (width, height) = (self.cpu.width(), self.cpu.height());
+        if self.screen_pixels.len() != width * height * 4 {
+            return Ok(()); // the resolution changed and it hasn't been drawn yet
+        }
+
+        let mut pixels = Vec::with_capacity(self.screen_pixels.len() * SCREENSHOT_SCALE * SCREENSHOT_SCALE);
+        for y in 0..height * SCREENSHOT_SCALE {
+            let row = &self.screen_pixels[y / SCREENSHOT_SCALE * width * 4..][..width * 4];
+            for x in 0..width * SCREENSHOT_SCALE {
+                pixels.extend_from_slice(&row[x / SCREENSHOT_SCALE * 4..][..4]);
+            }
+        }
+
+        let image = Image::from_pixels(&ctx.gfx, &pixels, ImageFormat::Rgba8UnormSrgb, (width * SCREENSHOT_SCALE) as u32, (height * SCREENSHOT_SCALE) as u32);
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis()).unwrap_or_default();
+
+        let file_name = format!("{}.png", time);
+
+        ctx.fs.create_dir(SCREENSHOT_DIR)?;
+        image.encode(ctx, ImageEncodingFormat::Png, format!("{}/{}", SCREENSHOT_DIR, file_name))?;
+
+        self.gallery.add(&self.gui.ctx(), &self.screen_pixels, &self.cpu, file_name, ctx.time.time_since_start(), self.rom_id.clone());
 
         Ok(())
     }
-}
 
-pub fn emulator_main() {
-    let resource_dir = if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
-        let mut path = path::PathBuf::from(manifest_dir);
-        path.push("resources");
-        path
-    } else {
-        path::PathBuf::from("./resources")
-    };
+    fn control_scheme(&self) -> Option<&ControlScheme> {
+        self.config.control_schemes.get(self.rom_id.as_ref()?)
+    }
 
-    let (mut ctx, event_loop) = ContextBuilder::new("fish_n_chip8", "jenningsfan")
-        .window_setup(WindowSetup::default().title("Fish n CHIP-8"))
-        .window_mode(ggez::conf::WindowMode::default()
-            .dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1)
-            .resizable(true)
-        )
-        .add_resource_path(resource_dir)
-        .build()
-        .expect("Failed to create game context");
+    // the keypad key a keyboard key is bound to, if any
+    fn keypad_key(&self, input: KeyInput) -> Option<u8> {
+        let keyboard = &self.config.keyboard;
+        keyboard.key_for_input(self.control_scheme(), input.keycode, input.scancode).or_else(|| {
+            keyboard.second_keypad_key(input.keycode).filter(|_| keyboard.share_second_keypad)
+        })
+    }
 
-    let game = EmulatorIO::new(&mut ctx);
+    fn is_idle(&self, ctx: &Context) -> bool {
+        // a halted program is left running until its sound timer has run out so the last beep isn't cut off
+        self.paused || (self.cpu.halted() && !self.beeping) || ctx.gfx.window().is_minimized() == Some(true)
+    }
 
-    event::run(ctx, event_loop, game);
+    fn tick_cpu(&mut self, ctx: &mut Context, pressed_keys: u16, second_keypad: u16) -> GameResult {
+        if let Some(history) = &mut self.history {
+            history.record(self.timeline_frame, &self.cpu, FrameInput { keys: pressed_keys, second_keypad, ..Default::default() });
+            self.timeline_frame += 1;
+        }
+
+        self.cpu.set_keys(pressed_keys);
+        self.cpu.set_second_keypad(second_keypad);
+        self.beeping = self.cpu.timer_tick();
+        self.draw_log.next_frame();
+        self.call_tree.next_frame(&self.cpu);
+        self.run_timer.tick(pressed_keys);
+        if let Some(cheats) = self.rom_id.as_ref().and_then(|rom_id| self.config.cheats.get(rom_id)) {
+            cheats::apply_frozen(cheats, &mut self.cpu);
+        }
+
+        // taken out of the config for the tick so it's only looked up once, then put back whatever happened
+        let rom_id = self.rom_id.clone();
+        let mut set = rom_id.as_ref().and_then(|rom_id| self.config.breakpoints.get_mut(rom_id)).map(std::mem::take).unwrap_or_default();
+        let cycles = self.run_cycles(ctx, &mut set);
+        if let Some(breakpoints) = rom_id.as_ref().and_then(|rom_id| self.config.breakpoints.get_mut(rom_id)) {
+            *breakpoints = set;
+        }
+        let cycles = cycles?;
+
+        if let Some(history) = &mut self.history {
+            history.ran(cycles);
+        }
+
+        self.run_plugins();
+        if let Some(bridge) = &mut self.display_bridge {
+            if let Err(e) = bridge.send(&self.cpu) {
+                show_error("Display bridge stopped", format!("Could not send the screen to {}: {}", self.config.display_bridge_address, e));
+                self.display_bridge = None;
+            }
+        }
+
+        let (on_colour, off_colour) = self.screen_colours();
+        if self.ambient_lights.is_some() || !self.built_in_lights.is_empty() {
+            let colours = AmbientColours::from_screen(&self.cpu, light_colour(on_colour), light_colour(off_colour));
+            if let Some(lights) = &mut self.ambient_lights {
+                if let Err(e) = lights.send(&colours) {
+                    show_error("Ambient lights stopped", format!("Could not send the colours to {}: {}", self.config.ambient.address, e));
+                    self.ambient_lights = None;
+                }
+            }
+            self.built_in_lights.retain_mut(|lights| match lights.send(&colours) {
+                Ok(()) => true,
+                Err(e) => {
+                    show_error("Ambient lights stopped", format!("Could not send the colours: {}", e));
+                    false
+                },
+            });
+        }
+        if let Some(recording) = &mut self.recording {
+            if let Err(e) = recording.frame(&self.cpu, on_colour, off_colour, self.beeping) {
+                show_error("Recording stopped", e);
+                self.recording = None;
+            }
+        }
+
+        if let (Some(_), false) = (self.cpu.error(), self.error_reported) {
+            self.error_reported = true;
+            self.halt_window_open = true;
+        }
+
+        Ok(())
+    }
+
+    // the plugins get the CPU after each frame. What they change can't be replayed from the history's input, so it
+    // has to keep a copy of the CPU from after them. In netplay they only get a copy, as the other player's plugins
+    // wouldn't make the same changes
+    fn run_plugins(&mut self) {
+        if self.plugins.is_empty() {
+            return;
+        }
+        if self.netplay.is_some() {
+            self.plugins.frame(&mut self.cpu.clone());
+            return;
+        }
+
+        let state = |cpu: &CPU| (cpu.memory().to_vec(), *cpu.registers(), cpu.addr_reg(), cpu.pc());
+        let before = self.history.is_some().then(|| state(&self.cpu));
+        self.plugins.frame(&mut self.cpu);
+        if let (Some(history), Some(before)) = (&mut self.history, before) {
+            if before != state(&self.cpu) {
+                history.changed();
+            }
+        }
+    }
+
+    // the instructions for one tick, stopping early at a breakpoint. Returns how many ran
+    fn run_cycles(&mut self, ctx: &mut Context, set: &mut [Breakpoint]) -> GameResult<u32> {
+        // breakpoints are checked after each instruction so resuming doesn't stop again straight away, which would miss
+        // one on the very first instruction
+        if std::mem::take(&mut self.at_entry) && !set.is_empty() && self.check_breakpoints(set, &[]) {
+            return Ok(0);
+        }
+
+        let mut cycles = 0;
+        for _ in 0..self.cycles_this_tick() {
+            let watched = if set.is_empty() { vec![] } else { breakpoints::watched_values(set, &self.cpu) };
+            let draw = self.draw_log.pending(&self.cpu);
+            let call = self.call_tree.pending(&self.cpu);
+            let code_write = self.code_trap.as_ref().and_then(|map| breakpoints::code_write(map, &self.cpu));
+            let pc = self.cpu.pc();
+            self.coverage.record(pc);
+
+            let effects = self.cpu.handle_opcode();
+            cycles += 1;
+            self.draw_log.finish(draw, &self.cpu);
+            self.call_tree.finish(call, &self.cpu);
+
+            self.display_dirty |= effects.display_changed;
+            if effects.screen_cleared {
+                self.start_transition(self.config.transitions.on_clear);
+            }
+            if effects.resolution_changed {
+                self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)?;
+            }
+
+            if let (true, Some(watch)) = (effects.display_watch_hit, self.cpu.display_watch) {
+                self.paused = true;
+                self.breakpoint_hit = Some(format!("{} by the instruction at {:03X}", watch, pc));
+                self.breakpoints_window_open = true;
+                if watch == DisplayWatch::AnyUpdate {
+                    self.cpu.display_watch = None; // it's only for the next one
+                }
+                break;
+            }
+
+            if let Some(addr) = code_write {
+                self.code_written(addr, pc);
+                break;
+            }
+
+            if !set.is_empty() && self.check_breakpoints(set, &watched) {
+                break;
+            }
+        }
+
+        Ok(cycles)
+    }
+
+    fn code_written(&mut self, addr: u16, pc: u16) {
+        self.paused = true;
+        self.breakpoint_hit = Some(format!("Write to the code at {:03X} by the instruction at {:03X}", addr, pc));
+        self.breakpoints_window_open = true;
+    }
+
+    // the code map is of the ROM that's loaded, so it's made again whenever that changes
+    fn rebuild_code_trap(&mut self) {
+        if self.code_trap.is_some() {
+            self.code_trap = Some(CodeMap::analyse(self.last_loaded_rom.as_deref().unwrap_or_default()));
+        }
+    }
+
+    // pauses if any of the breakpoints have triggered, given the watched bytes from before the instruction
+    fn check_breakpoints(&mut self, set: &mut [Breakpoint], before: &[u8]) -> bool {
+        let Some(hit) = breakpoints::check(set, &self.cpu, before) else {
+            return false;
+        };
+
+        self.paused = true;
+        self.breakpoint_hit = Some(set[hit].to_string());
+        self.breakpoints_window_open = true;
+        true
+    }
+
+    // runs a single instruction without ticking the timers, for stepping through a program
+    // the changes come from running the instruction on a copy, so they match the current quirks and keys
+    fn preview_step(&self, pressed_keys: u16) -> Result<StateDiff, String> {
+        let before = self.cpu.save_state();
+        let mut next = CPU::new();
+        next.quirks = self.cpu.quirks;
+        next.load_state(&before)?;
+        next.set_keys(pressed_keys);
+        next.handle_opcode();
+
+        Ok(savestate::diff(&before, &next.save_state()))
+    }
+
+    fn step_instruction(&mut self, ctx: &mut Context, pressed_keys: u16) -> GameResult {
+        let draw = self.draw_log.pending(&self.cpu);
+        let call = self.call_tree.pending(&self.cpu);
+        let code_write = self.code_trap.as_ref().and_then(|map| breakpoints::code_write(map, &self.cpu));
+        let pc = self.cpu.pc();
+        self.at_entry = false;
+        self.cpu.set_keys(pressed_keys);
+        self.coverage.record(pc);
+        let effects = self.cpu.handle_opcode();
+        self.draw_log.finish(draw, &self.cpu);
+        self.call_tree.finish(call, &self.cpu);
+        self.restart_history(); // a lone instruction isn't a frame so it can't be replayed
+        if let Some(addr) = code_write {
+            self.code_written(addr, pc);
+        }
+
+        self.display_dirty |= effects.display_changed;
+        if effects.resolution_changed {
+            self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)?;
+        }
+
+        Ok(())
+    }
+
+    // starts the timeline again from now, for when the CPU jumps to a state the recorded frames don't lead to
+    fn restart_history(&mut self) {
+        if self.history.is_some() {
+            self.history = Some(History::new());
+            self.timeline_frame = 0;
+        }
+    }
+
+    // goes back or forward to a recorded frame by replaying from the snapshot before it
+    fn seek_history(&mut self, ctx: &mut Context, frame: u32) -> GameResult {
+        let Some(history) = &self.history else {
+            return Ok(());
+        };
+
+        let cheats = self.rom_id.as_ref().and_then(|rom_id| self.config.cheats.get(rom_id));
+        let sought = history.seek(frame, |cpu, input| {
+            cpu.timer_tick();
+            if let Some(cheats) = cheats {
+                cheats::apply_frozen(cheats, cpu);
+            }
+            for _ in 0..input.cycles {
+                cpu.handle_opcode();
+            }
+        });
+
+        if let Some(cpu) = sought {
+            self.cpu = cpu;
+            self.timeline_frame = frame;
+            self.paused = true; // otherwise it would record over what comes after straight away
+            self.beeping = false;
+            self.display_dirty = true;
+            self.error_reported = self.cpu.error().is_some();
+            self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)?;
+        }
+
+        Ok(())
+    }
+
+    fn toggle_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            if let Err(e) = recording.finish() {
+                show_error("Could not save the video", e);
+            }
+            return;
+        }
+
+        let Some(path) = rfd::FileDialog::new().add_filter("Videos", &VIDEO_EXTENSIONS).save_file() else {
+            return;
+        };
+        // MP4 unless they picked WebM
+        let path = match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if VIDEO_EXTENSIONS.contains(&extension.to_lowercase().as_str()) => path,
+            _ => path.with_extension(VIDEO_EXTENSIONS[0]),
+        };
+
+        match Recording::start(&path) {
+            Ok(recording) => self.recording = Some(recording),
+            Err(e) => show_error("Could not start recording", e),
+        }
+    }
+
+    // the off colour's alpha is how much of it is drawn
+    fn screen_colours(&self) -> (Color, Color) {
+        if self.config.accessibility.high_contrast {
+            return (Color::WHITE, Color::BLACK);
+        }
+
+        let alpha = match self.config.off_pixels {
+            OffPixels::Coloured => 1.0,
+            OffPixels::Translucent => self.config.off_pixel_alpha.clamp(0.0, 1.0),
+            OffPixels::Hidden => 0.0,
+        };
+        (self.config.pixel_on_colour, Color { a: alpha, ..self.config.pixel_off_colour })
+    }
+
+    fn set_shader(&mut self, ctx: &Context, shader: Option<ScreenShader>) {
+        self.post_shader = shader.as_ref().and_then(|shader| {
+            PostShader::load(ctx, &ctx.fs.user_data_dir().join(SHADER_DIR), shader, self.config.led_matrix)
+                .map_err(|e| show_error("Could not load the shader", e))
+                .ok()
+        });
+        self.config.shader = shader.filter(|_| self.post_shader.is_some());
+    }
+
+    fn cycles_this_tick(&mut self) -> u32 {
+        self.config.cycles_per_tick(&mut self.cycle_budget)
+    }
+
+    fn update_background_tabs(&mut self, ctx: &Context) {
+        if !self.config.background_tabs || self.tabs.len() == 1 {
+            self.background_accumulator = Duration::ZERO;
+            return;
+        }
+
+        let tick_length = Duration::from_secs_f64(1.0 / TIMER_HZ);
+        self.background_accumulator += ctx.time.delta();
+
+        let mut ticks = 0;
+        while self.background_accumulator >= tick_length && ticks < MAX_TICKS_PER_UPDATE {
+            self.background_accumulator -= tick_length;
+            ticks += 1;
+
+            for (i, tab) in self.tabs.iter_mut().enumerate() {
+                if i != self.active_tab {
+                    let cycles = self.config.cycles_per_tick(&mut tab.cycle_budget);
+                    tab.tick(cycles);
+                }
+            }
+        }
+        if ticks == MAX_TICKS_PER_UPDATE {
+            self.background_accumulator = Duration::ZERO;
+        }
+    }
+
+    // exchanges the focused tab's state with what's stored for a tab
+    fn swap_tab(&mut self, index: usize) {
+        let tab = &mut self.tabs[index];
+        mem::swap(&mut self.cpu, &mut tab.cpu);
+        mem::swap(&mut self.paused, &mut tab.paused);
+        mem::swap(&mut self.cycle_budget, &mut tab.cycle_budget);
+        mem::swap(&mut self.error_reported, &mut tab.error_reported);
+        mem::swap(&mut self.last_loaded_rom, &mut tab.last_loaded_rom);
+        mem::swap(&mut self.rom_path, &mut tab.rom_path);
+        mem::swap(&mut self.rom_id, &mut tab.rom_id);
+        mem::swap(&mut self.run_timer, &mut tab.run_timer);
+        mem::swap(&mut self.memory_search, &mut tab.memory_search);
+        mem::swap(&mut self.watches, &mut tab.watches);
+        mem::swap(&mut self.coverage, &mut tab.coverage);
+        mem::swap(&mut self.undo, &mut tab.undo);
+    }
+
+    fn switch_tab(&mut self, ctx: &mut Context, index: usize) -> GameResult {
+        if index == self.active_tab {
+            return Ok(());
+        }
+
+        // the keys held down are going to a different game now. Only those are let go of, as a key let go of that
+        // wasn't held would answer an FX0A
+        self.release_keys(self.cpu.keys());
+        self.cpu.set_second_keypad(0);
+        self.input_queue.clear();
+        self.swap_tab(self.active_tab);
+        self.swap_tab(index);
+        self.active_tab = index;
+
+        self.netplay = None; // the other player is still on the old tab
+        self.tick_accumulator = Duration::ZERO;
+        self.state_diff = None;
+        self.rom_properties = None;
+        self.beeping = false;
+        self.display_dirty = true;
+        self.restart_history();
+        self.rom_changed(ctx);
+        self.rebuild_code_trap();
+
+        let title = match self.rom_id {
+            Some(_) => format!("{} - {}", self.tabs[index].name, WINDOW_TITLE),
+            None => String::from(WINDOW_TITLE),
+        };
+        ctx.gfx.set_window_title(&title);
+        self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)
+    }
+
+    fn new_tab(&mut self, ctx: &mut Context) -> GameResult {
+        let mut cpu = CPU::new();
+        cpu.quirks = self.config.quirks;
+        cpu.load_rom(SPLASH_ROM);
+
+        self.tabs.push(Tab::new(cpu));
+        self.switch_tab(ctx, self.tabs.len() - 1)
+    }
+
+    fn close_tab(&mut self, ctx: &mut Context, index: usize) -> GameResult {
+        if self.tabs.len() == 1 {
+            return Ok(());
+        }
+
+        if index == self.active_tab {
+            self.switch_tab(ctx, if index == 0 { 1 } else { index - 1 })?;
+        }
+        self.tabs.remove(index);
+        if self.active_tab > index {
+            self.active_tab -= 1;
+        }
+
+        Ok(())
+    }
+
+    fn restart_buzzer(&mut self, ctx: &Context) {
+        self.buzzer = None; // close the old stream before opening the device again
+        self.pattern_editor.stop_preview(); // it would carry on playing on the old device
+
+        match Buzzer::new(ctx, &self.config.audio) {
+            Ok(buzzer) => self.buzzer = Some(buzzer),
+            Err(e) => show_error(
+                "No sound",
+                format!("Could not open the audio output: {}\n\nPick another device or buffer size in the configuration. The emulator will keep running without sound.", e),
+            ),
+        }
+    }
+
+    fn reset_cpu(&mut self, rom: &[u8]) {
+        let quirks = self.cpu.quirks;
+
+        self.cpu = CPU::new();
+        self.cpu.load_rom(rom);
+        self.cpu.quirks = quirks;
+        if self.code_trap.is_some() {
+            self.code_trap = Some(CodeMap::analyse(rom));
+        }
+        self.coverage = Coverage::default();
+        self.display_dirty = true;
+        self.paused = false;
+        self.error_reported = false;
+        self.breakpoint_hit = None;
+        self.at_entry = true;
+        self.run_timer.reset();
+        self.restart_history();
+    }
+
+    // what would be lost or affected by loading another ROM or quitting
+    fn unsaved_work(&self) -> Vec<&'static str> {
+        let mut work = vec![];
+        if self.recording.is_some() {
+            work.push("A video is being recorded.");
+        }
+        if self.history.as_ref().is_some_and(|history| history.frames().end() > history.frames().start()) {
+            work.push("The timeline's rewind history will be cleared.");
+        }
+        work
+    }
+
+    // whether to go ahead with action, asking first if there's anything it would lose
+    fn confirm_discard(&mut self, action: &str) -> bool {
+        let work = self.unsaved_work();
+        if !self.config.confirm_discard || work.is_empty() {
+            return true;
+        }
+
+        let result = rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title(action)
+            .set_description(format!("{}\n\n{} anyway?", work.join("\n"), action))
+            .set_buttons(rfd::MessageButtons::YesNoCancelCustom(action.to_string(), String::from(DONT_ASK_AGAIN), String::from("Cancel")))
+            .show();
+
+        // some platforms say which button was pressed by its text, others by its position
+        match result {
+            rfd::MessageDialogResult::Custom(button) if button == DONT_ASK_AGAIN => {
+                self.config.confirm_discard = false;
+                true
+            },
+            rfd::MessageDialogResult::No => {
+                self.config.confirm_discard = false;
+                true
+            },
+            rfd::MessageDialogResult::Custom(button) => button == action,
+            rfd::MessageDialogResult::Yes | rfd::MessageDialogResult::Ok => true,
+            rfd::MessageDialogResult::Cancel => false,
+        }
+    }
+
+    // keeps what's about to be thrown away so Edit > Undo can bring it back
+    // the program, the ROM and the tab's name, with none of the settings
+    fn rom_snapshot(&self) -> RomSnapshot {
+        RomSnapshot {
+            cpu: self.cpu.clone(),
+            settings: None,
+            last_loaded_rom: self.last_loaded_rom.clone(),
+            rom_path: self.rom_path.clone(),
+            rom_id: self.rom_id.clone(),
+            tab_name: self.tabs[self.active_tab].name.clone(),
+        }
+    }
+
+    fn remember_for_undo(&mut self, action: &'static str) {
+        let snapshot = self.rom_snapshot();
+        self.undo.push(action, UndoSnapshot::Rom(Box::new(snapshot)));
+    }
+
+    // loads a ROM, only adding to the undo list once one has actually replaced what was running
+    fn load_with_undo(&mut self, ctx: &mut Context, load: impl FnOnce(&mut EmulatorIO, &mut Context) -> Result<(), String>) {
+        let settings = SharedSettings::from_config(&self.config, self.cpu.quirks, self.rom_id.as_ref());
+        let snapshot = RomSnapshot { settings: Some(settings), ..self.rom_snapshot() };
+        let choosing = self.archive_choice.is_some();
+
+        match load(self, ctx) {
+            Ok(()) if self.archive_choice.is_some() && !choosing => {}, // nothing's loaded until one is picked from the archive
+            Ok(()) => self.undo.push("Load ROM", UndoSnapshot::Rom(Box::new(snapshot))),
+            Err(e) => show_error("Could not load ROM", e),
+        }
+    }
+
+    fn undo(&mut self, ctx: &mut Context) {
+        match self.undo.pop() {
+            Some((_, UndoSnapshot::Rom(snapshot))) => self.undo_rom(ctx, *snapshot),
+            Some((_, UndoSnapshot::Settings(config))) => {
+                let old_config = mem::replace(&mut self.config, *config);
+                self.cpu.quirks = self.config.quirks;
+                self.gui_style_dirty = true;
+                self.display_dirty = true;
+                self.settings_replaced(ctx, &old_config);
+            },
+            None => {},
+        }
+    }
+
+    fn undo_rom(&mut self, ctx: &mut Context, snapshot: RomSnapshot) {
+        self.netplay = None; // the other player wouldn't go back too
+
+        self.last_loaded_rom = snapshot.last_loaded_rom;
+        self.rom_path = snapshot.rom_path;
+        self.rom_id = snapshot.rom_id;
+        self.tabs[self.active_tab].name = snapshot.tab_name;
+        match &self.rom_id {
+            Some(_) => ctx.gfx.set_window_title(format!("{} - {}", self.tabs[self.active_tab].name, WINDOW_TITLE).as_str()),
+            None => ctx.gfx.set_window_title(WINDOW_TITLE),
+        }
+
+        self.cpu = snapshot.cpu;
+        self.display_dirty = true;
+        self.error_reported = self.cpu.error().is_some();
+        self.breakpoint_hit = None;
+        self.restart_history();
+        self.rom_changed(ctx);
+        self.rebuild_code_trap();
+        if let Some(settings) = snapshot.settings {
+            settings.apply(&mut self.config, self.rom_id.as_ref());
+        }
+        report_error("Could not resize the screen", self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1));
+    }
+
+    // restarts whatever depends on the settings that changed when they were all replaced at once
+    fn settings_replaced(&mut self, ctx: &mut Context, old: &Config) {
+        if self.config.audio != old.audio {
+            self.restart_buzzer(ctx);
+        }
+        if (self.config.remote_api, self.config.remote_api_port) != (old.remote_api, old.remote_api_port) {
+            self.restart_remote();
+        }
+        let crowd = |config: &Config| (config.crowd_play, config.crowd_play_port, config.crowd_play_public, config.crowd_queue_length);
+        if crowd(&self.config) != crowd(old) {
+            self.restart_crowd();
+        }
+        // the mappings are looked up as notes arrive so changing them doesn't need a reconnect
+        if (self.config.midi.enabled, &self.config.midi.port) != (old.midi.enabled, &old.midi.port) {
+            self.restart_midi();
+        }
+        if (self.config.display_bridge, &self.config.display_bridge_address) != (old.display_bridge, &old.display_bridge_address) {
+            self.restart_display_bridge();
+        }
+        if self.config.ambient != old.ambient {
+            self.restart_ambient_lights();
+        }
+        if (&self.config.shader, self.config.led_matrix) != (&old.shader, old.led_matrix) {
+            self.set_shader(ctx, self.config.shader.clone());
+        }
+    }
+
+    fn eject_rom(&mut self, ctx: &mut Context) -> GameResult {
+        self.netplay = None;
+        self.netplay_host = None;
+        self.last_loaded_rom = None;
+        self.rom_path = None;
+        self.rom_id = None;
+        self.tabs[self.active_tab].name = String::from(NEW_TAB_NAME);
+        self.reset_cpu(SPLASH_ROM);
+        self.beeping = false;
+        if let Some(buzzer) = &mut self.buzzer {
+            buzzer.set_beeping(false);
+        }
+
+        self.rom_changed(ctx);
+
+        ctx.gfx.set_window_title(WINDOW_TITLE);
+        self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)
+    }
+
+    fn load_rom_file(&mut self, ctx: &mut Context, path: &path::Path) {
+        if let Err(e) = self.open_rom_file(ctx, path) {
+            show_error("Could not load ROM", e);
+        }
+    }
+
+    fn open_rom_file(&mut self, ctx: &mut Context, path: &path::Path) -> Result<(), String> {
+        if archive::is_archive(path) {
+            let roms = archive::roms(path)?;
+            match roms.len() {
+                0 => return Err(format!("There are no CHIP-8 ROMs in {}.", path.display())),
+                1 => return self.open_rom_file(ctx, &path.join(&roms[0])),
+                _ => self.archive_choice = Some((path.to_path_buf(), roms)),
+            }
+            return Ok(());
+        }
+
+        let (rom, metadata) = read_rom(path)?;
+        let file_name = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        self.start_rom(ctx, rom, &file_name);
+        if let Some(metadata) = metadata {
+            self.apply_metadata(&metadata);
+        }
+        self.rom_path = Some(path.to_path_buf());
+        self.add_to_library(&file_name);
+        Ok(())
+    }
+
+    fn start_rom(&mut self, ctx: &mut Context, rom: Vec<u8>, name: &str) {
+        ctx.gfx.set_window_title(format!("{} - {}", name, WINDOW_TITLE).as_str());
+
+        // the unpatched ROM is kept so patches can be turned on and off without loading it again
+        self.rom_id = Some(config::rom_id(&rom));
+        self.last_loaded_rom = Some(rom);
+        self.tabs[self.active_tab].name = name.to_string();
+        self.rom_path = None;
+        self.start_transition(self.config.transitions.on_load);
+        self.restart_rom();
+        self.rom_changed(ctx);
+
+        report_error("Could not resize the screen", self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1));
+    }
+
+    // the speed, quirks, colours and controls the ROM asks for, as if they'd been imported from a settings file
+    fn apply_metadata(&mut self, metadata: &RomMetadata) {
+        let mut settings = SharedSettings::from_config(&self.config, self.cpu.quirks, self.rom_id.as_ref());
+        metadata.apply(&mut settings);
+        settings.apply(&mut self.config, self.rom_id.as_ref());
+        self.cpu.quirks = settings.quirks;
+        self.display_dirty = true;
+    }
+
+    // remembers the ROM that was just started, with rom_path if it came from a file
+    fn add_to_library(&mut self, name: &str) {
+        let Some(rom_id) = &self.rom_id else {
+            return;
+        };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+        library::played(&mut self.config.library, rom_id, name, self.rom_path.clone(), now);
+    }
+
+    // the ROMs stay in the archive, the library remembers them by their path inside it
+    fn add_archive_to_library(&mut self, path: &path::Path) -> Result<(), String> {
+        let roms = archive::roms(path)?;
+        let mut unreadable = vec![];
+
+        for rom in roms {
+            let rom_path = path.join(&rom);
+            match read_rom(&rom_path) {
+                Ok((contents, _)) => {
+                    let name = rom_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or(rom);
+                    library::add(&mut self.config.library, &config::rom_id(&contents), &name, rom_path);
+                },
+                Err(e) => unreadable.push(e),
+            }
+        }
+
+        if !unreadable.is_empty() {
+            return Err(format!("Some of the ROMs were left out:\n\n{}", unreadable.join("\n")));
+        }
+        Ok(())
+    }
+
+    fn export_manifest(&self) {
+        let Some(path) = rfd::FileDialog::new().add_filter("CSV", &["csv"]).add_filter("JSON", &["json"]).set_file_name("library.csv").save_file() else {
+            return;
+        };
+
+        let rows = manifest::build(&self.config.library, |rom_id, entry| match &entry.path {
+            Some(path) => read_rom(path).map(|(rom, _)| rom),
+            None => BUILT_IN_ROMS.iter().find(|(_, rom)| config::rom_id(rom) == rom_id).map(|(_, rom)| rom.to_vec())
+                .ok_or_else(|| String::from("Wasn't loaded from a file")),
+        });
+        let contents = if path.extension().is_some_and(|extension| extension == "json") { manifest::to_json(&rows) } else { Ok(manifest::to_csv(&rows)) };
+
+        if let Err(e) = contents.and_then(|contents| fs::write(&path, contents).map_err(|e| format!("Could not write {}: {}", path.display(), e))) {
+            show_error("Could not export the manifest", e);
+        }
+    }
+
+    fn play_from_library(&mut self, ctx: &mut Context, rom_id: &str) -> Result<(), String> {
+        let Some(entry) = self.config.library.get(rom_id).cloned() else {
+            return Err(format!("{} isn't in the library any more.", rom_id));
+        };
+
+        if let Some((name, rom)) = BUILT_IN_ROMS.iter().find(|(_, rom)| config::rom_id(rom) == rom_id) {
+            self.start_rom(ctx, rom.to_vec(), name);
+            self.add_to_library(name);
+            Ok(())
+        }
+        else if let Some(path) = entry.path {
+            self.open_rom_file(ctx, &path)
+        }
+        else {
+            Err(format!("{} wasn't loaded from a file so it can't be opened again.", entry.name))
+        }
+    }
+
+    // the screen as it was last drawn disappears over whatever comes next, if transitions are on and enabled says this
+    // kind of change should have one. One that's already going carries on rather than starting again
+    fn start_transition(&mut self, enabled: bool) {
+        if let (Some(kind), true) = (self.config.transitions.kind, enabled) {
+            self.start_transition_of(kind);
+        }
+    }
+
+    fn start_transition_of(&mut self, kind: TransitionKind) {
+        let settings = self.config.transitions;
+        if self.config.accessibility.reduce_motion || self.transition.is_some() {
+            return;
+        }
+        let (width, height) = (self.screen_image.width() as usize, self.screen_image.height() as usize);
+        if self.screen_pixels.len() != width * height * 4 {
+            return; // nothing has been drawn yet
+        }
+
+        let length = Duration::from_secs_f32(settings.length.max(0.05));
+        self.transition = Some(Transition::new(kind, &self.screen_pixels, width, height, length));
+    }
+
+    fn start_attract(&mut self, ctx: &mut Context) {
+        self.attract = Some(Attract::default());
+        self.play_attract(ctx, 0);
+    }
+
+    // nobody's there to close an error, so ROMs that won't load are skipped and left out from then on
+    fn play_attract(&mut self, ctx: &mut Context, position: usize) {
+        let mut position = Some(position);
+        while let Some(rom_id) = position.and_then(|position| self.config.attract.playlist.get(position).cloned()) {
+            self.start_transition_of(self.config.attract.transition);
+            let Err(e) = self.play_from_library(ctx, &rom_id) else {
+                return;
+            };
+
+            // listed in the Attract mode window rather than interrupting the display with an error
+            let Some(attract) = &mut self.attract else {
+                return;
+            };
+            position = attract.drop_unplayable(&self.config.attract, rom_id, e);
+        }
+    }
+
+    fn update_attract(&mut self, ctx: &mut Context) {
+        let Some(attract) = &mut self.attract else {
+            return;
+        };
+        let input = self.cpu.keys() != 0;
+        if let Some(position) = attract.update(&self.config.attract, ctx.time.delta().as_secs_f32(), input) {
+            self.play_attract(ctx, position);
+        }
+    }
+
+    fn restart_rom(&mut self) {
+        let Some(mut rom) = self.last_loaded_rom.clone() else {
+            return;
+        };
+        self.netplay = None; // the other player wouldn't restart too
+
+        let patches = self.rom_id.as_ref().and_then(|rom_id| self.config.patches.get(rom_id));
+        for patch_file in patches.into_iter().flatten().filter(|patch_file| patch_file.enabled) {
+            match Patch::load(&patch_file.path) {
+                Ok(patch) => patch.apply(&mut rom),
+                Err(e) => show_error("Could not apply patch", format!("{}\n\nThe ROM will run without it.", e)),
+            }
+        }
+
+        if rom.len() > cpu::MAX_ROM_SIZE {
+            show_error("Could not apply patches", format!("The patched ROM is {} bytes but ROMs can be at most {} bytes. Running it unpatched instead.", rom.len(), cpu::MAX_ROM_SIZE));
+            rom = self.last_loaded_rom.clone().unwrap_or_default();
+        }
+
+        self.reset_cpu(&rom);
+    }
+
+    // writes bytes into the ROM file on disk as well as memory, so edits survive restarting it
+    fn patch_rom_file(&mut self, address: u16, bytes: &[u8]) -> Result<(), String> {
+        let (Some(path), Some(rom)) = (&self.rom_path, &mut self.last_loaded_rom) else {
+            return Err(String::from("Only ROMs loaded from a file can be patched."));
+        };
+        if archive::split(path).is_some() {
+            return Err(String::from("ROMs inside archives can't be patched. Unpack it first."));
+        }
+
+        let offset = (address as usize).checked_sub(cpu::PROGRAM_START)
+            .filter(|&offset| offset + bytes.len() <= rom.len())
+            .ok_or_else(|| format!(
+                "{:03X} to {:03X} isn't all inside the ROM, which is loaded from 200 to {:03X}.",
+                address, address as usize + bytes.len() - 1, cpu::PROGRAM_START + rom.len() - 1,
+            ))?;
+
+        let mut patched = rom.clone();
+        patched[offset..offset + bytes.len()].copy_from_slice(bytes);
+        fs::write(path, &patched).map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+        *rom = patched;
+
+        for (i, &byte) in bytes.iter().enumerate() {
+            self.cpu.write_memory(address + i as u16, byte);
+        }
+
+        Ok(())
+    }
+
+    fn update_gui(&mut self, ctx: &mut Context) -> GameResult {
+        if ctx.gfx.drawable_size() == (0.0, 0.0) {
+            return Ok(());
+        }
+
+        let gui_ctx = &self.gui.ctx();
+        let old_colours = (self.screen_colours(), self.config.accessibility, self.config.gradient, self.config.gradient_colour);
+
+        if self.gui_style_dirty {
+            gui_ctx.set_style(self.config.style());
+            self.gui_style_dirty = false;
+        }
+
+        let mut tour_anchors = TourAnchors { configuration: egui::Rect::NOTHING, debugger: egui::Rect::NOTHING };
+        let height = egui::TopBottomPanel::top("MenuBar").show_animated(gui_ctx, !self.menu_bar_hidden, |ui| {
+            menu::bar(ui, |ui| {
+                if ui.button("New tab").clicked() {
+                    report_error("Could not open a tab", self.new_tab(ctx));
+                }
+                if ui.button("Load ROM").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().pick_file().filter(|_| self.confirm_discard("Load ROM")) {
+                        self.load_with_undo(ctx, |io, ctx| io.open_rom_file(ctx, &path));
+                    }
+                }
+                if ui.button("Library").clicked() {
+                    self.library_window_open = true;
+                }
+                ui.menu_button("Built-in ROMs", |ui| {
+                    for (name, rom) in BUILT_IN_ROMS {
+                        if ui.button(name).clicked() && self.confirm_discard("Load ROM") {
+                            self.load_with_undo(ctx, |io, ctx| {
+                                io.start_rom(ctx, rom.to_vec(), name);
+                                io.add_to_library(name);
+                                Ok(())
+                            });
+                            ui.close_menu();
+                        }
+                    }
+                });
+                ui.menu_button("Edit", |ui| {
+                    let action = self.undo.next_action();
+                    let text = action.map_or(String::from("Undo"), |action| format!("Undo {}", action.to_lowercase()));
+                    if ui.add_enabled(action.is_some(), egui::Button::new(text)).clicked() {
+                        ui.close_menu();
+                        self.undo(ctx);
+                    }
+                });
+                if ui.button("Restart current ROM").clicked() {
+                    self.remember_for_undo("Restart");
+                    self.start_transition(self.config.transitions.on_reset);
+                    self.restart_rom();
+                }
+                ui.menu_button("Savestates", |ui| {
+                    if ui.button("Save state").clicked() {
+                        ui.close_menu();
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Savestates", &[SAVESTATE_EXTENSION]).save_file() {
+                            if let Err(e) = savestate::save(&path.with_extension(SAVESTATE_EXTENSION), &self.cpu) {
+                                show_error("Could not save the state", e);
+                            }
+                        }
+                    }
+                    if ui.button("Load state").clicked() {
+                        ui.close_menu();
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Savestates", &[SAVESTATE_EXTENSION]).pick_file() {
+                            self.load_state_file(ctx, &path);
+                        }
+                    }
+                    if ui.add_enabled(self.rom_id.is_some(), egui::Button::new("Manage saves")).clicked() {
+                        ui.close_menu();
+                        self.refresh_save_slots(ctx);
+                        self.saves_window_open = true;
+                    }
+                    if ui.button("Compare states").clicked() {
+                        ui.close_menu();
+                        self.state_diff = Some([DiffSide::Live, DiffSide::Live]);
+                    }
+                });
+                if ui.button(if self.recording.is_some() { "Stop recording" } else { "Record video" }).clicked() {
+                    self.toggle_recording();
+                }
+                if ui.button("Screenshots").clicked() {
+                    self.gallery_window_open = true;
+                }
+                if ui.button("Timeline").clicked() {
+                    self.timeline_window_open = true;
+                }
+                if ui.button("Hide menu bar").on_hover_text("Esc brings it back").clicked() {
+                    self.menu_bar_hidden = true;
+                }
+                if ui.button(if self.paused { "Resume" } else { "Pause" }).clicked() {
+                    self.paused = !self.paused;
+                }
+                if ui.add_enabled(self.last_loaded_rom.is_some(), egui::Button::new("Eject ROM")).clicked() {
+                    self.remember_for_undo("Eject ROM");
+                    report_error("Could not eject the ROM", self.eject_rom(ctx));
+                }
+                if ui.add_enabled(self.rom_id.is_some(), egui::Button::new("ROM properties")).clicked() {
+                    let name = self.rom_path.as_ref().and_then(|path| path.file_name()).map(|name| name.to_string_lossy().into_owned());
+                    let metadata = self.rom_path.as_ref().and_then(|path| read_rom(path).ok()).and_then(|(_, metadata)| metadata);
+                    self.rom_properties = self.last_loaded_rom.as_ref().map(|rom| rom_properties(rom, name, metadata.as_ref()));
+                }
+                let export_web = ui.add_enabled(self.last_loaded_rom.is_some(), egui::Button::new("Export web build"))
+                    .on_hover_text("Writes a web page that plays this ROM with the current settings, ready to publish");
+                if export_web.clicked() {
+                    if let (Some(rom), Some(dir)) = (&self.last_loaded_rom, rfd::FileDialog::new().pick_folder()) {
+                        let settings = SharedSettings::from_config(&self.config, self.cpu.quirks, self.rom_id.as_ref());
+                        if let Err(e) = web_export::export(&dir, rom, &self.tabs[self.active_tab].name, &settings) {
+                            show_error("Could not export the web build", e);
+                        }
+                    }
+                }
+                let export_listing = ui.add_enabled(self.last_loaded_rom.is_some(), egui::Button::new("Export disassembly"))
+                    .on_hover_text("Writes an annotated listing of this ROM, marking the instructions that have run since it was last started");
+                if export_listing.clicked() {
+                    let name = &self.tabs[self.active_tab].name;
+                    if let (Some(rom), Some(path)) = (&self.last_loaded_rom, rfd::FileDialog::new().add_filter("Text", &["txt"]).set_file_name(format!("{}.txt", name)).save_file()) {
+                        let coverage = (!self.coverage.is_empty()).then_some(&self.coverage);
+                        if let Err(e) = fs::write(&path, disasm::annotated(rom, name, coverage)) {
+                            show_error("Could not export the disassembly", format!("Could not write {}: {}", path.display(), e));
+                        }
+                    }
+                }
+                if ui.add_enabled(self.rom_id.is_some(), egui::Button::new("Cheats")).clicked() {
+                    self.cheats_window_open = true;
+                }
+                if ui.add_enabled(self.rom_id.is_some(), egui::Button::new("Breakpoints")).clicked() {
+                    self.breakpoints_window_open = true;
+                }
+                if ui.add_enabled(self.rom_id.is_some(), egui::Button::new("Patches")).clicked() {
+                    self.patches_window_open = true;
+                }
+                if ui.add_enabled(self.rom_id.is_some() || self.netplay.is_some(), egui::Button::new("Netplay")).clicked() {
+                    self.netplay_window_open = true;
+                }
+                if ui.button("Memory").clicked() {
+                    self.memory_window_open = true;
+                }
+                if ui.button("Draw log").clicked() {
+                    self.draw_log_window_open = true;
+                }
+                if ui.button("Call tree").clicked() {
+                    self.call_tree_window_open = true;
+                }
+                if ui.button("Memory search").clicked() {
+                    self.search_window_open = true;
+                }
+                let step_button = ui.button("Step through");
+                if step_button.clicked() {
+                    self.step_window_open = true;
+                    self.paused = true;
+                }
+                tour_anchors.debugger = step_button.rect;
+                if ui.button("Detach debugger").on_hover_text("Opens the disassembly, memory and registers in a window of their own").clicked() {
+                    self.detach_debugger();
+                }
+                if ui.button("Octo editor").clicked() {
+                    self.editor_window_open = true;
+                }
+                if ui.button("Sprite editor").clicked() {
+                    self.sprites_window_open = true;
+                }
+                if ui.button("Sound editor").clicked() {
+                    self.patterns_window_open = true;
+                }
+                ui.menu_button("Plugins", |ui| {
+                    if self.plugins.is_empty() {
+                        ui.label("No plugins are loaded");
+                    }
+                    self.plugins.menu(ui);
+                    ui.separator();
+                    let dir = ctx.fs.user_data_dir().join(PLUGIN_DIR);
+                    if ui.button("Reload scripts").on_hover_text(format!("Restarts the programs in {}", dir.display())).clicked() {
+                        ui.close_menu();
+                        self.load_plugin_scripts(ctx);
+                    }
+                });
+                let config_button = ui.button("Configuration");
+                if config_button.clicked() {
+                    self.config_window_open = true;
+                    self.audio_devices = audio::output_device_names();
+                    self.midi_ports = midi::port_names();
+                }
+                tour_anchors.configuration = config_button.rect;
+                if ui.button("Tour").clicked() {
+                    self.tour = Some(Tour::default());
+                }
+                if self.config_window_open {
+                    let old_config = self.config.clone();
+                    let mut reset_to_default = false;
+
+                    Window::new("Configuration").open(&mut self.config_window_open).resizable(true).show(gui_ctx, |ui| {
+                        ui.heading("Speed: ");
+                        ui.checkbox(&mut self.config.background_tabs, "Keep running tabs in the background");
+                        ui.horizontal(|ui| {
+                            ui.label("Speed set by: ");
+                            ui.selectable_value(&mut self.config.speed_mode, SpeedMode::CyclesPerFrame, "Cycles per frame");
+                            ui.selectable_value(&mut self.config.speed_mode, SpeedMode::InstructionsPerSecond, "Instructions per second");
+                        });
+                        ui.horizontal(|ui| {
+                            match self.config.speed_mode {
+                                SpeedMode::CyclesPerFrame => {
+                                    ui.label("Cyles per frame (60 frames a second): ");
+                                    ui.add(egui::DragValue::new(&mut self.config.cycles_per_frame));
+
+                                    if ui.button("Reset to default").clicked() {
+                                        self.config.cycles_per_frame = DEFAULT_CYCLES_PER_FRAME;
+                                    }
+                                },
+                                SpeedMode::InstructionsPerSecond => {
+                                    ui.label("Instructions per second: ");
+                                    ui.add(egui::DragValue::new(&mut self.config.instructions_per_second).speed(10));
+
+                                    if ui.button("Reset to default").clicked() {
+                                        self.config.instructions_per_second = DEFAULT_INSTRUCTIONS_PER_SECOND;
+                                    }
+                                },
+                            }
+                        });
+                        ui.separator();
+
+                        ui.heading("Apperance: ");
+                        ui.horizontal(|ui| {
+                            ui.label("Pixel size: ");
+                            ui.add(egui::DragValue::new(&mut self.pixel_size)).changed().then(|| {
+                                    let width = self.pixel_size * self.cpu.width() as f32;
+                                    report_error("Could not resize the window", ctx.gfx.set_drawable_size(width, width / 2.0 + self.menu_bar_height));
+                            });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Background: ");
+
+                            let colour = self.config.pixel_off_colour;
+                            let mut colour = [colour.r, colour.g, colour.b];
+                            widgets::color_picker::color_edit_button_rgb(ui, &mut colour);
+                            self.config.pixel_off_colour = Color::new(colour[0], colour[1], colour[2], 1.0);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Foreground: ");
+
+                            let colour = self.config.pixel_on_colour;
+                            let mut colour = [colour.r, colour.g, colour.b];
+                            widgets::color_picker::color_edit_button_rgb(ui, &mut colour);
+                            self.config.pixel_on_colour = Color::new(colour[0], colour[1], colour[2], 1.0);
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Off pixels: ");
+                            ui.selectable_value(&mut self.config.off_pixels, OffPixels::Coloured, "Coloured");
+                            ui.selectable_value(&mut self.config.off_pixels, OffPixels::Translucent, "Translucent");
+                            ui.selectable_value(&mut self.config.off_pixels, OffPixels::Hidden, "Hidden")
+                                .on_hover_text("Screenshots keep the transparency");
+                            if self.config.off_pixels == OffPixels::Translucent {
+                                ui.add(egui::Slider::new(&mut self.config.off_pixel_alpha, 0.0..=1.0).text("opacity"));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let dir = ctx.fs.user_data_dir().join(SHADER_DIR);
+                            ui.label("Shader: ").on_hover_text(format!("WGSL files put in {} show up here", dir.display()));
+                            egui::ComboBox::from_id_source("Shader")
+                                .selected_text(self.config.shader.as_ref().map_or("None", |shader| shader.name()))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.config.shader, None, "None");
+                                    ui.selectable_value(&mut self.config.shader, Some(ScreenShader::LedMatrix), ScreenShader::LedMatrix.name());
+                                    for name in shaders::available(&dir) {
+                                        ui.selectable_value(&mut self.config.shader, Some(ScreenShader::File(name.clone())), name);
+                                    }
+                                });
+                        });
+                        if self.config.shader == Some(ScreenShader::LedMatrix) {
+                            ui.horizontal(|ui| {
+                                ui.label("Dots: ");
+                                ui.add(egui::Slider::new(&mut self.config.led_matrix.dot_radius, 0.1..=0.7).text("radius"));
+                                ui.add(egui::Slider::new(&mut self.config.led_matrix.glow, 0.0..=1.0).text("glow"));
+                            });
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("Gradient: ");
+                            ui.selectable_value(&mut self.config.gradient, Gradient::Off, "Off");
+                            ui.selectable_value(&mut self.config.gradient, Gradient::Rows, "Top to bottom");
+                            ui.selectable_value(&mut self.config.gradient, Gradient::Columns, "Left to right");
+                            if self.config.gradient != Gradient::Off {
+                                let colour = self.config.gradient_colour;
+                                let mut colour = [colour.r, colour.g, colour.b];
+                                widgets::color_picker::color_edit_button_rgb(ui, &mut colour);
+                                self.config.gradient_colour = Color::new(colour[0], colour[1], colour[2], 1.0);
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let transitions = &mut self.config.transitions;
+                            ui.label("Transitions: ");
+                            ui.selectable_value(&mut transitions.kind, None, "Off");
+                            ui.selectable_value(&mut transitions.kind, Some(TransitionKind::Fade), "Fade");
+                            ui.selectable_value(&mut transitions.kind, Some(TransitionKind::Dissolve), "Dissolve");
+                            if transitions.kind.is_some() {
+                                ui.add(egui::Slider::new(&mut transitions.length, 0.1..=2.0).suffix(" s"));
+                                ui.checkbox(&mut transitions.on_load, "Load");
+                                ui.checkbox(&mut transitions.on_reset, "Reset");
+                                ui.checkbox(&mut transitions.on_clear, "Clear screen");
+                            }
+                        });
+                        if ui.button("Reset apperance to default").clicked() {
+                            self.config.pixel_off_colour = DEFAULT_OFF_COLOUR;
+                            self.config.pixel_on_colour = DEFAULT_ON_COLOUR;
+                            self.config.off_pixels = OffPixels::Coloured;
+                            self.config.off_pixel_alpha = config::DEFAULT_OFF_PIXEL_ALPHA;
+                            self.config.gradient = Gradient::Off;
+                            self.config.gradient_colour = config::DEFAULT_GRADIENT_COLOUR;
+                            self.config.shader = None;
+                            self.config.led_matrix = LedMatrixSettings::default();
+                            self.config.transitions = TransitionSettings::default();
+
+                            let width = DEFAULT_PIXEL_SIZE * self.cpu.width() as f32;
+                            report_error("Could not resize the window", ctx.gfx.set_drawable_size(width, width / 2.0 + self.menu_bar_height));
+                        }
+                        ui.separator();
+
+                        ui.heading("Interface: ");
+                        ui.horizontal(|ui| {
+                            ui.label("Theme: ");
+                            self.gui_style_dirty |= ui.selectable_value(&mut self.config.theme, Theme::Dark, "Dark").changed();
+                            self.gui_style_dirty |= ui.selectable_value(&mut self.config.theme, Theme::Light, "Light").changed();
+                            self.gui_style_dirty |= ui.selectable_value(&mut self.config.theme, Theme::Custom, "Custom").changed();
+                        });
+                        if self.config.theme == Theme::Custom {
+                            let custom = &mut self.config.custom_theme;
+
+                            ui.horizontal(|ui| {
+                                ui.label("Base: ");
+                                self.gui_style_dirty |= ui.selectable_value(&mut custom.dark_base, true, "Dark").changed();
+                                self.gui_style_dirty |= ui.selectable_value(&mut custom.dark_base, false, "Light").changed();
+                            });
+                            for (label, colour) in [
+                                ("Panels: ", &mut custom.panel_fill),
+                                ("Windows: ", &mut custom.window_fill),
+                                ("Text: ", &mut custom.text),
+                                ("Accent: ", &mut custom.accent),
+                            ] {
+                                ui.horizontal(|ui| {
+                                    ui.label(label);
+
+                                    let mut rgb = [colour.r, colour.g, colour.b];
+                                    if widgets::color_picker::color_edit_button_rgb(ui, &mut rgb).changed() {
+                                        *colour = Color::new(rgb[0], rgb[1], rgb[2], 1.0);
+                                        self.gui_style_dirty = true;
+                                    }
+                                });
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.label("UI scale: ");
+                            let slider = egui::Slider::new(&mut self.config.ui_scale, config::MIN_UI_SCALE..=config::MAX_UI_SCALE).step_by(0.25);
+                            // only restyle once the drag is released, otherwise the slider moves out from under the cursor
+                            let response = ui.add(slider);
+                            self.gui_style_dirty |= response.drag_released() || (response.changed() && !response.dragged());
+                        });
+                        if ui.button("Reset interface to default").clicked() {
+                            self.config.theme = Theme::Dark;
+                            self.config.ui_scale = DEFAULT_UI_SCALE;
+                            self.gui_style_dirty = true;
+                        }
+                        ui.separator();
+
+                        ui.heading("Audio: ");
+                        ui.horizontal(|ui| {
+                            ui.label("Output device: ");
+                            egui::ComboBox::from_id_source("AudioDevice")
+                                .selected_text(self.config.audio.device.as_deref().unwrap_or("System default"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.config.audio.device, None, "System default");
+                                    for name in &self.audio_devices {
+                                        ui.selectable_value(&mut self.config.audio.device, Some(name.clone()), name);
+                                    }
+                                });
+                            if ui.button("Refresh").clicked() {
+                                self.audio_devices = audio::output_device_names();
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Buffer size: ");
+                            egui::ComboBox::from_id_source("AudioBufferSize")
+                                .selected_text(self.config.audio.buffer_size.map_or(String::from("Default"), |frames| format!("{} frames", frames)))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.config.audio.buffer_size, None, "Default");
+                                    for frames in BUFFER_SIZES {
+                                        ui.selectable_value(&mut self.config.audio.buffer_size, Some(frames), format!("{} frames", frames));
+                                    }
+                                });
+                            if let Some(latency) = self.buzzer.as_ref().and_then(|buzzer| buzzer.latency(&self.config.audio)) {
+                                ui.label(format!("(~{:.1} ms)", latency.as_secs_f32() * 1000.0));
+                            }
+                        });
+                        ui.label("Smaller buffers make the beep line up better with the screen but may crackle on slow or wireless devices.");
+                        ui.horizontal(|ui| {
+                            ui.label("Show buzzer indicator: ");
+                            ui.checkbox(&mut self.config.buzzer_indicator, "");
+                        });
+                        ui.separator();
+
+                        ui.heading("Accessibility: ");
+                        let accessibility = &mut self.config.accessibility;
+                        ui.horizontal(|ui| {
+                            ui.label("High contrast: ");
+                            ui.checkbox(&mut accessibility.high_contrast, "")
+                                .on_hover_text("Draws the screen white on black, ignoring the colours set above");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Reduce flicker: ");
+                            ui.checkbox(&mut accessibility.reduce_flicker, "")
+                                .on_hover_text("Pixels fade out instead of blinking off, so sprites that are redrawn every frame stop flashing");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Flash the screen border for the beep: ");
+                            ui.checkbox(&mut accessibility.visual_beep, "");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Reduce motion: ");
+                            let reduce_motion = ui.checkbox(&mut accessibility.reduce_motion, "")
+                                .on_hover_text("Turns off the shader, gradient, transitions, fading pixels and UI animations so the screen is drawn exactly as the program left it");
+                            if reduce_motion.changed() {
+                                self.gui_style_dirty = true;
+                            }
+                        });
+                        ui.separator();
+
+                        ui.heading("Controls: ");
+                        ui.horizontal(|ui| {
+                            ui.label("Keyboard layout: ");
+                            for layout in KeyboardLayout::ALL {
+                                ui.selectable_value(&mut self.config.keyboard.layout, layout, layout.name());
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Use physical key positions: ");
+                            ui.checkbox(&mut self.config.keyboard.use_scancodes, "")
+                                .on_hover_text("The keypad is always the 4x4 block of keys at the top left of the keyboard, whatever the layout");
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Numpad as a second keypad (CHIP-8X): ");
+                            ui.checkbox(&mut self.config.keyboard.second_keypad, "");
+                        });
+                        ui.add_enabled_ui(self.config.keyboard.second_keypad, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Numpad also presses the first keypad: ");
+                                ui.checkbox(&mut self.config.keyboard.share_second_keypad, "")
+                                    .on_hover_text("For two player games that read both players from the same keypad");
+                            });
+                        });
+
+                        match &self.rom_id {
+                            Some(rom_id) => {
+                                let mut natural_controls = self.config.control_schemes.contains_key(rom_id);
+                                ui.horizontal(|ui| {
+                                    ui.label("Natural controls for this ROM: ");
+                                    ui.checkbox(&mut natural_controls, "")
+                                        .on_hover_text("Move with the arrow keys or WASD and use space for the action key instead of remembering the keypad");
+                                });
+
+                                if !natural_controls {
+                                    self.config.control_schemes.remove(rom_id);
+                                }
+                                else {
+                                    let scheme = self.config.control_schemes.entry(rom_id.clone()).or_default();
+
+                                    ui.horizontal(|ui| {
+                                        ui.label("Move with: ");
+                                        ui.selectable_value(&mut scheme.direction_keys, DirectionKeys::Arrows, "Arrow keys");
+                                        ui.selectable_value(&mut scheme.direction_keys, DirectionKeys::Wasd, "WASD");
+                                    });
+                                    ui.horizontal(|ui| {
+                                        keypad_key_picker(ui, "Up: ", &mut scheme.up);
+                                        keypad_key_picker(ui, "Down: ", &mut scheme.down);
+                                        keypad_key_picker(ui, "Left: ", &mut scheme.left);
+                                        keypad_key_picker(ui, "Right: ", &mut scheme.right);
+                                        keypad_key_picker(ui, "Space: ", &mut scheme.action);
+                                    });
+                                    if ui.button("Reset to default").clicked() {
+                                        *scheme = ControlScheme::default();
+                                    }
+                                }
+                            },
+                            None => {
+                                ui.label("Load a ROM to set up natural controls for it");
+                            },
+                        }
+
+                        ui.horizontal(|ui| {
+                            ui.label("Show on-screen keypad: ");
+                            ui.checkbox(&mut self.config.touch.show_keypad, "")
+                                .on_hover_text("Tap the keys on a touchscreen or click them with the mouse");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Swipe controls: ");
+                            ui.checkbox(&mut self.config.touch.swipes, "")
+                                .on_hover_text("Swiping (or dragging with the mouse) across the screen presses a key");
+                        });
+                        ui.add_enabled_ui(self.config.touch.swipes, |ui| {
+                            ui.horizontal(|ui| {
+                                let touch = &mut self.config.touch;
+                                keypad_key_picker(ui, "Swipe up: ", &mut touch.swipe_up);
+                                keypad_key_picker(ui, "Swipe down: ", &mut touch.swipe_down);
+                                keypad_key_picker(ui, "Swipe left: ", &mut touch.swipe_left);
+                                keypad_key_picker(ui, "Swipe right: ", &mut touch.swipe_right);
+                            });
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("MIDI input: ");
+                            ui.checkbox(&mut self.config.midi.enabled, "").on_hover_text("Play with the pads or keys of a MIDI controller");
+                            egui::ComboBox::from_id_source("MidiPort")
+                                .selected_text(self.config.midi.port.as_deref().unwrap_or("First device"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut self.config.midi.port, None, "First device");
+                                    for name in &self.midi_ports {
+                                        ui.selectable_value(&mut self.config.midi.port, Some(name.clone()), name);
+                                    }
+                                });
+                            if ui.button("Refresh").clicked() {
+                                self.midi_ports = midi::port_names();
+                            }
+                        });
+                        ui.add_enabled_ui(self.midi.is_some(), |ui| {
+                            ui.label("Click a key then press the pad or note for it, right click to clear it:");
+                            egui::Grid::new("MidiMappings").show(ui, |ui| {
+                                for (i, &key) in KEYPAD_GRID.iter().enumerate() {
+                                    let notes: Vec<String> = self.config.midi.notes_for(key).into_iter().map(midi::note_name).collect();
+                                    let text = if self.midi_learn == Some(key) {
+                                        format!("{:X}: press one", key)
+                                    }
+                                    else if notes.is_empty() {
+                                        format!("{:X}: none", key)
+                                    }
+                                    else {
+                                        format!("{:X}: {}", key, notes.join(" "))
+                                    };
+
+                                    let button = ui.add(egui::Button::new(text).min_size(egui::vec2(90.0, 0.0)).selected(self.midi_learn == Some(key)));
+                                    if button.clicked() {
+                                        self.midi_learn = if self.midi_learn == Some(key) { None } else { Some(key) };
+                                    }
+                                    if button.secondary_clicked() {
+                                        self.config.midi.clear(key);
+                                    }
+                                    if i % 4 == 3 {
+                                        ui.end_row();
+                                    }
+                                }
+                            });
+                            if ui.button("Reset to default").clicked() {
+                                self.config.midi.mappings = MidiSettings::default().mappings;
+                                self.midi_learn = None;
+                            }
+                        });
+
+                        ui.label("Macros: ");
+                        let mut removed = None;
+                        for (i, input_macro) in self.config.macros.iter_mut().enumerate() {
+                            ui.push_id(i, |ui| ui.horizontal(|ui| {
+                                egui::ComboBox::from_id_source("macro trigger")
+                                    .selected_text(format!("{:?}", input_macro.trigger))
+                                    .show_ui(ui, |ui| {
+                                        for trigger in MACRO_TRIGGER_KEYS {
+                                            ui.selectable_value(&mut input_macro.trigger, trigger, format!("{:?}", trigger));
+                                        }
+                                    });
+
+                                let is_turbo = matches!(input_macro.action, MacroAction::Turbo { .. });
+                                if ui.selectable_label(is_turbo, "Turbo").clicked() && !is_turbo {
+                                    input_macro.action = MacroAction::Turbo { key: 0x5, rate: input::DEFAULT_TURBO_RATE };
+                                }
+                                if ui.selectable_label(!is_turbo, "Sequence").clicked() && is_turbo {
+                                    input_macro.action = MacroAction::Sequence { keys: vec![], hold_ticks: input::DEFAULT_SEQUENCE_HOLD_TICKS };
+                                }
+
+                                match &mut input_macro.action {
+                                    MacroAction::Turbo { key, rate } => {
+                                        keypad_key_picker(ui, "Key: ", key);
+                                        ui.add(egui::DragValue::new(rate).clamp_range(1.0..=30.0).suffix(" Hz"));
+                                    },
+                                    MacroAction::Sequence { keys, hold_ticks } => {
+                                        // one hex digit per keypad key, anything else is dropped
+                                        let mut text: String = keys.iter().map(|key| format!("{:X}", key)).collect();
+                                        if ui.add(egui::TextEdit::singleline(&mut text).desired_width(80.0).hint_text("keys")).changed() {
+                                            *keys = text.chars().filter_map(|c| c.to_digit(16)).map(|key| key as u8).collect();
+                                        }
+                                        ui.add(egui::DragValue::new(hold_ticks).clamp_range(1..=60).suffix(" frames each"));
+                                    },
+                                }
+
+                                if ui.button("Remove").clicked() {
+                                    removed = Some(i);
+                                }
+                            }));
+                        }
+                        if let Some(i) = removed {
+                            self.config.macros.remove(i);
+                        }
+                        if ui.button("Add macro").clicked() {
+                            self.config.macros.push(InputMacro::default());
+                        }
+                        ui.separator();
+
+                        ui.heading("Speedrunning: ");
+                        ui.horizontal(|ui| {
+                            ui.label("Show timer and input display: ");
+                            ui.checkbox(&mut self.config.speedrun.overlay, "")
+                                .on_hover_text("The timer starts on the first key press and counts emulated frames, so pausing stops it");
+                        });
+                        ui.separator();
+
+                        ui.heading("Hotkeys: ");
+                        egui::Grid::new("Hotkeys").num_columns(3).show(ui, |ui| {
+                            for action in HotkeyAction::ALL {
+                                ui.label(action.name());
+
+                                let text = match self.binding_hotkey {
+                                    Some(binding) if binding == action => String::from("Press a key..."),
+                                    _ => self.config.hotkeys.binding(action).to_string(),
+                                };
+                                if ui.button(text).clicked() {
+                                    self.binding_hotkey = Some(action);
+                                }
+
+                                let scheme = self.rom_id.as_ref().and_then(|rom_id| self.config.control_schemes.get(rom_id));
+                                let conflicts = self.config.hotkeys.conflicts(action, &self.config.keyboard, scheme, &self.config.macros);
+                                if !conflicts.is_empty() {
+                                    ui.colored_label(ui.visuals().warn_fg_color, format!("Also {}", conflicts.join(", ")));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        if ui.button("Reset hotkeys to default").clicked() {
+                            self.config.hotkeys = Hotkeys::default();
+                        }
+                        ui.separator();
+
+                        ui.heading("Quirks: ");
+                        let quirks_used = self.cpu.quirks_used();
+                        for quirk in Quirk::ALL {
+                            quirk_setting(ui, quirk, &mut self.cpu.quirks, quirks_used.contains(&quirk));
+                        }
+                        if ui.button("Reset quirks to default").clicked() {
+                            self.cpu.quirks = Quirks::default();
+                        }
+                        ui.separator();
+
+                        ui.heading("Remote control: ");
+                        ui.horizontal(|ui| {
+                            ui.label("WebSocket API: ");
+                            ui.checkbox(&mut self.config.remote_api, "")
+                                .on_hover_text("Lets other programs on this computer load ROMs, press keys and read the screen and CPU state");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Port: ");
+                            ui.add(egui::DragValue::new(&mut self.config.remote_api_port).clamp_range(1024..=u16::MAX));
+
+                            if ui.button("Reset to default").clicked() {
+                                self.config.remote_api_port = DEFAULT_REMOTE_PORT;
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Crowd play: ");
+                            ui.checkbox(&mut self.config.crowd_play, "")
+                                .on_hover_text("Takes key presses from a chat bot, one line of hex digits per message, for \"Twitch plays\" streams");
+                            if let Some(crowd) = &self.crowd {
+                                ui.label(format!("{} presses queued", crowd.queued()));
+                            }
+                        });
+                        ui.add_enabled_ui(self.config.crowd_play, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Port: ");
+                                ui.add(egui::DragValue::new(&mut self.config.crowd_play_port).clamp_range(1024..=u16::MAX));
+                                ui.checkbox(&mut self.config.crowd_play_public, "Let other computers connect")
+                                    .on_hover_text("Otherwise only a chat bot running on this computer can send key presses");
+                                ui.label("Queue length: ");
+                                ui.add(egui::DragValue::new(&mut self.config.crowd_queue_length).clamp_range(1..=1000));
+                                ui.label("Hold each key for: ");
+                                ui.add(egui::DragValue::new(&mut self.config.crowd_hold_ticks).clamp_range(1..=60).suffix(" frames"));
+
+                                if ui.button("Reset to default").clicked() {
+                                    self.config.crowd_play_port = crowd::DEFAULT_CROWD_PORT;
+                                    self.config.crowd_queue_length = crowd::DEFAULT_CROWD_QUEUE_LENGTH;
+                                    self.config.crowd_hold_ticks = crowd::DEFAULT_CROWD_HOLD_TICKS;
+                                }
+                            });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Display bridge: ");
+                            ui.checkbox(&mut self.config.display_bridge, "")
+                                .on_hover_text("Sends the screen over OSC whenever it changes, for mirroring it on LED matrices and other hardware");
+                        });
+                        ui.add_enabled_ui(self.config.display_bridge, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("Send to: ");
+                                address_edit(ui, "bridge address", &mut self.config.display_bridge_address);
+                                if ui.button("Reset to default").clicked() {
+                                    self.config.display_bridge_address = String::from(DEFAULT_BRIDGE_ADDRESS);
+                                }
+                            });
+                        });
+                        ui.horizontal(|ui| {
+                            ui.label("Ambient lights: ");
+                            ui.checkbox(&mut self.config.ambient.enabled, "")
+                                .on_hover_text("Sends the screen's colours to a WLED light strip every frame");
+                        });
+                        ui.add_enabled_ui(self.config.ambient.enabled, |ui| {
+                            ui.horizontal(|ui| {
+                                ui.label("WLED address: ");
+                                address_edit(ui, "WLED address", &mut self.config.ambient.address);
+                                if ui.button("Reset to default").clicked() {
+                                    self.config.ambient.address = String::from(ambient::DEFAULT_WLED_ADDRESS);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("LEDs: ");
+                                ui.add(egui::DragValue::new(&mut self.config.ambient.led_count).clamp_range(1..=ambient::MAX_LEDS));
+                                ui.radio_value(&mut self.config.ambient.mode, AmbientMode::Overall, "Whole screen")
+                                    .on_hover_text("Every LED is the screen's colour overall");
+                                ui.radio_value(&mut self.config.ambient.mode, AmbientMode::Borders, "Borders")
+                                    .on_hover_text("For a strip round the screen starting at the top left, each side matches that edge");
+                            });
+                        });
+                        ui.separator();
+
+                        ui.checkbox(&mut self.config.confirm_discard, "Ask before loading a ROM or quitting would lose a recording or rewind history");
+                        ui.horizontal(|ui| {
+                            if ui.button("Export settings").on_hover_text("Saves the speed, quirks, colours and controls to share with others").clicked() {
+                                let settings = SharedSettings::from_config(&self.config, self.cpu.quirks, self.rom_id.as_ref());
+                                if let Some(path) = rfd::FileDialog::new().add_filter("Settings", &["toml"]).set_file_name("settings.toml").save_file() {
+                                    if let Err(e) = settings.save(&path) {
+                                        show_error("Could not export the settings", e);
+                                    }
+                                }
+                            }
+                            if ui.button("Import settings").clicked() {
+                                if let Some(path) = rfd::FileDialog::new().add_filter("Settings", &["toml"]).pick_file() {
+                                    match SharedSettings::load(&path) {
+                                        Ok(settings) => {
+                                            let other_rom = settings.rom.is_some() && settings.rom != self.rom_id;
+                                            let confirmed = !other_rom || rfd::MessageDialog::new()
+                                                .set_level(rfd::MessageLevel::Warning)
+                                                .set_title("Import settings")
+                                                .set_description("These settings were made for a different ROM to the one loaded. Use them anyway?")
+                                                .set_buttons(rfd::MessageButtons::YesNo)
+                                                .show() == rfd::MessageDialogResult::Yes;
+
+                                            if confirmed {
+                                                settings.apply(&mut self.config, self.rom_id.as_ref());
+                                                self.cpu.quirks = settings.quirks;
+                                            }
+                                        },
+                                        Err(e) => show_error("Could not import the settings", e),
+                                    }
+                                }
+                            }
+                            reset_to_default = ui.button("Reset all to default").clicked();
+                        });
+                    });
+
+                    if reset_to_default {
+                        let snapshot = Config { quirks: self.cpu.quirks, ..self.config.clone() };
+                        self.undo.push("Reset settings", UndoSnapshot::Settings(Box::new(snapshot)));
+                        self.config = self.config.reset();
+                        self.cpu.quirks = self.config.quirks;
+                        self.gui_style_dirty = true;
+
+                        let width = DEFAULT_PIXEL_SIZE * self.cpu.width() as f32;
+                        report_error("Could not resize the window", ctx.gfx.set_drawable_size(width, width / 2.0 + self.menu_bar_height));
+                    }
+
+                    self.settings_replaced(ctx, &old_config);
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if self.config.buzzer_indicator {
+                        // still lights up without an audio device so the beep can be seen when it can't be heard
+                        let icon = if self.buzzer.is_some() { "🔊" } else { "🔇" };
+                        let colour = if self.beeping { ui.visuals().warn_fg_color } else { ui.visuals().weak_text_color() };
+
+                        ui.label(egui::RichText::new(icon).color(colour)).on_hover_text("Buzzer");
+                    }
+                    if self.cpu.waiting_for_key() {
+                        let ignored: Vec<String> = (0..16).filter(|key| self.cpu.ignored_keys() & (1 << key) != 0).map(|key| format!("{:X}", key)).collect();
+                        let mut hover = String::from("FX0A is waiting for a key to be pressed and let go of");
+                        if !ignored.is_empty() {
+                            hover += &format!(". {} were already held so have to be let go of and pressed again", ignored.join(", "));
+                        }
+                        ui.label(egui::RichText::new("⌨ Waiting for a key").color(ui.visuals().weak_text_color())).on_hover_text(hover);
+                    }
+                });
+            });
+
+            if self.tabs.len() > 1 {
+                let (mut switch_to, mut close) = (None, None);
+                ui.horizontal(|ui| {
+                    for (i, tab) in self.tabs.iter().enumerate() {
+                        if ui.selectable_label(i == self.active_tab, &tab.name).clicked() {
+                            switch_to = Some(i);
+                        }
+                        if ui.small_button("x").on_hover_text("Close tab").clicked() {
+                            close = Some(i);
+                        }
+                        ui.separator();
+                    }
+                });
+
+                if let Some(index) = switch_to {
+                    report_error("Could not switch tabs", self.switch_tab(ctx, index));
+                }
+                if let Some(index) = close {
+                    report_error("Could not close the tab", self.close_tab(ctx, index));
+                }
+            }
+        }).map_or(0.0, |menu_bar| menu_bar.response.rect.height());
+
+        if let (true, Some(rom_id)) = (self.breakpoints_window_open, &self.rom_id) {
+            let set = self.config.breakpoints.entry(rom_id.clone()).or_default();
+            let (pc, i) = (self.cpu.pc(), self.cpu.addr_reg());
+
+            Window::new("Breakpoints").open(&mut self.breakpoints_window_open).resizable(true).show(gui_ctx, |ui| {
+                ui.label("These are saved for this ROM. Breakpoints stop before the instruction at their address runs, watchpoints after the byte at their address changes.");
+                if let Some(hit) = self.breakpoint_hit.clone() {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(ui.visuals().warn_fg_color, format!("Stopped by: {}", hit));
+                        if ui.button("Resume").clicked() {
+                            self.paused = false;
+                            self.breakpoint_hit = None;
+                        }
+                    });
+                }
+                ui.separator();
+
+                let mut removed = None;
+                egui::Grid::new("Breakpoint list").num_columns(6).striped(true).show(ui, |ui| {
+                    for (index, breakpoint) in set.iter_mut().enumerate() {
+                        ui.checkbox(&mut breakpoint.enabled, "");
+                        ui.horizontal(|ui| {
+                            ui.selectable_value(&mut breakpoint.kind, BreakpointKind::Execute, "Execute");
+                            ui.selectable_value(&mut breakpoint.kind, BreakpointKind::Write, "Write");
+                        });
+                        ui.add(egui::DragValue::new(&mut breakpoint.address).hexadecimal(3, false, true).clamp_range(0..=0xFFF));
+
+                        ui.horizontal(|ui| {
+                            let mut conditional = breakpoint.condition.is_some();
+                            if ui.checkbox(&mut conditional, "If").changed() {
+                                breakpoint.condition = conditional.then(Condition::default);
+                            }
+                            if let Some(condition) = &mut breakpoint.condition {
+                                egui::ComboBox::from_id_source(("Condition register", index))
+                                    .width(40.0)
+                                    .selected_text(format!("V{:X}", condition.register))
+                                    .show_ui(ui, |ui| {
+                                        for register in 0..16 {
+                                            ui.selectable_value(&mut condition.register, register, format!("V{:X}", register));
+                                        }
+                                    });
+                                egui::ComboBox::from_id_source(("Condition comparison", index))
+                                    .width(40.0)
+                                    .selected_text(condition.comparison.symbol())
+                                    .show_ui(ui, |ui| {
+                                        for comparison in Comparison::ALL {
+                                            ui.selectable_value(&mut condition.comparison, comparison, comparison.symbol());
+                                        }
+                                    });
+                                ui.add(egui::DragValue::new(&mut condition.value).hexadecimal(2, false, true));
+                            }
+                        });
+
+                        ui.label(format!("{} hits", breakpoint.hits));
+                        if ui.button("Remove").clicked() {
+                            removed = Some(index);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(index) = removed {
+                    set.remove(index);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add breakpoint").on_hover_text("At the program counter").clicked() {
+                        set.push(Breakpoint { address: pc, ..Default::default() });
+                    }
+                    if ui.button("Add watchpoint").on_hover_text("On the byte I points at").clicked() {
+                        set.push(Breakpoint { kind: BreakpointKind::Write, address: i, ..Default::default() });
+                    }
+                    if ui.button("Reset hit counts").clicked() {
+                        set.iter_mut().for_each(|breakpoint| breakpoint.hits = 0);
+                    }
+                    if ui.button("Clear all").clicked() {
+                        set.clear();
+                    }
+                });
+                ui.separator();
+
+                // both run the program until they're set off
+                ui.horizontal(|ui| {
+                    let mut watch = None;
+                    if ui.button("Break on next display update").clicked() {
+                        watch = Some(DisplayWatch::AnyUpdate);
+                    }
+                    ui.label("or when pixel");
+                    ui.add(egui::DragValue::new(&mut self.watch_pixel.0).clamp_range(0..=cpu::WIDTH * 2 - 1).prefix("x: "));
+                    ui.add(egui::DragValue::new(&mut self.watch_pixel.1).clamp_range(0..=cpu::HEIGHT * 2 - 1).prefix("y: "));
+                    if ui.button("changes").on_hover_text("In high resolution coordinates, so double them in low resolution").clicked() {
+                        watch = Some(DisplayWatch::Pixel(self.watch_pixel.0, self.watch_pixel.1));
+                    }
+
+                    if watch.is_some() {
+                        self.cpu.display_watch = watch;
+                        self.paused = false;
+                        self.breakpoint_hit = None;
+                    }
+                });
+                if let Some(watch) = self.cpu.display_watch {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Watching for: {}", watch));
+                        if ui.button("Cancel").clicked() {
+                            self.cpu.display_watch = None;
+                        }
+                    });
+                }
+
+                let mut trap = self.code_trap.is_some();
+                let hover = "When FX55 or FX33 writes over an instruction the program can reach from the start. Code only reached through BNNN jumps isn't known about";
+                if ui.checkbox(&mut trap, "Break on writes to code").on_hover_text(hover).changed() {
+                    self.code_trap = if trap { self.last_loaded_rom.as_deref().map(CodeMap::analyse) } else { None };
+                }
+            });
+
+            if set.is_empty() {
+                self.config.breakpoints.remove(rom_id); // don't fill the config up with every ROM the window was opened for
+            }
+        }
+
+        if let (true, Some(rom_id)) = (self.cheats_window_open, &self.rom_id) {
+            let cheats = self.config.cheats.entry(rom_id.clone()).or_default();
+            let cpu = &mut self.cpu;
+
+            Window::new("Cheats").open(&mut self.cheats_window_open).resizable(true).show(gui_ctx, |ui| {
+                ui.label("Cheats are saved for this ROM. Frozen values are written every frame, pokes only when Write is pressed.");
+
+                let mut removed = None;
+                for (i, cheat) in cheats.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| ui.horizontal(|ui| {
+                        ui.add(egui::TextEdit::singleline(&mut cheat.name).desired_width(100.0).hint_text("name"));
+                        ui.label("Address: ");
+                        ui.add(egui::DragValue::new(&mut cheat.address).hexadecimal(3, false, true).clamp_range(0..=0xFFF));
+                        ui.label("Value: ");
+                        ui.add(egui::DragValue::new(&mut cheat.value).hexadecimal(2, false, true));
+                        ui.label(format!("(now {:02X})", cpu.read_memory(cheat.address)));
+
+                        ui.selectable_value(&mut cheat.kind, CheatKind::Freeze, "Freeze");
+                        ui.selectable_value(&mut cheat.kind, CheatKind::Poke, "Poke");
+                        match cheat.kind {
+                            CheatKind::Freeze => {
+                                ui.checkbox(&mut cheat.enabled, "Enabled");
+                            },
+                            CheatKind::Poke => if ui.button("Write").clicked() {
+                                cheat.write(cpu);
+                            },
+                        }
+
+                        if ui.button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    }));
+                }
+                if let Some(i) = removed {
+                    cheats.remove(i);
+                }
+                if ui.button("Add cheat").clicked() {
+                    cheats.push(Cheat::default());
+                }
+            });
+
+            if cheats.is_empty() {
+                self.config.cheats.remove(rom_id); // don't fill the config up with every ROM the window was opened for
+            }
+        }
+
+        if let (true, Some(rom_id)) = (self.patches_window_open, self.rom_id.clone()) {
+            let patches = self.config.patches.entry(rom_id.clone()).or_default();
+            let mut restart = false;
+
+            Window::new("Patches").open(&mut self.patches_window_open).resizable(true).show(gui_ctx, |ui| {
+                ui.label("IPS patches and text patch lists (lines of \"offset: byte byte...\" in hex) are applied in order each time this ROM starts.");
+
+                let mut removed = None;
+                let mut moved_up = None;
+                for (i, patch_file) in patches.iter_mut().enumerate() {
+                    ui.push_id(i, |ui| ui.horizontal(|ui| {
+                        ui.checkbox(&mut patch_file.enabled, "");
+                        let name = patch_file.path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                        ui.label(name).on_hover_text(patch_file.path.display().to_string());
+
+                        if ui.add_enabled(i > 0, egui::Button::new("⏶")).on_hover_text("Apply earlier").clicked() {
+                            moved_up = Some(i);
+                        }
+                        if ui.button("Remove").clicked() {
+                            removed = Some(i);
+                        }
+                    }));
+                }
+                if let Some(i) = moved_up {
+                    patches.swap(i - 1, i);
+                }
+                if let Some(i) = removed {
+                    patches.remove(i);
+                }
+
+                ui.horizontal(|ui| {
+                    if ui.button("Add patch").clicked() {
+                        if let Some(path) = rfd::FileDialog::new().add_filter("Patches", &["ips", "txt"]).add_filter("All files", &["*"]).pick_file() {
+                            patches.push(PatchFile { path, enabled: true });
+                        }
+                    }
+                    if ui.button("Restart with these patches").clicked() {
+                        restart = true;
+                    }
+                });
+            });
+
+            if patches.is_empty() {
+                self.config.patches.remove(&rom_id);
+            }
+            if restart {
+                self.restart_rom();
+            }
+        }
+
+        if self.netplay_window_open {
+            let mut window_open = true;
+            let mut joined = None;
+
+            Window::new("Netplay").open(&mut window_open).resizable(false).show(gui_ctx, |ui| {
+                if let Some(netplay) = &self.netplay {
+                    ui.label(format!("Playing with {}", netplay.peer()));
+                    if ui.button("Disconnect").clicked() {
+                        self.netplay = None;
+                    }
+                }
+                else if self.netplay_host.is_some() {
+                    ui.label(format!("Waiting for the other player to join on port {}...", self.config.netplay_port));
+                    if ui.button("Cancel").clicked() {
+                        self.netplay_host = None;
+                    }
+                }
+                else if let Some(rom_id) = &self.rom_id {
+                    ui.label("Both players need the same ROM loaded and the same speed and quirks. Each player presses their own keys on the shared keypad.");
+                    ui.horizontal(|ui| {
+                        ui.label("Port: ");
+                        ui.add(egui::DragValue::new(&mut self.config.netplay_port).clamp_range(1024..=u16::MAX));
+                        if ui.button("Reset to default").clicked() {
+                            self.config.netplay_port = DEFAULT_NETPLAY_PORT;
+                        }
+                        if ui.button("Host").clicked() {
+                            match NetplayHost::start(self.config.netplay_port, rom_id, NetplayRules::new(&self.config, self.cpu.quirks)) {
+                                Ok(host) => self.netplay_host = Some(host),
+                                Err(e) => show_error("Could not host", format!("Could not listen on port {}: {}", self.config.netplay_port, e)),
+                            }
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("Host address: ");
+                        ui.add(egui::TextEdit::singleline(&mut self.config.netplay_address).hint_text("192.168.0.2:8265"));
+                        if ui.button("Join").clicked() {
+                            match NetplaySession::connect(&self.config.netplay_address, rom_id, NetplayRules::new(&self.config, self.cpu.quirks)) {
+                                Ok(session) => joined = Some(session),
+                                Err(e) => show_error("Could not join", format!("Could not join {}: {}", self.config.netplay_address, e)),
+                            }
+                        }
+                    });
+                }
+            });
+
+            if let Some(session) = joined {
+                self.start_netplay(session);
+            }
+            self.netplay_window_open = window_open;
+        }
+
+        if self.search_window_open {
+            let memory = self.cpu.memory();
+            let rom_id = &self.rom_id;
+            let all_cheats = &mut self.config.cheats;
+            let search = &mut self.memory_search;
+            let search_value = &mut self.search_value;
+            let watches = &mut self.watches;
+
+            Window::new("Memory search").open(&mut self.search_window_open).resizable(true).show(gui_ctx, |ui| {
+                if !watches.is_empty() {
+                    ui.heading("Watching: ");
+                    let mut removed = None;
+                    for (i, &addr) in watches.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.monospace(format!("{:03X}: {:02X} ({})", addr, memory[addr as usize], memory[addr as usize]));
+                            if ui.button("Stop watching").clicked() {
+                                removed = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = removed {
+                        watches.remove(i);
+                    }
+                    ui.separator();
+                }
+
+                // play until the value you're after changes then search again to narrow it down
+                ui.horizontal(|ui| {
+                    if ui.button("New search").clicked() {
+                        *search = Some(MemorySearch::new(memory));
+                    }
+
+                    ui.add_enabled_ui(search.is_some(), |ui| {
+                        let mut filter = None;
+                        if ui.button("Equal to").clicked() {
+                            filter = Some(SearchFilter::Equal(*search_value));
+                        }
+                        ui.add(egui::DragValue::new(search_value));
+                        for (label, option) in [
+                            ("Changed", SearchFilter::Changed),
+                            ("Unchanged", SearchFilter::Unchanged),
+                            ("Increased", SearchFilter::Increased),
+                            ("Decreased", SearchFilter::Decreased),
+                        ] {
+                            if ui.button(label).clicked() {
+                                filter = Some(option);
+                            }
+                        }
+
+                        if let (Some(search), Some(filter)) = (search.as_mut(), filter) {
+                            search.refine(memory, filter);
+                        }
+                    });
+                });
+
+                let Some(search) = search else {
+                    ui.label("Start a new search to take a snapshot of memory.");
+                    return;
+                };
+                ui.label(format!("{} addresses left after {} searches", search.candidates().len(), search.searches()));
+
+                let mut promoted = None;
+                egui::ScrollArea::vertical().max_height(300.0).show_rows(ui, ui.spacing().interact_size.y, search.candidates().len(), |ui, rows| {
+                    for &addr in &search.candidates()[rows] {
+                        ui.horizontal(|ui| {
+                            ui.monospace(format!("{:03X}: {:02X} -> {:02X}", addr, search.previous(addr), memory[addr as usize]));
+                            if ui.add_enabled(rom_id.is_some(), egui::Button::new("Make cheat")).clicked() {
+                                promoted = Some(addr);
+                            }
+                            if ui.add_enabled(!watches.contains(&addr), egui::Button::new("Watch")).clicked() {
+                                watches.push(addr);
+                            }
+                        });
+                    }
+                });
+
+                if let (Some(addr), Some(rom_id)) = (promoted, rom_id) {
+                    all_cheats.entry(rom_id.clone()).or_default().push(Cheat {
+                        name: format!("{:03X}", addr),
+                        address: addr,
+                        value: memory[addr as usize],
+                        ..Cheat::default()
+                    });
+                }
+            });
+        }
+
+        if self.editor_window_open {
+            let mut assembled = None;
+
+            Window::new("Octo editor").open(&mut self.editor_window_open).resizable(true).default_width(500.0).show(gui_ctx, |ui| {
+                assembled = self.octo_editor.show(ui);
+            });
+
+            if let Some(rom) = assembled {
+                self.start_rom(ctx, rom, &self.octo_editor.name());
+            }
+        }
+
+        if self.netplay.is_some() {
+            self.plugins.show(gui_ctx, &mut self.cpu.clone());
+        }
+        else {
+            self.plugins.show(gui_ctx, &mut self.cpu);
+        }
+
+        if let Some(properties) = &self.rom_properties {
+            let mut window_open = true;
+
+            Window::new("ROM properties").open(&mut window_open).resizable(false).show(gui_ctx, |ui| {
+                egui::Grid::new("ROM properties grid").num_columns(2).striped(true).show(ui, |ui| {
+                    for (name, value) in properties {
+                        ui.label(*name);
+                        ui.monospace(value);
+                        ui.end_row();
+                    }
+                });
+
+                if ui.button("Copy").on_hover_text("For pasting into a compatibility report").clicked() {
+                    let text: Vec<String> = properties.iter().map(|(name, value)| format!("{}: {}", name, value)).collect();
+                    ui.output_mut(|output| output.copied_text = text.join("\n"));
+                }
+            });
+
+            if !window_open {
+                self.rom_properties = None;
+            }
+        }
+
+        if let Some((archive_path, roms)) = &self.archive_choice {
+            let mut window_open = true;
+            let mut chosen = None;
+
+            let title = archive_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            Window::new(format!("Choose a ROM from {}", title)).id(egui::Id::new("Archive choice")).open(&mut window_open).show(gui_ctx, |ui| {
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for rom in roms {
+                        if ui.button(rom).clicked() {
+                            chosen = Some(archive_path.join(rom));
+                        }
+                    }
+                });
+            });
+
+            if let Some(path) = chosen {
+                self.archive_choice = None;
+                if self.confirm_discard("Load ROM") {
+                    self.load_with_undo(ctx, |io, ctx| io.open_rom_file(ctx, &path));
+                }
+            }
+            else if !window_open {
+                self.archive_choice = None;
+            }
+        }
+
+        if let Some(sides) = &mut self.state_diff {
+            let mut window_open = true;
+            let live = self.cpu.save_state();
+
+            Window::new("Compare states").open(&mut window_open).resizable(true).show(gui_ctx, |ui| {
+                for (label, side) in ["A: ", "B: "].into_iter().zip(sides.iter_mut()) {
+                    ui.horizontal(|ui| {
+                        ui.label(label);
+                        if ui.selectable_label(matches!(side, DiffSide::Live), "Live").clicked() {
+                            *side = DiffSide::Live;
+                        }
+                        let name = match side {
+                            DiffSide::Saved { name, .. } => name.clone(),
+                            DiffSide::Live => String::from("From file..."),
+                        };
+                        if ui.selectable_label(matches!(side, DiffSide::Saved { .. }), name).clicked() {
+                            if let Some(path) = rfd::FileDialog::new().add_filter("Savestates", &[SAVESTATE_EXTENSION]).pick_file() {
+                                match savestate::load(&path) {
+                                    Ok(saved) => {
+                                        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+                                        *side = DiffSide::Saved { name, state: saved.state };
+                                    },
+                                    Err(e) => show_error("Could not load the state", e),
+                                }
+                            }
+                        }
+                    });
+                }
+                ui.separator();
+
+                let [a, b] = sides.each_ref().map(|side| match side {
+                    DiffSide::Live => &live,
+                    DiffSide::Saved { state, .. } => state,
+                });
+                let changes = savestate::diff(a, b);
+                if changes.is_empty() {
+                    ui.label("The states are the same.");
+                    return;
+                }
+
+                egui::Grid::new("State diff fields").num_columns(3).striped(true).show(ui, |ui| {
+                    for (name, a, b) in &changes.fields {
+                        ui.label(name);
+                        ui.monospace(a);
+                        ui.monospace(b);
+                        ui.end_row();
+                    }
+                });
+
+                if !changes.memory.is_empty() {
+                    ui.heading(format!("Memory: {} ranges changed", changes.memory.len()));
+                    egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                        for change in &changes.memory {
+                            let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+                            let end = change.start as usize + change.before.len() - 1;
+                            ui.monospace(format!("{:03X}-{:03X}: {} -> {}", change.start, end, hex(&change.before), hex(&change.after)));
+                        }
+                    });
+                }
+            });
+
+            if !window_open {
+                self.state_diff = None;
+            }
+        }
+
+        if self.step_window_open {
+            let mut step = false;
+            let pc = self.cpu.pc();
+            let opcode = self.cpu.read_opcode(pc);
+            let instruction = Instruction::decode(opcode);
+            let explanation = explain::explain(instruction, &self.cpu.quirks);
+            let pressed_keys = self.config.keyboard.pressed_keys(self.control_scheme(), &ctx.keyboard);
+
+            // running a copy of the whole machine is only worth it while paused, and only again once the state,
+            // quirks or keys it ran with have changed
+            let preview_key = (self.cpu.state_hash(), self.cpu.quirks, pressed_keys);
+            if self.paused && self.step_preview.as_ref().is_none_or(|(key, _)| *key != preview_key) {
+                self.step_preview = Some((preview_key, self.preview_step(pressed_keys)));
+            }
+            let changes = self.step_preview.take().filter(|_| self.paused).map(|(_, changes)| changes);
+
+            Window::new("Step through").open(&mut self.step_window_open).resizable(true).show(gui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button(if self.paused { "Run" } else { "Pause" }).clicked() {
+                        self.paused = !self.paused;
+                    }
+                    step = ui.add_enabled(self.paused && !self.cpu.halted(), egui::Button::new("Step")).clicked();
+                });
+                ui.label("Hold keypad keys down while pressing Step for instructions that read the keypad.");
+                ui.separator();
+
+                ui.monospace(format!("{:03X}: {:04X}  {}", pc, opcode, instruction));
+                ui.label(egui::RichText::new(format!("{}: {}", explanation.pattern, explanation.text)).strong());
+                if self.cpu.halted() {
+                    ui.label("The program has stopped, so nothing will happen.");
+                }
+                ui.separator();
+
+                ui.label("Will change: ");
+                match &changes {
+                    None => {
+                        ui.label("Pause to see what the next instruction will change.");
+                    },
+                    Some(Ok(changes)) if changes.is_empty() => {
+                        ui.label("Nothing, not even the program counter.");
+                    },
+                    Some(Ok(changes)) => {
+                        egui::Grid::new("Step changes").num_columns(3).show(ui, |ui| {
+                            for (name, before, after) in &changes.fields {
+                                // the copy has its own random numbers so the value it came up with means nothing
+                                let after = match instruction {
+                                    Instruction::Random(x, _) if *name == format!("V{:X}", x) => "random",
+                                    _ => after.as_str(),
+                                };
+                                ui.label(name);
+                                ui.monospace(before);
+                                ui.monospace(after);
+                                ui.end_row();
+                            }
+                            for change in &changes.memory {
+                                ui.label(format!("Memory {:03X}", change.start));
+                                ui.monospace(format!("{:02X?}", change.before));
+                                ui.monospace(format!("{:02X?}", change.after));
+                                ui.end_row();
+                            }
+                        });
+                    },
+                    Some(Err(e)) => {
+                        ui.label(e);
+                    },
+                }
+
+                if !explanation.quirks.is_empty() {
+                    ui.separator();
+                    ui.label("Quirks that change this instruction: ");
+                    let quirks_used = self.cpu.quirks_used();
+                    for quirk in explanation.quirks {
+                        quirk_setting(ui, quirk, &mut self.cpu.quirks, quirks_used.contains(&quirk));
+                    }
+                }
+            });
+
+            self.step_preview = changes.map(|changes| (preview_key, changes));
+
+            if step {
+                self.step_instruction(ctx, pressed_keys)?;
+            }
+        }
+
+        if self.memory_window_open {
+            Window::new("Memory").open(&mut self.memory_window_open).resizable(true).show(gui_ctx, |ui| {
+                self.hex_view.show(ui, &self.cpu);
+            });
+        }
+
+        if let (true, Some(_)) = (self.halt_window_open, self.cpu.error()) {
+            let report = diagnostics::report(&self.cpu);
+            let mut reset = false;
+
+            Window::new("Program stopped").open(&mut self.halt_window_open).resizable(true).show(gui_ctx, |ui| {
+                ui.label("The ROM may need different quirks or be for another CHIP-8 variant.");
+                ui.separator();
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    ui.monospace(&report);
+                });
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Copy report").clicked() {
+                        ui.output_mut(|output| output.copied_text = report.clone());
+                    }
+                    if ui.button("Open debugger").clicked() {
+                        self.step_window_open = true;
+                    }
+                    if ui.button("Reset").clicked() {
+                        reset = true;
+                    }
+                });
+            });
+
+            if reset {
+                match self.last_loaded_rom {
+                    Some(_) => {
+                        self.start_transition(self.config.transitions.on_reset);
+                        self.restart_rom();
+                    },
+                    None => self.reset_cpu(SPLASH_ROM),
+                }
+                self.halt_window_open = false;
+            }
+        }
+
+        if self.draw_log_window_open {
+            Window::new("Draw log").open(&mut self.draw_log_window_open).resizable(true).show(gui_ctx, |ui| {
+                self.draw_log.show(ui);
+            });
+        }
+
+        if self.call_tree_window_open {
+            Window::new("Call tree").open(&mut self.call_tree_window_open).resizable(true).show(gui_ctx, |ui| {
+                self.call_tree.show(ui, &self.cpu);
+            });
+        }
+
+        if self.timeline_window_open {
+            let mut recording = self.history.is_some();
+            let mut seek = None;
+
+            Window::new("Timeline").open(&mut self.timeline_window_open).resizable(false).show(gui_ctx, |ui| {
+                if ui.checkbox(&mut recording, "Record history").changed() {
+                    self.history = recording.then(History::new);
+                    self.timeline_frame = 0;
+                }
+                ui.label("Keeps the last ten minutes of input. Drag along the timeline to go back to any frame, then resume to play on from there, which records over what came after.");
+
+                if let Some(history) = &self.history {
+                    let frames = history.frames();
+                    let mut frame = self.timeline_frame;
+                    let slider = egui::Slider::new(&mut frame, frames.clone())
+                        .custom_formatter(|frame, _| speedrun::format_time(frame as u32, TIMER_HZ))
+                        .custom_parser(|_| None);
+                    if ui.add_enabled(frames.start() != frames.end(), slider).changed() {
+                        seek = Some(frame);
+                    }
+
+                    let keys = history.input(self.timeline_frame).map_or(0, |input| input.keys);
+                    ui.monospace(format!("Frame {} of {}, keys {:04X}", self.timeline_frame, frames.end(), keys));
+                }
+            });
+
+            if let Some(frame) = seek {
+                self.seek_history(ctx, frame)?;
+            }
+        }
+
+        if self.gallery_window_open {
+            let mut restore = None;
+            Window::new("Screenshots").open(&mut self.gallery_window_open).resizable(true).show(gui_ctx, |ui| {
+                restore = self.gallery.show(ui, self.rom_id.as_ref());
+            });
+
+            if let Some(state) = restore {
+                self.restore_state(ctx, &state);
+            }
+        }
+
+        if self.attract_window_open {
+            let mut action = None;
+            Window::new("Attract mode").open(&mut self.attract_window_open).resizable(true).show(gui_ctx, |ui| {
+                action = attract::show(ui, &mut self.config.attract, &self.config.library, self.attract.as_ref());
+            });
+
+            match action {
+                Some(AttractAction::Start) => self.start_attract(ctx),
+                Some(AttractAction::Stop) => self.attract = None,
+                None => {},
+            }
+        }
+
+        if self.saves_window_open {
+            let mut action = None;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+            Window::new("Saves").open(&mut self.saves_window_open).resizable(true).show(gui_ctx, |ui| {
+                action = saves::show(ui, &self.save_slots, &mut self.save_name, now);
+            });
+
+            if let (Some(action), Some(rom_id)) = (action, &self.rom_id) {
+                let path = saves::archive_path(ctx.fs.user_data_dir(), rom_id);
+                match action {
+                    SaveAction::Save(name) => {
+                        if let Err(e) = saves::save(&path, &name, &self.cpu) {
+                            show_error("Could not save the state", e);
+                        }
+                        self.save_name.clear();
+                    },
+                    SaveAction::Load(name) => match saves::load(&path, &name) {
+                        Ok(saved) => self.restore_saved(ctx, saved),
+                        Err(e) => show_error("Could not load the state", e),
+                    },
+                    SaveAction::Delete(name) => {
+                        if let Err(e) = saves::delete(&path, &name) {
+                            show_error("Could not delete the state", e);
+                        }
+                    },
+                }
+                self.refresh_save_slots(ctx);
+            }
+        }
+
+        if self.library_window_open {
+            let mut play = None;
+            let mut add_archive = false;
+            let mut export_manifest = false;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+            Window::new("Library").open(&mut self.library_window_open).resizable(true).show(gui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("Attract mode").on_hover_text("Cycles through a playlist of ROMs, for leaving running on a display").clicked() {
+                        self.attract_window_open = true;
+                    }
+                    add_archive = ui.button("Add ROMs from archive").on_hover_text("Adds every ROM in a zip file without unpacking it").clicked();
+                    export_manifest = ui.button("Export manifest")
+                        .on_hover_text("Checks every ROM's file against its hash and saves the results as CSV or JSON")
+                        .clicked();
+                });
+                play = library::show(ui, &mut self.config.library, &mut self.config.library_sort, now);
+            });
+
+            if let Some(rom_id) = play.filter(|_| self.confirm_discard("Load ROM")) {
+                self.load_with_undo(ctx, |io, ctx| io.play_from_library(ctx, &rom_id));
+            }
+            if export_manifest {
+                self.export_manifest();
+            }
+            if add_archive {
+                if let Some(path) = rfd::FileDialog::new().add_filter("Archives", &[archive::ARCHIVE_EXTENSION]).pick_file() {
+                    if let Err(e) = self.add_archive_to_library(&path) {
+                        show_error("Could not add the archive", e);
+                    }
+                }
+            }
+        }
+
+        if self.sprites_window_open {
+            let mut action = None;
+            let can_patch_rom = self.rom_path.as_ref().is_some_and(|path| archive::split(path).is_none());
+
+            Window::new("Sprite editor").open(&mut self.sprites_window_open).resizable(false).show(gui_ctx, |ui| {
+                action = self.sprite_editor.show(ui, &mut self.cpu, can_patch_rom);
+            });
+
+            if let Some(SpriteAction::PatchRom { address, bytes }) = action {
+                let name = self.rom_path.as_ref().and_then(|path| path.file_name()).map(|name| name.to_string_lossy()).unwrap_or_default();
+                let confirmed = rfd::MessageDialog::new()
+                    .set_title("Patch ROM file")
+                    .set_description(format!("Write {} bytes into {} at {:03X}? This changes the file itself.", bytes.len(), name, address))
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show();
+
+                if confirmed == rfd::MessageDialogResult::Yes {
+                    if let Err(e) = self.patch_rom_file(address, &bytes) {
+                        show_error("Could not patch the ROM", e);
+                    }
+                }
+            }
+        }
+
+        if self.patterns_window_open {
+            Window::new("XO-CHIP sound editor").open(&mut self.patterns_window_open).resizable(false).show(gui_ctx, |ui| {
+                self.pattern_editor.show(ui, &self.config.audio);
+            });
+        }
+        else {
+            self.pattern_editor.stop_preview(); // closing the window shouldn't leave it playing
+        }
+
+        if self.config.speedrun.overlay {
+            Window::new("Speedrun")
+                .title_bar(false)
+                .anchor(egui::Align2::LEFT_TOP, [8.0, self.menu_bar_height / self.scale_factor + 8.0])
+                .resizable(false)
+                .show(gui_ctx, |ui| {
+                    self.run_timer.show(ui, TIMER_HZ);
+                });
+        }
+
+        let tour_event = self.tour.as_mut().and_then(|tour| tour.show(gui_ctx, &tour_anchors, self.config.keyboard.layout));
+        match tour_event {
+            Some(TourEvent::LoadRom) => {
+                if let Some((name, rom)) = BUILT_IN_ROMS.iter().find(|(name, _)| *name == TOUR_ROM) {
+                    self.start_rom(ctx, rom.to_vec(), name);
+                }
+            },
+            Some(TourEvent::OpenConfiguration) => {
+                self.config_window_open = true;
+                self.audio_devices = audio::output_device_names();
+            },
+            Some(TourEvent::OpenDebugger) => self.step_window_open = true,
+            Some(TourEvent::Finished) => {
+                self.tour = None;
+                self.config.tour_finished = true;
+            },
+            None => {},
+        }
+
+        if self.config.touch.show_keypad {
+            let mut touch_keys = 0;
+
+            Window::new("Keypad")
+                .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+                .resizable(false)
+                .show(gui_ctx, |ui| {
+                    egui::Grid::new("Keypad buttons").show(ui, |ui| {
+                        for (i, key) in KEYPAD_GRID.into_iter().enumerate() {
+                            let button = ui.add(egui::Button::new(format!("{:X}", key)).min_size(egui::vec2(40.0, 40.0)));
+                            if button.is_pointer_button_down_on() {
+                                touch_keys |= 1 << key;
+                            }
+                            if i % 4 == 3 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+                });
+
+            self.release_keys(self.touch_keys & !touch_keys);
+            self.touch_keys = touch_keys;
+        }
+        else {
+            self.release_keys(self.touch_keys);
+            self.touch_keys = 0;
+        }
+
+        self.gui.update(ctx);
+
+        if (self.screen_colours(), self.config.accessibility, self.config.gradient, self.config.gradient_colour) != old_colours {
+            self.display_dirty = true;
+        }
+
+        let height = height * self.scale_factor;
+        if height != self.menu_bar_height {
+            // the menu bar grows and shrinks with the UI scale so the screen needs to be laid out again
+            self.menu_bar_height = height;
+            self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)?;
+        }
+        //ctx.gfx.set_drawable_size(SCREEN_SIZE.0, SCREEN_SIZE.1 as f32 + height)?; // make room for whole game
+
+        Ok(())
+    }
+
+    fn draw_gui(&mut self, canvas: &mut Canvas) {
+        canvas.draw(
+            &self.gui,
+            DrawParam::default().dest(Vec2::ZERO),
+        );
+    }
+
+    fn draw_screen(&mut self, ctx: &mut Context, canvas: &mut Canvas) -> GameResult {
+        let accessibility = self.config.accessibility;
+        let reduce_flicker = accessibility.reduce_flicker && !accessibility.reduce_motion; // fading out is motion too
+        let (width, height) = (self.cpu.width(), self.cpu.height());
+
+        if self.phosphor.len() != width * height {
+            self.phosphor = vec![0.0; width * height];
+        }
+        if reduce_flicker {
+            // lit pixels are at full brightness straight away, it's only turning off that's slowed down
+            let fade = ctx.time.delta().as_secs_f32() / PHOSPHOR_FADE_TIME.as_secs_f32();
+            for y in 0..height {
+                for x in 0..width {
+                    let level = &mut self.phosphor[y * width + x];
+                    if self.cpu.pixel(x, y) {
+                        *level = 1.0;
+                    }
+                    else if *level > 0.0 {
+                        *level = (*level - fade).max(0.0);
+                        self.display_dirty = true;
+                    }
+                }
+            }
+        }
+
+        // the texture only needs uploading again when something has changed since the last frame
+        if self.display_dirty {
+            self.display_dirty = false;
+
+            let (on_colour, off_colour) = self.screen_colours();
+            let on_colour = Color { a: 1.0, ..on_colour }; // lit pixels are always solid
+            let gradient = if accessibility.high_contrast || accessibility.reduce_motion { Gradient::Off } else { self.config.gradient };
+            let gradient_colour = Color { a: 1.0, ..self.config.gradient_colour };
+
+            self.screen_pixels.clear();
+            for y in 0..height {
+                for x in 0..width {
+                    let on_colour = match gradient {
+                        Gradient::Off => on_colour,
+                        Gradient::Rows => lerp_colour(on_colour, gradient_colour, y as f32 / (height - 1) as f32),
+                        Gradient::Columns => lerp_colour(on_colour, gradient_colour, x as f32 / (width - 1) as f32),
+                    };
+                    let level = if reduce_flicker { self.phosphor[y * width + x] } else { self.cpu.pixel(x, y) as u8 as f32 };
+                    let (r, g, b, a) = lerp_colour(off_colour, on_colour, level).to_rgba();
+                    self.screen_pixels.extend_from_slice(&[r, g, b, a]);
+                }
+            }
+
+            self.screen_image = Image::from_pixels(
+                &ctx.gfx,
+                &self.screen_pixels,
+                ImageFormat::Rgba8UnormSrgb,
+                self.cpu.width() as u32,
+                self.cpu.height() as u32,
+            );
+        }
+
+        if let (Some(post_shader), false) = (&mut self.post_shader, accessibility.reduce_motion) {
+            if let Err(e) = post_shader.reload_if_changed(ctx) {
+                show_error("Could not reload the shader", e);
+            }
+            canvas.set_shader(&post_shader.shader);
+        }
+        canvas.set_sampler(Sampler::nearest_clamp());
+        canvas.draw(
+            &self.screen_image,
+            DrawParam::new()
+                .dest(Vec2::new(self.width_offset, self.height_offset + self.menu_bar_height))
+                .scale(Vec2::splat(self.pixel_size)),
+        );
+        if accessibility.reduce_motion {
+            self.transition = None; // it may have been turned on part way through one
+        }
+        if let Some(transition) = &mut self.transition {
+            if transition.advance(ctx.time.delta()) {
+                // the old screen may have been a different resolution but it still covers the whole of the new one
+                let image = Image::from_pixels(
+                    &ctx.gfx,
+                    &transition.overlay(),
+                    ImageFormat::Rgba8UnormSrgb,
+                    transition.width() as u32,
+                    transition.height() as u32,
+                );
+                canvas.draw(
+                    &image,
+                    DrawParam::new()
+                        .dest(Vec2::new(self.width_offset, self.height_offset + self.menu_bar_height))
+                        .scale(Vec2::splat(self.pixel_size * width as f32 / transition.width() as f32)),
+                );
+            }
+            else {
+                self.transition = None;
+            }
+        }
+        canvas.set_sampler(Sampler::default());
+        canvas.set_default_shader();
+
+        if accessibility.visual_beep && self.beeping {
+            let (window_width, window_height) = ctx.gfx.drawable_size();
+            let border = Rect::new(
+                VISUAL_BEEP_WIDTH / 2.0,
+                self.menu_bar_height + VISUAL_BEEP_WIDTH / 2.0,
+                window_width - VISUAL_BEEP_WIDTH,
+                window_height - self.menu_bar_height - VISUAL_BEEP_WIDTH,
+            );
+            canvas.draw(&Mesh::new_rectangle(ctx, DrawMode::stroke(VISUAL_BEEP_WIDTH), border, VISUAL_BEEP_COLOUR)?, DrawParam::default());
+        }
+
+        Ok(())
+    }
+
+    fn update_frame(&mut self, ctx: &mut Context) -> GameResult {
+        self.update_scale_factor(ctx);
+        self.handle_remote_requests(ctx)?;
+        self.handle_midi();
+        self.poll_netplay_host();
+        self.handle_hotkeys(ctx)?;
+        self.update_attract(ctx);
+
+        let idle = self.is_idle(ctx);
+
+        if idle {
+            // nothing will change so there's no need to keep a core busy, the GUI just needs to stay responsive
+            self.tick_accumulator = Duration::ZERO;
+            self.beeping = false;
+            // there are no frames to put them in but letting go of a key still has to finish an FX0A
+            let typed = self.input_queue.take_until(Instant::now());
+            self.release_keys(typed.released);
+            if let Some(buzzer) = &mut self.buzzer {
+                buzzer.set_beeping(false);
+            }
+        }
+        else {
+            self.update_cpu(ctx)?;
+            if let Some(entry) = self.rom_id.as_ref().and_then(|rom_id| self.config.library.get_mut(rom_id)) {
+                entry.play_time += ctx.time.delta().as_secs_f64();
+            }
+        }
+        self.update_background_tabs(ctx);
+        if !self.gui_skipped() {
+            if mem::take(&mut self.gui_input_stale) {
+                let (width, height) = ctx.gfx.drawable_size();
+                self.gui.input.set_scale_factor(self.scale_factor, (width, height));
+                self.gui.input.resize_event(width, height);
+            }
+            self.update_gui(ctx)?;
+        }
+        else if self.menu_bar_height != 0.0 {
+            self.menu_bar_height = 0.0;
+            self.resize_event(ctx, ctx.gfx.drawable_size().0, ctx.gfx.drawable_size().1)?;
+        }
+
+        if idle {
+            thread::sleep(IDLE_FRAME_TIME);
+        }
+
+        if ctx.time.ticks() % 100 == 0 {
+            println!("Delta frame time: {:?} ", ctx.time.delta());
+            println!("Average FPS: {}", ctx.time.fps());
+        }
+
+        Ok(())
+    }
+
+    fn draw_frame(&mut self, ctx: &mut Context) -> GameResult {
+        let mut canvas = Canvas::from_frame(ctx, Color::BLACK);
+        
+        self.draw_screen(ctx, &mut canvas)?;
+        if !self.gui_skipped() {
+            self.draw_gui(&mut canvas);
+        }
+
+        canvas.finish(ctx)
+    }
+
+    // the state could be anything after a panic so rather than carry on, everything that might help work out what
+    // happened is written to disk and the emulator closes
+    fn crashed(&self, ctx: &Context) -> ! {
+        let dir = ctx.fs.user_data_dir().join(CRASH_DIR);
+        let context = CrashContext {
+            cpu: &self.cpu,
+            config: &self.config,
+            rom_name: &self.tabs[self.active_tab].name,
+            rom_id: self.rom_id.as_deref(),
+        };
+
+        match crash::write_report(&dir, &context) {
+            Ok(path) => show_error("Fish n CHIP-8 crashed", format!(
+                "Sorry, something went wrong and the emulator has to close.\n\nA crash report and a savestate of where the program was were saved to {}. Attaching them to a bug report will help get it fixed.",
+                path.display(),
+            )),
+            Err(e) => show_error("Fish n CHIP-8 crashed", format!("Sorry, something went wrong and the emulator has to close. The crash report couldn't be saved either: {}", e)),
+        }
+
+        process::exit(101);
+    }
+
+    // runs an event handler, crashing with a report if it panics
+    fn guarded<T>(&mut self, ctx: &mut Context, handler: impl FnOnce(&mut EmulatorIO, &mut Context) -> T) -> T {
+        match panic::catch_unwind(AssertUnwindSafe(|| handler(self, ctx))) {
+            Ok(result) => result,
+            Err(_) => self.crashed(ctx),
+        }
+    }
+}
+
+// the event handlers, each run by the EventHandler below so a panic in any of them writes a crash report
+impl EmulatorIO {
+    fn key_down(&mut self, ctx: &mut Context, input: KeyInput, repeated: bool) -> GameResult {
+        if let (Some(key), false, false) = (self.keypad_key(input), repeated, self.gui.ctx().wants_keyboard_input()) {
+            self.input_queue.push(key, true, Instant::now());
+        }
+
+        // Esc quits like it always has, unless the menu bar is hidden when it brings it back so there's always a way
+        // out that doesn't need remembering a hotkey
+        if input.keycode == Some(KeyCode::Escape) {
+            if self.menu_bar_hidden {
+                self.menu_bar_hidden = false;
+            }
+            else {
+                ctx.request_quit();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn key_up(&mut self, input: KeyInput) -> GameResult {
+        // still let go of while typing, it may have been held down since before
+        if let Some(key) = self.keypad_key(input) {
+            self.input_queue.push(key, false, Instant::now());
+        }
+
+        Ok(())
+    }
+
+    fn quit(&mut self, ctx: &mut Context) -> GameResult<bool> {
+        if !self.confirm_discard("Quit") {
+            return Ok(true); // stays open
+        }
+
+        self.config.quirks = self.cpu.quirks;
+        if let Some(recording) = self.recording.take() {
+            if let Err(e) = recording.finish() {
+                show_error("Could not save the video", e);
+            }
+        }
+        if let Some(mut debugger) = self.debugger.take() {
+            // it has nothing to follow once the emulator is gone
+            let _ = debugger.kill();
+            let _ = debugger.wait();
+        }
+
+        if self.lite {
+            return Ok(false); // its settings came from the command line, the configuration file stays as it was
+        }
+        self.save_window(ctx);
+        if !self.save_config {
+            return Ok(false);
+        }
+        if let Err(e) = self.config.save(ctx.fs.user_config_dir()) {
+            println!("Failed to save config: {}", e);
+        }
+
+        Ok(false)
+    }
+
+    // touches arrive as left clicks so swipes work with a mouse as well
+    fn mouse_button_down(&mut self, button: MouseButton, x: f32, y: f32) -> GameResult {
+        if button == MouseButton::Left && !self.gui.ctx().is_pointer_over_area() {
+            self.swipe_start = Some((x, y));
+        }
+
+        Ok(())
+    }
+
+    fn mouse_button_up(&mut self, button: MouseButton, x: f32, y: f32) -> GameResult {
+        if button != MouseButton::Left {
+            return Ok(());
+        }
+
+        if let Some(start) = self.swipe_start.take() {
+            if let Some(key) = self.config.touch.key_for_swipe(start, (x, y)) {
+                self.swipe_press = Some((key, SWIPE_PRESS_TICKS));
+            }
+        }
+
+        Ok(())
+    }
+
+    // typing while egui is left out would all turn up in whatever text box has focus once it's back
+    fn text_input(&mut self, character: char) -> GameResult {
+        if !self.gui_skipped() {
+            self.gui.input.text_input_event(character);
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, width: f32, height: f32) -> GameResult {
+        if width == 0.0 || height == 0.0 {
+            return Ok(());
+        }
+
+        self.pixel_size = (width / self.cpu.width() as f32).min(height / self.cpu.height() as f32).floor(); // allow resizing from both directions without part of the screen being cut off
+
+        match self.gui_skipped() {
+            true => self.gui_input_stale = true,
+            false => self.gui.input.resize_event(width, height),
+        }
+
+        self.height_offset = ((height - self.menu_bar_height) - self.pixel_size * self.cpu.height() as f32) / 2.0;
+        self.width_offset = (width - self.pixel_size * self.cpu.width() as f32) / 2.0;
+
+        Ok(())
+    }
+}
+
+impl EventHandler for EmulatorIO {
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeated: bool) -> GameResult {
+        self.guarded(ctx, |io, ctx| io.key_down(ctx, input, repeated))
+    }
+
+    fn key_up_event(&mut self, ctx: &mut Context, input: KeyInput) -> GameResult {
+        self.guarded(ctx, |io, _| io.key_up(input))
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> GameResult {
+        self.guarded(ctx, |io, ctx| io.update_frame(ctx))
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> GameResult {
+        self.guarded(ctx, |io, ctx| io.draw_frame(ctx))
+    }
+
+    fn quit_event(&mut self, ctx: &mut Context) -> GameResult<bool> {
+        self.guarded(ctx, |io, ctx| io.quit(ctx))
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> GameResult {
+        self.guarded(ctx, |io, _| io.mouse_button_down(button, x, y))
+    }
+
+    fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) -> GameResult {
+        self.guarded(ctx, |io, _| io.mouse_button_up(button, x, y))
+    }
+
+    fn text_input_event(&mut self, ctx: &mut Context, character: char) -> GameResult {
+        self.guarded(ctx, |io, _| io.text_input(character))
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        self.guarded(ctx, |io, _| io.resize(width, height))
+    }
+}
+
+// a summary of a ROM for cataloguing collections and compatibility reports
+fn rom_properties(rom: &[u8], file_name: Option<String>, metadata: Option<&RomMetadata>) -> Vec<(&'static str, String)> {
+    let map = CodeMap::analyse(rom);
+    let mut distinct_opcodes: Vec<u16> = map.opcodes().iter().map(|&(_, opcode)| opcode).collect();
+    distinct_opcodes.sort_unstable();
+    distinct_opcodes.dedup();
+
+    let entry_point = match map.opcodes().first() {
+        Some(&(_, opcode)) if opcode & 0xF000 == 0x1000 => format!("{:03X}, jumping straight to {:03X}", cpu::PROGRAM_START, opcode & 0xFFF),
+        _ => format!("{:03X}", cpu::PROGRAM_START),
+    };
+    let unsupported = if map.dead_ends().is_empty() {
+        String::from("None")
+    }
+    else {
+        let opcodes: Vec<String> = map.dead_ends().iter().map(|&(addr, opcode)| format!("{:04X} at {:03X}", opcode, addr)).collect();
+        opcodes.join(", ")
+    };
+
+    let mut properties = vec![
+        ("File", file_name.unwrap_or_else(|| String::from("Not loaded from a file"))),
+        ("Size", format!("{} bytes", rom.len())),
+        ("SHA-1", config::rom_id(rom)),
+        ("MD5", format!("{:x}", md5::compute(rom))),
+        ("Platform", map.platform().to_string()),
+        ("Entry point", entry_point),
+        ("Reachable code", format!("{} of {} bytes", map.code_bytes(), rom.len())),
+        ("Distinct opcodes", format!("{} in {} instructions", distinct_opcodes.len(), map.opcodes().len())),
+        ("Unsupported opcodes reached", unsupported),
+        ("Database entry", String::from("None, there's no ROM database yet")),
+    ];
+    // what came with it in a .c8b file or Octo options
+    properties.extend(metadata.map(RomMetadata::describe).unwrap_or_default());
+
+    properties
+}
+
+fn read_rom(path: &path::Path) -> Result<(Vec<u8>, Option<RomMetadata>), String> {
+    let (rom, metadata) = if archive::is_archive(path) {
+        archive::read(path, &archive::only_rom(path)?)?
+    }
+    else if let Some((archive, entry)) = archive::split(path) {
+        archive::read(&archive, &entry)?
+    }
+    else {
+        metadata::read(path)?
+    };
+
+    if rom.len() > cpu::MAX_ROM_SIZE {
+        return Err(format!("{} is {} bytes but ROMs can be at most {} bytes. Is this really a CHIP-8 ROM?", path.display(), rom.len(), cpu::MAX_ROM_SIZE));
+    }
+
+    Ok((rom, metadata))
+}
+
+pub(crate) fn show_error(title: &str, description: String) {
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title(title)
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
+// lights can't be see-through so whatever's translucent is dimmed towards black instead
+fn light_colour(colour: Color) -> ambient::Rgb {
+    let (r, g, b) = Color::new(colour.r * colour.a, colour.g * colour.a, colour.b * colour.a, 1.0).to_rgb();
+    [r, g, b]
+}
+
+fn lerp_colour(from: Color, to: Color, amount: f32) -> Color {
+    Color::new(
+        from.r + (to.r - from.r) * amount,
+        from.g + (to.g - from.g) * amount,
+        from.b + (to.b - from.b) * amount,
+        from.a + (to.a - from.a) * amount,
+    )
+}
+
+fn report_error(action: &str, result: GameResult) {
+    if let Err(e) = result {
+        show_error(action, format!("{}: {}", action, e));
+    }
+}
+
+// used is whether the quirk has made a difference to the program running, see CPU::quirks_used
+fn quirk_setting(ui: &mut egui::Ui, quirk: Quirk, quirks: &mut Quirks, used: bool) {
+    ui.horizontal(|ui| {
+        quirk_controls(ui, quirk, quirks);
+        if used {
+            ui.label("Used").on_hover_text("This program has run instructions this quirk changes the result of");
+        }
+        else {
+            ui.weak("Not used yet").on_hover_text("Nothing this program has done so far would come out differently with this changed");
+        }
+    });
+}
+
+fn quirk_controls(ui: &mut egui::Ui, quirk: Quirk, quirks: &mut Quirks) {
+    match quirk {
+        Quirk::VfReset => {
+            ui.label("VF reset by 8XY1, 8XY2 and 8XY3: ");
+            ui.checkbox(&mut quirks.vf_reset, "");
+        },
+        Quirk::Shifting => {
+            ui.label("Shifting opcodes operate on: ");
+            ui.selectable_value(&mut quirks.shifting, ShiftingReg::VX, "vX");
+            ui.selectable_value(&mut quirks.shifting, ShiftingReg::VY, "vY");
+        },
+        Quirk::RegSaveLoad => {
+            ui.label("Register save/load opcode behaviour: ");
+            ui.selectable_value(&mut quirks.reg_save_load, RegSaveLoadQuirk::Unchanged, "Do not modify I");
+            ui.selectable_value(&mut quirks.reg_save_load, RegSaveLoadQuirk::X, "I = I + X");
+            ui.selectable_value(&mut quirks.reg_save_load, RegSaveLoadQuirk::XPlusOne, "I = I + X + 1");
+        },
+        Quirk::Jump => {
+            ui.label("Jump opcode behaviour: ");
+            ui.selectable_value(&mut quirks.jump, JumpBehviour::BNNN, "BNNN");
+            ui.selectable_value(&mut quirks.jump, JumpBehviour::BXNN, "BXNN");
+        },
+        Quirk::ScreenWrap => {
+            ui.label("Sprites wrap at edges of screen: ");
+            ui.checkbox(&mut quirks.screen_wrap, "");
+        },
+        Quirk::Random => {
+            ui.label("Random number generator: ");
+            for algorithm in RngAlgorithm::ALL {
+                ui.selectable_value(&mut quirks.rng, algorithm, algorithm.name());
+            }
+        },
+        Quirk::KeyWaitGrace => {
+            ui.label("FX0A grace period: ");
+            ui.add(egui::Slider::new(&mut quirks.key_wait_grace, 0..=10).suffix(" frames"))
+                .on_hover_text("Keys held when FX0A starts are ignored until let go of, unless they were pressed this recently. Raise it if quick taps go missing");
+        },
+        Quirk::DisplayWait => {
+            ui.label("Wait for the display before drawing: ");
+            ui.checkbox(&mut quirks.display_wait, "")
+                .on_hover_text("Only one sprite is drawn each frame, as on the original COSMAC VIP. This slows some games down to their intended speed");
+        },
+    }
+}
+
+// edits a copy while it has focus so the address only changes once it's finished being typed,
+// or every half typed address would be tried
+fn address_edit(ui: &mut egui::Ui, id: &str, address: &mut String) {
+    let id = ui.make_persistent_id(id);
+    let mut text = ui.data_mut(|data| data.get_temp::<String>(id)).unwrap_or_else(|| address.clone());
+    let edit = ui.add(egui::TextEdit::singleline(&mut text).id(id).desired_width(160.0));
+
+    if edit.has_focus() {
+        ui.data_mut(|data| data.insert_temp(id, text));
+    } else {
+        ui.data_mut(|data| data.remove::<String>(id));
+        if edit.lost_focus() {
+            *address = text;
+        }
+    }
+}
+
+fn keypad_key_picker(ui: &mut egui::Ui, label: &str, key: &mut u8) {
+    ui.label(label);
+    egui::ComboBox::from_id_source(label)
+        .width(40.0)
+        .selected_text(format!("{:X}", key))
+        .show_ui(ui, |ui| {
+            for keypad_key in 0..16 {
+                ui.selectable_value(key, keypad_key, format!("{:X}", keypad_key));
+            }
+        });
+}
+
+pub fn emulator_main() {
+    emulator_main_with_plugins(vec![], vec![]);
+}
+
+// for builds of the emulator with plugins and ambient lights of their own compiled in, as well as the scripts in the
+// plugins folder and the lights in the configuration
+pub fn emulator_main_with_plugins(plugins: Vec<Box<dyn Plugin>>, lights: Vec<Box<dyn AmbientLights>>) {
+    let (mut ctx, event_loop) = context_builder().build().expect("Failed to create game context");
+
+    crash::install_panic_hook();
+    let game = EmulatorIO::new(&mut ctx, plugins, lights);
+
+    event::run(ctx, event_loop, game);
+}
+
+// the low resource profile, with everything set by the command line
+pub fn emulator_main_lite(options: LiteOptions, config: Config, quirks: Quirks) {
+    let (mut ctx, event_loop) = context_builder().build().expect("Failed to create game context");
+
+    crash::install_panic_hook();
+    let mut game = EmulatorIO::with_config(&mut ctx, vec![], vec![], config);
+    game.start_lite(&mut ctx, &options, quirks);
+
+    event::run(ctx, event_loop, game);
+}
+
+fn context_builder() -> ContextBuilder {
+    let resource_dir = if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+        let mut path = path::PathBuf::from(manifest_dir);
+        path.push("resources");
+        path
+    } else {
+        path::PathBuf::from("./resources")
+    };
+
+    ContextBuilder::new("fish_n_chip8", "jenningsfan")
+        .window_setup(WindowSetup::default().title(WINDOW_TITLE))
+        .window_mode(ggez::conf::WindowMode::default()
+            .dimensions(SCREEN_SIZE.0, SCREEN_SIZE.1)
+            .resizable(true)
+            .resize_on_scale_factor_change(true)
+        )
+        .add_resource_path(resource_dir)
 }
\ No newline at end of file
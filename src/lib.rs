@@ -0,0 +1,109 @@
+pub mod analysis;
+pub mod bench;
+pub mod cpu;
+pub mod diagnostics;
+pub mod disasm;
+pub mod explain;
+pub mod gym;
+pub mod history;
+pub mod instruction;
+pub mod movie;
+pub mod octo;
+pub mod random;
+
+// the desktop frontend, the browser has its own in web/
+#[cfg(not(target_arch = "wasm32"))]
+pub mod ambient;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod archive;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod attract;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod audio;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod breakpoints;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod bridge;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod calltree;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cheats;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod compare;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod config;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod crash;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod crowd;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod debugger;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod drawlog;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod editor;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod find;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod gallery;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hexview;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod hotkeys;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod input;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod input_queue;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod io;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod library;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod lite;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod manifest;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod metadata;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod midi;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod netplay;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod patch;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod patterns;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugins;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod recording;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod remote;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod roms;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod saves;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod savestate;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod shaders;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod speedrun;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sprites;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tabs;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod tour;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod trace;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod transition;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod undo;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod verify;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod web_export;
+
+#[cfg(target_arch = "wasm32")]
+pub mod web;
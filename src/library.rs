@@ -0,0 +1,154 @@
+use ggegui::egui;
+use serde::{Deserialize, Serialize};
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// a ROM that's been played, remembered so it can be found again
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default)]
+pub struct LibraryEntry {
+    pub name: String,
+    pub path: Option<PathBuf>, // None for built-in ROMs and ones that didn't come from a file
+    pub favourite: bool,
+    pub last_played: u64, // seconds since the Unix epoch
+    pub play_time: f64,   // seconds spent running it
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Default)]
+pub enum LibrarySort {
+    #[default]
+    LastPlayed,
+    PlayTime,
+    Name,
+}
+
+// keyed by rom_id
+pub type Library = HashMap<String, LibraryEntry>;
+
+pub fn played(library: &mut Library, rom_id: &str, name: &str, path: Option<PathBuf>, now: u64) {
+    let entry = library.entry(rom_id.to_string()).or_default();
+    entry.name = name.to_string();
+    entry.last_played = now;
+    // the same ROM loaded from the built-in menu shouldn't forget where its file is
+    if path.is_some() {
+        entry.path = path;
+    }
+}
+
+// for ROMs found rather than played, such as the ones in an archive, so they can be picked from the library without
+// being loaded first. Ones already in it are left as they are
+pub fn add(library: &mut Library, rom_id: &str, name: &str, path: PathBuf) {
+    library.entry(rom_id.to_string()).or_insert_with(|| LibraryEntry { name: name.to_string(), path: Some(path), ..LibraryEntry::default() });
+}
+
+// favourites first, then by whichever column was picked
+pub fn sorted(library: &Library, sort: LibrarySort) -> Vec<(&String, &LibraryEntry)> {
+    let mut entries: Vec<(&String, &LibraryEntry)> = library.iter().collect();
+    entries.sort_by(|(_, a), (_, b)| {
+        b.favourite.cmp(&a.favourite).then_with(|| match sort {
+            LibrarySort::LastPlayed => b.last_played.cmp(&a.last_played),
+            LibrarySort::PlayTime => b.play_time.total_cmp(&a.play_time),
+            LibrarySort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        })
+    });
+    entries
+}
+
+pub fn format_play_time(seconds: f64) -> String {
+    let minutes = (seconds / 60.0) as u64;
+    match minutes {
+        0 => String::from("under a minute"),
+        1..=59 => format!("{} min", minutes),
+        _ => format!("{} h {} min", minutes / 60, minutes % 60),
+    }
+}
+
+pub fn format_last_played(last_played: u64, now: u64) -> String {
+    if last_played == 0 {
+        return String::from("never");
+    }
+
+    let days = now.saturating_sub(last_played) / (24 * 60 * 60);
+    match days {
+        0 => String::from("today"),
+        1 => String::from("yesterday"),
+        _ => format!("{} days ago", days),
+    }
+}
+
+// the rom_id of the ROM to play, if one was picked
+pub fn show(ui: &mut egui::Ui, library: &mut Library, sort: &mut LibrarySort, now: u64) -> Option<String> {
+    if library.is_empty() {
+        ui.label("ROMs you play will show up here.");
+        return None;
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("Sort by: ");
+        ui.selectable_value(sort, LibrarySort::LastPlayed, "Last played");
+        ui.selectable_value(sort, LibrarySort::PlayTime, "Play time");
+        ui.selectable_value(sort, LibrarySort::Name, "Name");
+    });
+    ui.separator();
+
+    let mut play = None;
+    let mut favourite = None;
+    let mut removed = None;
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("Library").striped(true).show(ui, |ui| {
+            for (rom_id, entry) in sorted(library, *sort) {
+                if ui.selectable_label(entry.favourite, if entry.favourite { "★" } else { "☆" }).on_hover_text("Favourite").clicked() {
+                    favourite = Some(rom_id.clone());
+                }
+                ui.label(&entry.name).on_hover_text(entry.path.as_ref().map_or(String::from("Built in"), |path| path.display().to_string()));
+                ui.label(format_last_played(entry.last_played, now));
+                ui.label(format_play_time(entry.play_time));
+                if ui.button("Play").clicked() {
+                    play = Some(rom_id.clone());
+                }
+                if ui.button("Forget").clicked() {
+                    removed = Some(rom_id.clone());
+                }
+                ui.end_row();
+            }
+        });
+    });
+
+    if let Some(entry) = favourite.and_then(|rom_id| library.get_mut(&rom_id)) {
+        entry.favourite = !entry.favourite;
+    }
+    if let Some(rom_id) = removed {
+        library.remove(&rom_id);
+    }
+
+    play
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn favourites_sort_first() {
+        let mut library = Library::new();
+        played(&mut library, "a", "Pong", Some(PathBuf::from("pong.ch8")), 100);
+        played(&mut library, "b", "Brix", None, 300);
+        played(&mut library, "c", "Tetris", None, 200);
+        played(&mut library, "a", "Pong", None, 150);
+        add(&mut library, "a", "pong.ch8", PathBuf::from("pack.zip/pong.ch8"));
+        add(&mut library, "d", "brix.ch8", PathBuf::from("pack.zip/brix.ch8"));
+        library.get_mut("a").unwrap().play_time = 90.0;
+        library.get_mut("b").unwrap().play_time = 30.0;
+        library.get_mut("c").unwrap().favourite = true;
+
+        let order = |sort| sorted(&library, sort).into_iter().map(|(rom_id, _)| rom_id.as_str()).collect::<Vec<_>>();
+        assert_eq!(order(LibrarySort::LastPlayed), ["c", "b", "a", "d"]);
+        assert_eq!(order(LibrarySort::PlayTime), ["c", "a", "b", "d"]);
+        assert_eq!(order(LibrarySort::Name), ["c", "b", "d", "a"]);
+        assert_eq!(format_last_played(library["d"].last_played, 300), "never");
+        assert_eq!(library["a"].path, Some(PathBuf::from("pong.ch8")));
+        assert_eq!(library["a"].last_played, 150);
+        assert_eq!(format_play_time(library["a"].play_time), "1 min");
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+use std::process;
+
+use crate::config::{Config, Gradient, SharedSettings};
+use crate::cpu::Quirks;
+use crate::io;
+
+const USAGE: &str = "Usage: fish_n_chip8 --lite [rom] [--settings <file>] [--cycles <per frame>] [--windowed] [--mute]";
+
+// the low resource profile, for boards like a Pi Zero driving a small display. Everything is set on the command line,
+// starting from the defaults rather than the configuration file, which is left alone
+#[derive(PartialEq, Debug, Default)]
+pub struct LiteOptions {
+    pub rom: Option<PathBuf>,
+    pub settings: Option<PathBuf>, // exported from the configuration window on another machine
+    pub cycles_per_frame: Option<u16>,
+    pub windowed: bool,
+    pub mute: bool,
+}
+
+pub fn parse(args: &[String]) -> Result<LiteOptions, String> {
+    let mut options = LiteOptions::default();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+        match arg.as_str() {
+            "--settings" => options.settings = Some(PathBuf::from(value(arg)?)),
+            "--cycles" => {
+                let cycles = value(arg)?;
+                options.cycles_per_frame = Some(cycles.parse().map_err(|e| format!("Invalid cycles per frame {}: {}", cycles, e))?);
+            },
+            "--windowed" => options.windowed = true,
+            "--mute" => options.mute = true,
+            flag if flag.starts_with("--") => return Err(format!("Unknown option {}", flag)),
+            rom if options.rom.is_none() => options.rom = Some(PathBuf::from(rom)),
+            extra => return Err(format!("Only one ROM can be given, {} is one too many", extra)),
+        }
+    }
+
+    Ok(options)
+}
+
+// the configuration to run with, and the quirks from the settings file if there was one. Nothing that costs more than
+// drawing the pixels is turned on
+pub fn config(options: &LiteOptions) -> Result<(Config, Quirks), String> {
+    let mut config = Config::default();
+    let mut quirks = config.quirks;
+    if let Some(path) = &options.settings {
+        let settings = SharedSettings::load(path)?;
+        settings.apply(&mut config, None);
+        quirks = settings.quirks;
+    }
+    if let Some(cycles) = options.cycles_per_frame {
+        config.cycles_per_frame = cycles;
+    }
+
+    config.shader = None;
+    config.transitions.kind = None;
+    config.gradient = Gradient::Off;
+    config.accessibility.reduce_flicker = false;
+    config.tour_finished = true;
+    Ok((config, quirks))
+}
+
+pub fn lite_main(args: &[String]) {
+    let options = parse(args).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    });
+    let (config, quirks) = config(&options).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+
+    io::emulator_main_lite(options, config, quirks);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_options() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        let options = parse(&args(&["--cycles", "20", "pong.ch8", "--mute"])).unwrap();
+        assert_eq!(options, LiteOptions { rom: Some(PathBuf::from("pong.ch8")), cycles_per_frame: Some(20), mute: true, ..LiteOptions::default() });
+        assert_eq!(parse(&[]).unwrap(), LiteOptions::default());
+
+        assert!(parse(&args(&["--settings"])).unwrap_err().contains("needs a value"));
+        assert!(parse(&args(&["--cycles", "fast"])).is_err());
+        assert!(parse(&args(&["--shader", "crt"])).unwrap_err().contains("Unknown option"));
+        assert!(parse(&args(&["a.ch8", "b.ch8"])).is_err());
+
+        let (config, _) = config(&options).unwrap();
+        assert_eq!(config.cycles_per_frame, 20);
+        assert!(config.shader.is_none() && config.transitions.kind.is_none());
+    }
+}
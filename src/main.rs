@@ -1,6 +1,18 @@
-mod cpu;
-mod io;
+use std::env;
+
+use fish_n_chip8::{bench, compare, debugger, disasm, io, lite, trace, verify};
 
 fn main() {
-    io::emulator_main();
-}
\ No newline at end of file
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match args.first().map(String::as_str) {
+        Some("--bench") => bench::bench_main(&args[1..]),
+        Some("--compare") => compare::compare_main(&args[1..]),
+        Some("--debugger") => debugger::debugger_main(&args[1..]),
+        Some("--disasm") => disasm::disasm_main(&args[1..]),
+        Some("--lite") => lite::lite_main(&args[1..]),
+        Some("--trace") => trace::trace_main(&args[1..]),
+        Some("--verify") => verify::verify_main(&args[1..]),
+        _ => io::emulator_main(),
+    }
+}
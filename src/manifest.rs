@@ -0,0 +1,130 @@
+use serde::Serialize;
+
+use crate::analysis::CodeMap;
+use crate::config;
+use crate::library::{self, Library, LibraryEntry, LibrarySort};
+
+// one ROM in the library as it was checked when the manifest was made
+#[derive(Serialize, PartialEq, Debug)]
+pub struct ManifestRow {
+    pub name: String,
+    pub path: String,          // empty for ROMs that didn't come from a file
+    pub sha1: String,          // what the ROM was when it was added to the library
+    pub file_sha1: String,     // what the file is now, empty when it couldn't be read
+    pub md5: String,           // of the file now, for checking against sets that only list MD5s
+    pub size: Option<usize>,
+    pub platform: String,
+    pub database: String,      // always empty until there's a ROM database to match against
+    pub file: &'static str,    // verified, changed, missing or built in
+    pub compatibility: String,
+}
+
+const CSV_HEADER: &str = "name,path,sha1,file_sha1,md5,size,platform,database,file,compatibility\n";
+
+// contents is the ROM as it is now, read from its file or the built-in ROMs, or why it couldn't be
+pub fn row(rom_id: &str, entry: &LibraryEntry, contents: Result<Vec<u8>, String>) -> ManifestRow {
+    let mut row = ManifestRow {
+        name: entry.name.clone(),
+        path: entry.path.as_ref().map(|path| path.display().to_string()).unwrap_or_default(),
+        sha1: rom_id.to_string(),
+        file_sha1: String::new(),
+        md5: String::new(),
+        size: None,
+        platform: String::new(),
+        database: String::new(),
+        file: "missing",
+        compatibility: String::new(),
+    };
+
+    let rom = match contents {
+        Ok(rom) => rom,
+        Err(e) => {
+            row.compatibility = e;
+            return row;
+        },
+    };
+
+    let map = CodeMap::analyse(&rom);
+    row.file_sha1 = config::rom_id(&rom);
+    row.md5 = format!("{:x}", md5::compute(&rom));
+    row.size = Some(rom.len());
+    row.platform = map.platform().to_string();
+    row.file = match (&entry.path, row.file_sha1 == rom_id) {
+        (None, _) => "built in",
+        (Some(_), true) => "verified",
+        (Some(_), false) => "changed",
+    };
+    row.compatibility = if map.dead_ends().is_empty() {
+        String::from("ok")
+    }
+    else {
+        let opcodes: Vec<String> = map.dead_ends().iter().map(|&(addr, opcode)| format!("{:04X} at {:03X}", opcode, addr)).collect();
+        format!("reaches unsupported opcodes {}", opcodes.join(", "))
+    };
+
+    row
+}
+
+// every ROM in the library, in the same order as the library window sorted by name. read gets the ROM as it is now
+pub fn build(library: &Library, mut read: impl FnMut(&str, &LibraryEntry) -> Result<Vec<u8>, String>) -> Vec<ManifestRow> {
+    library::sorted(library, LibrarySort::Name).into_iter().map(|(rom_id, entry)| row(rom_id, entry, read(rom_id, entry))).collect()
+}
+
+pub fn to_csv(rows: &[ManifestRow]) -> String {
+    let mut csv = String::from(CSV_HEADER);
+    for row in rows {
+        let size = row.size.map(|size| size.to_string()).unwrap_or_default();
+        let fields = [&row.name, &row.path, &row.sha1, &row.file_sha1, &row.md5, &size, &row.platform, &row.database, row.file, &row.compatibility];
+        csv += &fields.map(csv_field).join(",");
+        csv.push('\n');
+    }
+
+    csv
+}
+
+// names and paths can have commas and quotes in them
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    }
+    else {
+        field.to_string()
+    }
+}
+
+pub fn to_json(rows: &[ManifestRow]) -> Result<String, String> {
+    serde_json::to_string_pretty(rows).map_err(|e| format!("Could not write the manifest: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn checks_roms_against_their_hashes() {
+        let rom = vec![0x00, 0xE0, 0x12, 0x02];
+        let rom_id = config::rom_id(&rom);
+        let mut library = Library::new();
+        let mut add = |rom_id: &str, name: &str, path: Option<&str>| {
+            library.insert(rom_id.to_string(), LibraryEntry { name: name.to_string(), path: path.map(PathBuf::from), ..LibraryEntry::default() });
+        };
+        add(&rom_id, "Clear, \"forever\"", Some("roms/clear.ch8"));
+        add("0123", "Edited", Some("roms/edited.ch8"));
+        add("4567", "Gone", Some("roms/gone.ch8"));
+
+        let rows = build(&library, |_, entry| match entry.name.as_str() {
+            "Gone" => Err(String::from("Could not read roms/gone.ch8")),
+            _ => Ok(rom.clone()),
+        });
+        assert_eq!(rows.iter().map(|row| row.file).collect::<Vec<_>>(), ["verified", "changed", "missing"]);
+        assert_eq!(rows[1].file_sha1, rom_id);
+        assert_eq!(rows[2].compatibility, "Could not read roms/gone.ch8");
+
+        let csv = to_csv(&rows);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER.trim_end());
+        assert_eq!(lines[1], format!("\"Clear, \"\"forever\"\"\",roms/clear.ch8,{0},{0},{1:x},4,CHIP-8,,verified,ok", rom_id, md5::compute(&rom)));
+        assert_eq!(lines[3], "Gone,roms/gone.ch8,4567,,,,,,missing,Could not read roms/gone.ch8");
+    }
+}
@@ -0,0 +1,393 @@
+// Hints some ROMs come with about how they should be played, read from a CHIP-8 binary (.c8b) container or from an
+// Octo options file next to the ROM.
+//
+// A .c8b file is laid out as:
+//   "CBF", a version byte (0), then the offset of the property table as a big endian u16
+//   the bytecode table, a platform byte followed by the big endian u16 offset and length of its program, for each
+//   platform the ROM has been built for, ending with a 0xFF platform byte
+//   the property table, a tag byte, a length byte and that many bytes of data for each property, ending with a 0xFF tag
+//
+// An options file is JSON named after the ROM (pong.ch8 goes with pong.json), either Octo's options object on its own
+// or a chip-8 archive entry with the options inside it
+
+use ggez::graphics::Color;
+use serde::Deserialize;
+
+use std::fs;
+use std::path::Path;
+
+use crate::analysis::Platform;
+use crate::config::{SharedSettings, SpeedMode};
+use crate::cpu::{JumpBehviour, Quirks, RegSaveLoadQuirk, ShiftingReg, MAX_ROM_SIZE};
+use crate::input::ControlScheme;
+
+pub const C8B_EXTENSION: &str = "c8b";
+const C8B_MAGIC: &[u8] = b"CBF";
+const C8B_VERSION: u8 = 0;
+const C8B_END: u8 = 0xFF;
+
+// .c8b platform bytes
+const C8B_CHIP8: u8 = 0x00;
+const C8B_SUPER_CHIP: u8 = 0x01;
+const C8B_XO_CHIP: u8 = 0x02;
+
+// .c8b property tags
+const TAG_CYCLES_PER_FRAME: u8 = 0x00; // big endian u16
+const TAG_TITLE: u8 = 0x01;
+const TAG_DESCRIPTION: u8 = 0x02;
+const TAG_AUTHORS: u8 = 0x03;
+const TAG_COLOURS: u8 = 0x04; // RGB off colour then RGB on colour
+const TAG_KEYS: u8 = 0x05;    // the keypad keys for up, down, left, right and action
+
+// the quirks Octo can set, None where it doesn't say
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct QuirkHints {
+    pub shift: Option<bool>,      // shifts work on VX alone
+    pub load_store: Option<bool>, // FX55/FX65 leave I alone
+    pub jump: Option<bool>,       // BXNN
+    pub clip: Option<bool>,       // sprites are cut off at the edges rather than wrapping
+    pub logic: Option<bool>,      // 8XY1/2/3 reset VF
+}
+
+#[derive(Clone, Default, PartialEq, Debug)]
+pub struct RomMetadata {
+    pub title: Option<String>,
+    pub authors: Option<String>,
+    pub description: Option<String>,
+    pub platform: Option<Platform>,
+    pub cycles_per_frame: Option<u16>,
+    pub colours: Option<(Color, Color)>, // on, off
+    pub quirks: QuirkHints,
+    pub controls: Option<ControlScheme>,
+    pub ignored: Option<String>, // why the options file next to the ROM couldn't be used
+}
+
+impl RomMetadata {
+    // what it sets, for showing in the ROM properties
+    pub fn describe(&self) -> Vec<(&'static str, String)> {
+        let mut rows = vec![];
+        let mut add = |name, value: Option<String>| {
+            if let Some(value) = value {
+                rows.push((name, value));
+            }
+        };
+
+        add("Options file ignored", self.ignored.clone());
+        add("Title", self.title.clone());
+        add("Authors", self.authors.clone());
+        add("Description", self.description.clone());
+        add("Made for", self.platform.map(|platform| platform.to_string()));
+        add("Suggested speed", self.cycles_per_frame.map(|cycles| format!("{} cycles per frame", cycles)));
+        add("Suggested colours", self.colours.map(|(on, off)| format!("{} on {}", hex_colour(on), hex_colour(off))));
+        add("Suggested controls", self.controls.map(|controls| {
+            format!("up {:X}, down {:X}, left {:X}, right {:X}, action {:X}", controls.up, controls.down, controls.left, controls.right, controls.action)
+        }));
+
+        rows
+    }
+
+    // the platform picks a starting point for the quirks and any the ROM sets itself go on top of that
+    pub fn apply(&self, settings: &mut SharedSettings) {
+        if let Some(platform) = self.platform {
+            settings.quirks = platform_quirks(platform, settings.quirks);
+        }
+
+        let quirks = &mut settings.quirks;
+        let hints = self.quirks;
+        if let Some(shift) = hints.shift {
+            quirks.shifting = if shift { ShiftingReg::VX } else { ShiftingReg::VY };
+        }
+        if let Some(load_store) = hints.load_store {
+            quirks.reg_save_load = if load_store { RegSaveLoadQuirk::Unchanged } else { RegSaveLoadQuirk::XPlusOne };
+        }
+        if let Some(jump) = hints.jump {
+            quirks.jump = if jump { JumpBehviour::BXNN } else { JumpBehviour::BNNN };
+        }
+        if let Some(clip) = hints.clip {
+            quirks.screen_wrap = !clip;
+        }
+        if let Some(logic) = hints.logic {
+            quirks.vf_reset = logic;
+        }
+
+        if let Some(cycles) = self.cycles_per_frame {
+            settings.speed_mode = SpeedMode::CyclesPerFrame;
+            settings.cycles_per_frame = cycles;
+        }
+        if let Some((on, off)) = self.colours {
+            settings.pixel_on_colour = on;
+            settings.pixel_off_colour = off;
+        }
+        if self.controls.is_some() {
+            settings.control_scheme = self.controls;
+        }
+    }
+}
+
+// the quirks the original interpreters for each platform had, leaving the ones they don't decide as they are
+fn platform_quirks(platform: Platform, quirks: Quirks) -> Quirks {
+    match platform {
+        Platform::Chip8 => Quirks {
+            vf_reset: true,
+            shifting: ShiftingReg::VY,
+            reg_save_load: RegSaveLoadQuirk::XPlusOne,
+            jump: JumpBehviour::BNNN,
+            screen_wrap: false,
+            display_wait: true,
+            ..quirks
+        },
+        Platform::SuperChip => Quirks {
+            vf_reset: false,
+            shifting: ShiftingReg::VX,
+            reg_save_load: RegSaveLoadQuirk::Unchanged,
+            jump: JumpBehviour::BXNN,
+            screen_wrap: false,
+            display_wait: false,
+            ..quirks
+        },
+        Platform::XoChip => Quirks {
+            vf_reset: false,
+            shifting: ShiftingReg::VY,
+            reg_save_load: RegSaveLoadQuirk::XPlusOne,
+            jump: JumpBehviour::BNNN,
+            screen_wrap: true,
+            display_wait: false,
+            ..quirks
+        },
+    }
+}
+
+// the program to run from path, with the hints that came with it if there were any
+pub fn read(path: &Path) -> Result<(Vec<u8>, Option<RomMetadata>), String> {
+    let contents = fs::read(path).map_err(|e| {
+        format!("Could not read {}: {}\n\nCheck that the file exists and that you have permission to read it.", path.display(), e)
+    })?;
+
+    parse(path, contents, |options_path| fs::read_to_string(options_path).ok())
+}
+
+// the same as read for a file that's already been read, with read_options finding the options file that goes with it
+pub fn parse(path: &Path, contents: Vec<u8>, read_options: impl FnOnce(&Path) -> Option<String>) -> Result<(Vec<u8>, Option<RomMetadata>), String> {
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case(C8B_EXTENSION)) {
+        let (rom, metadata) = parse_c8b(&contents).map_err(|e| format!("{} isn't a CHIP-8 binary that can be read: {}", path.display(), e))?;
+        return Ok((rom, Some(metadata)));
+    }
+
+    // most ROMs don't have an options file, and a broken one is no reason not to play the ROM, so it's only noted
+    let options_path = path.with_extension("json");
+    let metadata = read_options(&options_path).map(|options| parse_octo_options(&options).unwrap_or_else(|e| RomMetadata {
+        ignored: Some(format!("{} isn't an Octo options file: {}", options_path.display(), e)),
+        ..Default::default()
+    }));
+
+    Ok((contents, metadata))
+}
+
+pub fn parse_c8b(file: &[u8]) -> Result<(Vec<u8>, RomMetadata), String> {
+    if !file.starts_with(C8B_MAGIC) {
+        return Err(String::from("it doesn't start with CBF"));
+    }
+    let version = *file.get(3).ok_or("it ends in the header")?;
+    if version != C8B_VERSION {
+        return Err(format!("it's version {} and only version {} is supported", version, C8B_VERSION));
+    }
+    let properties_start = read_u16(file, 4).ok_or("it ends in the header")? as usize;
+
+    // the platforms it was built for, picking the first one this emulator runs best
+    let mut programs = vec![];
+    let mut offset = 6;
+    loop {
+        let platform = *file.get(offset).ok_or("the bytecode table doesn't end")?;
+        if platform == C8B_END {
+            break;
+        }
+        let start = read_u16(file, offset + 1).ok_or("the bytecode table doesn't end")? as usize;
+        let length = read_u16(file, offset + 3).ok_or("the bytecode table doesn't end")? as usize;
+        let program = file.get(start..start + length).ok_or("a program goes past the end of the file")?;
+        programs.push((platform, program));
+        offset += 5;
+    }
+    // a platform byte this doesn't know still gets its program run, just without saying what it was made for
+    let known = [(C8B_CHIP8, Platform::Chip8), (C8B_SUPER_CHIP, Platform::SuperChip), (C8B_XO_CHIP, Platform::XoChip)]
+        .iter()
+        .find_map(|&(wanted, platform)| programs.iter().find(|(id, _)| *id == wanted).map(|&(_, program)| (Some(platform), program)));
+    let (platform, program) = known.or_else(|| programs.first().map(|&(_, program)| (None, program))).ok_or("there are no programs in it")?;
+    if program.len() > MAX_ROM_SIZE {
+        return Err(format!("its program is {} bytes but ROMs can be at most {} bytes", program.len(), MAX_ROM_SIZE));
+    }
+
+    let mut metadata = RomMetadata { platform, ..Default::default() };
+
+    let mut offset = properties_start;
+    loop {
+        let tag = *file.get(offset).ok_or("the property table doesn't end")?;
+        if tag == C8B_END {
+            break;
+        }
+        let length = *file.get(offset + 1).ok_or("the property table doesn't end")? as usize;
+        let data = file.get(offset + 2..offset + 2 + length).ok_or("a property goes past the end of the file")?;
+        let text = || Some(String::from_utf8_lossy(data).into_owned());
+
+        match tag {
+            TAG_CYCLES_PER_FRAME => metadata.cycles_per_frame = read_u16(data, 0),
+            TAG_TITLE => metadata.title = text(),
+            TAG_DESCRIPTION => metadata.description = text(),
+            TAG_AUTHORS => metadata.authors = text(),
+            TAG_COLOURS if data.len() >= 6 => {
+                metadata.colours = Some((Color::from_rgb(data[3], data[4], data[5]), Color::from_rgb(data[0], data[1], data[2])));
+            },
+            TAG_KEYS if data.len() >= 5 && data.iter().all(|&key| key < 16) => {
+                metadata.controls = Some(ControlScheme { up: data[0], down: data[1], left: data[2], right: data[3], action: data[4], ..Default::default() });
+            },
+            _ => {}, // ones this emulator has no use for
+        }
+        offset += 2 + length;
+    }
+
+    Ok((program.to_vec(), metadata))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+struct OctoOptions {
+    tickrate: Option<u16>,
+    fill_color: Option<String>,
+    background_color: Option<String>,
+    shift_quirks: Option<bool>,
+    load_store_quirks: Option<bool>,
+    jump_quirks: Option<bool>,
+    clip_quirks: Option<bool>,
+    logic_quirks: Option<bool>,
+}
+
+// an entry in the chip-8 archive's programs.json
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ArchiveEntry {
+    title: Option<String>,
+    authors: Vec<String>,
+    desc: Option<String>,
+    platform: Option<String>,
+    options: Option<OctoOptions>,
+}
+
+pub fn parse_octo_options(json: &str) -> Result<RomMetadata, String> {
+    let value: serde_json::Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let (entry, options) = if value.get("options").is_some() {
+        let mut entry: ArchiveEntry = serde_json::from_value(value).map_err(|e| e.to_string())?;
+        let options = entry.options.take().unwrap_or_default();
+        (entry, options)
+    }
+    else {
+        (ArchiveEntry::default(), serde_json::from_value(value).map_err(|e| e.to_string())?)
+    };
+
+    let colours = match (options.fill_color.as_deref().and_then(parse_colour), options.background_color.as_deref().and_then(parse_colour)) {
+        (Some(on), Some(off)) => Some((on, off)),
+        _ => None,
+    };
+
+    Ok(RomMetadata {
+        title: entry.title,
+        authors: Some(entry.authors.join(", ")).filter(|authors| !authors.is_empty()),
+        description: entry.desc,
+        platform: entry.platform.as_deref().and_then(|platform| match platform.to_lowercase().as_str() {
+            "chip8" | "chip-8" => Some(Platform::Chip8),
+            "schip" | "superchip" | "super-chip" => Some(Platform::SuperChip),
+            "xochip" | "xo-chip" => Some(Platform::XoChip),
+            _ => None,
+        }),
+        cycles_per_frame: options.tickrate,
+        colours,
+        quirks: QuirkHints {
+            shift: options.shift_quirks,
+            load_store: options.load_store_quirks,
+            jump: options.jump_quirks,
+            clip: options.clip_quirks,
+            logic: options.logic_quirks,
+        },
+        controls: None,
+        ignored: None,
+    })
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]))
+}
+
+// "#RRGGBB" the way Octo writes them
+fn parse_colour(colour: &str) -> Option<Color> {
+    let hex = colour.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let rgb = u32::from_str_radix(hex, 16).ok()?;
+    Some(Color::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+}
+
+fn hex_colour(colour: Color) -> String {
+    let (r, g, b) = colour.to_rgb();
+    format!("#{:02X}{:02X}{:02X}", r, g, b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_c8b() {
+        let mut file = b"CBF\x00\x00\x20".to_vec();
+        file.extend_from_slice(&[C8B_XO_CHIP, 0x00, 0x40, 0x00, 0x02, C8B_SUPER_CHIP, 0x00, 0x42, 0x00, 0x02, C8B_END]);
+        file.resize(0x20, 0);
+        file.extend_from_slice(&[TAG_TITLE, 4, b'P', b'o', b'n', b'g', TAG_CYCLES_PER_FRAME, 2, 0x00, 0x1E]);
+        file.extend_from_slice(&[TAG_KEYS, 5, 1, 4, 7, 0xC, 0xA, 0x42, 1, 0, C8B_END]);
+        file.resize(0x40, 0);
+        file.extend_from_slice(&[0xF0, 0x02, 0x00, 0xFF]);
+
+        let (rom, metadata) = parse_c8b(&file).unwrap();
+        assert_eq!(rom, [0x00, 0xFF]); // the SUPER-CHIP build rather than the XO-CHIP one
+        assert_eq!(metadata.platform, Some(Platform::SuperChip));
+        assert_eq!(metadata.title.as_deref(), Some("Pong"));
+        assert_eq!(metadata.cycles_per_frame, Some(30));
+        assert_eq!(metadata.controls.map(|controls| (controls.up, controls.action)), Some((1, 0xA)));
+
+        // a platform it doesn't know is still run
+        let (rom, metadata) = parse_c8b(b"CBF\x00\x00\x0C\x30\x00\x0D\x00\x02\xFF\xFF\x12\x0C").unwrap();
+        assert_eq!((rom, metadata.platform), (vec![0x12, 0x0C], None));
+
+        assert!(parse_c8b(b"CBF\x01\x00\x06\xFF").is_err());
+        assert!(parse_c8b(b"CBF\x00\x00\x06\x00\x01\x00\x00\x02\xFF").is_err());
+        assert!(parse_c8b(b"CBF\x00\x00\x07\xFF\xFF").is_err());
+    }
+
+    #[test]
+    fn ignores_broken_options_files() {
+        let (rom, metadata) = parse(Path::new("pong.ch8"), vec![0x12, 0x00], |_| Some(String::from("not json"))).unwrap();
+        assert_eq!(rom, [0x12, 0x00]);
+        let metadata = metadata.unwrap();
+        assert!(metadata.ignored.unwrap().starts_with("pong.json isn't an Octo options file"));
+        assert_eq!(metadata.cycles_per_frame, None);
+    }
+
+    #[test]
+    fn applies_octo_options() {
+        let entry = r##"{
+            "title": "Outlaw", "authors": ["John Earnest"], "platform": "xochip",
+            "options": { "tickrate": 1000, "fillColor": "#FFCC00", "backgroundColor": "#996600", "clipQuirks": true, "vBlankQuirks": false }
+        }"##;
+        let metadata = parse_octo_options(entry).unwrap();
+        assert_eq!(metadata.authors.as_deref(), Some("John Earnest"));
+
+        let mut settings = SharedSettings::default();
+        metadata.apply(&mut settings);
+        assert_eq!(settings.cycles_per_frame, 1000);
+        assert_eq!(settings.pixel_on_colour, Color::from_rgb(0xFF, 0xCC, 0x00));
+        assert_eq!(settings.quirks.reg_save_load, RegSaveLoadQuirk::XPlusOne); // from the platform
+        assert!(!settings.quirks.screen_wrap);                                 // the options win over it
+
+        let options = parse_octo_options(r#"{ "shiftQuirks": true }"#).unwrap();
+        assert_eq!(options.quirks.shift, Some(true));
+        assert_eq!(options.platform, None);
+        assert!(parse_octo_options("not json").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use midir::{MidiInput, MidiInputConnection};
+use serde::{Deserialize, Serialize};
+
+use std::sync::mpsc::{self, Receiver, Sender, TryIter};
+
+use crate::input::KEYPAD_GRID;
+
+const CLIENT_NAME: &str = "fish_n_chip8";
+
+// pad controllers usually send 36 upwards from their bottom left pad, a row at a time, so by default a 4x4 grid of pads
+// is laid out like the keypad
+const FIRST_PAD_NOTE: u8 = 36;
+
+const NOTE_NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub struct NoteMapping {
+    pub note: u8,
+    pub key: u8,
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(default)]
+pub struct MidiSettings {
+    pub enabled: bool,
+    pub port: Option<String>,       // None for the first one there is
+    pub mappings: Vec<NoteMapping>, // a key can have more than one note but each note only presses one key
+}
+
+impl Default for MidiSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: None,
+            mappings: (0..16).map(|pad| NoteMapping { note: FIRST_PAD_NOTE + pad as u8, key: KEYPAD_GRID[(3 - pad / 4) * 4 + pad % 4] }).collect(),
+        }
+    }
+}
+
+impl MidiSettings {
+    // mappings come from the config file so one could name a key the keypad doesn't have
+    pub fn key_for(&self, note: u8) -> Option<u8> {
+        self.mappings.iter().find(|mapping| mapping.note == note).map(|mapping| mapping.key).filter(|&key| key <= 0xF)
+    }
+
+    pub fn notes_for(&self, key: u8) -> Vec<u8> {
+        self.mappings.iter().filter(|mapping| mapping.key == key).map(|mapping| mapping.note).collect()
+    }
+
+    // makes note press key, instead of whatever it pressed before
+    pub fn learn(&mut self, note: u8, key: u8) {
+        self.mappings.retain(|mapping| mapping.note != note);
+        self.mappings.push(NoteMapping { note, key });
+    }
+
+    pub fn clear(&mut self, key: u8) {
+        self.mappings.retain(|mapping| mapping.key != key);
+    }
+}
+
+// middle C is C4
+pub fn note_name(note: u8) -> String {
+    format!("{}{}", NOTE_NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NoteEvent {
+    pub note: u8,
+    pub down: bool,
+}
+
+// note on and note off on any channel, everything else a controller sends is ignored
+pub fn parse(message: &[u8]) -> Option<NoteEvent> {
+    match *message {
+        [status, note, velocity, ..] if status & 0xF0 == 0x90 => Some(NoteEvent { note, down: velocity > 0 }), // velocity 0 is a note off too
+        [status, note, ..] if status & 0xF0 == 0x80 => Some(NoteEvent { note, down: false }),
+        _ => None,
+    }
+}
+
+pub fn port_names() -> Vec<String> {
+    let Ok(input) = MidiInput::new(CLIENT_NAME) else {
+        return vec![];
+    };
+    input.ports().iter().filter_map(|port| input.port_name(port).ok()).collect()
+}
+
+// notes from a MIDI device, it disconnects when dropped
+pub struct MidiKeypad {
+    _connection: MidiInputConnection<Sender<NoteEvent>>,
+    events: Receiver<NoteEvent>,
+}
+
+impl MidiKeypad {
+    pub fn connect(port_name: Option<&str>) -> Result<MidiKeypad, String> {
+        let input = MidiInput::new(CLIENT_NAME).map_err(|e| e.to_string())?;
+        let ports = input.ports();
+        let port = match port_name {
+            Some(name) => ports.iter().find(|port| input.port_name(port).is_ok_and(|port_name| port_name == name))
+                .ok_or_else(|| format!("{} isn't plugged in", name))?,
+            None => ports.first().ok_or_else(|| String::from("There are no MIDI devices plugged in"))?,
+        };
+
+        let (sender, events) = mpsc::channel();
+        let connection = input.connect(port, "keypad", |_, message, sender: &mut Sender<NoteEvent>| {
+            if let Some(event) = parse(message) {
+                // the emulator may be closing, there's nothing to do about it here
+                let _ = sender.send(event);
+            }
+        }, sender).map_err(|e| e.to_string())?;
+
+        Ok(MidiKeypad { _connection: connection, events })
+    }
+
+    pub fn events(&self) -> TryIter<'_, NoteEvent> {
+        self.events.try_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_notes_to_keys() {
+        assert_eq!(parse(&[0x91, 60, 100]), Some(NoteEvent { note: 60, down: true }));
+        assert_eq!(parse(&[0x90, 60, 0]), Some(NoteEvent { note: 60, down: false }));
+        assert_eq!(parse(&[0x80, 61, 64]), Some(NoteEvent { note: 61, down: false }));
+        assert_eq!(parse(&[0xB0, 1, 127]), None);
+        assert_eq!(parse(&[0xF8]), None);
+
+        let mut settings = MidiSettings::default();
+        assert_eq!(settings.key_for(36), Some(0xA));
+        assert_eq!(settings.key_for(41), Some(0x8));
+        assert_eq!(settings.key_for(48), Some(0x1));
+        assert_eq!(settings.key_for(51), Some(0xC));
+        assert_eq!(settings.key_for(60), None);
+
+        settings.learn(60, 0x5);
+        settings.learn(36, 0x5);
+        assert_eq!(settings.notes_for(0x5), [45, 60, 36]);
+        assert_eq!(settings.key_for(36), Some(0x5));
+        assert!(settings.notes_for(0xA).is_empty());
+        settings.clear(0x5);
+        assert_eq!(settings.key_for(60), None);
+        settings.learn(60, 0x10);
+        assert_eq!(settings.key_for(60), None);
+
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(37), "C#2");
+        assert_eq!(note_name(0), "C-1");
+    }
+}
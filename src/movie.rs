@@ -0,0 +1,53 @@
+use std::fs;
+use std::path::Path;
+
+// the keypad state for every frame of a run, so it can be played back exactly
+//
+// stored as text with one "<frame> <keys>" line each time the keys change, keys being the keypad as a hex bitmask
+// (bit N set while key N is held), e.g. "120 0020" holds 5 from frame 120 until the next line
+#[derive(Default, Clone)]
+pub struct InputMovie {
+    changes: Vec<(u32, u16)>, // sorted by frame
+}
+
+impl InputMovie {
+    pub fn load(path: &Path) -> Result<InputMovie, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        InputMovie::parse(&text).map_err(|e| format!("Could not read {}: {}", path.display(), e))
+    }
+
+    pub fn parse(text: &str) -> Result<InputMovie, String> {
+        let mut changes: Vec<(u32, u16)> = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || format!("line {} should be a frame number then the keys in hex", number + 1);
+            let (frame, keys) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let frame = frame.parse().map_err(|_| invalid())?;
+            let keys = u16::from_str_radix(keys.trim(), 16).map_err(|_| invalid())?;
+
+            if changes.last().is_some_and(|&(last, _)| last >= frame) {
+                return Err(format!("line {} goes back in time, frames have to be in order", number + 1));
+            }
+            changes.push((frame, keys));
+        }
+
+        Ok(InputMovie { changes })
+    }
+
+    pub fn keys_at(&self, frame: u32) -> u16 {
+        match self.changes.partition_point(|&(change, _)| change <= frame) {
+            0 => 0,
+            i => self.changes[i - 1].1,
+        }
+    }
+
+    // the frame after the last change, nothing new happens from there on
+    pub fn length(&self) -> u32 {
+        self.changes.last().map_or(0, |&(frame, _)| frame + 1)
+    }
+}
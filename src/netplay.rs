@@ -0,0 +1,294 @@
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::config::{Config, SpeedMode};
+use crate::cpu::Quirks;
+
+pub const DEFAULT_NETPLAY_PORT: u16 = 8265;
+
+// frames of input sent ahead so a little latency doesn't stall every frame
+const INPUT_DELAY: usize = 3;
+
+const MAGIC: &[u8; 4] = b"FNC8";
+const ROM_ID_LEN: usize = 40; // a sha1 in hex
+const HELLO_LEN: usize = MAGIC.len() + 8 + ROM_ID_LEN + 4; // followed by that many bytes of the host's rules
+const MAX_RULES_LEN: usize = 4096;
+const ACCEPTED: [u8; 1] = [1];
+const REFUSED: [u8; 1] = [0];
+const FRAME_LEN: usize = 4;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// everything besides the keys that decides what the CPU does each frame, which has to match on both sides or the games drift apart
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub struct NetplayRules {
+    quirks: Quirks,
+    speed_mode: SpeedMode,
+    speed: u32, // cycles per frame or instructions per second, whichever the speed mode uses
+}
+
+impl NetplayRules {
+    pub fn new(config: &Config, quirks: Quirks) -> NetplayRules {
+        let speed = match config.speed_mode {
+            SpeedMode::CyclesPerFrame => config.cycles_per_frame as u32,
+            SpeedMode::InstructionsPerSecond => config.instructions_per_second,
+        };
+        NetplayRules { quirks, speed_mode: config.speed_mode, speed }
+    }
+}
+
+// the host waits for one other player to join
+pub struct NetplayHost {
+    listener: TcpListener,
+    seed: u64,
+    rom_id: String,
+    rules: NetplayRules,
+}
+
+impl NetplayHost {
+    pub fn start(port: u16, rom_id: &str, rules: NetplayRules) -> io::Result<NetplayHost> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, port))?;
+        listener.set_nonblocking(true)?;
+
+        Ok(NetplayHost { listener, seed: rand::random(), rom_id: rom_id.to_owned(), rules })
+    }
+
+    pub fn accept(&self) -> io::Result<Option<NetplaySession>> {
+        let mut stream = match self.listener.accept() {
+            Ok((stream, _)) => stream,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        stream.set_nonblocking(false)?;
+
+        // the host picks the seed so both CPUs roll the same random numbers
+        let mut hello = MAGIC.to_vec();
+        hello.extend(self.seed.to_be_bytes());
+        hello.extend(self.rom_id.as_bytes());
+        let rules = serde_json::to_vec(&self.rules)?;
+        hello.extend((rules.len() as u32).to_be_bytes());
+        hello.extend(rules);
+        stream.write_all(&hello)?;
+
+        // the other player says whether they're running the same thing, so a refusal isn't mistaken for them leaving
+        let mut accepted = [0];
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+        stream.read_exact(&mut accepted)?;
+        if accepted != ACCEPTED {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the other player has a different ROM, speed or quirks"));
+        }
+        stream.set_read_timeout(None)?;
+
+        NetplaySession::new(stream, self.seed).map(Some)
+    }
+}
+
+// both players send their keys for every frame and neither runs a frame until it has the other's, so the games never drift apart
+pub struct NetplaySession {
+    stream: TcpStream,
+    peer: SocketAddr,
+    seed: u64,
+    received: Vec<u8>,
+    unsent: Vec<u8>, // what the socket couldn't take yet, sent before anything newer
+    local: VecDeque<u32>,
+    remote: VecDeque<u32>,
+}
+
+impl NetplaySession {
+    pub fn connect(address: &str, rom_id: &str, rules: NetplayRules) -> io::Result<NetplaySession> {
+        let address = address.to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "the address did not resolve"))?;
+        let mut stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)?;
+        stream.set_read_timeout(Some(CONNECT_TIMEOUT))?;
+
+        let seed = match NetplaySession::read_hello(&mut stream, rom_id, rules) {
+            Ok(seed) => seed,
+            Err(e) => {
+                let _ = stream.write_all(&REFUSED);
+                return Err(e);
+            },
+        };
+        stream.write_all(&ACCEPTED)?;
+
+        stream.set_read_timeout(None)?;
+        NetplaySession::new(stream, seed)
+    }
+
+    // the host's seed, once it's checked the host is running the same thing
+    fn read_hello(stream: &mut TcpStream, rom_id: &str, rules: NetplayRules) -> io::Result<u64> {
+        let mut hello = [0; HELLO_LEN];
+        stream.read_exact(&mut hello)?;
+        let (magic, rest) = hello.split_at(MAGIC.len());
+        let (seed, rest) = rest.split_at(8);
+        let (host_rom_id, rules_len) = rest.split_at(ROM_ID_LEN);
+
+        if magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "that is not a fish_n_chip8 netplay host"));
+        }
+        if host_rom_id != rom_id.as_bytes() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the host is playing a different ROM, both players need to load the same one"));
+        }
+
+        let rules_len = u32::from_be_bytes(rules_len.try_into().unwrap()) as usize;
+        if rules_len > MAX_RULES_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the host sent settings far bigger than any real ones"));
+        }
+        let mut host_rules = vec![0; rules_len];
+        stream.read_exact(&mut host_rules)?;
+        let host_rules: NetplayRules = serde_json::from_slice(&host_rules)?;
+        if host_rules.quirks != rules.quirks {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the host has different quirks set, both players need the same ones"));
+        }
+        if host_rules != rules {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "the host runs at a different speed, both players need the same speed mode and speed"));
+        }
+
+        Ok(u64::from_be_bytes(seed.try_into().unwrap()))
+    }
+
+    fn new(stream: TcpStream, seed: u64) -> io::Result<NetplaySession> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+
+        let mut session = NetplaySession {
+            peer: stream.peer_addr()?,
+            stream,
+            seed,
+            received: vec![],
+            unsent: vec![],
+            local: VecDeque::from([0; INPUT_DELAY]),
+            remote: VecDeque::new(),
+        };
+
+        // neither side has anything for the first few frames so they start with nothing held
+        session.send(&[0; FRAME_LEN * INPUT_DELAY])?;
+        Ok(session)
+    }
+
+    // writes as much as the socket will take without waiting, a full socket isn't the other player leaving
+    fn send(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.unsent.extend(bytes);
+        while !self.unsent.is_empty() {
+            match self.stream.write(&self.unsent) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "the other player left")),
+                Ok(written) => drop(self.unsent.drain(..written)),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    // whether the other player's keys for the next frame have arrived. Until they have the frame can't run, so
+    // nothing should be taken from the local input for it yet
+    pub fn ready(&mut self) -> io::Result<bool> {
+        self.send(&[])?;
+
+        let mut buffer = [0; 256];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "the other player left")),
+                Ok(read) => self.received.extend(&buffer[..read]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e),
+            }
+        }
+        for frame in self.received.chunks_exact(FRAME_LEN) {
+            self.remote.push_back(u32::from_be_bytes(frame.try_into().unwrap()));
+        }
+        let used = self.received.len() - self.received.len() % FRAME_LEN;
+        self.received.drain(..used);
+
+        Ok(!self.remote.is_empty())
+    }
+
+    // sends this frame's keys and gives back the ones to run the next frame with, once ready says they're here.
+    // Keys are the keypad in the low 16 bits and the second keypad in the high 16
+    pub fn exchange(&mut self, keys: u32) -> io::Result<u32> {
+        self.send(&keys.to_be_bytes())?;
+        self.local.push_back(keys);
+
+        // the keypad is shared so each player just uses their own keys, like two people at one keyboard
+        Ok(self.local.pop_front().unwrap_or_default() | self.remote.pop_front().unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    const ROM_ID: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    fn rules(cycles_per_frame: u16, quirks: Quirks) -> NetplayRules {
+        NetplayRules::new(&Config { cycles_per_frame, ..Config::default() }, quirks)
+    }
+
+    // waits for the other player's keys like the emulator does, just without running anything in between
+    fn next_frame(session: &mut NetplaySession, keys: u32) -> io::Result<u32> {
+        while !session.ready()? {
+            thread::sleep(Duration::from_millis(1));
+        }
+        session.exchange(keys)
+    }
+
+    #[test]
+    fn exchanges_keys_every_frame() {
+        let host = NetplayHost::start(0, ROM_ID, rules(15, Quirks::default())).unwrap();
+        let address = format!("127.0.0.1:{}", host.listener.local_addr().unwrap().port());
+
+        let join = |rom_id: &'static str, rules: NetplayRules| {
+            let address = address.clone();
+            let guest = thread::spawn(move || NetplaySession::connect(&address, rom_id, rules));
+            let host = loop {
+                match host.accept() {
+                    Ok(Some(session)) => break Ok(session),
+                    Ok(None) => thread::sleep(Duration::from_millis(1)),
+                    Err(e) => break Err(e),
+                }
+            };
+            (host, guest.join().unwrap())
+        };
+
+        // both sides are told, rather than the host thinking the other player just left
+        let (refused, wrong_rom) = join("fedcba9876543210fedcba9876543210fedcba98", rules(15, Quirks::default()));
+        assert_eq!(refused.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+        assert_eq!(wrong_rom.err().map(|e| e.kind()), Some(io::ErrorKind::InvalidData));
+
+        // the same ROM still plays out differently at another speed or with other quirks
+        let (_, wrong_speed) = join(ROM_ID, rules(30, Quirks::default()));
+        assert!(wrong_speed.is_err_and(|e| e.to_string().contains("speed")));
+        let (_, wrong_quirks) = join(ROM_ID, rules(15, Quirks { vf_reset: !Quirks::default().vf_reset, ..Quirks::default() }));
+        assert!(wrong_quirks.is_err_and(|e| e.to_string().contains("quirks")));
+
+        let (host, guest) = join(ROM_ID, rules(15, Quirks::default()));
+        let (mut host, mut guest) = (host.unwrap(), guest.unwrap());
+        assert_eq!(host.seed(), guest.seed());
+
+        // each frame's keys come back INPUT_DELAY frames later, the same on both sides
+        for frame in 0..10usize {
+            let expected = match frame.checked_sub(INPUT_DELAY) {
+                Some(sent) => 1 << sent | 1 << (sent + 16),
+                None => 0,
+            };
+            assert_eq!(next_frame(&mut host, 1 << frame).unwrap(), expected);
+            assert_eq!(next_frame(&mut guest, 1 << (frame + 16)).unwrap(), expected);
+        }
+
+        drop(guest);
+        let left = (0..INPUT_DELAY + 1).map(|_| next_frame(&mut host, 0)).find_map(Result::err);
+        assert_eq!(left.map(|e| e.kind()), Some(io::ErrorKind::ConnectionAborted));
+    }
+}
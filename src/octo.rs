@@ -0,0 +1,590 @@
+// An assembler for Octo (.8o) source, the most common way CHIP-8 programs are written nowadays.
+//
+// It covers the statements, labels, :const, :alias, :org, :byte, :call, :unpack and the if/then, if/begin/else/end
+// and loop/while/again control structures. Macros, :calc and the XO-CHIP extensions aren't supported and are
+// reported as errors rather than being assembled wrongly
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::cpu::{MAX_ROM_SIZE, PROGRAM_START};
+
+// words with a meaning of their own, used for highlighting too
+pub const KEYWORDS: [&str; 33] = [
+    "return", "clear", "bcd", "save", "load", "saveflags", "loadflags", "sprite", "jump", "jump0", "native",
+    "hires", "lores", "scroll-down", "scroll-left", "scroll-right", "exit", "delay", "buzzer", "random", "key", "-key",
+    "hex", "bighex", "if", "then", "begin", "else", "end", "loop", "again", "while", "i",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssembleError {
+    pub line: usize, // counting from 1
+    pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+// splits a line into words the same way the assembler does, along with where each word starts in the line
+pub fn words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    let code = line.split('#').next().unwrap_or_default();
+
+    code.split_whitespace().map(move |word| (word.as_ptr() as usize - code.as_ptr() as usize, word))
+}
+
+pub fn is_register(word: &str) -> bool {
+    register_number(word).is_some()
+}
+
+fn register_number(word: &str) -> Option<u8> {
+    let digit = word.strip_prefix('v').or_else(|| word.strip_prefix('V'))?;
+    if digit.len() != 1 {
+        return None;
+    }
+
+    u8::from_str_radix(digit, 16).ok()
+}
+
+pub fn parse_number(word: &str) -> Option<i64> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    }
+    else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    }
+    else {
+        digits.parse().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+struct Token<'a> {
+    text: &'a str,
+    line: usize,
+}
+
+// where a label's address needs writing once it's known
+enum Fixup {
+    Address(usize), // the low 12 bits of the opcode at this offset
+    HighNibble(usize), // the low nibble of the byte at this offset gets the address's top 4 bits
+    LowByte(usize),
+}
+
+enum Flow {
+    Begin(usize),            // the jump past the block, to be aimed at the else or end
+    Else(usize),             // the jump over the else block to the end
+    Loop(u16, Vec<usize>),   // where the loop starts and the jumps out of it from each while
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u8>,
+    fixups: Vec<(Fixup, &'a str, usize)>, // with the label and the line it was used on
+    flow: Vec<(Flow, usize)>,              // with the line the structure was opened on
+    errors: Vec<AssembleError>,
+}
+
+pub fn assemble(source: &str) -> Result<Vec<u8>, Vec<AssembleError>> {
+    let tokens = source.lines()
+        .enumerate()
+        .flat_map(|(number, line)| words(line).map(move |(_, text)| Token { text, line: number + 1 }))
+        .collect();
+
+    let mut assembler = Assembler {
+        tokens,
+        pos: 0,
+        rom: vec![],
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: vec![],
+        flow: vec![],
+        errors: vec![],
+    };
+    assembler.run();
+
+    if assembler.errors.is_empty() {
+        Ok(assembler.rom)
+    }
+    else {
+        assembler.errors.sort_by_key(|error| error.line);
+        Err(assembler.errors)
+    }
+}
+
+impl<'a> Assembler<'a> {
+    fn run(&mut self) {
+        // programs start at main, which needs a jump to it unless it's right at the start
+        let mut first = 0;
+        while self.tokens.get(first).is_some_and(|token| token.text == ":const" || token.text == ":alias") {
+            first += 3;
+        }
+        let starts_with_main = matches!(self.tokens.get(first..first + 2), Some([colon, name]) if colon.text == ":" && name.text == "main");
+        if !starts_with_main {
+            self.emit_fixup(0x1000, "main", 1);
+        }
+
+        while self.pos < self.tokens.len() {
+            let line = self.tokens[self.pos].line;
+
+            if let Err(message) = self.statement() {
+                self.errors.push(AssembleError { line, message });
+
+                // carry on from the next line so one mistake doesn't hide the rest
+                while self.pos < self.tokens.len() && self.tokens[self.pos].line == line {
+                    self.pos += 1;
+                }
+            }
+        }
+
+        for (flow, line) in std::mem::take(&mut self.flow) {
+            let message = match flow {
+                Flow::Begin(_) | Flow::Else(_) => "begin without a matching end",
+                Flow::Loop(..) => "loop without a matching again",
+            };
+            self.errors.push(AssembleError { line, message: String::from(message) });
+        }
+
+        for (fixup, label, line) in std::mem::take(&mut self.fixups) {
+            let Some(&addr) = self.labels.get(label) else {
+                let message = if label == "main" {
+                    String::from("there's no main label for the program to start at")
+                }
+                else {
+                    format!("undefined label {}", label)
+                };
+                self.errors.push(AssembleError { line, message });
+                continue;
+            };
+
+            match fixup {
+                Fixup::Address(offset) => {
+                    self.rom[offset] |= (addr >> 8) as u8 & 0xF;
+                    self.rom[offset + 1] = addr as u8;
+                }
+                Fixup::HighNibble(offset) => self.rom[offset] |= (addr >> 8) as u8 & 0xF,
+                Fixup::LowByte(offset) => self.rom[offset] = addr as u8,
+            }
+        }
+
+        if self.rom.len() > MAX_ROM_SIZE {
+            let line = self.tokens.last().map_or(1, |token| token.line);
+            self.errors.push(AssembleError { line, message: format!("the program is {} bytes, more than the {} that fit in memory", self.rom.len(), MAX_ROM_SIZE) });
+        }
+    }
+
+    fn here(&self) -> u16 {
+        (PROGRAM_START + self.rom.len()) as u16
+    }
+
+    fn next(&mut self) -> Result<&'a str, String> {
+        let token = self.tokens.get(self.pos).ok_or_else(|| String::from("the program ends in the middle of a statement"))?;
+        self.pos += 1;
+
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).map(|token| token.text)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        match self.next()? {
+            word if word == expected => Ok(()),
+            word => Err(format!("expected {} but found {}", expected, word)),
+        }
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend(opcode.to_be_bytes());
+    }
+
+    fn emit_fixup(&mut self, opcode: u16, label: &'a str, line: usize) {
+        self.fixups.push((Fixup::Address(self.rom.len()), label, line));
+        self.emit(opcode);
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.pos.saturating_sub(1)].line
+    }
+
+    fn register(&mut self) -> Result<u16, String> {
+        let word = self.next()?;
+
+        register_number(word)
+            .or_else(|| self.aliases.get(word).copied())
+            .map(u16::from)
+            .ok_or_else(|| format!("expected a register but found {}", word))
+    }
+
+    fn value(&self, word: &str) -> Option<i64> {
+        parse_number(word).or_else(|| self.constants.get(word).copied())
+    }
+
+    fn byte(&mut self) -> Result<u16, String> {
+        let word = self.next()?;
+        match self.value(word) {
+            Some(value) if (-128..=255).contains(&value) => Ok(value as u8 as u16),
+            Some(value) => Err(format!("{} doesn't fit in a byte", value)),
+            None => Err(format!("expected a number but found {}", word)),
+        }
+    }
+
+    fn nibble(&mut self) -> Result<u16, String> {
+        let word = self.next()?;
+        match self.value(word) {
+            Some(value) if (0..=15).contains(&value) => Ok(value as u16),
+            _ => Err(format!("expected a number from 0 to 15 but found {}", word)),
+        }
+    }
+
+    // an address operand, either a number or a label that may not have been defined yet
+    fn address(&mut self, opcode: u16) -> Result<(), String> {
+        let word = self.next()?;
+
+        match self.value(word) {
+            Some(addr) if (0..=0xFFF).contains(&addr) => self.emit(opcode | addr as u16),
+            Some(addr) => return Err(format!("{} is outside memory", addr)),
+            None if is_register(word) || KEYWORDS.contains(&word) => return Err(format!("expected an address but found {}", word)),
+            None => self.emit_fixup(opcode, word, self.line()),
+        }
+
+        Ok(())
+    }
+
+    // the skip that jumps over the next instruction when the condition is true, and the one when it's false
+    fn condition(&mut self) -> Result<(u16, u16), String> {
+        let x = self.register()? << 8;
+        let op = self.next()?;
+
+        match op {
+            "key" => return Ok((0xE09E | x, 0xE0A1 | x)),
+            "-key" => return Ok((0xE0A1 | x, 0xE09E | x)),
+            "==" | "!=" => (),
+            "<" | ">" | "<=" | ">=" => return Err(format!("{} comparisons aren't supported, use -= and check vf instead", op)),
+            _ => return Err(format!("expected a comparison but found {}", op)),
+        }
+
+        let (equal, not_equal) = match self.peek() {
+            Some(word) if register_number(word).is_some() || self.aliases.contains_key(word) => {
+                let y = self.register()? << 4;
+                (0x5000 | x | y, 0x9000 | x | y)
+            }
+            _ => {
+                let nn = self.byte()?;
+                (0x3000 | x | nn, 0x4000 | x | nn)
+            }
+        };
+
+        Ok(if op == "==" { (equal, not_equal) } else { (not_equal, equal) })
+    }
+
+    fn patch_jump(&mut self, offset: usize) {
+        let addr = self.here();
+        self.rom[offset] = 0x10 | (addr >> 8) as u8;
+        self.rom[offset + 1] = addr as u8;
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let word = self.next()?;
+        let line = self.line();
+
+        match word {
+            ":" => {
+                let name = self.next()?;
+                if is_register(name) || parse_number(name).is_some() || KEYWORDS.contains(&name) {
+                    return Err(format!("{} can't be used as a label", name));
+                }
+                if self.labels.insert(name, self.here()).is_some() {
+                    return Err(format!("the label {} is defined twice", name));
+                }
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value_word = self.next()?;
+                let value = self.value(value_word)
+                    .or_else(|| self.labels.get(value_word).map(|&addr| addr as i64))
+                    .ok_or_else(|| format!("expected a number but found {}", value_word))?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let reg = self.register()?;
+                self.aliases.insert(name, reg as u8);
+            }
+            ":org" => {
+                let word = self.next()?;
+                let addr = self.value(word).ok_or_else(|| format!("expected an address but found {}", word))?;
+                if addr < self.here() as i64 || addr > 0xFFF {
+                    return Err(format!(":org can only move forward, to at most 0xFFF, and the program is already at 0x{:03X}", self.here()));
+                }
+                self.rom.resize(addr as usize - PROGRAM_START, 0);
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.rom.push(byte as u8);
+            }
+            ":call" => self.address(0x2000)?,
+            ":unpack" => {
+                // v0 gets the nibble and the label's top 4 bits, v1 the rest of it
+                let nibble = self.nibble()? as u8;
+                let label = self.next()?;
+                if let Some(addr) = self.value(label).or_else(|| self.labels.get(label).map(|&addr| addr as i64)) {
+                    self.emit(0x6000 | (nibble as u16) << 4 | (addr as u16 >> 8) & 0xF);
+                    self.emit(0x6100 | addr as u16 & 0xFF);
+                }
+                else {
+                    self.fixups.push((Fixup::HighNibble(self.rom.len() + 1), label, line));
+                    self.emit(0x6000 | (nibble as u16) << 4);
+                    self.fixups.push((Fixup::LowByte(self.rom.len() + 1), label, line));
+                    self.emit(0x6100);
+                }
+            }
+            ":breakpoint" => {
+                self.next()?; // only means something to Octo's debugger
+            }
+            ":macro" | ":calc" | ":next" | ":stringmode" | ":pointer" | ":assert" | ":monitor" => {
+                return Err(format!("{} isn't supported by this assembler", word));
+            }
+            "return" | ";" => self.emit(0x00EE),
+            "clear" => self.emit(0x00E0),
+            "hires" => self.emit(0x00FF),
+            "lores" => self.emit(0x00FE),
+            "scroll-left" => self.emit(0x00FC),
+            "scroll-right" => self.emit(0x00FB),
+            "exit" => self.emit(0x00FD),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.emit(0x00C0 | n);
+            }
+            "bcd" | "save" | "load" | "saveflags" | "loadflags" => {
+                let x = self.register()? << 8;
+                if self.peek() == Some("-") {
+                    return Err(String::from("saving and loading ranges of registers is XO-CHIP, which isn't supported"));
+                }
+                let low_byte = match word {
+                    "bcd" => 0x33,
+                    "save" => 0x55,
+                    "load" => 0x65,
+                    "saveflags" => 0x75,
+                    _ => 0x85,
+                };
+                self.emit(0xF000 | x | low_byte);
+            }
+            "sprite" => {
+                let x = self.register()? << 8;
+                let y = self.register()? << 4;
+                let n = self.nibble()?;
+                self.emit(0xD000 | x | y | n);
+            }
+            "jump" => self.address(0x1000)?,
+            "jump0" => self.address(0xB000)?,
+            "native" => self.address(0x0000)?,
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let x = self.register()? << 8;
+                self.emit(0xF000 | x | if word == "delay" { 0x15 } else { 0x18 });
+            }
+            "i" => match self.next()? {
+                ":=" => match self.peek() {
+                    Some("hex") | Some("bighex") => {
+                        let font = self.next()?;
+                        let x = self.register()? << 8;
+                        self.emit(0xF000 | x | if font == "hex" { 0x29 } else { 0x30 });
+                    }
+                    _ => self.address(0xA000)?,
+                },
+                "+=" => {
+                    let x = self.register()? << 8;
+                    self.emit(0xF01E | x);
+                }
+                op => return Err(format!("expected := or += after i but found {}", op)),
+            },
+            "if" => {
+                let (skip_if_true, skip_if_false) = self.condition()?;
+                match self.next()? {
+                    "then" => self.emit(skip_if_false),
+                    "begin" => {
+                        self.emit(skip_if_true);
+                        self.flow.push((Flow::Begin(self.rom.len()), line));
+                        self.emit(0x1000);
+                    }
+                    word => return Err(format!("expected then or begin but found {}", word)),
+                }
+            }
+            "else" => {
+                let Some((Flow::Begin(offset), opened)) = self.flow.pop() else {
+                    return Err(String::from("else without a matching if ... begin"));
+                };
+                self.flow.push((Flow::Else(self.rom.len()), opened));
+                self.emit(0x1000);
+                self.patch_jump(offset);
+            }
+            "end" => match self.flow.pop() {
+                Some((Flow::Begin(offset) | Flow::Else(offset), _)) => self.patch_jump(offset),
+                _ => return Err(String::from("end without a matching begin")),
+            },
+            "loop" => self.flow.push((Flow::Loop(self.here(), vec![]), line)),
+            "while" => {
+                let (skip_if_true, _) = self.condition()?;
+                let Some((Flow::Loop(_, exits), _)) = self.flow.iter_mut().rev().find(|(flow, _)| matches!(flow, Flow::Loop(..))) else {
+                    return Err(String::from("while outside of a loop"));
+                };
+                exits.push(self.rom.len() + 2);
+                self.emit(skip_if_true);
+                self.emit(0x1000);
+            }
+            "again" => {
+                let Some((Flow::Loop(start, exits), _)) = self.flow.pop() else {
+                    return Err(String::from("again without a matching loop"));
+                };
+                self.emit(0x1000 | start);
+                for exit in exits {
+                    self.patch_jump(exit);
+                }
+            }
+            _ if register_number(word).is_some() || self.aliases.contains_key(word) => {
+                self.pos -= 1;
+                self.register_statement()?;
+            }
+            _ => match self.value(word) {
+                Some(value) if (-128..=255).contains(&value) => self.rom.push(value as u8),
+                Some(value) => return Err(format!("{} doesn't fit in a byte", value)),
+                None if KEYWORDS.contains(&word) || word.starts_with(':') => return Err(format!("unexpected {}", word)),
+                None => self.emit_fixup(0x2000, word, line), // calling a subroutine by its name
+            },
+        }
+
+        Ok(())
+    }
+
+    fn register_statement(&mut self) -> Result<(), String> {
+        let x = self.register()? << 8;
+        let op = self.next()?;
+        let takes_register = matches!(self.peek(), Some(word) if register_number(word).is_some() || self.aliases.contains_key(word));
+
+        let opcode = match op {
+            ":=" if takes_register => 0x8000 | x | self.register()? << 4,
+            ":=" => match self.peek() {
+                Some("random") => {
+                    self.next()?;
+                    0xC000 | x | self.byte()?
+                }
+                Some("key") => {
+                    self.next()?;
+                    0xF00A | x
+                }
+                Some("delay") => {
+                    self.next()?;
+                    0xF007 | x
+                }
+                _ => 0x6000 | x | self.byte()?,
+            },
+            "+=" if takes_register => 0x8004 | x | self.register()? << 4,
+            "+=" => 0x7000 | x | self.byte()?,
+            "-=" if takes_register => 0x8005 | x | self.register()? << 4,
+            "-=" => 0x7000 | x | (self.byte()? as u8).wrapping_neg() as u16,
+            "=-" => 0x8007 | x | self.register()? << 4,
+            "|=" => 0x8001 | x | self.register()? << 4,
+            "&=" => 0x8002 | x | self.register()? << 4,
+            "^=" => 0x8003 | x | self.register()? << 4,
+            ">>=" => 0x8006 | x | self.register()? << 4,
+            "<<=" => 0x800E | x | self.register()? << 4,
+            _ => return Err(format!("expected an operator such as := or += but found {}", op)),
+        };
+        self.emit(opcode);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opcodes(source: &str) -> Vec<u16> {
+        let rom = assemble(source).unwrap_or_else(|errors| panic!("{:?}", errors));
+        rom.chunks(2).map(|pair| (pair[0] as u16) << 8 | *pair.get(1).unwrap_or(&0) as u16).collect()
+    }
+
+    #[test]
+    fn statements() {
+        let source = "
+            : main
+                clear
+                v3 := 0x1F  v3 += v4  v3 -= 1  va =- vb  v1 >>= v1
+                i := hex v3  i += v0  delay := v2  v5 := key
+                sprite v0 v1 5
+                bcd v3 save v2 load vf
+                return
+        ";
+
+        assert_eq!(opcodes(source), [
+            0x00E0, 0x631F, 0x8344, 0x73FF, 0x8AB7, 0x8116, 0xF329, 0xF01E, 0xF215, 0xF50A, 0xD015, 0xF333, 0xF255, 0xFF65, 0x00EE,
+        ]);
+    }
+
+    #[test]
+    fn labels_and_jump_to_main() {
+        let source = "
+            : sprite-data 0x3C 0x7E
+            : draw i := sprite-data sprite v0 v0 2 ;
+            : main draw jump main
+        ";
+
+        assert_eq!(opcodes(source), [0x120A, 0x3C7E, 0xA202, 0xD002, 0x00EE, 0x2204, 0x120A]);
+    }
+
+    #[test]
+    fn control_structures() {
+        let source = "
+            : main
+                loop
+                    if v0 == 3 then v1 += 1
+                    if v0 key begin v2 := 1 else v2 := 2 end
+                    while v0 != v1
+                again
+        ";
+
+        assert_eq!(opcodes(source), [
+            0x4003, 0x7101,          // 200: if then
+            0xE09E, 0x120C, 0x6201,  // 204: skip the jump to else when pressed
+            0x120E, 0x6202,          // 20A: else
+            0x9010, 0x1214,          // 20E: while
+            0x1200,                  // 212: again
+        ]);
+    }
+
+    #[test]
+    fn constants_aliases_and_unpack() {
+        let source = "
+            :const SPEED 4
+            :alias ball-x v6
+            : main ball-x += SPEED :unpack 0xA data
+            : data 0
+        ";
+
+        assert_eq!(opcodes(source), [0x7604, 0x60A2, 0x6106, 0x0000]);
+    }
+
+    #[test]
+    fn errors_have_lines() {
+        let errors = assemble(": main\n  v0 := 300\n  jump nowhere\n  v1 ++ 1\n  loop").unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|error| error.line).collect();
+
+        assert_eq!(lines, [2, 3, 4, 5]);
+    }
+}
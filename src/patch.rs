@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cpu::MAX_ROM_SIZE;
+
+const IPS_HEADER: &[u8] = b"PATCH";
+const IPS_FOOTER: &[u8] = b"EOF";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PatchFile {
+    pub path: PathBuf,
+    pub enabled: bool,
+}
+
+// a list of changes to make to a ROM, read from an IPS patch or a plain text list of offsets and bytes
+pub struct Patch {
+    records: Vec<(usize, Vec<u8>)>,
+    truncate: Option<usize>,
+}
+
+impl Patch {
+    pub fn load(path: &Path) -> Result<Patch, String> {
+        let contents = fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+        let patch = if contents.starts_with(IPS_HEADER) {
+            Patch::parse_ips(&contents)
+        }
+        else {
+            let text = String::from_utf8(contents).map_err(|_| String::from("it is neither an IPS patch nor a text patch list"))?;
+            Patch::parse_list(&text)
+        };
+
+        patch.map_err(|e| format!("Could not use {}: {}", path.display(), e))
+    }
+
+    pub fn parse_ips(data: &[u8]) -> Result<Patch, String> {
+        let mut records = vec![];
+        let mut pos = IPS_HEADER.len();
+
+        let mut take = |len: usize| -> Result<&[u8], String> {
+            let bytes = data.get(pos..pos + len).ok_or_else(|| String::from("the patch ends part way through a record"))?;
+            pos += len;
+            Ok(bytes)
+        };
+
+        loop {
+            let offset = take(3)?;
+            if offset == IPS_FOOTER {
+                break;
+            }
+            let offset = be_number(offset);
+
+            let size = be_number(take(2)?);
+            let bytes = if size == 0 {
+                // run length encoded, a count then the byte to repeat
+                let count = be_number(take(2)?);
+                vec![take(1)?[0]; count]
+            }
+            else {
+                take(size)?.to_vec()
+            };
+
+            records.push(record(offset, bytes)?);
+        }
+
+        // some patches end with the size to cut the file down to
+        let truncate = take(3).ok().map(be_number);
+
+        Ok(Patch { records, truncate })
+    }
+
+    // lines like "0x2A4: 12 34" write those bytes starting at that offset into the ROM, # starts a comment
+    pub fn parse_list(text: &str) -> Result<Patch, String> {
+        let mut records = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || format!("line {} should look like \"offset: byte byte...\" in hex", number + 1);
+            let (offset, bytes) = line.split_once(':').ok_or_else(invalid)?;
+            let offset = usize::from_str_radix(offset.trim().trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+            let bytes = bytes.split_whitespace()
+                .map(|byte| u8::from_str_radix(byte.trim_start_matches("0x"), 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| invalid())?;
+
+            records.push(record(offset, bytes).map_err(|e| format!("line {}: {}", number + 1, e))?);
+        }
+
+        Ok(Patch { records, truncate: None })
+    }
+
+    pub fn apply(&self, rom: &mut Vec<u8>) {
+        for (offset, bytes) in &self.records {
+            let end = offset + bytes.len();
+            if rom.len() < end {
+                rom.resize(end, 0);
+            }
+            rom[*offset..end].copy_from_slice(bytes);
+        }
+
+        if let Some(size) = self.truncate {
+            rom.truncate(size);
+        }
+    }
+}
+
+// a record has to fit in the space a ROM can take up, so a bad patch can't make it any bigger than that
+fn record(offset: usize, bytes: Vec<u8>) -> Result<(usize, Vec<u8>), String> {
+    match offset.checked_add(bytes.len()) {
+        Some(end) if end <= MAX_ROM_SIZE => Ok((offset, bytes)),
+        _ => Err(format!("the change at {:X} goes past the end of the largest ROM there can be", offset)),
+    }
+}
+
+fn be_number(bytes: &[u8]) -> usize {
+    bytes.iter().fold(0, |number, &byte| number << 8 | byte as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_patches() {
+        let mut rom = vec![0x12, 0x00];
+        Patch::parse_list("0x0: 13 # jump somewhere else\n\n3: AA BB").unwrap().apply(&mut rom);
+        assert_eq!(rom, [0x13, 0x00, 0x00, 0xAA, 0xBB]);
+
+        // a record of 2 bytes at 1, then 3 copies of FF at 4, then cut down to 6 bytes
+        let ips = b"PATCH\x00\x00\x01\x00\x02\x01\x02\x00\x00\x04\x00\x00\x00\x03\xFFEOF\x00\x00\x06";
+        let mut rom = vec![0; 10];
+        Patch::parse_ips(ips).unwrap().apply(&mut rom);
+        assert_eq!(rom, [0x00, 0x01, 0x02, 0x00, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn rejects_records_past_the_largest_rom() {
+        assert!(Patch::parse_list(&format!("{:X}: 01", MAX_ROM_SIZE - 1)).is_ok());
+        assert!(Patch::parse_list(&format!("{:X}: 01", MAX_ROM_SIZE)).is_err());
+        assert!(Patch::parse_list(&format!("{:X}: 01", usize::MAX)).is_err());
+        assert!(Patch::parse_list("0: 12 zz").is_err());
+
+        // FFFF copies of 00 at 0 run length encoded, then a record at FFFFFF
+        assert!(Patch::parse_ips(b"PATCH\x00\x00\x00\x00\x00\xFF\xFF\x00EOF").is_err());
+        assert!(Patch::parse_ips(b"PATCH\xFF\xFF\xFF\x00\x01\x00EOF").is_err());
+        assert!(Patch::parse_ips(b"PATCH\x00\x00\x00\x00\x05\x01").is_err());
+    }
+}
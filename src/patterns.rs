@@ -0,0 +1,132 @@
+use ggegui::egui::{self, Color32, Rect, Sense, Stroke};
+
+use crate::audio::{pattern_rate, AudioSettings, PatternPreview, DEFAULT_PITCH, PATTERN_BYTES};
+use crate::io::show_error;
+
+const CELL_SIZE: f32 = 12.0;
+const BITS_PER_ROW: usize = 32;
+const ROWS: usize = PATTERN_BYTES * 8 / BITS_PER_ROW;
+
+const SQUARE_WAVE: [u8; PATTERN_BYTES] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0];
+
+// designs the 128 bit audio patterns XO-CHIP plays with F002, one bit at a time
+//
+// the core doesn't play XO-CHIP sound yet so patterns are only heard in the preview here, but they can be copied
+// into programs for XO-CHIP interpreters
+pub struct PatternEditor {
+    pattern: [u8; PATTERN_BYTES],
+    pitch: u8,
+    preview: Option<PatternPreview>,
+    painting: Option<bool>,
+}
+
+impl Default for PatternEditor {
+    fn default() -> Self {
+        Self {
+            pattern: SQUARE_WAVE,
+            pitch: DEFAULT_PITCH,
+            preview: None,
+            painting: None,
+        }
+    }
+}
+
+impl PatternEditor {
+    // previews on the output device from the configuration, like the buzzer
+    pub fn show(&mut self, ui: &mut egui::Ui, audio: &AudioSettings) {
+        ui.horizontal(|ui| {
+            ui.label("Pitch: ");
+            ui.add(egui::DragValue::new(&mut self.pitch));
+            // the pattern is one cycle of the wave so the note is the bit rate over its length
+            let rate = pattern_rate(self.pitch);
+            ui.label(format!("{:.0} bits a second, a {:.1}Hz tone", rate, rate / (PATTERN_BYTES * 8) as f32));
+        });
+
+        ui.horizontal(|ui| {
+            for (label, pattern) in [
+                ("Square", SQUARE_WAVE),
+                ("Thin pulse", [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]),
+                ("Octave up", [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]),
+                ("Silence", [0; PATTERN_BYTES]),
+            ] {
+                if ui.button(label).clicked() {
+                    self.pattern = pattern;
+                }
+            }
+            if ui.button("Noise").clicked() {
+                self.pattern = rand::random();
+            }
+        });
+
+        self.show_grid(ui);
+
+        let mut previewing = self.preview.is_some();
+        if ui.checkbox(&mut previewing, "Preview").changed() {
+            self.preview = None;
+            if previewing {
+                match PatternPreview::new(audio, &self.pattern, self.pitch) {
+                    Ok(preview) => self.preview = Some(preview),
+                    Err(e) => show_error("Could not preview the pattern", e.to_string()),
+                }
+            }
+        }
+        if let Some(preview) = &self.preview {
+            preview.update(&self.pattern, self.pitch);
+        }
+
+        let bytes: Vec<String> = self.pattern.iter().map(|byte| format!("0x{:02X}", byte)).collect();
+        let bytes = bytes.join(" ");
+        ui.horizontal(|ui| {
+            if ui.button("Copy bytes").clicked() {
+                ui.output_mut(|output| output.copied_text = bytes.clone());
+            }
+            if ui.button("Copy as Octo").clicked() {
+                let source = format!(": sound\n\t{}\n\n# to play it\n# i := sound audio\n# v0 := {} pitch := v0\n# v0 := 30 buzzer := v0\n", bytes, self.pitch);
+                ui.output_mut(|output| output.copied_text = source);
+            }
+        });
+        ui.monospace(bytes);
+    }
+
+    pub fn stop_preview(&mut self) {
+        self.preview = None;
+    }
+
+    fn show_grid(&mut self, ui: &mut egui::Ui) {
+        let size = egui::vec2(BITS_PER_ROW as f32 * CELL_SIZE, ROWS as f32 * CELL_SIZE);
+        let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+        let origin = response.rect.min;
+
+        // bits run left to right then on to the next row, in the order they're played
+        if let Some(pos) = response.interact_pointer_pos() {
+            let x = ((pos.x - origin.x) / CELL_SIZE).floor();
+            let y = ((pos.y - origin.y) / CELL_SIZE).floor();
+
+            if x >= 0.0 && y >= 0.0 && (x as usize) < BITS_PER_ROW && (y as usize) < ROWS {
+                let bit = y as usize * BITS_PER_ROW + x as usize;
+                let mask = 0x80 >> (bit % 8);
+                let value = *self.painting.get_or_insert(self.pattern[bit / 8] & mask == 0);
+
+                if value {
+                    self.pattern[bit / 8] |= mask;
+                }
+                else {
+                    self.pattern[bit / 8] &= !mask;
+                }
+            }
+        }
+        if !response.is_pointer_button_down_on() {
+            self.painting = None;
+        }
+
+        let visuals = ui.visuals();
+        for bit in 0..PATTERN_BYTES * 8 {
+            let min = origin + egui::vec2((bit % BITS_PER_ROW) as f32, (bit / BITS_PER_ROW) as f32) * CELL_SIZE;
+            let cell = Rect::from_min_size(min, egui::vec2(CELL_SIZE, CELL_SIZE));
+            let set = self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            let fill = if set { visuals.strong_text_color() } else { visuals.extreme_bg_color };
+
+            painter.rect(cell, 0.0, fill, Stroke::new(1.0, Color32::from_gray(64)));
+        }
+    }
+}
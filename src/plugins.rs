@@ -0,0 +1,338 @@
+// extensions to the frontend that live outside this crate. A plugin is anything implementing Plugin, either compiled
+// into a build of the emulator that passes it to emulator_main_with_plugins, or a script in the plugins folder that's
+// run as its own process and talks to the emulator with a line of JSON at a time over stdin and stdout.
+//
+// scripts are sent {"event": "frame", ...} with the registers, timers and keys after every emulated frame and
+// {"event": "memory", "address": ..., "bytes": [...]} in reply to read_memory. They can send back:
+//   {"command": "input", "hold": 0, "block": 0}           keys to hold down and keys to hide from the game, as bitmasks
+//   {"command": "read_memory", "address": 512, "length": 16}
+//   {"command": "write_memory", "address": 512, "bytes": [1, 2]}
+//   {"command": "panel", "lines": ["shown in the plugin's window"]}
+//
+// Rust has no stable ABI so plugins aren't loaded from dynamic libraries, a library built with a different compiler
+// or version of egui would crash the emulator rather than failing to load
+
+use ggegui::egui;
+use serde::Deserialize;
+use serde_json::json;
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError};
+use std::thread;
+
+use crate::cpu::CPU;
+
+pub const PLUGIN_DIR: &str = "plugins"; // in the user data directory
+
+const EVENT_BACKLOG: usize = 60; // events are dropped instead of piling up once a script falls this far behind
+
+// every hook does nothing by default so a plugin only has to write the ones it uses
+pub trait Plugin {
+    fn name(&self) -> &str;
+
+    // the keys the game is about to be given this frame, returning the ones it should be given instead
+    fn filter_input(&mut self, keys: u16) -> u16 {
+        keys
+    }
+
+    // after each emulated frame, with everything the program can see
+    fn frame(&mut self, _cpu: &mut CPU) {}
+
+    // drawn in the plugin's window, which is only offered when this is true
+    fn has_panel(&self) -> bool {
+        false
+    }
+
+    fn panel(&mut self, _ui: &mut egui::Ui, _cpu: &mut CPU) {}
+}
+
+struct LoadedPlugin {
+    plugin: Box<dyn Plugin>,
+    script: bool, // from the plugins folder rather than compiled in
+    window_open: bool,
+}
+
+// the loaded plugins, run in the order they were loaded in
+#[derive(Default)]
+pub struct Plugins {
+    loaded: Vec<LoadedPlugin>,
+}
+
+impl Plugins {
+    pub fn new(plugins: Vec<Box<dyn Plugin>>) -> Plugins {
+        Plugins { loaded: plugins.into_iter().map(|plugin| LoadedPlugin { plugin, script: false, window_open: false }).collect() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.loaded.is_empty()
+    }
+
+    // starts every script in dir, giving back why any of them couldn't be
+    pub fn load_scripts(&mut self, dir: &Path) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(dir) else {
+            return vec![]; // nobody has made the folder yet
+        };
+
+        let mut paths: Vec<_> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).filter(|path| path.is_file()).collect();
+        paths.sort();
+
+        let mut errors = vec![];
+        for path in paths {
+            match ScriptPlugin::start(&path) {
+                Ok(plugin) => self.loaded.push(LoadedPlugin { plugin: Box::new(plugin), script: true, window_open: false }),
+                Err(e) => errors.push(e),
+            }
+        }
+        errors
+    }
+
+    // stops the scripts, the plugins compiled in stay
+    pub fn unload_scripts(&mut self) {
+        self.loaded.retain(|loaded| !loaded.script);
+    }
+
+    pub fn filter_input(&mut self, keys: u16) -> u16 {
+        self.loaded.iter_mut().fold(keys, |keys, loaded| loaded.plugin.filter_input(keys))
+    }
+
+    pub fn frame(&mut self, cpu: &mut CPU) {
+        for loaded in &mut self.loaded {
+            loaded.plugin.frame(cpu);
+        }
+    }
+
+    // a checkbox for each plugin with a window, for the Plugins menu
+    pub fn menu(&mut self, ui: &mut egui::Ui) {
+        for loaded in &mut self.loaded {
+            if loaded.plugin.has_panel() {
+                ui.checkbox(&mut loaded.window_open, loaded.plugin.name().to_string());
+            }
+            else {
+                ui.label(loaded.plugin.name());
+            }
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context, cpu: &mut CPU) {
+        for loaded in &mut self.loaded {
+            if loaded.window_open && loaded.plugin.has_panel() {
+                let name = loaded.plugin.name().to_string();
+                let plugin = &mut loaded.plugin;
+                egui::Window::new(&name).id(egui::Id::new(("Plugin", &name))).open(&mut loaded.window_open).show(ctx, |ui| plugin.panel(ui, cpu));
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, PartialEq, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+enum ScriptCommand {
+    Input { #[serde(default)] hold: u16, #[serde(default)] block: u16 },
+    ReadMemory { address: u16, length: u16 },
+    WriteMemory { address: u16, bytes: Vec<u8> },
+    Panel { lines: Vec<String> },
+}
+
+// a plugin running as its own process. Its output is read on a thread and its input written on another so a script
+// that's slow or stuck can't hold up the emulator
+struct ScriptPlugin {
+    name: String,
+    child: Child,
+    events: SyncSender<String>,
+    commands: Receiver<String>,
+    hold: u16,
+    block: u16,
+    panel: Vec<String>,
+    error: Option<String>, // why it stopped, once it has
+}
+
+impl ScriptPlugin {
+    fn start(path: &Path) -> Result<ScriptPlugin, String> {
+        let file_name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Could not start the plugin {}: {}", path.display(), e))?;
+
+        let (events, events_receiver) = mpsc::sync_channel::<String>(EVENT_BACKLOG);
+        if let Some(mut stdin) = child.stdin.take() {
+            thread::spawn(move || {
+                for event in events_receiver {
+                    if writeln!(stdin, "{}", event).and_then(|_| stdin.flush()).is_err() {
+                        break; // it's exited
+                    }
+                }
+            });
+        }
+
+        let (commands_sender, commands) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    let Ok(line) = line else {
+                        break;
+                    };
+                    if commands_sender.send(line).is_err() {
+                        break; // the plugin has been unloaded
+                    }
+                }
+            });
+        }
+
+        Ok(ScriptPlugin {
+            name: file_name,
+            child,
+            events,
+            commands,
+            hold: 0,
+            block: 0,
+            panel: vec![],
+            error: None,
+        })
+    }
+
+    fn send(&mut self, event: serde_json::Value) {
+        if self.error.is_none() {
+            if let Err(TrySendError::Disconnected(_)) = self.events.try_send(event.to_string()) {
+                self.stopped();
+            }
+        }
+    }
+
+    fn stopped(&mut self) {
+        let status = self.child.try_wait().ok().flatten();
+        self.error = Some(match status {
+            Some(status) => format!("The plugin exited ({})", status),
+            None => String::from("The plugin stopped talking to the emulator"),
+        });
+        self.hold = 0;
+        self.block = 0;
+    }
+
+    fn run(&mut self, command: ScriptCommand, cpu: &mut CPU) {
+        match command {
+            ScriptCommand::Input { hold, block } => {
+                self.hold = hold;
+                self.block = block;
+            },
+            ScriptCommand::ReadMemory { address, length } => {
+                let bytes: Vec<u8> = (0..length).map(|offset| cpu.read_memory(address.wrapping_add(offset))).collect();
+                self.send(json!({ "event": "memory", "address": address, "bytes": bytes }));
+            },
+            ScriptCommand::WriteMemory { address, bytes } => {
+                for (offset, byte) in bytes.into_iter().enumerate() {
+                    cpu.write_memory(address.wrapping_add(offset as u16), byte);
+                }
+            },
+            ScriptCommand::Panel { lines } => self.panel = lines,
+        }
+    }
+}
+
+impl Plugin for ScriptPlugin {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn filter_input(&mut self, keys: u16) -> u16 {
+        (keys | self.hold) & !self.block
+    }
+
+    fn frame(&mut self, cpu: &mut CPU) {
+        if self.error.is_some() {
+            return;
+        }
+
+        loop {
+            match self.commands.try_recv() {
+                Ok(line) => match serde_json::from_str(&line) {
+                    Ok(command) => self.run(command, cpu),
+                    Err(e) => self.panel = vec![format!("Invalid command {}: {}", line, e)],
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.stopped();
+                    return;
+                },
+            }
+        }
+
+        self.send(json!({
+            "event": "frame",
+            "pc": cpu.pc(),
+            "i": cpu.addr_reg(),
+            "registers": cpu.registers(),
+            "delay_timer": cpu.delay_timer(),
+            "sound_timer": cpu.sound_timer(),
+            "keys": cpu.keys(),
+        }));
+    }
+
+    fn has_panel(&self) -> bool {
+        true
+    }
+
+    fn panel(&mut self, ui: &mut egui::Ui, _cpu: &mut CPU) {
+        if let Some(error) = &self.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+        if self.panel.is_empty() && self.error.is_none() {
+            ui.label("This plugin hasn't shown anything yet.");
+        }
+        for line in &self.panel {
+            ui.monospace(line);
+        }
+    }
+}
+
+impl Drop for ScriptPlugin {
+    fn drop(&mut self) {
+        // it may well have exited already
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Turbo;
+
+    impl Plugin for Turbo {
+        fn name(&self) -> &str {
+            "Turbo"
+        }
+
+        fn filter_input(&mut self, keys: u16) -> u16 {
+            keys | 1 << 5
+        }
+
+        fn frame(&mut self, cpu: &mut CPU) {
+            cpu.write_memory(0xE00, cpu.read_memory(0xE00) + 1);
+        }
+    }
+
+    #[test]
+    fn runs_hooks_and_reads_commands() {
+        let mut plugins = Plugins::new(vec![Box::new(Turbo)]);
+        let mut cpu = CPU::new();
+        plugins.frame(&mut cpu);
+        plugins.frame(&mut cpu);
+        assert_eq!(cpu.read_memory(0xE00), 2);
+        assert_eq!(plugins.filter_input(1 << 2), 1 << 2 | 1 << 5);
+
+        // plugins that aren't scripts survive the scripts being reloaded
+        plugins.unload_scripts();
+        assert!(!plugins.is_empty());
+
+        let command = |line| serde_json::from_str::<ScriptCommand>(line).unwrap();
+        assert_eq!(command(r#"{"command": "input", "hold": 3}"#), ScriptCommand::Input { hold: 3, block: 0 });
+        assert_eq!(command(r#"{"command": "write_memory", "address": 512, "bytes": [1, 2]}"#), ScriptCommand::WriteMemory { address: 512, bytes: vec![1, 2] });
+        assert!(serde_json::from_str::<ScriptCommand>(r#"{"command": "format_disk"}"#).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// how CXNN comes up with its random numbers. Most ROMs don't mind but some were only ever tested against one
+// interpreter and rely on the numbers it happened to give, such as never rolling the same value twice in a row
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+pub enum RngAlgorithm {
+    Standard, // rand's StdRng, the best numbers
+    Xorshift, // Marsaglia's 64 bit xorshift, cheap and used by a lot of small interpreters
+    Lcg,      // the LCG behind C's rand() on Windows, so rand() % 256 in an interpreter written in C
+    Counter,  // a counter run through a mixing function, each number only depends on how many came before it
+}
+
+impl RngAlgorithm {
+    pub const ALL: [RngAlgorithm; 4] = [RngAlgorithm::Standard, RngAlgorithm::Xorshift, RngAlgorithm::Lcg, RngAlgorithm::Counter];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RngAlgorithm::Standard => "Standard",
+            RngAlgorithm::Xorshift => "Xorshift",
+            RngAlgorithm::Lcg => "C rand()",
+            RngAlgorithm::Counter => "Counter",
+        }
+    }
+}
+
+// all the generators side by side so the algorithm can be switched while a program is running
+#[derive(Clone)]
+pub struct Random {
+    standard: StdRng,
+    state: u64, // the xorshift, LCG or counter state, whichever is in use
+}
+
+impl Random {
+    pub fn from_entropy() -> Random {
+        let mut standard = StdRng::from_entropy();
+        let state = standard.gen();
+        Random { standard, state }
+    }
+
+    pub fn seeded(seed: u64) -> Random {
+        Random { standard: StdRng::seed_from_u64(seed), state: seed }
+    }
+
+    pub fn next(&mut self, algorithm: RngAlgorithm) -> u8 {
+        match algorithm {
+            RngAlgorithm::Standard => self.standard.gen(),
+            RngAlgorithm::Xorshift => {
+                if self.state == 0 {
+                    self.state = 0x9E3779B97F4A7C15; // xorshift never leaves 0
+                }
+                self.state ^= self.state << 13;
+                self.state ^= self.state >> 7;
+                self.state ^= self.state << 17;
+                (self.state >> 32) as u8
+            },
+            RngAlgorithm::Lcg => {
+                let state = (self.state as u32).wrapping_mul(214013).wrapping_add(2531011);
+                self.state = state as u64;
+                (state >> 16) as u8
+            },
+            RngAlgorithm::Counter => {
+                self.state = self.state.wrapping_add(1);
+                splitmix(self.state) as u8
+            },
+        }
+    }
+
+    // stands in for the whole state, for telling whether two generators will give the same numbers
+    pub fn fingerprint(&self) -> u64 {
+        self.standard.clone().gen::<u64>() ^ splitmix(self.state)
+    }
+}
+
+fn splitmix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E3779B97F4A7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D049BB133111EB);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn algorithms_are_repeatable() {
+        // what srand(1) then rand() % 256 gives with Microsoft's C runtime
+        let mut lcg = Random::seeded(1);
+        let numbers: Vec<u8> = (0..3).map(|_| lcg.next(RngAlgorithm::Lcg)).collect();
+        assert_eq!(numbers, [41, 35, 190]);
+
+        for algorithm in RngAlgorithm::ALL {
+            let (mut a, mut b) = (Random::seeded(5), Random::seeded(5));
+            let a: Vec<u8> = (0..32).map(|_| a.next(algorithm)).collect();
+            let b: Vec<u8> = (0..32).map(|_| b.next(algorithm)).collect();
+            assert_eq!(a, b, "{:?}", algorithm);
+            assert!(a.iter().any(|&number| number != a[0]), "{:?}", algorithm);
+        }
+
+        let mut xorshift = Random::seeded(0);
+        assert_ne!(xorshift.next(RngAlgorithm::Xorshift), xorshift.next(RngAlgorithm::Xorshift));
+    }
+}
@@ -0,0 +1,146 @@
+use ggez::graphics::Color;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+
+use crate::audio::{SquareWave, BEEP_FREQUENCY, SAMPLE_RATE};
+use crate::cpu::CPU;
+
+// every frame is recorded at the high resolution size so switching resolution partway through still works
+const FRAME_WIDTH: usize = 128;
+const FRAME_HEIGHT: usize = 64;
+const VIDEO_SCALE: usize = 8; // video players blur small videos when they scale them up
+const FRAMES_PER_SECOND: u32 = 60;
+const SAMPLES_PER_FRAME: usize = (SAMPLE_RATE / FRAMES_PER_SECOND) as usize;
+
+pub const VIDEO_EXTENSIONS: [&str; 2] = ["mp4", "webm"];
+
+// records every emulated frame and the buzzer into a video with ffmpeg. The pictures are encoded as they come in
+// and the sound is muxed in once recording stops
+pub struct Recording {
+    ffmpeg: Child,
+    path: PathBuf,
+    video_path: PathBuf, // the pictures without the sound, until they're muxed together
+    frame: Vec<u8>,
+    samples: Vec<i16>,
+    beep: SquareWave,
+}
+
+impl Recording {
+    pub fn start(path: &Path) -> Result<Recording, String> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_lowercase();
+        let video_codec = match extension.as_str() {
+            "mp4" => ["-c:v", "libx264", "-pix_fmt", "yuv420p"],
+            "webm" => ["-c:v", "libvpx-vp9", "-pix_fmt", "yuv420p"],
+            _ => return Err(format!("Videos can be saved as {}", VIDEO_EXTENSIONS.join(" or "))),
+        };
+        let video_path = path.with_extension(format!("video.{}", extension));
+
+        let ffmpeg = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{}x{}", FRAME_WIDTH, FRAME_HEIGHT), "-r", &FRAMES_PER_SECOND.to_string(), "-i", "-"])
+            .args(["-vf", &format!("scale=iw*{}:ih*{}:flags=neighbor", VIDEO_SCALE, VIDEO_SCALE)])
+            .args(video_codec)
+            .arg(&video_path)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| ffmpeg_error(&e))?;
+
+        Ok(Recording {
+            ffmpeg,
+            path: path.to_path_buf(),
+            video_path,
+            frame: Vec::with_capacity(FRAME_WIDTH * FRAME_HEIGHT * 3),
+            samples: vec![],
+            beep: SquareWave::new(BEEP_FREQUENCY),
+        })
+    }
+
+    // adds one 60Hz tick's worth of picture and sound
+    pub fn frame(&mut self, cpu: &CPU, on_colour: Color, off_colour: Color, beeping: bool) -> Result<(), String> {
+        let (on, off) = (on_colour.to_rgb(), off_colour.to_rgb());
+        let scale = FRAME_WIDTH / cpu.width();
+
+        self.frame.clear();
+        for y in 0..FRAME_HEIGHT {
+            for x in 0..FRAME_WIDTH {
+                let (r, g, b) = if cpu.pixel(x / scale, y / scale) { on } else { off };
+                self.frame.extend_from_slice(&[r, g, b]);
+            }
+        }
+
+        let stdin = self.ffmpeg.stdin.as_mut().ok_or("ffmpeg's input was closed")?;
+        stdin.write_all(&self.frame).map_err(|e| format!("ffmpeg stopped taking frames: {}", e))?;
+
+        // the wave keeps going while it's quiet so it doesn't restart mid cycle each beep
+        for sample in self.beep.by_ref().take(SAMPLES_PER_FRAME) {
+            self.samples.push(if beeping { (sample * i16::MAX as f32) as i16 } else { 0 });
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        drop(self.ffmpeg.stdin.take()); // ends the input so ffmpeg finishes the file
+        let status = self.ffmpeg.wait().map_err(|e| e.to_string())?;
+        if !status.success() {
+            return Err(format!("ffmpeg could not encode the video ({})", status));
+        }
+
+        let sound_path = self.path.with_extension("wav");
+        write_wav(&sound_path, &self.samples).map_err(|e| format!("Could not write {}: {}", sound_path.display(), e))?;
+
+        let audio_codec = if self.path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("webm")) { "libopus" } else { "aac" };
+        let muxed = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-i"])
+            .arg(&self.video_path)
+            .arg("-i")
+            .arg(&sound_path)
+            .args(["-c:v", "copy", "-c:a", audio_codec, "-shortest"])
+            .arg(&self.path)
+            .status();
+
+        let _ = fs::remove_file(&self.video_path);
+        let _ = fs::remove_file(&sound_path);
+        match muxed {
+            Ok(status) if status.success() => Ok(()),
+            Ok(status) => Err(format!("ffmpeg could not add the sound to the video ({})", status)),
+            Err(e) => Err(ffmpeg_error(&e)),
+        }
+    }
+}
+
+fn ffmpeg_error(e: &io::Error) -> String {
+    if e.kind() == io::ErrorKind::NotFound {
+        String::from("Recording videos needs ffmpeg. Install it and make sure it's on your PATH.")
+    }
+    else {
+        format!("Could not run ffmpeg: {}", e)
+    }
+}
+
+// 16 bit mono PCM
+fn write_wav(path: &Path, samples: &[i16]) -> io::Result<()> {
+    let data_size = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+
+    fs::write(path, wav)
+}
@@ -0,0 +1,163 @@
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tungstenite::Message;
+
+use std::io;
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryIter};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_REMOTE_PORT: u16 = 8264;
+
+const ACCEPT_POLL_TIME: Duration = Duration::from_millis(100);
+const MAX_STEPS: u32 = 100_000; // more than a second's worth at any speed, so a step can't hang the emulator
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum Command {
+    LoadRom { path: Option<String>, rom: Option<Vec<u8>> }, // either a file to read or the ROM itself
+    Pause,
+    Resume,
+    Step { #[serde(default = "one")] count: u32 },
+    SetKeys { keys: u16 },                                  // bit N set while key N is held
+    GetFramebuffer,
+    GetState,
+    GetMemory,
+}
+
+fn one() -> u32 {
+    1
+}
+
+impl Command {
+    pub fn parse(text: &str) -> Result<Command, String> {
+        let command = serde_json::from_str(text).map_err(|e| format!("Invalid command: {}", e))?;
+        Ok(match command {
+            Command::Step { count } => Command::Step { count: count.min(MAX_STEPS) },
+            command => command,
+        })
+    }
+}
+
+// a command waiting for the emulator to run it on the main thread
+pub struct Request {
+    pub command: Command,
+    reply: Sender<Value>,
+}
+
+impl Request {
+    pub fn reply(self, result: Result<Value, String>) {
+        let response = match result {
+            Ok(Value::Object(mut fields)) => {
+                fields.insert(String::from("ok"), Value::Bool(true));
+                Value::Object(fields)
+            },
+            Ok(_) => json!({ "ok": true }),
+            Err(e) => json!({ "ok": false, "error": e }),
+        };
+
+        // the connection may have gone away in the meantime, there's no one to tell if it has
+        let _ = self.reply.send(response);
+    }
+}
+
+// a WebSocket server on localhost that takes JSON commands, it stops when dropped
+pub struct RemoteServer {
+    requests: Receiver<Request>,
+    running: Arc<AtomicBool>,
+}
+
+impl RemoteServer {
+    pub fn start(port: u16) -> io::Result<RemoteServer> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+        listener.set_nonblocking(true)?; // so the thread notices when the server is stopped
+
+        let (sender, requests) = mpsc::channel();
+        let running = Arc::new(AtomicBool::new(true));
+
+        let thread_running = running.clone();
+        thread::spawn(move || {
+            while thread_running.load(Ordering::Relaxed) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        let sender = sender.clone();
+                        thread::spawn(move || handle_connection(stream, sender));
+                    },
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(ACCEPT_POLL_TIME),
+                    Err(e) => println!("Remote API could not accept a connection: {}", e),
+                }
+            }
+        });
+
+        Ok(RemoteServer { requests, running })
+    }
+
+    pub fn requests(&self) -> TryIter<'_, Request> {
+        self.requests.try_iter()
+    }
+}
+
+impl Drop for RemoteServer {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+}
+
+fn handle_connection(stream: TcpStream, requests: Sender<Request>) {
+    if stream.set_nonblocking(false).is_err() {
+        return;
+    }
+    let Ok(mut socket) = tungstenite::accept(stream) else {
+        return;
+    };
+
+    loop {
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Close(_)) | Err(_) => break,
+            Ok(_) => continue,
+        };
+
+        let response = match Command::parse(&text) {
+            Ok(command) => {
+                let (reply, response) = mpsc::channel();
+                if requests.send(Request { command, reply }).is_err() {
+                    break; // the server has been stopped
+                }
+                match response.recv() {
+                    Ok(response) => response,
+                    Err(_) => break,
+                }
+            },
+            Err(e) => json!({ "ok": false, "error": e }),
+        };
+
+        if socket.send(Message::Text(response.to_string())).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands() {
+        assert!(matches!(Command::parse(r#"{"command": "pause"}"#), Ok(Command::Pause)));
+        assert!(matches!(Command::parse(r#"{"command": "set_keys", "keys": 32}"#), Ok(Command::SetKeys { keys: 32 })));
+        assert!(matches!(Command::parse(r#"{"command": "load_rom", "rom": [0, 224]}"#),
+            Ok(Command::LoadRom { path: None, rom: Some(rom) }) if rom == [0x00, 0xE0]));
+
+        assert!(matches!(Command::parse(r#"{"command": "step"}"#), Ok(Command::Step { count: 1 })));
+        assert!(matches!(Command::parse(r#"{"command": "step", "count": 20}"#), Ok(Command::Step { count: 20 })));
+        assert!(matches!(Command::parse(r#"{"command": "step", "count": 4294967295}"#), Ok(Command::Step { count: MAX_STEPS })));
+
+        assert!(Command::parse(r#"{"command": "explode"}"#).unwrap_err().starts_with("Invalid command"));
+        assert!(Command::parse(r#"{"command": "set_keys", "keys": 65536}"#).is_err());
+        assert!(Command::parse("not json").is_err());
+    }
+}
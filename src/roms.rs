@@ -0,0 +1,11 @@
+// Timendus' test suite ROMs are built in so there's something to run straight away
+pub const BUILT_IN_ROMS: [(&str, &[u8]); 8] = [
+    ("CHIP-8 logo", include_bytes!("../roms/tests/1-chip8-logo.ch8")),
+    ("IBM logo", include_bytes!("../roms/tests/2-ibm-logo.ch8")),
+    ("Corax+ opcode test", include_bytes!("../roms/tests/3-corax+.ch8")),
+    ("Flags test", include_bytes!("../roms/tests/4-flags.ch8")),
+    ("Quirks test", include_bytes!("../roms/tests/5-quirks.ch8")),
+    ("Keypad test", include_bytes!("../roms/tests/6-keypad.ch8")),
+    ("Beep test", include_bytes!("../roms/tests/7-beep.ch8")),
+    ("Scrolling test", include_bytes!("../roms/tests/8-scrolling.ch8")),
+];
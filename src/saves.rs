@@ -0,0 +1,247 @@
+use ggegui::egui;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::archive::ARCHIVE_EXTENSION;
+use crate::cpu::CPU;
+use crate::library;
+use crate::savestate::{self, SavedState, StateHeader, SAVESTATE_EXTENSION};
+
+pub const SAVES_DIR: &str = "saves"; // in the user data directory
+pub const QUICK_SAVE: &str = "Quick save";
+
+const MAX_STATE_SIZE: u64 = 0x400000; // far more than a compressed state of all 64K of XO-CHIP memory could take up
+
+// every state saved for a ROM goes in one zip file named after its rom_id, so the folder has one file per game
+// however many slots each one has
+pub fn archive_path(data_dir: &Path, rom_id: &str) -> PathBuf {
+    data_dir.join(SAVES_DIR).join(rom_id).with_extension(ARCHIVE_EXTENSION)
+}
+
+// where the quick save went before there were archives
+fn loose_quick_save_path(data_dir: &Path, rom_id: &str) -> PathBuf {
+    data_dir.join("states").join(rom_id).with_extension(SAVESTATE_EXTENSION)
+}
+
+// moves a quick save from before there were archives into the ROM's archive, unless there's a newer one there already
+pub fn import_loose_quick_save(data_dir: &Path, rom_id: &str) -> Result<(), String> {
+    let loose = loose_quick_save_path(data_dir, rom_id);
+    let Ok(bytes) = fs::read(&loose) else {
+        return Ok(());
+    };
+
+    let path = archive_path(data_dir, rom_id);
+    let mut entries = read_entries(&path)?;
+    if !entries.iter().any(|(entry, _)| *entry == entry_name(QUICK_SAVE)) {
+        entries.push((entry_name(QUICK_SAVE), bytes));
+        write_entries(&path, &entries)?;
+    }
+    fs::remove_file(&loose).map_err(|e| format!("Could not remove {}: {}", loose.display(), e))
+}
+
+pub struct SaveSlot {
+    pub name: String,
+    pub header: Option<StateHeader>, // None for states from before savestates had a header, or ones that can't be read
+}
+
+// the slots in the order they were first saved in
+pub fn slots(path: &Path) -> Result<Vec<SaveSlot>, String> {
+    Ok(read_entries(path)?.into_iter().filter_map(|(entry, bytes)| {
+        let name = entry.strip_suffix(&format!(".{}", SAVESTATE_EXTENSION))?.to_string();
+        let header = savestate::from_bytes(&bytes).ok().and_then(|saved| saved.header);
+        Some(SaveSlot { name, header })
+    }).collect())
+}
+
+// saving over a slot that's already there replaces it
+pub fn save(path: &Path, name: &str, cpu: &CPU) -> Result<(), String> {
+    let bytes = savestate::to_bytes(cpu)?;
+    let mut entries = read_entries(path)?;
+    let entry = entry_name(name);
+    match entries.iter_mut().find(|(existing, _)| *existing == entry) {
+        Some((_, existing)) => *existing = bytes,
+        None => entries.push((entry, bytes)),
+    }
+    write_entries(path, &entries)
+}
+
+pub fn load(path: &Path, name: &str) -> Result<SavedState, String> {
+    let entry = entry_name(name);
+    let (_, bytes) = read_entries(path)?.into_iter().find(|(existing, _)| *existing == entry)
+        .ok_or_else(|| format!("There's no savestate called {}", name))?;
+    savestate::from_bytes(&bytes).map_err(|e| format!("{} isn't a savestate that can be loaded: {}", name, e))
+}
+
+pub fn has(path: &Path, name: &str) -> bool {
+    slots(path).is_ok_and(|slots| slots.iter().any(|slot| slot.name == name))
+}
+
+pub fn delete(path: &Path, name: &str) -> Result<(), String> {
+    let entry = entry_name(name);
+    let mut entries = read_entries(path)?;
+    entries.retain(|(existing, _)| *existing != entry);
+    write_entries(path, &entries)
+}
+
+// slashes would turn into folders inside the archive
+fn entry_name(name: &str) -> String {
+    format!("{}.{}", name.replace(['/', '\\'], "-"), SAVESTATE_EXTENSION)
+}
+
+// every file in the archive, nothing if it hasn't been made yet
+fn read_entries(path: &Path) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let Ok(file) = File::open(path) else {
+        return Ok(vec![]);
+    };
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("{} isn't a zip archive that can be read: {}", path.display(), e))?;
+
+    let mut entries = vec![];
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let name = file.name().to_string();
+        let too_big = || format!("{} in {} is bigger than any savestate could be", name, path.display());
+        if file.size() > MAX_STATE_SIZE {
+            return Err(too_big());
+        }
+        let mut bytes = Vec::with_capacity(file.size() as usize);
+        file.by_ref().take(MAX_STATE_SIZE + 1).read_to_end(&mut bytes).map_err(|e| format!("Could not read {} from {}: {}", name, path.display(), e))?;
+        if bytes.len() as u64 > MAX_STATE_SIZE {
+            return Err(too_big());
+        }
+        entries.push((name, bytes));
+    }
+    Ok(entries)
+}
+
+// zip files can't be changed in place so the whole archive is written again, to the side first so a failed write
+// doesn't lose the saves already in it. An archive with nothing left in it is removed
+fn write_entries(path: &Path, entries: &[(String, Vec<u8>)]) -> Result<(), String> {
+    if entries.is_empty() {
+        return match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(format!("Could not remove {}: {}", path.display(), e)),
+            _ => Ok(()),
+        };
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Could not create {}: {}", dir.display(), e))?;
+    }
+    let temp_path = path.with_extension("tmp");
+    let write = || -> Result<(), String> {
+        let file = File::create(&temp_path).map_err(|e| e.to_string())?;
+        let mut writer = ZipWriter::new(file);
+        // the states are compressed already
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, bytes) in entries {
+            writer.start_file(name.as_str(), options).map_err(|e| e.to_string())?;
+            writer.write_all(bytes).map_err(|e| e.to_string())?;
+        }
+        writer.finish().map_err(|e| e.to_string())?;
+        Ok(())
+    };
+    write().map_err(|e| format!("Could not write {}: {}", temp_path.display(), e))?;
+    fs::rename(&temp_path, path).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+pub enum SaveAction {
+    Save(String),
+    Load(String),
+    Delete(String),
+}
+
+// the save manager's window for one ROM's slots
+pub fn show(ui: &mut egui::Ui, slots: &[SaveSlot], new_name: &mut String, now: u64) -> Option<SaveAction> {
+    let mut action = None;
+
+    ui.horizontal(|ui| {
+        ui.add(egui::TextEdit::singleline(new_name).hint_text("Slot name").desired_width(160.0));
+        let name = new_name.trim();
+        if ui.add_enabled(!name.is_empty(), egui::Button::new("Save")).clicked() {
+            action = Some(SaveAction::Save(name.to_string()));
+        }
+    });
+    ui.separator();
+
+    if slots.is_empty() {
+        ui.label("States saved for this ROM will show up here.");
+        return action;
+    }
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        egui::Grid::new("Saves").striped(true).show(ui, |ui| {
+            for slot in slots {
+                ui.label(&slot.name);
+                match &slot.header {
+                    Some(header) => {
+                        ui.label(library::format_last_played(header.saved, now));
+                        ui.label(&header.platform);
+                    },
+                    None => {
+                        ui.label("");
+                        ui.label("");
+                    },
+                }
+                if ui.button("Load").clicked() {
+                    action = Some(SaveAction::Load(slot.name.clone()));
+                }
+                if ui.button("Save over").clicked() {
+                    action = Some(SaveAction::Save(slot.name.clone()));
+                }
+                if ui.button("Delete").clicked() {
+                    action = Some(SaveAction::Delete(slot.name.clone()));
+                }
+                ui.end_row();
+            }
+        });
+    });
+
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn keeps_slots_in_one_archive() {
+        let dir = env::temp_dir().join(format!("fish_n_chip8-saves-{}", std::process::id()));
+        let path = archive_path(&dir, "0123");
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x6A, 0x05]);
+
+        assert!(slots(&path).unwrap().is_empty());
+        save(&path, QUICK_SAVE, &cpu).unwrap();
+        cpu.handle_opcode();
+        save(&path, "Boss/level 3", &cpu).unwrap();
+        save(&path, QUICK_SAVE, &cpu).unwrap();
+
+        let names: Vec<String> = slots(&path).unwrap().into_iter().map(|slot| slot.name).collect();
+        assert_eq!(names, [QUICK_SAVE, "Boss-level 3"]);
+        assert_eq!(load(&path, QUICK_SAVE).unwrap().state.regs[0xA], 5);
+        assert!(has(&path, "Boss-level 3"));
+
+        delete(&path, QUICK_SAVE).unwrap();
+        assert!(load(&path, QUICK_SAVE).is_err());
+        delete(&path, "Boss-level 3").unwrap();
+        assert!(!path.exists());
+
+        // a quick save from before the archives is moved into one
+        let loose = loose_quick_save_path(&dir, "0123");
+        fs::create_dir_all(loose.parent().unwrap()).unwrap();
+        savestate::save(&loose, &cpu).unwrap();
+        import_loose_quick_save(&dir, "0123").unwrap();
+        assert!(!loose.exists());
+        assert!(has(&path, QUICK_SAVE));
+
+        // an archive claiming to hold more than a state ever could isn't read into memory
+        write_entries(&path, &[(entry_name("Huge"), vec![0; MAX_STATE_SIZE as usize + 1])]).unwrap();
+        assert!(slots(&path).is_err_and(|e| e.contains("bigger than any savestate")));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
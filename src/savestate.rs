@@ -0,0 +1,221 @@
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::analysis::CodeMap;
+use crate::cpu::{Quirks, Resolution, SaveState, CPU, PROGRAM_START};
+
+pub const SAVESTATE_EXTENSION: &str = "c8state";
+
+// a savestate file is MAGIC, the version as a little endian u16, the length of the header as a little endian u32, the
+// header as JSON and then the state as JSON compressed with zlib. Before there was a container states were just the
+// JSON, which is version 0
+const MAGIC: &[u8; 8] = b"C8STATE\0";
+pub const VERSION: u16 = 1;
+
+// each turns a state saved by the version at its index into what the next version would have saved, so an old state
+// is brought up to date one version at a time. A change to SaveState that serde's defaults can't cover gets a
+// migration here and VERSION goes up by one
+const MIGRATIONS: [fn(Value) -> Result<Value, String>; VERSION as usize] = [
+    Ok, // version 1 put the same JSON in the container
+];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StateHeader {
+    pub platform: String, // what the program in memory needs, for telling states apart without loading them
+    pub quirks: Quirks,   // what the program was running with, it might not carry on the same way with any others
+    #[serde(default)]
+    pub saved: u64, // seconds since the Unix epoch
+}
+
+pub struct SavedState {
+    pub header: Option<StateHeader>, // None for states from before the container
+    pub state: SaveState,
+}
+
+pub fn save(path: &Path, cpu: &CPU) -> Result<(), String> {
+    fs::write(path, to_bytes(cpu)?).map_err(|e| format!("Could not write {}: {}", path.display(), e))
+}
+
+pub fn load(path: &Path) -> Result<SavedState, String> {
+    let contents = fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    from_bytes(&contents).map_err(|e| format!("{} isn't a savestate that can be loaded: {}", path.display(), e))
+}
+
+pub(crate) fn to_bytes(cpu: &CPU) -> Result<Vec<u8>, String> {
+    let state = cpu.save_state();
+    let platform = CodeMap::analyse(&state.memory[PROGRAM_START..]).platform().to_string();
+    let saved = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+    let header = serde_json::to_vec(&StateHeader { platform, quirks: cpu.quirks, saved }).map_err(|e| format!("Could not save the state: {}", e))?;
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    bytes.extend((header.len() as u32).to_le_bytes());
+    bytes.extend(header);
+
+    let mut encoder = ZlibEncoder::new(bytes, Compression::default());
+    serde_json::to_writer(&mut encoder, &state).map_err(|e| format!("Could not save the state: {}", e))?;
+    encoder.finish().map_err(|e| format!("Could not compress the state: {}", e))
+}
+
+pub(crate) fn from_bytes(bytes: &[u8]) -> Result<SavedState, String> {
+    let Some(rest) = bytes.strip_prefix(MAGIC) else {
+        let state = serde_json::from_slice(bytes).map_err(|e| e.to_string())?;
+        return migrate(state, 0).map(|state| SavedState { header: None, state });
+    };
+
+    let truncated = || String::from("it's been cut short");
+    let version = u16::from_le_bytes(rest.get(..2).ok_or_else(truncated)?.try_into().expect("2 bytes"));
+    if version > VERSION {
+        return Err(format!("it was saved by a newer version of the emulator (savestate version {}, this reads up to {})", version, VERSION));
+    }
+    let header_length = u32::from_le_bytes(rest.get(2..6).ok_or_else(truncated)?.try_into().expect("4 bytes")) as usize;
+    let header = rest.get(6..6 + header_length).ok_or_else(truncated)?;
+    let header = serde_json::from_slice(header).map_err(|e| format!("its header is broken: {}", e))?;
+
+    let mut state = String::new();
+    ZlibDecoder::new(&rest[6 + header_length..]).read_to_string(&mut state).map_err(|e| format!("it couldn't be decompressed: {}", e))?;
+    let state = serde_json::from_str(&state).map_err(|e| e.to_string())?;
+    migrate(state, version).map(|state| SavedState { header: Some(header), state })
+}
+
+fn migrate(mut state: Value, version: u16) -> Result<SaveState, String> {
+    for migration in &MIGRATIONS[version as usize..] {
+        state = migration(state)?;
+    }
+    serde_json::from_value(state).map_err(|e| e.to_string())
+}
+
+// one side of a comparison, either the emulator as it is now or a state loaded from a file
+pub enum DiffSide {
+    Live,
+    Saved { name: String, state: SaveState },
+}
+
+// a run of bytes that differ between two states
+pub struct MemoryChange {
+    pub start: u16,
+    pub before: Vec<u8>,
+    pub after: Vec<u8>,
+}
+
+pub struct StateDiff {
+    pub fields: Vec<(String, String, String)>, // name and the value in each state
+    pub memory: Vec<MemoryChange>,
+}
+
+impl StateDiff {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.memory.is_empty()
+    }
+}
+
+pub fn diff(a: &SaveState, b: &SaveState) -> StateDiff {
+    let mut fields = vec![];
+    let mut check = |name: &str, a: String, b: String| {
+        if a != b {
+            fields.push((String::from(name), a, b));
+        }
+    };
+
+    check("PC", format!("{:03X}", a.pc), format!("{:03X}", b.pc));
+    check("I", format!("{:03X}", a.addr_reg), format!("{:03X}", b.addr_reg));
+    for reg in 0..16 {
+        check(&format!("V{:X}", reg), format!("{:02X}", a.regs[reg]), format!("{:02X}", b.regs[reg]));
+    }
+    check("Stack", format!("{:03X?}", a.stack), format!("{:03X?}", b.stack));
+    check("Delay timer", a.delay_timer.to_string(), b.delay_timer.to_string());
+    check("Sound timer", a.sound_timer.to_string(), b.sound_timer.to_string());
+    check("High resolution", (a.resolution == Resolution::HighRes).to_string(), (b.resolution == Resolution::HighRes).to_string());
+    check("Waiting for a key", a.waiting_for_key_press.to_string(), b.waiting_for_key_press.to_string());
+    check("Halted", a.halted.to_string(), b.halted.to_string());
+
+    let differing_pixels: u32 = a.display.iter().zip(&b.display).map(|(a, b)| (a ^ b).count_ones()).sum();
+    if differing_pixels > 0 {
+        fields.push((String::from("Screen"), format!("{} pixels differ", differing_pixels), String::new()));
+    }
+
+    StateDiff { fields, memory: memory_changes(&a.memory, &b.memory) }
+}
+
+fn memory_changes(a: &[u8], b: &[u8]) -> Vec<MemoryChange> {
+    let mut changes: Vec<MemoryChange> = vec![];
+
+    for (addr, (&before, &after)) in a.iter().zip(b).enumerate() {
+        if before == after {
+            continue;
+        }
+
+        match changes.last_mut() {
+            Some(change) if change.start as usize + change.before.len() == addr => {
+                change.before.push(before);
+                change.after.push(after);
+            }
+            _ => changes.push(MemoryChange { start: addr as u16, before: vec![before], after: vec![after] }),
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CPU;
+
+    #[test]
+    fn differences() {
+        let mut cpu = CPU::new();
+        let before = cpu.save_state();
+
+        for (addr, value) in [(0x300, 1), (0x301, 2), (0x305, 3)] {
+            cpu.write_memory(addr, value);
+        }
+        cpu.load_rom(&[0x6A, 0x05]);
+        cpu.handle_opcode();
+        let changes = diff(&before, &cpu.save_state());
+
+        let names: Vec<&str> = changes.fields.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["PC", "VA"]);
+
+        let ranges: Vec<(u16, &[u8], &[u8])> = changes.memory.iter().map(|change| (change.start, &change.before[..], &change.after[..])).collect();
+        assert_eq!(ranges, [(0x200, &[0, 0][..], &[0x6A, 0x05][..]), (0x300, &[0, 0], &[1, 2]), (0x305, &[0], &[3])]);
+
+        assert!(diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn versioned_files() {
+        let mut cpu = CPU::new();
+        cpu.load_rom(&[0x6A, 0x05, 0x00, 0xFF]); // 00FF is SUPER-CHIP's high resolution
+        cpu.handle_opcode();
+        cpu.quirks.vf_reset = true;
+
+        let bytes = to_bytes(&cpu).unwrap();
+        assert!(bytes.starts_with(b"C8STATE\0\x01\x00"));
+        let saved = from_bytes(&bytes).unwrap();
+        let header = saved.header.unwrap();
+        assert_eq!(header.platform, "SUPER-CHIP");
+        assert!(header.quirks.vf_reset);
+        assert_eq!(saved.state.regs[0xA], 5);
+
+        // states from before the container still load, just without a header
+        let plain = serde_json::to_vec(&cpu.save_state()).unwrap();
+        let saved = from_bytes(&plain).unwrap();
+        assert!(saved.header.is_none());
+        assert_eq!(saved.state.pc, 0x202);
+
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = VERSION as u8 + 1;
+        assert!(from_bytes(&newer).err().unwrap().contains("newer version"));
+        assert!(from_bytes(&bytes[..bytes.len() - 4]).is_err());
+        assert!(from_bytes(&bytes[..10]).err().unwrap().contains("cut short"));
+    }
+}
@@ -0,0 +1,171 @@
+use ggez::graphics::{Shader, ShaderBuilder};
+use ggez::Context;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::config::LedMatrixSettings;
+
+pub const SHADER_DIR: &str = "shaders"; // in the user data directory
+pub const SHADER_EXTENSION: &str = "wgsl";
+
+const RELOAD_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// what the screen is drawn through
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub enum ScreenShader {
+    LedMatrix,    // built in
+    File(String), // a file in the shaders folder
+}
+
+impl ScreenShader {
+    pub fn name(&self) -> &str {
+        match self {
+            ScreenShader::LedMatrix => "LED matrix",
+            ScreenShader::File(name) => name,
+        }
+    }
+}
+
+// each pixel as a round dot with gaps between them and a glow spilling onto its neighbours, like an LED panel or the
+// HP48's LCD. The settings are written into the source as constants so there are no uniforms to pass
+pub fn led_matrix_source(settings: LedMatrixSettings) -> String {
+    format!(
+        "const DOT_RADIUS: f32 = {:?};\nconst GLOW: f32 = {:?};\n{}",
+        settings.dot_radius.clamp(0.1, 0.7),
+        settings.glow.clamp(0.0, 1.0),
+        include_str!("../shaders/led_matrix.wgsl"),
+    )
+}
+
+// the names of the shaders in dir, for picking from
+pub fn available(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return vec![]; // nobody has made the folder yet
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == SHADER_EXTENSION))
+        .filter_map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
+
+// a compiled ScreenShader. Ones from files are loaded again whenever the file changes so they can be worked on with
+// the emulator running
+pub struct PostShader {
+    pub shader: Shader,
+    path: Option<PathBuf>, // None for the built in ones, which never change
+    modified: Option<SystemTime>,
+    last_checked: Instant,
+}
+
+impl PostShader {
+    pub fn load(ctx: &Context, dir: &Path, shader: &ScreenShader, led_matrix: LedMatrixSettings) -> Result<PostShader, String> {
+        let (path, compiled) = match shader {
+            ScreenShader::LedMatrix => (None, compile(ctx, &led_matrix_source(led_matrix))?),
+            ScreenShader::File(name) => {
+                let path = dir.join(name);
+                let compiled = compile_file(ctx, &path)?;
+                (Some(path), compiled)
+            },
+        };
+
+        let modified = path.as_deref().and_then(modified);
+        Ok(PostShader { shader: compiled, path, modified, last_checked: Instant::now() })
+    }
+
+    // a broken edit keeps the last shader that worked, and isn't reported again until the file changes once more
+    pub fn reload_if_changed(&mut self, ctx: &Context) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.last_checked.elapsed() < RELOAD_CHECK_INTERVAL {
+            return Ok(());
+        }
+        self.last_checked = Instant::now();
+
+        let modified = modified(path);
+        if modified == self.modified {
+            return Ok(());
+        }
+        self.modified = modified;
+
+        self.shader = compile_file(ctx, path)?;
+        Ok(())
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn compile_file(ctx: &Context, path: &Path) -> Result<Shader, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    compile(ctx, &source).map_err(|e| format!("{} isn't a shader that can be used: {}", path.display(), e))
+}
+
+fn compile(ctx: &Context, source: &str) -> Result<Shader, String> {
+    validate(source)?;
+    ShaderBuilder::new().fragment_code(source).build(&ctx.gfx).map_err(|e| e.to_string())
+}
+
+// wgpu panics on a shader it can't compile rather than returning an error, so naga checks it first the same way wgpu
+// would
+fn validate(source: &str) -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| e.emit_to_string(source))?;
+    Validator::new(ValidationFlags::all(), Capabilities::empty()).validate(&module).map_err(|e| e.to_string())?;
+
+    let has_fragment = module.entry_points.iter().any(|entry| entry.name == "fs_main" && entry.stage == naga::ShaderStage::Fragment);
+    if !has_fragment {
+        return Err(String::from("there's no @fragment function called fs_main"));
+    }
+
+    // it also panics drawing with a shader that wants anything ggez doesn't bind, which is only its draw uniforms and
+    // the screen's texture and sampler
+    for (_, global) in module.global_variables.iter() {
+        let Some(binding) = &global.binding else {
+            continue;
+        };
+        let bound = match (binding.group, binding.binding) {
+            (0, 0) => global.space == naga::AddressSpace::Uniform,
+            (1, 0) => matches!(module.types[global.ty].inner, naga::TypeInner::Image { .. }),
+            (1, 1) => matches!(module.types[global.ty].inner, naga::TypeInner::Sampler { .. }),
+            _ => false,
+        };
+        if !bound {
+            return Err(format!(
+                "nothing is bound to @group({}) @binding({}) for {}. There's only a uniform at @group(0) @binding(0), \
+                the screen's texture at @group(1) @binding(0) and its sampler at @group(1) @binding(1)",
+                binding.group, binding.binding, global.name.as_deref().unwrap_or("it"),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_shaders() {
+        assert_eq!(validate(include_str!("../shaders/scanlines.wgsl")), Ok(()));
+        assert_eq!(validate(&led_matrix_source(LedMatrixSettings::default())), Ok(()));
+        assert!(validate("@fragment fn main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }").is_err());
+        assert!(validate("@fragment fn fs_main() -> @location(0) vec4<f32> { return 1.0; }").is_err());
+
+        let extra_binding = "@group(1) @binding(2) var<uniform> time: f32;\n\
+            @fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(time); }";
+        assert!(validate(extra_binding).unwrap_err().contains("@group(1) @binding(2) for time"));
+        let wrong_type = "@group(1) @binding(1) var t: texture_2d<f32>;\n\
+            @fragment fn fs_main() -> @location(0) vec4<f32> { return textureLoad(t, vec2<i32>(0, 0), 0); }";
+        assert!(validate(wrong_type).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use ggegui::egui::{self, RichText};
+use serde::{Deserialize, Serialize};
+
+use crate::input::KEYPAD_GRID;
+
+// the reset and split keys are hotkeys like any other
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+#[serde(default)]
+pub struct SpeedrunSettings {
+    pub overlay: bool,
+}
+
+// times a run in emulated frames so pausing or the emulator falling behind doesn't count against it
+#[derive(Default)]
+pub struct RunTimer {
+    ticks: Option<u32>, // None until the first key is pressed
+    splits: Vec<u32>,
+    keys: u16,          // pressed on the last tick, for the input display
+}
+
+impl RunTimer {
+    pub fn tick(&mut self, keys: u16) {
+        self.keys = keys;
+
+        match &mut self.ticks {
+            Some(ticks) => *ticks += 1,
+            None if keys != 0 => self.ticks = Some(0),
+            None => {},
+        }
+    }
+
+    pub fn split(&mut self) {
+        if let Some(ticks) = self.ticks {
+            self.splits.push(ticks);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        *self = RunTimer::default();
+    }
+
+    pub fn show(&self, ui: &mut egui::Ui, ticks_per_second: f64) {
+        let time = match self.ticks {
+            Some(ticks) => format_time(ticks, ticks_per_second),
+            None => String::from("Press a key to start"),
+        };
+        ui.label(RichText::new(time).monospace().size(24.0));
+
+        let mut last = 0;
+        for (i, &split) in self.splits.iter().enumerate() {
+            ui.monospace(format!("{:>2}. {} (+{})", i + 1, format_time(split, ticks_per_second), format_time(split - last, ticks_per_second)));
+            last = split;
+        }
+
+        ui.separator();
+        egui::Grid::new("Input display").spacing([2.0, 2.0]).show(ui, |ui| {
+            for (i, key) in KEYPAD_GRID.into_iter().enumerate() {
+                let pressed = self.keys & (1 << key) != 0;
+                let text = RichText::new(format!("{:X}", key)).monospace();
+                ui.label(if pressed { text.strong().background_color(ui.visuals().selection.bg_fill) } else { text.weak() });
+
+                if i % 4 == 3 {
+                    ui.end_row();
+                }
+            }
+        });
+    }
+}
+
+// minutes, seconds and hundredths, like most speedrun timers
+pub fn format_time(ticks: u32, ticks_per_second: f64) -> String {
+    let hundredths = (ticks as f64 / ticks_per_second * 100.0).round() as u64;
+    format!("{}:{:02}.{:02}", hundredths / 6000, hundredths / 100 % 60, hundredths % 100)
+}
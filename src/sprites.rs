@@ -0,0 +1,157 @@
+use ggegui::egui::{self, Color32, Rect, Sense, Stroke};
+
+use crate::cpu::CPU;
+
+const CELL_SIZE: f32 = 16.0;
+const MAX_SMALL_HEIGHT: usize = 15;
+
+// what the sprite editor wants done that it can't do itself
+pub enum SpriteAction {
+    PatchRom { address: u16, bytes: Vec<u8> },
+}
+
+// edits sprites as a grid of pixels, either 8 wide and up to 15 tall or SUPER-CHIP's 16x16
+pub struct SpriteEditor {
+    address: u16,
+    large: bool,
+    height: usize,
+    rows: Vec<u16>, // a bit per pixel with the leftmost in the top bit of the row's width
+    painting: Option<bool>, // what dragging over the grid sets pixels to
+}
+
+impl Default for SpriteEditor {
+    fn default() -> Self {
+        Self {
+            address: 0x200,
+            large: false,
+            height: 8,
+            rows: vec![0; MAX_SMALL_HEIGHT],
+            painting: None,
+        }
+    }
+}
+
+impl SpriteEditor {
+    fn width(&self) -> usize {
+        if self.large { 16 } else { 8 }
+    }
+
+    fn visible_height(&self) -> usize {
+        if self.large { 16 } else { self.height }
+    }
+
+    // the sprite as DXYN reads it from memory
+    pub fn bytes(&self) -> Vec<u8> {
+        self.rows[..self.visible_height()]
+            .iter()
+            .flat_map(|&row| if self.large { row.to_be_bytes().to_vec() } else { vec![row as u8] })
+            .collect()
+    }
+
+    pub fn read(&mut self, cpu: &CPU) {
+        let bytes_per_row = if self.large { 2 } else { 1 };
+
+        self.rows = (0..self.visible_height().max(MAX_SMALL_HEIGHT))
+            .map(|y| {
+                let addr = self.address.wrapping_add((y * bytes_per_row) as u16);
+                if self.large {
+                    (cpu.read_memory(addr) as u16) << 8 | cpu.read_memory(addr.wrapping_add(1)) as u16
+                }
+                else {
+                    cpu.read_memory(addr) as u16
+                }
+            })
+            .collect();
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, cpu: &mut CPU, can_patch_rom: bool) -> Option<SpriteAction> {
+        let mut action = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Address: ");
+            ui.add(egui::DragValue::new(&mut self.address).hexadecimal(3, false, true).clamp_range(0..=0xFFF));
+            if ui.selectable_label(!self.large, "8xN").clicked() {
+                self.large = false;
+            }
+            if ui.selectable_label(self.large, "16x16").clicked() {
+                self.large = true;
+            }
+            if !self.large {
+                ui.label("Height: ");
+                ui.add(egui::DragValue::new(&mut self.height).clamp_range(1..=MAX_SMALL_HEIGHT));
+            }
+        });
+        if self.rows.len() < self.visible_height() {
+            self.rows.resize(self.visible_height(), 0);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.button("Read from memory").clicked() {
+                self.read(cpu);
+            }
+            if ui.button("Write to memory").clicked() {
+                for (i, byte) in self.bytes().into_iter().enumerate() {
+                    cpu.write_memory(self.address.wrapping_add(i as u16), byte);
+                }
+            }
+            let patch_button = ui.add_enabled(can_patch_rom, egui::Button::new("Patch ROM file"))
+                .on_disabled_hover_text("Only ROMs loaded from a file can be patched");
+            if patch_button.clicked() {
+                action = Some(SpriteAction::PatchRom { address: self.address, bytes: self.bytes() });
+            }
+            if ui.button("Clear").clicked() {
+                self.rows.fill(0);
+            }
+        });
+
+        self.show_grid(ui);
+
+        let hex: Vec<String> = self.bytes().iter().map(|byte| format!("0x{:02X}", byte)).collect();
+        let mut hex = hex.join(" ");
+        ui.add(egui::TextEdit::singleline(&mut hex).code_editor().interactive(false).desired_width(f32::INFINITY));
+        if ui.button("Copy bytes").clicked() {
+            ui.output_mut(|output| output.copied_text = hex);
+        }
+
+        action
+    }
+
+    fn show_grid(&mut self, ui: &mut egui::Ui) {
+        let (width, height) = (self.width(), self.visible_height());
+        let size = egui::vec2(width as f32 * CELL_SIZE, height as f32 * CELL_SIZE);
+        let (response, painter) = ui.allocate_painter(size, Sense::click_and_drag());
+        let origin = response.rect.min;
+
+        let cell_at = |pos: egui::Pos2| {
+            let x = ((pos.x - origin.x) / CELL_SIZE).floor();
+            let y = ((pos.y - origin.y) / CELL_SIZE).floor();
+            (x >= 0.0 && y >= 0.0 && (x as usize) < width && (y as usize) < height).then_some((x as usize, y as usize))
+        };
+        let bit = |x: usize| 1 << (width - 1 - x);
+
+        // a drag sets every pixel it passes over to the opposite of the one it started on
+        if let Some((x, y)) = response.interact_pointer_pos().and_then(cell_at) {
+            let value = *self.painting.get_or_insert(self.rows[y] & bit(x) == 0);
+            if value {
+                self.rows[y] |= bit(x);
+            }
+            else {
+                self.rows[y] &= !bit(x);
+            }
+        }
+        if !response.is_pointer_button_down_on() {
+            self.painting = None;
+        }
+
+        let visuals = ui.visuals();
+        for y in 0..height {
+            for x in 0..width {
+                let min = origin + egui::vec2(x as f32, y as f32) * CELL_SIZE;
+                let cell = Rect::from_min_size(min, egui::vec2(CELL_SIZE, CELL_SIZE));
+                let fill = if self.rows[y] & bit(x) != 0 { visuals.strong_text_color() } else { visuals.extreme_bg_color };
+
+                painter.rect(cell, 0.0, fill, Stroke::new(1.0, Color32::from_gray(64)));
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use std::path::PathBuf;
+
+use crate::analysis::Coverage;
+use crate::cheats::MemorySearch;
+use crate::config::{Config, SharedSettings};
+use crate::cpu::CPU;
+use crate::speedrun::RunTimer;
+use crate::undo::UndoStack;
+
+pub const NEW_TAB_NAME: &str = "Empty";
+
+// what an action replaced, so undoing it doesn't throw away anything changed since
+pub enum UndoSnapshot {
+    Rom(Box<RomSnapshot>), // before loading, restarting or ejecting a ROM
+    Settings(Box<Config>), // before resetting the settings, with the quirks the CPU was running with
+}
+
+pub struct RomSnapshot {
+    pub cpu: CPU,
+    pub settings: Option<SharedSettings>, // for loads, which may take the speed, colours and controls from the ROM
+    pub last_loaded_rom: Option<Vec<u8>>,
+    pub rom_path: Option<PathBuf>,
+    pub rom_id: Option<String>,
+    pub tab_name: String,
+}
+
+// everything that belongs to one open ROM. The focused tab's copy lives in the emulator itself and is swapped
+// in and out of here when switching tabs, so the rest of the emulator doesn't need to know about tabs
+pub struct Tab {
+    pub name: String, // kept up to date for the focused tab too
+    pub cpu: CPU,
+    pub paused: bool,
+    pub cycle_budget: f64,
+    pub error_reported: bool,
+    pub last_loaded_rom: Option<Vec<u8>>,
+    pub rom_path: Option<PathBuf>,
+    pub rom_id: Option<String>,
+    pub run_timer: RunTimer,
+    pub memory_search: Option<MemorySearch>,
+    pub watches: Vec<u16>,
+    pub coverage: Coverage,
+    pub undo: UndoStack<UndoSnapshot>, // so Undo only ever brings back what was in this tab
+}
+
+impl Tab {
+    pub fn new(cpu: CPU) -> Tab {
+        Tab {
+            name: String::from(NEW_TAB_NAME),
+            cpu,
+            paused: false,
+            cycle_budget: 0.0,
+            error_reported: false,
+            last_loaded_rom: None,
+            rom_path: None,
+            rom_id: None,
+            run_timer: RunTimer::default(),
+            memory_search: None,
+            watches: vec![],
+            coverage: Coverage::default(),
+            undo: UndoStack::default(),
+        }
+    }
+
+    // runs an unfocused tab for one 60Hz tick with nothing pressed. It has no sound and stops quietly on an error
+    pub fn tick(&mut self, cycles: u32) {
+        if self.paused || self.cpu.halted() {
+            return;
+        }
+
+        self.cpu.set_keys(0);
+        self.cpu.set_second_keypad(0);
+        self.cpu.timer_tick();
+        self.run_timer.tick(0);
+        for _ in 0..cycles {
+            self.coverage.record(self.cpu.pc());
+            self.cpu.handle_opcode();
+        }
+    }
+}
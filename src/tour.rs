@@ -0,0 +1,122 @@
+use ggegui::egui::{self, Align2, Rect, Window};
+
+use crate::input::{KeyboardLayout, KEYPAD_GRID};
+
+const POPOVER_WIDTH: f32 = 320.0;
+
+// the ROM loaded at the start so there's something responding to the keys being pointed out
+pub const TOUR_ROM: &str = "Keypad test";
+
+#[derive(PartialEq, Clone, Copy)]
+enum TourStep {
+    Welcome,
+    Keypad,
+    Configuration,
+    Quirks,
+    Debugger,
+}
+
+// what the emulator has to do for the tour as it moves on
+pub enum TourEvent {
+    LoadRom,
+    OpenConfiguration,
+    OpenDebugger,
+    Finished, // finished or skipped, either way it shouldn't start again by itself
+}
+
+// where the menu buttons the tour points at are this frame
+pub struct TourAnchors {
+    pub configuration: Rect,
+    pub debugger: Rect,
+}
+
+// walks newcomers through the emulator one popover at a time
+pub struct Tour {
+    step: TourStep,
+}
+
+impl Default for Tour {
+    fn default() -> Self {
+        Self { step: TourStep::Welcome }
+    }
+}
+
+impl Tour {
+    pub fn show(&mut self, ctx: &egui::Context, anchors: &TourAnchors, layout: KeyboardLayout) -> Option<TourEvent> {
+        let (title, pivot, position) = match self.step {
+            TourStep::Welcome => ("Welcome to Fish n CHIP-8", Align2::CENTER_CENTER, ctx.screen_rect().center()),
+            TourStep::Keypad => ("The keypad", Align2::CENTER_CENTER, ctx.screen_rect().center()),
+            TourStep::Configuration | TourStep::Quirks => ("Configuration", Align2::LEFT_TOP, anchors.configuration.left_bottom()),
+            TourStep::Debugger => ("Stepping through", Align2::LEFT_TOP, anchors.debugger.left_bottom()),
+        };
+
+        let mut event = None;
+        Window::new(title)
+            .id(egui::Id::new("Tour"))
+            .pivot(pivot)
+            .fixed_pos(position + egui::vec2(0.0, 4.0))
+            .fixed_size([POPOVER_WIDTH, 0.0])
+            .collapsible(false)
+            .show(ctx, |ui| {
+                match self.step {
+                    TourStep::Welcome => {
+                        ui.label("CHIP-8 is a tiny virtual computer from the 1970s, and this runs programs written for it.");
+                        ui.label("This tour shows you around. It loads a test ROM to try things on, any you were playing will be closed.");
+                    },
+                    TourStep::Keypad => {
+                        ui.label("CHIP-8 has a 16 key hex keypad. It's on the left of your keyboard, laid out like this:");
+                        egui::Grid::new("Tour keypad").striped(true).show(ui, |ui| {
+                            for (i, (key, keycode)) in KEYPAD_GRID.into_iter().zip(layout.grid()).enumerate() {
+                                let name = format!("{:?}", keycode);
+                                ui.monospace(format!("{} → {:X}", name.trim_start_matches("Key"), key));
+                                if i % 4 == 3 {
+                                    ui.end_row();
+                                }
+                            }
+                        });
+                        ui.label("The keypad test ROM that's running now lights up each key as you press it, try a few.");
+                    },
+                    TourStep::Configuration => {
+                        ui.label("The configuration window sets the speed, colours, sound and controls.");
+                        ui.label("If your keyboard isn't QWERTY, pick its layout under Controls so the keypad stays in the same place.");
+                    },
+                    TourStep::Quirks => {
+                        ui.label("CHIP-8 interpreters disagree about what a few instructions do. These differences are called quirks.");
+                        ui.label("If a game misbehaves, try changing the quirks at the bottom of the configuration window. The Quirks test ROM shows which ones are on.");
+                    },
+                    TourStep::Debugger => {
+                        ui.label("Step through runs a program one instruction at a time and explains what each instruction does.");
+                        ui.label("For digging deeper there's also a memory search, a sprite editor and an Octo editor for writing your own programs.");
+                    },
+                }
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.button("Skip tour").clicked() {
+                        event = Some(TourEvent::Finished);
+                    }
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        let last = self.step == TourStep::Debugger;
+                        if ui.button(if last { "Finish" } else { "Next" }).clicked() {
+                            event = Some(self.advance());
+                        }
+                    });
+                });
+            });
+
+        event
+    }
+
+    fn advance(&mut self) -> TourEvent {
+        let (next, event) = match self.step {
+            TourStep::Welcome => (TourStep::Keypad, TourEvent::LoadRom),
+            TourStep::Keypad => (TourStep::Configuration, TourEvent::OpenConfiguration),
+            TourStep::Configuration => (TourStep::Quirks, TourEvent::OpenConfiguration),
+            TourStep::Quirks => (TourStep::Debugger, TourEvent::OpenDebugger),
+            TourStep::Debugger => (TourStep::Debugger, TourEvent::Finished),
+        };
+        self.step = next;
+
+        event
+    }
+}
@@ -0,0 +1,176 @@
+use serde_json::{json, Map, Value};
+
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+use crate::breakpoints;
+use crate::compare::{exit_with, load_config, read_rom, Run};
+use crate::config;
+use crate::cpu::CPU;
+use crate::movie::InputMovie;
+
+const DEFAULT_FRAMES: u32 = 60;
+
+// the state an instruction is traced against, taken before it runs
+struct Snapshot {
+    pc: u16,
+    opcode: u16,
+    regs: [u8; 16],
+    addr_reg: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+}
+
+impl Snapshot {
+    fn take(cpu: &CPU) -> Snapshot {
+        let pc = cpu.pc();
+
+        Snapshot {
+            pc,
+            opcode: cpu.read_opcode(pc),
+            regs: *cpu.registers(),
+            addr_reg: cpu.addr_reg(),
+            delay_timer: cpu.delay_timer(),
+            sound_timer: cpu.sound_timer(),
+        }
+    }
+
+    // the registers the instruction changed and their new values
+    fn changed_regs<'a>(&'a self, cpu: &'a CPU) -> impl Iterator<Item = (usize, u8)> + 'a {
+        (0..16)
+            .filter(move |&reg| cpu.registers()[reg] != self.regs[reg])
+            .map(move |reg| (reg, cpu.registers()[reg]))
+    }
+}
+
+// runs a ROM headless and prints every instruction it executes, either as text to read or as one JSON object
+// per line for diffing against another emulator's trace
+pub fn trace_main(args: &[String]) {
+    let json = args.iter().any(|arg| arg == "--json");
+    let dump_ascii = args.iter().any(|arg| arg == "--dump-ascii");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--json" && *arg != "--dump-ascii").collect();
+
+    let Some(rom_path) = args.first() else {
+        eprintln!("Usage: fish_n_chip8 --trace <rom> [config] [input movie] [frames] [--json] [--dump-ascii]");
+        eprintln!("Use - for the config to run with the defaults or for the movie to run without input");
+        eprintln!("--dump-ascii draws the screen whenever one of the ROM's breakpoints in the config is hit and at the end");
+        process::exit(2);
+    };
+
+    let rom = read_rom(rom_path);
+    let config = load_config(args.get(1).map_or("-", |path| path.as_str()));
+    let movie = match args.get(2).map(|path| path.as_str()) {
+        Some("-") | None => InputMovie::default(),
+        Some(path) => InputMovie::load(Path::new(path)).unwrap_or_else(|e| exit_with(e)),
+    };
+    let frames = match args.get(3).map(|frames| frames.parse::<u32>()) {
+        Some(Ok(frames)) => frames,
+        Some(Err(e)) => exit_with(format!("Invalid frame count: {}", e)),
+        None => DEFAULT_FRAMES.max(movie.length()),
+    };
+
+    // the same breakpoints as the emulator would stop at, only used to know when to draw the screen
+    let mut breakpoints = config.breakpoints.get(&config::rom_id(&rom)).cloned().unwrap_or_default();
+    let mut run = Run::new(&rom, config);
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut step = 0u64;
+
+    for frame in 0..frames {
+        run.cpu.set_keys(movie.keys_at(frame));
+        run.cpu.timer_tick();
+
+        for _ in 0..run.cycles_this_frame() {
+            if run.cpu.halted() {
+                break;
+            }
+
+            let before = Snapshot::take(&run.cpu);
+            let watched = breakpoints::watched_values(&breakpoints, &run.cpu);
+            run.cpu.handle_opcode();
+
+            let written = if json {
+                write_json(&mut out, step, frame, &before, &run.cpu)
+            }
+            else {
+                write_text(&mut out, step, frame, &before, &run.cpu)
+            };
+            let hit = breakpoints::check(&mut breakpoints, &run.cpu, &watched).filter(|_| dump_ascii);
+            let written = written.and_then(|_| match hit {
+                Some(hit) => write_screen(&mut out, json, &format!("{} hit on frame {}", breakpoints[hit], frame), &run.cpu),
+                None => Ok(()),
+            });
+            // stop quietly if whatever is reading the trace has had enough, such as head
+            if written.is_err() {
+                return;
+            }
+            step += 1;
+        }
+
+        if let Some(error) = run.cpu.error() {
+            if dump_ascii {
+                let _ = write_screen(&mut out, json, &format!("Stopped on frame {}", frame), &run.cpu);
+            }
+            let _ = out.flush();
+            eprintln!("Stopped on frame {}: {}", frame, error);
+            process::exit(1);
+        }
+        if run.cpu.halted() {
+            break;
+        }
+    }
+
+    if dump_ascii {
+        let _ = write_screen(&mut out, json, "At the end", &run.cpu);
+    }
+    let _ = out.flush();
+}
+
+// with --json the screen goes to stderr instead so the trace stays one JSON object per line
+fn write_screen(out: &mut impl Write, json: bool, heading: &str, cpu: &CPU) -> io::Result<()> {
+    if json {
+        eprint!("{}\n{}", heading, cpu.ascii_screen());
+        Ok(())
+    }
+    else {
+        write!(out, "{}\n{}", heading, cpu.ascii_screen())
+    }
+}
+
+fn write_text(out: &mut impl Write, step: u64, frame: u32, before: &Snapshot, cpu: &CPU) -> io::Result<()> {
+    write!(out, "{:>8} {:>6} {:03X}: {:04X}  I={:03X} DT={:02X} ST={:02X}",
+        step, frame, before.pc, before.opcode, before.addr_reg, before.delay_timer, before.sound_timer)?;
+
+    for (reg, value) in before.changed_regs(cpu) {
+        write!(out, " V{:X}={:02X}->{:02X}", reg, before.regs[reg], value)?;
+    }
+    if cpu.addr_reg() != before.addr_reg {
+        write!(out, " I->{:03X}", cpu.addr_reg())?;
+    }
+
+    writeln!(out)
+}
+
+// each line describes one instruction: where it was and the state it ran against, then what it changed.
+// every number is plain decimal so scripts don't have to parse hex
+fn write_json(out: &mut impl Write, step: u64, frame: u32, before: &Snapshot, cpu: &CPU) -> io::Result<()> {
+    let regs: Map<String, Value> = before.changed_regs(cpu)
+        .map(|(reg, value)| (format!("v{:x}", reg), json!(value)))
+        .collect();
+
+    let mut line = json!({
+        "step": step,
+        "frame": frame,
+        "pc": before.pc,
+        "opcode": before.opcode,
+        "i": before.addr_reg,
+        "dt": before.delay_timer,
+        "st": before.sound_timer,
+        "regs": regs,
+    });
+    if cpu.addr_reg() != before.addr_reg {
+        line["new_i"] = json!(cpu.addr_reg());
+    }
+
+    writeln!(out, "{}", line)
+}
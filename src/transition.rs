@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy)]
+pub enum TransitionKind {
+    Fade,     // the old screen fades out evenly
+    Dissolve, // the old screen's pixels disappear one by one in a scattered order
+}
+
+// the screen as it was before a ROM was loaded, reset or cleared it, drawn over the new one while it disappears.
+// It's only ever drawn, the emulator carries on underneath as if it wasn't there
+pub struct Transition {
+    kind: TransitionKind,
+    pixels: Vec<u8>, // RGBA, one per CHIP-8 pixel
+    width: usize,
+    height: usize,
+    elapsed: Duration,
+    length: Duration,
+}
+
+impl Transition {
+    pub fn new(kind: TransitionKind, pixels: &[u8], width: usize, height: usize, length: Duration) -> Transition {
+        Transition { kind, pixels: pixels.to_vec(), width, height, elapsed: Duration::ZERO, length }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    // false once it's finished
+    pub fn advance(&mut self, delta: Duration) -> bool {
+        self.elapsed += delta;
+        self.elapsed < self.length
+    }
+
+    // the old screen with how much of it is left in the alpha
+    pub fn overlay(&self) -> Vec<u8> {
+        let progress = (self.elapsed.as_secs_f32() / self.length.as_secs_f32()).min(1.0);
+        let mut overlay = self.pixels.clone();
+
+        for (i, pixel) in overlay.chunks_exact_mut(4).enumerate() {
+            let left = match self.kind {
+                TransitionKind::Fade => 1.0 - progress,
+                TransitionKind::Dissolve => if dissolve_order(i % self.width, i / self.width) < progress { 0.0 } else { 1.0 },
+            };
+            pixel[3] = (pixel[3] as f32 * left) as u8;
+        }
+
+        overlay
+    }
+}
+
+// from 0 to 1, how far through the dissolve the pixel goes. A hash rather than random so it's the same every frame
+fn dissolve_order(x: usize, y: usize) -> f32 {
+    let mut hash = (x as u32).wrapping_mul(0x9E3779B1) ^ (y as u32).wrapping_mul(0x85EBCA77);
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(0x2C1B3C6D);
+    hash ^= hash >> 12;
+    (hash & 0xFFFF) as f32 / 0x10000 as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn overlays_disappear() {
+        let pixels = [255; 8 * 4 * 4];
+        let alphas = |transition: &Transition| transition.overlay().chunks(4).map(|pixel| pixel[3]).collect::<Vec<u8>>();
+
+        let mut fade = Transition::new(TransitionKind::Fade, &pixels, 8, 4, Duration::from_millis(100));
+        assert!(alphas(&fade).iter().all(|&alpha| alpha == 255));
+        assert!(fade.advance(Duration::from_millis(50)));
+        assert!(alphas(&fade).iter().all(|&alpha| alpha == 127));
+
+        let mut dissolve = Transition::new(TransitionKind::Dissolve, &pixels, 8, 4, Duration::from_millis(100));
+        dissolve.advance(Duration::from_millis(50));
+        let halfway = alphas(&dissolve);
+        assert!(halfway.contains(&0) && halfway.contains(&255));
+        assert!(!dissolve.advance(Duration::from_millis(50)));
+        assert!(alphas(&dissolve).iter().all(|&alpha| alpha == 0));
+    }
+}
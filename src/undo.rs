@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+
+pub const UNDO_LIMIT: usize = 10; // the oldest snapshot is forgotten once there are more than this
+
+// what things were like before each of the last few actions that threw something away, newest last, each named after
+// the action it would undo
+pub struct UndoStack<T> {
+    snapshots: VecDeque<(&'static str, T)>,
+}
+
+impl<T> Default for UndoStack<T> {
+    fn default() -> Self {
+        Self { snapshots: VecDeque::new() }
+    }
+}
+
+impl<T> UndoStack<T> {
+    pub fn push(&mut self, action: &'static str, snapshot: T) {
+        if self.snapshots.len() == UNDO_LIMIT {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((action, snapshot));
+    }
+
+    pub fn pop(&mut self) -> Option<(&'static str, T)> {
+        self.snapshots.pop_back()
+    }
+
+    // what undoing would undo
+    pub fn next_action(&self) -> Option<&'static str> {
+        self.snapshots.back().map(|&(action, _)| action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgets_the_oldest() {
+        let mut stack = UndoStack::default();
+        assert_eq!(stack.next_action(), None);
+
+        for i in 0..UNDO_LIMIT + 2 {
+            stack.push(if i % 2 == 0 { "Load ROM" } else { "Restart" }, i);
+        }
+        assert_eq!(stack.next_action(), Some("Restart"));
+        assert_eq!(stack.pop(), Some(("Restart", UNDO_LIMIT + 1)));
+
+        let remaining: Vec<usize> = std::iter::from_fn(|| stack.pop().map(|(_, i)| i)).collect();
+        assert_eq!(remaining.len(), UNDO_LIMIT - 1);
+        assert_eq!(remaining.last(), Some(&2));
+    }
+}
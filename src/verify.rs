@@ -0,0 +1,200 @@
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use crate::compare::{differences, exit_with, load_config, read_rom, Run};
+use crate::config::Config;
+use crate::movie::InputMovie;
+
+const DEFAULT_FRAMES: u32 = 60 * 60;
+const CHECKPOINT_INTERVAL: u32 = 60;
+
+// the state hash after every CHECKPOINT_INTERVAL frames and after the last one, as (frames run, hash)
+//
+// stored as text with one "<frame> <hash>" line each, the hash in hex
+#[derive(PartialEq, Debug)]
+pub(crate) struct Checkpoints(Vec<(u32, u64)>);
+
+impl Checkpoints {
+    pub fn parse(text: &str) -> Result<Checkpoints, String> {
+        let mut checkpoints = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let invalid = || format!("line {} should be a frame number then a hash in hex", number + 1);
+            let (frame, hash) = line.split_once(char::is_whitespace).ok_or_else(invalid)?;
+            let frame = frame.parse().map_err(|_| invalid())?;
+            let hash = u64::from_str_radix(hash.trim(), 16).map_err(|_| invalid())?;
+            checkpoints.push((frame, hash));
+        }
+
+        Ok(Checkpoints(checkpoints))
+    }
+
+    pub fn to_text(&self) -> String {
+        self.0.iter().map(|(frame, hash)| format!("{} {:016x}\n", frame, hash)).collect()
+    }
+
+    // how many frames the run they were taken from was
+    pub fn frames(&self) -> u32 {
+        self.0.last().map_or(0, |&(frame, _)| frame)
+    }
+
+    // the first checkpoint where these and another run's disagree, as (frame, this hash, the other hash)
+    pub fn first_mismatch(&self, other: &Checkpoints) -> Option<(u32, Option<u64>, Option<u64>)> {
+        let frames = self.0.iter().map(|&(frame, _)| frame).chain(other.0.iter().map(|&(frame, _)| frame));
+        let hash_at = |checkpoints: &Checkpoints, frame| checkpoints.0.iter().find(|&&(at, _)| at == frame).map(|&(_, hash)| hash);
+
+        frames.filter_map(|frame| {
+            let (ours, theirs) = (hash_at(self, frame), hash_at(other, frame));
+            (ours != theirs).then_some((frame, ours, theirs))
+        })
+        .min_by_key(|&(frame, _, _)| frame)
+    }
+}
+
+// runs one frame of a movie, letting go of the keys that were held the frame before and aren't now
+fn run_frame(run: &mut Run, movie: &InputMovie, frame: u32) {
+    run.cpu.set_keys(movie.keys_at(frame));
+    run.cpu.timer_tick();
+
+    for _ in 0..run.cycles_this_frame() {
+        run.cpu.handle_opcode();
+    }
+}
+
+fn is_checkpoint(frames_run: u32, frames: u32) -> bool {
+    frames_run.is_multiple_of(CHECKPOINT_INTERVAL) || frames_run == frames
+}
+
+pub(crate) fn checkpoints(run: &mut Run, movie: &InputMovie, frames: u32) -> Checkpoints {
+    let mut checkpoints = vec![(0, run.cpu.state_hash())];
+    for frame in 0..frames {
+        run_frame(run, movie, frame);
+        if is_checkpoint(frame + 1, frames) {
+            checkpoints.push((frame + 1, run.cpu.state_hash()));
+        }
+    }
+
+    Checkpoints(checkpoints)
+}
+
+// plays an input movie through a ROM twice side by side, or against the checkpoints written by an earlier run,
+// and checks the whole machine hashes the same at every checkpoint. Recording, rewinding and netplay all rely on
+// the same input always giving the same run, so this catches anything that breaks that
+pub fn verify_main(args: &[String]) {
+    let write = args.iter().any(|arg| arg == "--write");
+    let dump_ascii = args.iter().any(|arg| arg == "--dump-ascii");
+    let args: Vec<&String> = args.iter().filter(|arg| *arg != "--write" && *arg != "--dump-ascii").collect();
+
+    let [rom_path, movie_path, rest @ ..] = args.as_slice() else {
+        eprintln!("Usage: fish_n_chip8 --verify <rom> <input movie> [config] [checkpoints] [--write] [--dump-ascii]");
+        eprintln!("Use - for the movie to run without input or for the config to run with the defaults");
+        eprintln!("With a checkpoints file the run is checked against it, or --write saves this run's checkpoints there");
+        eprintln!("--dump-ascii draws the screen at the end of the run");
+        process::exit(2);
+    };
+
+    let rom = read_rom(rom_path);
+    let movie = match movie_path.as_str() {
+        "-" => InputMovie::default(),
+        path => InputMovie::load(Path::new(path)).unwrap_or_else(|e| exit_with(e)),
+    };
+    let config = load_config(rest.first().map_or("-", |path| path.as_str()));
+
+    let Some(checkpoints_path) = rest.get(1) else {
+        verify_twice(&rom, &movie, config, dump_ascii);
+        return;
+    };
+
+    let mut run = Run::new(&rom, config);
+    if write {
+        let frames = DEFAULT_FRAMES.max(movie.length());
+        let checkpoints = checkpoints(&mut run, &movie, frames);
+        fs::write(checkpoints_path, checkpoints.to_text())
+            .unwrap_or_else(|e| exit_with(format!("Could not write {}: {}", checkpoints_path, e)));
+        println!("Wrote {} checkpoints over {} frames", checkpoints.0.len(), frames);
+        if dump_ascii {
+            print!("{}", run.cpu.ascii_screen());
+        }
+        return;
+    }
+
+    let expected = fs::read_to_string(checkpoints_path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| Checkpoints::parse(&text))
+        .unwrap_or_else(|e| exit_with(format!("Could not read {}: {}", checkpoints_path, e)));
+    let actual = checkpoints(&mut run, &movie, expected.frames());
+    if dump_ascii {
+        print!("{}", run.cpu.ascii_screen());
+    }
+
+    match actual.first_mismatch(&expected) {
+        Some((frame, actual, expected)) => {
+            let hash = |hash: Option<u64>| hash.map_or(String::from("nothing"), |hash| format!("{:016x}", hash));
+            println!("Diverged by frame {}: hashed {} but the checkpoint has {}", frame, hash(actual), hash(expected));
+            process::exit(1);
+        },
+        None => println!("All {} checkpoints matched over {} frames", expected.0.len(), expected.frames()),
+    }
+}
+
+// both runs go frame by frame together so if they split up the differences can be shown
+fn verify_twice(rom: &[u8], movie: &InputMovie, config: Config, dump_ascii: bool) {
+    let frames = DEFAULT_FRAMES.max(movie.length());
+    let mut a = Run::new(rom, config.clone());
+    let mut b = Run::new(rom, config);
+
+    let mut checked = 0;
+    for frame in 0..frames {
+        run_frame(&mut a, movie, frame);
+        run_frame(&mut b, movie, frame);
+        if !is_checkpoint(frame + 1, frames) {
+            continue;
+        }
+
+        checked += 1;
+        if a.cpu.state_hash() != b.cpu.state_hash() {
+            println!("The two runs diverged by frame {}", frame + 1);
+            for difference in differences(&a.cpu, &b.cpu, true).unwrap_or_default() {
+                println!("  {}", difference);
+            }
+            if dump_ascii {
+                print!("First run:\n{}Second run:\n{}", a.cpu.ascii_screen(), b.cpu.ascii_screen());
+            }
+            process::exit(1);
+        }
+    }
+
+    println!("Both runs matched at all {} checkpoints over {} frames", checked, frames);
+    if dump_ascii {
+        print!("{}", a.cpu.ascii_screen());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_input_same_checkpoints() {
+        // 200: V0 = random, 202: V1 = key pressed and let go, 204: draw V0 at V0, V1, 206: back to 200
+        let rom = [0xC0, 0xFF, 0xF1, 0x0A, 0xD0, 0x15, 0x12, 0x00];
+        let movie = InputMovie::parse("10 0001\n20 0000\n70 0100\n75 0000").unwrap();
+
+        let first = checkpoints(&mut Run::new(&rom, Config::default()), &movie, 150);
+        let second = checkpoints(&mut Run::new(&rom, Config::default()), &movie, 150);
+        assert_eq!(first.0.iter().map(|&(frame, _)| frame).collect::<Vec<_>>(), [0, 60, 120, 150]);
+        assert_eq!(first.first_mismatch(&second), None);
+        assert_eq!(Checkpoints::parse(&first.to_text()), Ok(first));
+
+        // different input has to show up at the first checkpoint after it
+        let other = InputMovie::parse("10 0001\n20 0000\n70 0200\n75 0000").unwrap();
+        let diverged = checkpoints(&mut Run::new(&rom, Config::default()), &other, 150);
+        assert_eq!(second.first_mismatch(&diverged).map(|(frame, _, _)| frame), Some(120));
+    }
+}
@@ -0,0 +1,135 @@
+use wasm_bindgen::prelude::*;
+
+use crate::cpu::{CPU, DEFAULT_CYCLES_PER_FRAME, MAX_ROM_SIZE, JumpBehviour, RegSaveLoadQuirk, ScrollingBehviour, ShiftingReg};
+use crate::random::RngAlgorithm;
+
+const ON_COLOUR: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const OFF_COLOUR: [u8; 4] = [0x0F, 0x0F, 0x0F, 0xFF]; // the same as the desktop default
+
+// everything the browser frontend needs, the page calls run_frame 60 times a second and draws framebuffer
+#[wasm_bindgen]
+pub struct WebEmulator {
+    cpu: CPU,
+    cycles_per_frame: u16,
+    display_changed: bool,
+    on_colour: [u8; 4],
+    off_colour: [u8; 4],
+}
+
+#[wasm_bindgen]
+impl WebEmulator {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> WebEmulator {
+        WebEmulator {
+            cpu: CPU::new(),
+            cycles_per_frame: DEFAULT_CYCLES_PER_FRAME,
+            display_changed: true,
+            on_colour: ON_COLOUR,
+            off_colour: OFF_COLOUR,
+        }
+    }
+
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(JsError::new(&format!("ROMs can be at most {} bytes but this one is {} bytes", MAX_ROM_SIZE, rom.len())));
+        }
+
+        let quirks = self.cpu.quirks;
+        self.cpu = CPU::new();
+        self.cpu.quirks = quirks;
+        self.cpu.load_rom(rom);
+        self.display_changed = true;
+
+        Ok(())
+    }
+
+    pub fn set_cycles_per_frame(&mut self, cycles: u16) {
+        self.cycles_per_frame = cycles;
+    }
+
+    // 0xRRGGBB, as exported web builds give them
+    pub fn set_colours(&mut self, on: u32, off: u32) {
+        let rgba = |colour: u32| [(colour >> 16) as u8, (colour >> 8) as u8, colour as u8, 0xFF];
+        self.on_colour = rgba(on);
+        self.off_colour = rgba(off);
+        self.display_changed = true;
+    }
+
+    // load_store is 0 to leave I alone, 1 to add X and 2 to add X + 1
+    pub fn set_quirks(&mut self, vf_reset: bool, shift_vy: bool, load_store: u8, jump_vx: bool, screen_wrap: bool, legacy_scrolling: bool) {
+        let quirks = &mut self.cpu.quirks;
+        quirks.vf_reset = vf_reset;
+        quirks.shifting = if shift_vy { ShiftingReg::VY } else { ShiftingReg::VX };
+        quirks.reg_save_load = match load_store {
+            1 => RegSaveLoadQuirk::X,
+            2 => RegSaveLoadQuirk::XPlusOne,
+            _ => RegSaveLoadQuirk::Unchanged,
+        };
+        quirks.jump = if jump_vx { JumpBehviour::BXNN } else { JumpBehviour::BNNN };
+        quirks.screen_wrap = screen_wrap;
+        quirks.scrolling = if legacy_scrolling { ScrollingBehviour::Legacy } else { ScrollingBehviour::Modern };
+    }
+
+    // an index into RngAlgorithm::ALL
+    pub fn set_rng(&mut self, rng: usize) {
+        self.cpu.quirks.rng = RngAlgorithm::ALL.get(rng).copied().unwrap_or(RngAlgorithm::Standard);
+    }
+
+    pub fn set_key_wait_grace(&mut self, frames: u8) {
+        self.cpu.quirks.key_wait_grace = frames;
+    }
+
+    pub fn set_display_wait(&mut self, display_wait: bool) {
+        self.cpu.quirks.display_wait = display_wait;
+    }
+
+    pub fn key_down(&mut self, key: u8) {
+        self.cpu.key_down(key);
+    }
+
+    pub fn key_up(&mut self, key: u8) {
+        self.cpu.key_up(key);
+    }
+
+    // returns whether the buzzer is sounding
+    pub fn run_frame(&mut self) -> bool {
+        let beeping = self.cpu.timer_tick();
+
+        for _ in 0..self.cycles_per_frame {
+            let effects = self.cpu.handle_opcode();
+            self.display_changed |= effects.display_changed;
+        }
+
+        beeping
+    }
+
+    // why the program stopped, if it hit something it couldn't carry on from
+    pub fn error(&self) -> Option<String> {
+        self.cpu.error().map(|error| error.to_string())
+    }
+
+    pub fn width(&self) -> usize {
+        self.cpu.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.cpu.height()
+    }
+
+    // only true once per change so the page can skip redrawing the canvas
+    pub fn take_display_changed(&mut self) -> bool {
+        std::mem::take(&mut self.display_changed)
+    }
+
+    // RGBA, ready to go into an ImageData
+    pub fn framebuffer(&self) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(self.cpu.width() * self.cpu.height() * 4);
+        for y in 0..self.cpu.height() {
+            for x in 0..self.cpu.width() {
+                pixels.extend_from_slice(if self.cpu.pixel(x, y) { &self.on_colour } else { &self.off_colour });
+            }
+        }
+
+        pixels
+    }
+}
@@ -0,0 +1,148 @@
+use serde::Serialize;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{SharedSettings, SpeedMode};
+use crate::cpu::{JumpBehviour, RegSaveLoadQuirk, ScrollingBehviour, ShiftingReg};
+use crate::random::RngAlgorithm;
+
+const INDEX_HTML: &str = include_str!("../web/index.html");
+const MAIN_JS: &str = include_str!("../web/main.js");
+
+// what wasm-pack builds, which can't be included here because the desktop build doesn't make it
+const PKG_DIR: &str = "web/pkg";
+const PKG_FILES: [&str; 2] = ["fish_n_chip8.js", "fish_n_chip8_bg.wasm"];
+
+// the ROM and how it's played, read by main.js from game.js when it's there
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct WebGame {
+    name: String,
+    rom: Vec<u8>,
+    cycles_per_frame: u16, // the web frontend only counts in cycles per frame
+    on_colour: u32,        // 0xRRGGBB
+    off_colour: u32,
+    quirks: WebQuirks,
+}
+
+#[derive(Serialize, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+struct WebQuirks {
+    vf_reset: bool,
+    shift_vy: bool,
+    load_store: u8, // 0 leaves I alone, 1 adds X and 2 adds X + 1
+    jump_vx: bool,
+    screen_wrap: bool,
+    legacy_scrolling: bool,
+    rng: u8, // an index into RngAlgorithm::ALL
+    key_wait_grace: u8,
+    display_wait: bool,
+}
+
+impl WebGame {
+    fn new(rom: &[u8], name: &str, settings: &SharedSettings) -> WebGame {
+        let quirks = settings.quirks;
+        let colour = |colour: ggez::graphics::Color| {
+            let (r, g, b) = colour.to_rgb();
+            (r as u32) << 16 | (g as u32) << 8 | b as u32
+        };
+
+        WebGame {
+            name: name.to_string(),
+            rom: rom.to_vec(),
+            cycles_per_frame: match settings.speed_mode {
+                SpeedMode::CyclesPerFrame => settings.cycles_per_frame,
+                SpeedMode::InstructionsPerSecond => (settings.instructions_per_second as f64 / 60.0).round().max(1.0) as u16,
+            },
+            on_colour: colour(settings.pixel_on_colour),
+            off_colour: colour(settings.pixel_off_colour),
+            quirks: WebQuirks {
+                vf_reset: quirks.vf_reset,
+                shift_vy: quirks.shifting == ShiftingReg::VY,
+                load_store: match quirks.reg_save_load {
+                    RegSaveLoadQuirk::Unchanged => 0,
+                    RegSaveLoadQuirk::X => 1,
+                    RegSaveLoadQuirk::XPlusOne => 2,
+                },
+                jump_vx: quirks.jump == JumpBehviour::BXNN,
+                screen_wrap: quirks.screen_wrap,
+                legacy_scrolling: quirks.scrolling == ScrollingBehviour::Legacy,
+                rng: RngAlgorithm::ALL.iter().position(|&rng| rng == quirks.rng).unwrap_or_default() as u8,
+                key_wait_grace: quirks.key_wait_grace,
+                display_wait: quirks.display_wait,
+            },
+        }
+    }
+}
+
+fn game_js(game: &WebGame) -> Result<String, String> {
+    let json = serde_json::to_string(game).map_err(|e| format!("Could not write out the game: {}", e))?;
+    Ok(format!("export default {};\n", json))
+}
+
+// the wasm-pack output, looked for from where the emulator was started and from where its executable is, so it's
+// found when running from a checkout with cargo run too
+fn find_pkg() -> Result<PathBuf, String> {
+    let exe_dir = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf));
+    let candidates = env::current_dir().ok().into_iter()
+        .chain(exe_dir.iter().flat_map(|dir| dir.ancestors().map(Path::to_path_buf)));
+
+    for dir in candidates {
+        let pkg = dir.join(PKG_DIR);
+        if PKG_FILES.iter().all(|file| pkg.join(file).is_file()) {
+            return Ok(pkg);
+        }
+    }
+
+    Err(format!(
+        "The WebAssembly build couldn't be found. Build it from the emulator's source folder first with\n\nwasm-pack build --target web --out-dir {}",
+        PKG_DIR,
+    ))
+}
+
+// writes a page that starts straight into the ROM with these settings to dir, ready to be put on any web server
+pub fn export(dir: &Path, rom: &[u8], name: &str, settings: &SharedSettings) -> Result<(), String> {
+    let pkg = find_pkg()?;
+    let out_pkg = dir.join("pkg");
+    fs::create_dir_all(&out_pkg).map_err(|e| format!("Could not create {}: {}", out_pkg.display(), e))?;
+
+    for file in PKG_FILES {
+        fs::copy(pkg.join(file), out_pkg.join(file)).map_err(|e| format!("Could not copy {} to {}: {}", file, out_pkg.display(), e))?;
+    }
+
+    let game = game_js(&WebGame::new(rom, name, settings))?;
+    for (file, contents) in [("index.html", INDEX_HTML), ("main.js", MAIN_JS), ("game.js", &game)] {
+        let path = dir.join(file);
+        fs::write(&path, contents).map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Quirks;
+    use ggez::graphics::Color;
+
+    #[test]
+    fn writes_the_game_for_the_page() {
+        let settings = SharedSettings {
+            speed_mode: SpeedMode::InstructionsPerSecond,
+            instructions_per_second: 1000,
+            pixel_on_colour: Color::from_rgb(0xFF, 0x80, 0x00),
+            pixel_off_colour: Color::from_rgb(0x00, 0x00, 0x20),
+            quirks: Quirks { shifting: ShiftingReg::VY, reg_save_load: RegSaveLoadQuirk::XPlusOne, rng: RngAlgorithm::Lcg, ..Quirks::default() },
+            ..SharedSettings::default()
+        };
+
+        let game = game_js(&WebGame::new(&[0x12, 0x00], "Loop \"forever\"", &settings)).unwrap();
+        assert_eq!(game, concat!(
+            r#"export default {"name":"Loop \"forever\"","rom":[18,0],"cyclesPerFrame":17,"onColour":16744448,"offColour":32,"#,
+            r#""quirks":{"vfReset":false,"shiftVy":true,"loadStore":2,"jumpVx":false,"screenWrap":false,"legacyScrolling":false,"rng":2,"keyWaitGrace":0,"displayWait":false}};"#,
+            "\n",
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+// properties of the 8XYN arithmetic that have to hold for every pair of operands and every quirk setting,
+// including when X or Y is VF or both are the same register
+
+use proptest::prelude::*;
+
+use fish_n_chip8::cpu::{Quirks, ShiftingReg, CPU};
+
+// loads VX with a and VY with b (so both are b when X == Y) then runs 8XYN, returning the registers before and after
+fn run_alu(quirks: Quirks, x: u8, y: u8, a: u8, b: u8, n: u8) -> ([u8; 16], [u8; 16]) {
+    let program = [
+        0x6000 | (x as u16) << 8 | a as u16,
+        0x6000 | (y as u16) << 8 | b as u16,
+        0x8000 | (x as u16) << 8 | (y as u16) << 4 | n as u16,
+    ];
+    let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect();
+
+    let mut cpu = CPU::new();
+    cpu.quirks = quirks;
+    cpu.load_rom(&rom);
+    cpu.handle_opcode();
+    cpu.handle_opcode();
+    let before = *cpu.registers();
+    cpu.handle_opcode();
+
+    (before, *cpu.registers())
+}
+
+// the VF reset quirk clears VF before the operands are read, so VF reads as 0 when it's X or Y
+fn operands(quirks: Quirks, x: u8, y: u8, before: &[u8; 16]) -> (u8, u8) {
+    let mut regs = *before;
+    if quirks.vf_reset {
+        regs[0xF] = 0;
+    }
+    (regs[x as usize], regs[y as usize])
+}
+
+// VF is written after the result, so when X is F the flag is what's left
+fn expected_vx(x: u8, result: u8, flag: u8) -> u8 {
+    if x == 0xF { flag } else { result }
+}
+
+fn check_untouched(x: u8, before: &[u8; 16], after: &[u8; 16]) {
+    for reg in 0..0xF {
+        if reg != x as usize {
+            assert_eq!(before[reg], after[reg], "V{:X} changed", reg);
+        }
+    }
+}
+
+fn check_add(quirks: Quirks, x: u8, y: u8, a: u8, b: u8) {
+    let (before, after) = run_alu(quirks, x, y, a, b, 0x4);
+    let (vx, vy) = operands(quirks, x, y, &before);
+    let (result, carry) = vx.overflowing_add(vy);
+
+    assert_eq!(after[0xF], carry as u8);
+    assert_eq!(after[x as usize], expected_vx(x, result, carry as u8));
+    check_untouched(x, &before, &after);
+}
+
+fn check_sub(quirks: Quirks, x: u8, y: u8, a: u8, b: u8, reversed: bool) {
+    let (before, after) = run_alu(quirks, x, y, a, b, if reversed { 0x7 } else { 0x5 });
+    let (vx, vy) = operands(quirks, x, y, &before);
+    let (minuend, subtrahend) = if reversed { (vy, vx) } else { (vx, vy) };
+    let not_borrow = (minuend >= subtrahend) as u8;
+
+    assert_eq!(after[0xF], not_borrow);
+    assert_eq!(after[x as usize], expected_vx(x, minuend.wrapping_sub(subtrahend), not_borrow));
+    check_untouched(x, &before, &after);
+}
+
+fn check_shift(quirks: Quirks, x: u8, y: u8, a: u8, b: u8, left: bool) {
+    let (before, after) = run_alu(quirks, x, y, a, b, if left { 0xE } else { 0x6 });
+    let (vx, vy) = operands(quirks, x, y, &before);
+    let source = match quirks.shifting {
+        ShiftingReg::VX => vx,
+        ShiftingReg::VY => vy,
+    };
+    let (result, shifted_out) = if left { (source << 1, source >> 7) } else { (source >> 1, source & 1) };
+
+    assert_eq!(after[0xF], shifted_out);
+    assert_eq!(after[x as usize], expected_vx(x, result, shifted_out));
+    check_untouched(x, &before, &after);
+}
+
+fn check_logic(quirks: Quirks, x: u8, y: u8, a: u8, b: u8, n: u8) {
+    let (before, after) = run_alu(quirks, x, y, a, b, n);
+    let (vx, vy) = operands(quirks, x, y, &before);
+    let result = match n {
+        0x1 => vx | vy,
+        0x2 => vx & vy,
+        _ => vx ^ vy,
+    };
+
+    assert_eq!(after[x as usize], result);
+    if x != 0xF {
+        // without the quirk VF is left alone
+        assert_eq!(after[0xF], if quirks.vf_reset { 0 } else { before[0xF] });
+    }
+    check_untouched(x, &before, &after);
+}
+
+fn quirks() -> impl Strategy<Value = Quirks> {
+    (any::<bool>(), any::<bool>()).prop_map(|(vf_reset, shift_vy)| Quirks {
+        vf_reset,
+        shifting: if shift_vy { ShiftingReg::VY } else { ShiftingReg::VX },
+        ..Quirks::default()
+    })
+}
+
+proptest! {
+    #[test]
+    fn add(quirks in quirks(), x in 0..16u8, y in 0..16u8, a: u8, b: u8) {
+        check_add(quirks, x, y, a, b);
+    }
+
+    #[test]
+    fn sub(quirks in quirks(), x in 0..16u8, y in 0..16u8, a: u8, b: u8) {
+        check_sub(quirks, x, y, a, b, false);
+    }
+
+    #[test]
+    fn sub_reversed(quirks in quirks(), x in 0..16u8, y in 0..16u8, a: u8, b: u8) {
+        check_sub(quirks, x, y, a, b, true);
+    }
+
+    #[test]
+    fn shift_right(quirks in quirks(), x in 0..16u8, y in 0..16u8, a: u8, b: u8) {
+        check_shift(quirks, x, y, a, b, false);
+    }
+
+    #[test]
+    fn shift_left(quirks in quirks(), x in 0..16u8, y in 0..16u8, a: u8, b: u8) {
+        check_shift(quirks, x, y, a, b, true);
+    }
+
+    #[test]
+    fn logic(quirks in quirks(), x in 0..16u8, y in 0..16u8, a: u8, b: u8, n in 1..4u8) {
+        check_logic(quirks, x, y, a, b, n);
+    }
+}
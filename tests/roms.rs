@@ -0,0 +1,115 @@
+// runs the test suite ROMs headless and checks the screen they end up on against the images in tests/golden
+//
+// set UPDATE_GOLDEN=1 to write out new images after a change that is meant to alter what they show
+
+use std::env;
+use std::fs;
+
+use fish_n_chip8::cpu::{JumpBehviour, Quirks, RegSaveLoadQuirk, ShiftingReg, CPU, DEFAULT_CYCLES_PER_FRAME};
+
+const GOLDEN_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden");
+const ROM_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/roms/tests");
+
+const PRESS_FRAMES: u32 = 20; // the menus only look at the keypad every so often
+
+// presses are the frame a key goes down and the key, each one is held for PRESS_FRAMES
+fn run_rom(rom: &str, frames: u32, presses: &[(u32, u8)]) -> String {
+    run_rom_with(rom, Quirks::default(), frames, presses)
+}
+
+fn run_rom_with(rom: &str, quirks: Quirks, frames: u32, presses: &[(u32, u8)]) -> String {
+    let mut cpu = CPU::new();
+    cpu.quirks = quirks;
+    cpu.seed_rng(0);
+    cpu.load_rom(&fs::read(format!("{}/{}", ROM_DIR, rom)).unwrap());
+
+    for frame in 0..frames {
+        let mut keys = 0;
+        for &(start, key) in presses {
+            if (start..start + PRESS_FRAMES).contains(&frame) {
+                keys |= 1 << key;
+            }
+        }
+
+        cpu.set_keys(keys);
+        cpu.timer_tick();
+        for _ in 0..DEFAULT_CYCLES_PER_FRAME {
+            cpu.handle_opcode();
+        }
+    }
+
+    format!("{}hash {:016x}\n", screen_text(&cpu), cpu.display_hash())
+}
+
+fn screen_text(cpu: &CPU) -> String {
+    let mut text = String::new();
+    for y in 0..cpu.height() {
+        text.extend((0..cpu.width()).map(|x| if cpu.pixel(x, y) { '#' } else { '.' }));
+        text.push('\n');
+    }
+    text
+}
+
+fn check_golden(name: &str, screen: String) {
+    let path = format!("{}/{}.txt", GOLDEN_DIR, name);
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &screen).unwrap();
+        return;
+    }
+
+    let golden = fs::read_to_string(&path).unwrap_or_else(|e| panic!("Could not read {}: {}, run with UPDATE_GOLDEN=1 to create it", path, e));
+    assert!(golden == screen, "{} doesn't match {}, the screen was:\n{}", name, path, screen);
+}
+
+#[test]
+fn chip8_logo() {
+    check_golden("1-chip8-logo", run_rom("1-chip8-logo.ch8", 60, &[]));
+}
+
+#[test]
+fn ibm_logo() {
+    check_golden("2-ibm-logo", run_rom("2-ibm-logo.ch8", 60, &[]));
+}
+
+#[test]
+fn corax_plus() {
+    check_golden("3-corax+", run_rom("3-corax+.ch8", 60, &[]));
+}
+
+#[test]
+fn flags() {
+    check_golden("4-flags", run_rom("4-flags.ch8", 60, &[]));
+}
+
+#[test]
+fn quirks_chip8() {
+    // 1 picks the original CHIP-8 from the menu, run with its quirks. The display wait test takes a while
+    let quirks = Quirks {
+        vf_reset: true,
+        shifting: ShiftingReg::VY,
+        reg_save_load: RegSaveLoadQuirk::XPlusOne,
+        jump: JumpBehviour::BNNN,
+        screen_wrap: false,
+        display_wait: true,
+        ..Quirks::default()
+    };
+    check_golden("5-quirks-chip8", run_rom_with("5-quirks.ch8", quirks, 1500, &[(30, 1)]));
+}
+
+#[test]
+fn quirks_superchip() {
+    // 2 picks SUPER-CHIP then 1 the modern variant, the display wait test takes a while
+    check_golden("5-quirks-superchip", run_rom("5-quirks.ch8", 1500, &[(30, 2), (120, 1)]));
+}
+
+#[test]
+fn scrolling_first_options() {
+    // the first option from each of the three menus
+    check_golden("8-scrolling-1-1-1", run_rom("8-scrolling.ch8", 600, &[(60, 1), (150, 1), (240, 1)]));
+}
+
+#[test]
+fn scrolling_second_mode() {
+    check_golden("8-scrolling-1-1-2", run_rom("8-scrolling.ch8", 600, &[(60, 1), (150, 1), (240, 2)]));
+}
@@ -0,0 +1,165 @@
+import init, { WebEmulator } from "./pkg/fish_n_chip8.js";
+
+const FRAME_TIME = 1000 / 60;
+const BEEP_FREQUENCY = 440;
+const BEEP_VOLUME = 0.1;
+
+// KeyboardEvent.code is the physical key so this is the 1234/QWER/ASDF/ZXCV block on any layout
+const KEY_CODES = {
+    Digit1: 0x1, Digit2: 0x2, Digit3: 0x3, Digit4: 0xC,
+    KeyQ: 0x4, KeyW: 0x5, KeyE: 0x6, KeyR: 0xD,
+    KeyA: 0x7, KeyS: 0x8, KeyD: 0x9, KeyF: 0xE,
+    KeyZ: 0xA, KeyX: 0x0, KeyC: 0xB, KeyV: 0xF,
+};
+
+await init();
+
+// web builds exported from the desktop emulator come with a game.js holding the ROM and the settings it's played with
+const game = await import("./game.js").then((module) => module.default, () => null);
+
+const emulator = new WebEmulator();
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+
+let romLoaded = false;
+let errorShown = false;
+
+let audio = null;
+let beepGain = null;
+
+// browsers only allow audio to start after the user has interacted with the page
+function startAudio() {
+    if (audio !== null) {
+        return;
+    }
+
+    audio = new AudioContext();
+    const oscillator = audio.createOscillator();
+    oscillator.type = "square";
+    oscillator.frequency.value = BEEP_FREQUENCY;
+
+    beepGain = audio.createGain();
+    beepGain.gain.value = 0;
+
+    oscillator.connect(beepGain).connect(audio.destination);
+    oscillator.start();
+}
+
+function setBeeping(beeping) {
+    if (beepGain !== null) {
+        // a short ramp instead of switching straight on and off avoids pops
+        beepGain.gain.setTargetAtTime(beeping ? BEEP_VOLUME : 0, audio.currentTime, 0.002);
+    }
+}
+
+async function loadRom(file) {
+    startAudio();
+
+    try {
+        emulator.load_rom(new Uint8Array(await file.arrayBuffer()));
+        romLoaded = true;
+        errorShown = false;
+        document.title = `${file.name} - Fish n CHIP-8`;
+    }
+    catch (e) {
+        alert(`Could not load ${file.name}: ${e}`);
+    }
+}
+
+if (game !== null) {
+    const quirks = game.quirks;
+    emulator.set_quirks(quirks.vfReset, quirks.shiftVy, quirks.loadStore, quirks.jumpVx, quirks.screenWrap, quirks.legacyScrolling);
+    emulator.set_rng(quirks.rng);
+    emulator.set_key_wait_grace(quirks.keyWaitGrace);
+    emulator.set_display_wait(quirks.displayWait);
+    emulator.set_colours(game.onColour, game.offColour);
+    emulator.set_cycles_per_frame(game.cyclesPerFrame);
+
+    emulator.load_rom(new Uint8Array(game.rom));
+    romLoaded = true;
+    document.title = game.name;
+    // it's a page for the one game so there's nothing to pick
+    document.getElementById("menu").hidden = true;
+}
+
+document.getElementById("rom-file").addEventListener("change", (event) => {
+    if (event.target.files.length > 0) {
+        loadRom(event.target.files[0]);
+    }
+});
+
+document.getElementById("cycles").addEventListener("change", (event) => {
+    emulator.set_cycles_per_frame(Math.max(1, parseInt(event.target.value) || 1));
+});
+
+canvas.addEventListener("dragover", (event) => {
+    event.preventDefault();
+    canvas.classList.add("dragging");
+});
+canvas.addEventListener("dragleave", () => canvas.classList.remove("dragging"));
+canvas.addEventListener("drop", (event) => {
+    event.preventDefault();
+    canvas.classList.remove("dragging");
+
+    if (event.dataTransfer.files.length > 0) {
+        loadRom(event.dataTransfer.files[0]);
+    }
+});
+
+document.addEventListener("keydown", (event) => {
+    const key = KEY_CODES[event.code];
+    if (key !== undefined) {
+        startAudio(); // exported games start without a ROM being picked so this may be the first interaction
+        emulator.key_down(key);
+        event.preventDefault();
+    }
+});
+document.addEventListener("keyup", (event) => {
+    const key = KEY_CODES[event.code];
+    if (key !== undefined) {
+        emulator.key_up(key);
+    }
+});
+
+function draw() {
+    const width = emulator.width();
+    const height = emulator.height();
+
+    if (canvas.width !== width || canvas.height !== height) {
+        canvas.width = width;
+        canvas.height = height;
+    }
+
+    const pixels = new Uint8ClampedArray(emulator.framebuffer());
+    context.putImageData(new ImageData(pixels, width, height), 0, 0);
+}
+
+// the same fixed 60Hz step as the desktop version, whatever the display's refresh rate is
+let lastTime = performance.now();
+let accumulator = 0;
+let beeping = false;
+
+function frame(time) {
+    accumulator = Math.min(accumulator + time - lastTime, FRAME_TIME * 6);
+    lastTime = time;
+
+    while (romLoaded && accumulator >= FRAME_TIME) {
+        accumulator -= FRAME_TIME;
+        beeping = emulator.run_frame();
+    }
+    setBeeping(beeping);
+
+    const error = emulator.error();
+    if (error && !errorShown) {
+        errorShown = true;
+        alert(`The program stopped: ${error}`);
+    }
+
+    if (emulator.take_display_changed()) {
+        draw();
+    }
+
+    requestAnimationFrame(frame);
+}
+
+requestAnimationFrame(frame);